@@ -1,20 +1,29 @@
 global start
+global bottom_stack
+
+; the kernel is linked this far above where grub loads it, the same as KERNEL_OFFSET in linker.ld and memory/mod.rs
+%define KERNEL_OFFSET 0xC0000000
+; 4 MiB pages covering DIRECT_MAP_SIZE from memory/mod.rs, the kernel builds finer tables later
+%define BOOT_HUGE_PAGES 192
+%define HUGE_PAGE_SIZE 0x400000
+%define PRESENT_WRITABLE_HUGE 0b10000011
+%define CR4_PAGE_SIZE_EXTENSION 1 << 4
+%define CR0_PAGING 1 << 31
 
 
 
 section .bss
 align 4096
-page_directory:
-    resb 4096
-page_table:
+boot_page_directory:
     resb 4096
 bottom_stack:
-    resb 4096 * 8 ; leave space for stack
+    resb 4096 * 9 ; leave space for stack, and a page below it that becomes a guard
 top_stack:
 
 
 
-section .text
+; everything in here runs before paging, so it is linked at the physical address grub loads it to
+section .boot_text exec
 bits 32
 
 panic:    
@@ -35,45 +44,56 @@ check_multiboot:
     jmp panic
 
 
+; the low memory is mapped twice, to itself so we keep running right after paging is enabled
+; and at KERNEL_OFFSET where the rest of the kernel is linked
 set_paging:
-    mov eax, page_table
-    or eax, 0b11 ; rw+p
-    mov [page_directory], eax
-
+    mov edi, boot_page_directory - KERNEL_OFFSET
+    mov eax, PRESENT_WRITABLE_HUGE
     mov ecx, 0
 
-    ; identity map first 4M of memory
-    .map_page_table: 
-    
-    mov eax, 0x1000 ; 4K of memory
-    mul ecx
-    or eax, 0b11 ; w/r+p
-    mov [page_table + ecx * 4], eax
-    
+    .map_huge_page:
+    mov [edi + ecx * 4], eax
+    mov [edi + ecx * 4 + (KERNEL_OFFSET >> 22) * 4], eax
+
+    add eax, HUGE_PAGE_SIZE
     inc ecx
-    cmp ecx, 512
-    jnz .map_page_table
+    cmp ecx, BOOT_HUGE_PAGES
+    jnz .map_huge_page
 
     ret
 
 enable_paging:
-    mov eax, page_directory
+    mov eax, boot_page_directory - KERNEL_OFFSET
     mov cr3, eax
 
+    mov eax, cr4
+    or eax, CR4_PAGE_SIZE_EXTENSION
+    mov cr4, eax
+
     mov eax, cr0
-    or eax, 0x80000001
+    or eax, CR0_PAGING
     mov cr0, eax
 
     ret
 
 start:
-    mov esp, top_stack ; setup stack pointer
-    push ebx ; push multi boot info to stack for rust function
+    mov esp, top_stack - KERNEL_OFFSET ; the stack by its physical address until we are in the higher half
 
     ; call check_multiboot
-    ; call set_paging
-    ; call enable_paging I followed phil guild but its seems like he uses 64 bits that makes some of the identity mapping very easy to do,
-    ; in particular our multiboot info is outside the page table so we triple fault while trying to access it
+    call set_paging
+    call enable_paging
+
+    ; an absolute jump, eip moves from the physical address to the higher half
+    mov eax, higher_half
+    jmp eax
+
+
+
+section .text
+
+higher_half:
+    mov esp, top_stack ; setup stack pointer
+    push ebx ; push the physical address of the multi boot info to stack for rust function
 
     extern _start
     call _start
@@ -0,0 +1,92 @@
+use super::{parse_number, Archive, ArchiveError, Entry, EntryKind, Result};
+
+const HEADER_SIZE: usize = 110;
+// 070702 is the same with the check field filled in, we don't verify it
+const MAGICS: [&[u8]; 2] = [b"070701", b"070702"];
+const TRAILER: &str = "TRAILER!!!";
+
+// every field is 8 hex digits after the 6 byte magic
+const fn field(index: usize) -> core::ops::Range<usize> {
+    6 + index * 8..6 + (index + 1) * 8
+}
+
+const MODE: core::ops::Range<usize> = field(1);
+const MODIFIED: core::ops::Range<usize> = field(5);
+const FILE_SIZE: core::ops::Range<usize> = field(6);
+const NAME_SIZE: core::ops::Range<usize> = field(11);
+
+const MODE_TYPE_MASK: usize = 0o170000;
+const MODE_FILE: usize = 0o100000;
+const MODE_DIRECTORY: usize = 0o040000;
+
+pub fn is_cpio(bytes: &[u8]) -> bool {
+    bytes.get(..6).is_some_and(|magic| MAGICS.contains(&magic))
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+pub fn parse(bytes: &'static [u8], archive: &mut Archive) -> Result<()> {
+    let mut offset = 0;
+
+    loop {
+        let header = bytes
+            .get(offset..offset + HEADER_SIZE)
+            .ok_or(ArchiveError::Truncated(offset))?;
+
+        if !is_cpio(header) {
+            return Err(ArchiveError::InvalidHeader(offset));
+        }
+
+        let number = |range: core::ops::Range<usize>| {
+            parse_number(&header[range], 16).ok_or(ArchiveError::InvalidHeader(offset))
+        };
+
+        let mode = number(MODE)?;
+        let modified = number(MODIFIED)?;
+        let file_size = number(FILE_SIZE)?;
+        let name_size = number(NAME_SIZE)?;
+
+        // the name size counts the terminating zero
+        let name_start = offset + HEADER_SIZE;
+        let name_end = name_start
+            .checked_add(name_size)
+            .ok_or(ArchiveError::InvalidHeader(offset))?;
+        let name = bytes
+            .get(name_start..name_start + name_size.saturating_sub(1))
+            .ok_or(ArchiveError::Truncated(offset))?;
+        let name = core::str::from_utf8(name).map_err(|_| ArchiveError::InvalidHeader(offset))?;
+
+        if name == TRAILER {
+            return Ok(());
+        }
+
+        let data_start = align(name_end);
+        let data_end = data_start
+            .checked_add(file_size)
+            .ok_or(ArchiveError::InvalidHeader(offset))?;
+        let data = bytes
+            .get(data_start..data_end)
+            .ok_or(ArchiveError::Truncated(offset))?;
+
+        let kind = match mode & MODE_TYPE_MASK {
+            MODE_FILE => Some(EntryKind::File),
+            MODE_DIRECTORY => Some(EntryKind::Directory),
+            _ => None,
+        };
+
+        if let Some(kind) = kind {
+            archive.insert(
+                name,
+                Entry {
+                    kind,
+                    modified: modified as u32,
+                    data,
+                },
+            );
+        }
+
+        offset = align(data_end);
+    }
+}
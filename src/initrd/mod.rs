@@ -0,0 +1,124 @@
+// the initial ramdisk, a read-only archive grub loads as the module named initrd.
+// ustar https://pubs.opengroup.org/onlinepubs/9699919799/utilities/pax.html#tag_20_92_13_06
+// cpio newc https://www.kernel.org/doc/html/latest/driver-api/early-userspace/buffer-format.html
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+};
+use thiserror::Error;
+
+pub mod cpio;
+pub mod ustar;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Not a ustar or cpio archive")]
+    UnknownFormat,
+    #[error("Archive ends in the middle of an entry at offset {0}")]
+    Truncated(usize),
+    #[error("Malformed header at offset {0}")]
+    InvalidHeader(usize),
+    #[error("Header checksum mismatch at offset {0}")]
+    BadChecksum(usize),
+}
+
+pub type Result<T> = core::result::Result<T, ArchiveError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub kind: EntryKind,
+    // seconds since the unix epoch
+    pub modified: u32,
+    // points into the module, nothing is copied
+    pub data: &'static [u8],
+}
+
+// entries are keyed by their path without leading or trailing slashes, the root is ""
+#[derive(Debug, Clone, Default)]
+pub struct Archive {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Archive {
+    // symlinks, devices and the like are skipped
+    pub fn parse(bytes: &'static [u8]) -> Result<Self> {
+        let mut archive = Self::default();
+
+        if ustar::is_ustar(bytes) {
+            ustar::parse(bytes, &mut archive)?;
+        } else if cpio::is_cpio(bytes) {
+            cpio::parse(bytes, &mut archive)?;
+        } else {
+            return Err(ArchiveError::UnknownFormat);
+        }
+
+        Ok(archive)
+    }
+
+    fn insert(&mut self, path: &str, entry: Entry) {
+        let path = normalize(path);
+
+        if !path.is_empty() {
+            self.entries.insert(path.to_string(), entry);
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Entry> {
+        self.entries.get(normalize(path))
+    }
+
+    // archives don't always have entries for their directories, a file under the path is enough
+    pub fn is_directory(&self, path: &str) -> bool {
+        let path = normalize(path);
+
+        if path.is_empty() {
+            return true;
+        }
+
+        if let Some(entry) = self.entries.get(path) {
+            return entry.kind == EntryKind::Directory;
+        }
+
+        let prefix = format!("{}/", path);
+        self.entries
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|(name, _)| name.starts_with(&prefix))
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (&str, &Entry)> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.kind == EntryKind::File)
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+}
+
+// tar and cpio both like to start paths with ./
+fn normalize(path: &str) -> &str {
+    let path = path.trim_start_matches("./").trim_matches('/');
+
+    match path {
+        "." => "",
+        path => path,
+    }
+}
+
+// the numeric fields of both formats are ascii digits in some base
+fn parse_number(field: &[u8], radix: u32) -> Option<usize> {
+    let field = core::str::from_utf8(field).ok()?;
+    let field = field.trim_matches(|c: char| c == '\0' || c == ' ');
+
+    match field {
+        "" => Some(0),
+        field => usize::from_str_radix(field, radix).ok(),
+    }
+}
@@ -0,0 +1,118 @@
+use alloc::string::String;
+
+use super::{parse_number, Archive, ArchiveError, Entry, EntryKind, Result};
+
+const BLOCK_SIZE: usize = 512;
+
+const NAME: core::ops::Range<usize> = 0..100;
+const SIZE: core::ops::Range<usize> = 124..136;
+const MODIFIED: core::ops::Range<usize> = 136..148;
+const CHECKSUM: core::ops::Range<usize> = 148..156;
+const TYPE_FLAG: usize = 156;
+const MAGIC: core::ops::Range<usize> = 257..262;
+const PREFIX: core::ops::Range<usize> = 345..500;
+
+const TYPE_FILE: u8 = b'0';
+// pre-POSIX tar marks regular files with a zero byte
+const TYPE_OLD_FILE: u8 = 0;
+const TYPE_DIRECTORY: u8 = b'5';
+// GNU tar puts names longer than 100 bytes in an entry of their own in front of the file
+const TYPE_GNU_LONG_NAME: u8 = b'L';
+
+pub fn is_ustar(bytes: &[u8]) -> bool {
+    bytes.get(MAGIC) == Some(b"ustar")
+}
+
+fn field_str(field: &[u8]) -> &str {
+    let length = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    core::str::from_utf8(&field[..length]).unwrap_or("")
+}
+
+// the checksum field itself counts as spaces
+fn verify_checksum(header: &[u8], offset: usize) -> Result<()> {
+    let expected = parse_number(&header[CHECKSUM], 8).ok_or(ArchiveError::InvalidHeader(offset))?;
+
+    let sum: usize = header
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| match CHECKSUM.contains(&i) {
+            true => b' ' as usize,
+            false => byte as usize,
+        })
+        .sum();
+
+    match sum == expected {
+        true => Ok(()),
+        false => Err(ArchiveError::BadChecksum(offset)),
+    }
+}
+
+pub fn parse(bytes: &'static [u8], archive: &mut Archive) -> Result<()> {
+    let mut offset = 0;
+    let mut long_name: Option<String> = None;
+
+    while let Some(header) = bytes.get(offset..offset + BLOCK_SIZE) {
+        // the archive ends with two zero blocks, the first one is enough for us
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+
+        verify_checksum(header, offset)?;
+
+        let size = parse_number(&header[SIZE], 8).ok_or(ArchiveError::InvalidHeader(offset))?;
+        let modified =
+            parse_number(&header[MODIFIED], 8).ok_or(ArchiveError::InvalidHeader(offset))?;
+
+        let data_start = offset + BLOCK_SIZE;
+        let data_end = data_start
+            .checked_add(size)
+            .ok_or(ArchiveError::InvalidHeader(offset))?;
+        let data = bytes
+            .get(data_start..data_end)
+            .ok_or(ArchiveError::Truncated(offset))?;
+
+        let path = match long_name.take() {
+            Some(name) => name,
+            None => {
+                let (prefix, name) = (field_str(&header[PREFIX]), field_str(&header[NAME]));
+                match prefix {
+                    "" => String::from(name),
+                    prefix => alloc::format!("{}/{}", prefix, name),
+                }
+            }
+        };
+
+        let kind = match header[TYPE_FLAG] {
+            TYPE_FILE | TYPE_OLD_FILE => Some(EntryKind::File),
+            TYPE_DIRECTORY => Some(EntryKind::Directory),
+            TYPE_GNU_LONG_NAME => {
+                long_name = Some(String::from(field_str(data)));
+                None
+            }
+            _ => None,
+        };
+
+        if let Some(kind) = kind {
+            archive.insert(
+                &path,
+                Entry {
+                    kind,
+                    modified: modified as u32,
+                    data,
+                },
+            );
+        }
+
+        // the data is padded to whole blocks
+        offset = size
+            .div_ceil(BLOCK_SIZE)
+            .checked_mul(BLOCK_SIZE)
+            .and_then(|padded| data_start.checked_add(padded))
+            .ok_or(ArchiveError::InvalidHeader(offset))?;
+    }
+
+    Ok(())
+}
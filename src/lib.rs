@@ -7,23 +7,44 @@
 extern crate alloc;
 extern crate bitflags;
 
-use core::panic::PanicInfo;
+use alloc::{string::String, vec::Vec};
+use core::{ops::Range, panic::PanicInfo};
 
 use crate::{
-    memory::physical::{buddy_allocator::buddy_allocator::BuddyAllocator, global_alloc::ALLOCATOR},
-    multiboot::{memory_map::MemoryEntryType, MultiBootInfo},
-    network_stack::{arp::ArpPacket, ethernet::EthernetFrame},
-    pci::{
-        check_pci_buses,
-        drivers::{network::NETWORK_DRIVER, PCI_DRIVERS},
+    initrd::Archive,
+    memory::{
+        kernel_image, paging,
+        physical::{global_alloc::ALLOCATOR, physical_memory_manager::PhysicalMemoryManager},
+        physical_to_virtual, PhysicalAddress, DIRECT_MAP_SIZE,
     },
+    multiboot::{memory_map::MemoryMapEntry, MultiBootInfo},
+    network_stack::{
+        dhcp::{self, StaticConfiguration},
+        dns,
+        http::{
+            middleware::{log_requests, Chain},
+            request::Request,
+            response::{Response, StatusCode},
+            router::Router,
+            server::HttpServer,
+            static_files::StaticFiles,
+            websocket::{self, Message, Session, WebSocket},
+        },
+        icmp,
+        interface::{print_interfaces, RECEIVE_EVENT},
+        ipv4::{Ipv4Address, SocketAddressV4},
+        ndp, poll_until,
+    },
+    pci::{check_pci_buses, drivers::PCI_DRIVERS},
     x86::{
         gdt::load_gdt,
         hlt_loop,
         interrupts::{enable_interrupt, idt::load_idt, pic_8259::PIC},
+        pit,
     },
 };
 
+mod initrd;
 mod memory;
 mod multiboot;
 mod mutex;
@@ -38,31 +59,104 @@ pub extern "C" fn _start(multiboot_info_ptr: usize) -> ! {
     load_gdt();
     load_idt();
 
-    let multiboot_info = MultiBootInfo::new(multiboot_info_ptr);
+    // boot.s hands over the physical address, it is reachable through the boot mapping
+    let multiboot_info = MultiBootInfo::new(physical_to_virtual(multiboot_info_ptr));
 
     let memory_map_tag = multiboot_info
         .memory_map_tag()
         .expect("Memory Map is missing from multiboot info");
 
-    let mut largest_mem_addr: usize = 0;
-    let mut largest_size: usize = 0;
+    // the part of every usable region the kernel can reach through the direct map
+    let available_memory = || {
+        memory_map_tag
+            .get_available_memory_map_entries()
+            .map(direct_mapped_part)
+            .filter(|region| !region.is_empty())
+    };
+
+    // acpi tables and storage, only what is in the direct map could be handed out
+    let firmware_memory = || {
+        memory_map_tag
+            .get_firmware_memory_map_entries()
+            .map(direct_mapped_part)
+    };
+
+    let modules = || {
+        multiboot_info
+            .module_tags()
+            .map(|module| module.start()..module.end())
+    };
+
+    // memory that stays in use after boot, the boot stack is in the kernel's bss
+    let reserved = [
+        // the real mode interrupt table and the bios data
+        0..LOW_MEMORY_END,
+        kernel_image(),
+        multiboot_info_ptr..multiboot_info_ptr + multiboot_info.size(),
+    ];
 
-    for entry in memory_map_tag.get_memory_map_entries() {
-        if (entry.memory_type == MemoryEntryType::Available)
-            && ((entry.length as usize) > largest_size)
-        {
-            largest_mem_addr = entry.base_addr as usize;
-            largest_size = entry.length as usize;
+    let mut memory_manager = PhysicalMemoryManager::new();
+    for region in available_memory() {
+        if let Err(err) = memory_manager.add_region(region.clone()) {
+            println!("Ignoring physical memory at {:#x?}: {:?}", region, err);
         }
     }
+    for range in reserved
+        .iter()
+        .cloned()
+        .chain(modules())
+        .chain(firmware_memory())
+    {
+        memory_manager
+            .reserve(range)
+            .expect("Failed to reserve memory in use");
+    }
+    memory_manager.populate();
 
-    let buddy_allocator = BuddyAllocator::new(largest_mem_addr, largest_size);
+    println!(
+        "{} KiB of physical memory in {} arenas",
+        memory_manager.remaining_memory() / 1024,
+        memory_manager.arena_count()
+    );
 
     {
         let mut alloc = ALLOCATOR.lock();
-        alloc.init(buddy_allocator);
+        alloc.init(memory_manager);
     };
 
+    // the modules are reserved in the allocator, so the initrd can be read in place.
+    // grub.cfg names it on its module2 line
+    let initrd = multiboot_info
+        .module_tags()
+        .find(|module| module.name() == INITRD_MODULE)
+        .map(|module| module.bytes());
+
+    let command_line = String::from(
+        multiboot_info
+            .command_line_tag()
+            .map_or("", |tag| tag.command_line()),
+    );
+
+    let archive = initrd.and_then(|bytes| match Archive::parse(bytes) {
+        Ok(archive) => {
+            println!("Loaded initrd with {} files", archive.files().count());
+            Some(archive)
+        }
+        Err(err) => {
+            println!("Ignoring the initrd: {}", err);
+            None
+        }
+    });
+
+    // what the kernel still uses of the boot mapping, everything else faults from here on.
+    // the low memory has the vga buffer, every usable region may be in the heap
+    let direct_mapped: Vec<_> = reserved
+        .into_iter()
+        .chain(modules())
+        .chain(available_memory())
+        .collect();
+    paging::init(&direct_mapped).expect("Failed to set up paging");
+
     let mut pci_devices = check_pci_buses();
 
     for device in &mut pci_devices {
@@ -85,6 +179,7 @@ pub extern "C" fn _start(multiboot_info_ptr: usize) -> ! {
     {
         unsafe {
             PIC.lock().init();
+            pit::init();
             // PIC.lock().master.write_mask(0xFE);
             // PIC.lock().slave.write_mask(0xFF);
 
@@ -95,36 +190,175 @@ pub extern "C" fn _start(multiboot_info_ptr: usize) -> ! {
         };
     }
 
-    unsafe {
-        let mut card_lock = NETWORK_DRIVER.lock();
-        let card = card_lock.as_mut().unwrap();
-
-        // https://wiki.qemu.org/Documentation/Networking
-        let arp_packet = ArpPacket {
-            hardware_len: 6,
-            hardware_type: network_stack::arp::HardwareType::Ethernet,
-            operation: network_stack::arp::Operation::Request,
-            protocol_len: 4,
-            protocol_type: network_stack::ethernet::EitherType::Ipv4,
-            sender_hardware_address: &card.get_address().bytes,
-            sender_protocol_address: &[0x01, 0x01, 0x01, 0x01],
-            target_hardware_address: &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
-            target_protocol_address: &[10, 0, 2, 2],
-        };
+    print_interfaces();
+    network_stack::init();
 
-        let frame = EthernetFrame {
-            data: &arp_packet.to_bytes(),
-            destination_address: network_stack::ethernet::EthernetAddress::broadcast(),
-            source_address: card.get_address(),
-            ether_type: network_stack::ethernet::EitherType::Arp,
-        };
+    configure_network(&command_line);
+
+    println!("hello form the other side!");
+
+    // an unspecified address takes connections over IPv4 and IPv6
+    let mut server = match HttpServer::bind(
+        SocketAddressV4::new(Ipv4Address::UNSPECIFIED, HTTP_PORT).into(),
+        Chain::new(routes(archive)).with(log_requests),
+    ) {
+        Ok(server) => {
+            println!("Serving HTTP on {}", server.local_address());
+            Some(server)
+        }
+        Err(err) => {
+            println!("Failed to start the HTTP server: {}", err);
+            None
+        }
+    };
+
+    loop {
+        RECEIVE_EVENT.wait_timeout(network_stack::TIMER_POLL_INTERVAL);
+        network_stack::poll();
 
-        card.transmit_packet(&frame.to_bytes(true), true).unwrap();
+        if let Some(server) = &mut server {
+            server.poll();
+        }
     }
+}
 
-    println!("hello form the other side!");
+const HTTP_PORT: u16 = 80;
+const PING_TIMEOUT: usize = 2 * pit::TICKS_PER_SECOND;
+const LOW_MEMORY_END: usize = 0x100000;
+const INITRD_MODULE: &str = "initrd";
+const MAX_UPTIME_BACKLOG: usize = 1024;
 
-    hlt_loop();
+fn direct_mapped_part(entry: &MemoryMapEntry) -> Range<PhysicalAddress> {
+    let end = (entry.base_addr + entry.length).min(DIRECT_MAP_SIZE as u64);
+    entry.base_addr.min(end) as usize..end as usize
+}
+
+// the files of the initrd are served from the root, anything not covered by another route
+fn routes(archive: Option<Archive>) -> Router {
+    let router = Router::new()
+        .get("/hello/:name", |request: Request| {
+            let name = request.param("name").unwrap_or("stranger");
+            let greeting = request.query("greeting").unwrap_or("hello");
+            Response::text(StatusCode::Ok, &alloc::format!("{} {}!\n", greeting, name))
+        })
+        .get("/ping/:address", |request: Request| {
+            let Some(address) = request
+                .param("address")
+                .and_then(|address| address.parse::<Ipv4Address>().ok())
+            else {
+                return Response::text(StatusCode::BadRequest, "not an IPv4 address\n");
+            };
+
+            match icmp::ping(address, PING_TIMEOUT) {
+                Ok(Some(milliseconds)) => Response::text(
+                    StatusCode::Ok,
+                    &alloc::format!("reply from {} in {} ms\n", address, milliseconds),
+                ),
+                Ok(None) => Response::text(StatusCode::GatewayTimeout, "no reply\n"),
+                Err(err) => Response::text(StatusCode::BadGateway, &alloc::format!("{}\n", err)),
+            }
+        })
+        // an address is looked up in reverse, anything else is resolved to its addresses
+        .get("/resolve/:name", |request: Request| {
+            let name = request.param("name").unwrap_or("");
+            let result = match name.parse::<Ipv4Address>() {
+                Ok(address) => dns::reverse_lookup(address),
+                Err(_) => dns::resolve(name).map(|addresses| {
+                    addresses
+                        .into_iter()
+                        .map(|address| alloc::format!("{}", address))
+                        .collect()
+                }),
+            };
+
+            match result {
+                Ok(answers) => Response::text(StatusCode::Ok, &(answers.join("\n") + "\n")),
+                Err(err @ dns::DnsError::NameNotFound(_)) => {
+                    Response::text(StatusCode::NotFound, &alloc::format!("{}\n", err))
+                }
+                Err(err) => Response::text(StatusCode::BadGateway, &alloc::format!("{}\n", err)),
+            }
+        })
+        .get("/echo", |request: Request| {
+            websocket::accept(&request, |socket: &mut WebSocket, message: Message| {
+                let _ = socket.send(message);
+            })
+        })
+        .get("/uptime", |request: Request| {
+            websocket::accept(&request, UptimeFeed { sent_at: None })
+        });
+
+    match archive {
+        Some(archive) => router.get("/*path", StaticFiles::new(archive)),
+        None => router.get("/", |_| {
+            Response::text(StatusCode::Ok, "hello form the other side!\n")
+        }),
+    }
+}
+
+// pushes the seconds since boot once a second
+struct UptimeFeed {
+    sent_at: Option<usize>,
+}
+
+impl Session for UptimeFeed {
+    fn on_open(&mut self, socket: &mut WebSocket) {
+        println!("{} is watching the uptime", socket.peer_address());
+    }
+
+    fn on_message(&mut self, _socket: &mut WebSocket, _message: Message) {}
+
+    fn poll(&mut self, socket: &mut WebSocket) {
+        // a client that doesn't keep up misses updates instead of piling them up
+        let due = self
+            .sent_at
+            .is_none_or(|sent_at| pit::elapsed(sent_at, pit::TICKS_PER_SECOND));
+        if !due || socket.buffered_amount() > MAX_UPTIME_BACKLOG {
+            return;
+        }
+
+        self.sent_at = Some(pit::ticks());
+        let seconds = pit::ticks() / pit::TICKS_PER_SECOND;
+        let _ = socket.send_text(&alloc::format!("{}", seconds));
+    }
+}
+
+// IPv6 configures itself in the background from the link-local address and router advertisements.
+// for IPv4 DHCP comes first, the address from the kernel command line if no server answers in time.
+// without one DHCP keeps trying in the background
+fn configure_network(command_line: &str) {
+    const INTERFACE_ID: usize = 0;
+    const DHCP_TIMEOUT: usize = 10 * pit::TICKS_PER_SECOND;
+
+    let static_configuration = match StaticConfiguration::from_command_line(command_line) {
+        Ok(configuration) => configuration,
+        Err(err) => {
+            println!(
+                "Ignoring the network configuration on the command line: {}",
+                err
+            );
+            None
+        }
+    };
+
+    if let Err(err) = ndp::start(INTERFACE_ID) {
+        println!("Failed to start IPv6: {}", err);
+    }
+
+    if let Err(err) = dhcp::start(INTERFACE_ID) {
+        println!("Failed to start DHCP: {}", err);
+    } else if poll_until(Some(DHCP_TIMEOUT), || dhcp::lease(INTERFACE_ID)).is_some() {
+        return;
+    }
+
+    if let Some(configuration) = static_configuration {
+        println!(
+            "No DHCP lease, using {} from the command line",
+            configuration.cidr
+        );
+        dhcp::stop(INTERFACE_ID);
+        configuration.apply(INTERFACE_ID);
+    }
 }
 
 #[panic_handler]
@@ -1,3 +1,52 @@
+use core::{ops::Range, ptr::addr_of};
+
+pub mod paging;
 pub mod physical;
 
 pub type PhysicalAddress = usize;
+pub type VirtualAddress = usize;
+
+// the kernel is linked this far above where it is loaded, the same as in linker.ld and boot.s
+pub const KERNEL_OFFSET: VirtualAddress = 0xC000_0000;
+// physical memory below this is reachable at KERNEL_OFFSET + its address, boot.s maps all of it
+// until paging::init replaces that with only what is in use
+pub const DIRECT_MAP_SIZE: usize = 768 * 1024 * 1024;
+
+extern "C" {
+    // from linker.ld
+    static kernel_start: u8;
+    static kernel_end: u8;
+    static text_start: u8;
+    static text_end: u8;
+    // from boot.s
+    static bottom_stack: u8;
+}
+
+// where grub loaded the kernel, code, data and the bss with the boot stack
+pub fn kernel_image() -> Range<PhysicalAddress> {
+    virtual_to_physical(addr_of!(kernel_start) as usize)
+        ..virtual_to_physical(addr_of!(kernel_end) as usize)
+}
+
+// the kernel's code, linker.ld starts it on a page of its own
+pub fn kernel_text() -> Range<VirtualAddress> {
+    addr_of!(text_start) as usize..addr_of!(text_end) as usize
+}
+
+// the lowest page of the boot stack, paging::init unmaps it
+pub fn boot_stack_guard() -> Range<VirtualAddress> {
+    let start = addr_of!(bottom_stack) as usize;
+    start..start + paging::PAGE_SIZE
+}
+
+pub fn physical_to_virtual(address: PhysicalAddress) -> VirtualAddress {
+    debug_assert!(address < DIRECT_MAP_SIZE);
+    address + KERNEL_OFFSET
+}
+
+// only for the direct map, which the kernel image and the heap are in.
+// devices doing DMA need this for every buffer they get
+pub fn virtual_to_physical(address: VirtualAddress) -> PhysicalAddress {
+    debug_assert!((KERNEL_OFFSET..KERNEL_OFFSET + DIRECT_MAP_SIZE).contains(&address));
+    address - KERNEL_OFFSET
+}
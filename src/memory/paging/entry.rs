@@ -0,0 +1,51 @@
+use bitflags::bitflags;
+
+use crate::memory::PhysicalAddress;
+
+use super::ENTRY_COUNT;
+
+bitflags! {
+    // the same bits are used in directory and table entries, intel sdm vol 3a table 4-5 and 4-6
+    pub struct PageFlags: u32 {
+        const PRESENT = 1 << 0;
+        const WRITABLE = 1 << 1;
+        const USER = 1 << 2;
+        const WRITE_THROUGH = 1 << 3;
+        const CACHE_DISABLE = 1 << 4;
+        const ACCESSED = 1 << 5;
+        const DIRTY = 1 << 6;
+        // in a directory entry, it maps a 4 MiB page instead of pointing at a table
+        const HUGE = 1 << 7;
+        const GLOBAL = 1 << 8;
+    }
+}
+
+const ADDRESS_MASK: u32 = 0xFFFF_F000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct PageTableEntry(u32);
+
+impl PageTableEntry {
+    pub fn new(address: PhysicalAddress, flags: PageFlags) -> Self {
+        Self((address as u32 & ADDRESS_MASK) | flags.bits())
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.flags().contains(PageFlags::PRESENT)
+    }
+
+    pub fn address(&self) -> PhysicalAddress {
+        (self.0 & ADDRESS_MASK) as PhysicalAddress
+    }
+
+    pub fn flags(&self) -> PageFlags {
+        PageFlags::from_bits_truncate(self.0 & !ADDRESS_MASK)
+    }
+}
+
+// a page directory or a page table, both are one page of entries
+#[repr(C, align(4096))]
+pub struct PageTable {
+    pub entries: [PageTableEntry; ENTRY_COUNT],
+}
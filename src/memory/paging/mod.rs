@@ -0,0 +1,133 @@
+// 32-bit paging without PAE, intel sdm vol 3a 4.3
+// https://wiki.osdev.org/Paging
+
+use core::ops::Range;
+use thiserror::Error;
+
+use crate::mutex::Mutex;
+
+use self::{entry::PageFlags, page_directory::PageDirectory, regions::RegionKind};
+
+use super::{
+    boot_stack_guard, kernel_text, physical::AllocatorError, physical_to_virtual, PhysicalAddress,
+    VirtualAddress, DIRECT_MAP_SIZE, KERNEL_OFFSET,
+};
+
+pub mod entry;
+pub mod page_directory;
+pub mod regions;
+
+pub const PAGE_SIZE: usize = 4 * 1024;
+// what a directory entry with the HUGE flag maps
+pub const HUGE_PAGE_SIZE: usize = 4 * 1024 * 1024;
+// in the directory and in every table
+const ENTRY_COUNT: usize = 1024;
+// a pointer run past the end of the direct map faults here instead of writing device registers
+const DIRECT_MAP_GUARD: Range<VirtualAddress> =
+    KERNEL_OFFSET + DIRECT_MAP_SIZE..KERNEL_OFFSET + DIRECT_MAP_SIZE + PAGE_SIZE;
+// device registers are mapped here, right above the direct map and its guard page
+const MMIO_WINDOW: Range<VirtualAddress> = DIRECT_MAP_GUARD.end..0xFF00_0000;
+// demand paged regions go above it. the last page is left out so the end fits in a usize
+const DEMAND_PAGED_WINDOW: Range<VirtualAddress> = MMIO_WINDOW.end..0xFFFF_F000;
+
+#[derive(Error, Debug)]
+pub enum PagingError {
+    #[error("Address {0:#x} is not page aligned")]
+    Unaligned(usize),
+    #[error("Page {0:#x} is already mapped")]
+    AlreadyMapped(VirtualAddress),
+    #[error("Page {0:#x} is not mapped")]
+    NotMapped(VirtualAddress),
+    #[error("Failed to allocate a page table: {0:?}")]
+    OutOfMemory(AllocatorError),
+    #[error("No room left for {0:#x} bytes of device registers")]
+    MmioWindowFull(usize),
+    #[error("No room left for {0:#x} bytes of demand paged memory")]
+    DemandPagedWindowFull(usize),
+    #[error("Region overlaps the one at {0:#x}")]
+    Overlapping(VirtualAddress),
+    #[error("The kernel page directory is not set up yet")]
+    Uninitialized,
+}
+
+pub type Result<T> = core::result::Result<T, PagingError>;
+
+// the directory the kernel runs on, None while it still runs on the one from boot.s
+pub static KERNEL_PAGE_DIRECTORY: Mutex<Option<PageDirectory>> = Mutex::new(None);
+// where the next device mapping goes, they are never unmapped
+static NEXT_MMIO_ADDRESS: Mutex<VirtualAddress> = Mutex::new(MMIO_WINDOW.start);
+// the same for demand paged regions, they are never released either
+static NEXT_DEMAND_PAGED_ADDRESS: Mutex<VirtualAddress> = Mutex::new(DEMAND_PAGED_WINDOW.start);
+
+// builds the kernel's directory with the ranges in the direct map and switches to it. the boot
+// mapping goes away, so the heap's memory has to be among the ranges
+pub fn init(direct_mapped: &[Range<PhysicalAddress>]) -> Result<()> {
+    let mut directory = PageDirectory::new()?;
+
+    for range in direct_mapped {
+        directory.map_range(
+            physical_to_virtual(range.start),
+            range.clone(),
+            PageFlags::WRITABLE,
+        )?;
+    }
+
+    // nothing writes to the kernel's code, a stray pointer into it faults instead
+    for page in kernel_text().step_by(PAGE_SIZE) {
+        let flags = directory.flags(page).ok_or(PagingError::NotMapped(page))?;
+        directory.set_flags(page, flags - PageFlags::WRITABLE)?;
+    }
+
+    // an overflowing boot stack faults here instead of running into the bss below it. without a
+    // stack for the double fault that still resets the machine, but nothing gets overwritten
+    let stack_guard = boot_stack_guard();
+    directory.unmap(stack_guard.start)?;
+
+    unsafe { directory.activate() };
+    *KERNEL_PAGE_DIRECTORY.lock() = Some(directory);
+
+    regions::reserve("the direct map", DIRECT_MAP_GUARD, RegionKind::Guard)?;
+    regions::reserve("the boot stack", stack_guard, RegionKind::Guard)
+}
+
+// device registers can be anywhere in physical memory, they get uncached pages in the mmio window.
+// returns the address the range starts at in there
+pub fn map_mmio(range: Range<PhysicalAddress>) -> Result<VirtualAddress> {
+    let mut directory = KERNEL_PAGE_DIRECTORY.lock();
+    let directory = directory.as_mut().ok_or(PagingError::Uninitialized)?;
+    let mut next_address = NEXT_MMIO_ADDRESS.lock();
+
+    let offset = range.start % PAGE_SIZE;
+    let size = (offset + range.len() + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+
+    if MMIO_WINDOW.end - *next_address < size {
+        return Err(PagingError::MmioWindowFull(size));
+    }
+
+    let start = *next_address;
+    directory.map_range(start, range, PageFlags::WRITABLE | PageFlags::CACHE_DISABLE)?;
+    *next_address += size;
+
+    Ok(start + offset)
+}
+
+// sets aside `size` bytes whose pages get a zeroed frame on their first access, see
+// regions::resolve_fault. returns the address the region starts at
+pub fn reserve_demand_paged(
+    name: &'static str,
+    size: usize,
+    flags: PageFlags,
+) -> Result<VirtualAddress> {
+    let mut next_address = NEXT_DEMAND_PAGED_ADDRESS.lock();
+
+    let size = (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    if DEMAND_PAGED_WINDOW.end - *next_address < size {
+        return Err(PagingError::DemandPagedWindowFull(size));
+    }
+
+    let start = *next_address;
+    regions::reserve(name, start..start + size, RegionKind::DemandPaged(flags))?;
+    *next_address += size;
+
+    Ok(start)
+}
@@ -0,0 +1,213 @@
+use core::ops::Range;
+
+use crate::{
+    memory::{
+        physical::global_alloc::ALLOCATOR, physical_to_virtual, virtual_to_physical,
+        PhysicalAddress, VirtualAddress,
+    },
+    x86::control_registers::{enable_paging, invalidate_page, write_cr3},
+};
+
+use super::{
+    entry::{PageFlags, PageTable, PageTableEntry},
+    PagingError, Result, ENTRY_COUNT, HUGE_PAGE_SIZE, PAGE_SIZE,
+};
+
+// the directory index and the table index, intel sdm vol 3a figure 4-2
+fn indices(address: VirtualAddress) -> (usize, usize) {
+    (address >> 22, (address >> 12) & (ENTRY_COUNT - 1))
+}
+
+// tables come from the heap, so they are in the direct map
+unsafe fn table_at(address: PhysicalAddress) -> &'static mut PageTable {
+    &mut *(physical_to_virtual(address) as *mut PageTable)
+}
+
+// a zeroed page from the buddy allocator, every entry in it is not present
+fn allocate_table() -> Result<PhysicalAddress> {
+    let block = ALLOCATOR
+        .lock()
+        .allocate(PAGE_SIZE)
+        .map_err(PagingError::OutOfMemory)?;
+    debug_assert!(block.base_address % PAGE_SIZE == 0);
+
+    unsafe { (block.base_address as *mut PageTable).write_bytes(0, 1) };
+    Ok(virtual_to_physical(block.base_address))
+}
+
+fn check_aligned(address: usize) -> Result<()> {
+    match address % PAGE_SIZE {
+        0 => Ok(()),
+        _ => Err(PagingError::Unaligned(address)),
+    }
+}
+
+// the two levels of tables behind one address space. mappings made through it are always 4 KiB
+// pages, 4 MiB pages someone else put in the directory are only read
+pub struct PageDirectory {
+    address: PhysicalAddress,
+}
+
+impl PageDirectory {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            address: allocate_table()?,
+        })
+    }
+
+    fn directory(&self) -> &PageTable {
+        unsafe { table_at(self.address) }
+    }
+
+    fn directory_mut(&mut self) -> &mut PageTable {
+        unsafe { table_at(self.address) }
+    }
+
+    // the page's entry in its table, None without a table
+    fn entry(&self, address: VirtualAddress) -> Option<PageTableEntry> {
+        let (directory_index, table_index) = indices(address);
+        let directory_entry = self.directory().entries[directory_index];
+
+        if !directory_entry.is_present() || directory_entry.flags().contains(PageFlags::HUGE) {
+            return None;
+        }
+
+        Some(unsafe { table_at(directory_entry.address()) }.entries[table_index])
+    }
+
+    fn entry_mut(&mut self, address: VirtualAddress) -> Option<&mut PageTableEntry> {
+        let (directory_index, table_index) = indices(address);
+        let directory_entry = self.directory_mut().entries[directory_index];
+
+        if !directory_entry.is_present() || directory_entry.flags().contains(PageFlags::HUGE) {
+            return None;
+        }
+
+        Some(&mut unsafe { table_at(directory_entry.address()) }.entries[table_index])
+    }
+
+    // the table for the page is allocated when it's the first one in its 4 MiB
+    pub fn map(
+        &mut self,
+        virtual_address: VirtualAddress,
+        physical_address: PhysicalAddress,
+        flags: PageFlags,
+    ) -> Result<()> {
+        check_aligned(virtual_address)?;
+        check_aligned(physical_address)?;
+
+        let (directory_index, table_index) = indices(virtual_address);
+        let directory_entry = self.directory().entries[directory_index];
+
+        if directory_entry.flags().contains(PageFlags::HUGE) {
+            return Err(PagingError::AlreadyMapped(virtual_address));
+        }
+
+        // the directory entry allows everything, access is decided per page. except for user
+        // access, which both levels have to allow
+        let directory_flags = PageFlags::PRESENT | PageFlags::WRITABLE | (flags & PageFlags::USER);
+
+        let table_address = if directory_entry.is_present() {
+            directory_entry.address()
+        } else {
+            allocate_table()?
+        };
+        self.directory_mut().entries[directory_index] =
+            PageTableEntry::new(table_address, directory_entry.flags() | directory_flags);
+
+        let entry = &mut unsafe { table_at(table_address) }.entries[table_index];
+        if entry.is_present() {
+            return Err(PagingError::AlreadyMapped(virtual_address));
+        }
+
+        *entry = PageTableEntry::new(physical_address, flags | PageFlags::PRESENT);
+        Ok(())
+    }
+
+    // returns the frame the page was mapped to, it is not freed. empty tables are kept
+    pub fn unmap(&mut self, virtual_address: VirtualAddress) -> Result<PhysicalAddress> {
+        check_aligned(virtual_address)?;
+
+        let entry = self
+            .entry_mut(virtual_address)
+            .filter(|entry| entry.is_present())
+            .ok_or(PagingError::NotMapped(virtual_address))?;
+
+        let physical_address = entry.address();
+        *entry = PageTableEntry::default();
+        unsafe { invalidate_page(virtual_address) };
+
+        Ok(physical_address)
+    }
+
+    // replaces the flags of a mapped page, it stays present
+    pub fn set_flags(&mut self, virtual_address: VirtualAddress, flags: PageFlags) -> Result<()> {
+        check_aligned(virtual_address)?;
+
+        let entry = self
+            .entry_mut(virtual_address)
+            .filter(|entry| entry.is_present())
+            .ok_or(PagingError::NotMapped(virtual_address))?;
+
+        *entry = PageTableEntry::new(entry.address(), flags | PageFlags::PRESENT);
+        unsafe { invalidate_page(virtual_address) };
+
+        Ok(())
+    }
+
+    pub fn flags(&self, virtual_address: VirtualAddress) -> Option<PageFlags> {
+        self.entry(virtual_address)
+            .filter(|entry| entry.is_present())
+            .map(|entry| entry.flags())
+    }
+
+    pub fn translate(&self, virtual_address: VirtualAddress) -> Option<PhysicalAddress> {
+        let (directory_index, _) = indices(virtual_address);
+        let directory_entry = self.directory().entries[directory_index];
+
+        if directory_entry.is_present() && directory_entry.flags().contains(PageFlags::HUGE) {
+            let frame = directory_entry.address() & !(HUGE_PAGE_SIZE - 1);
+            return Some(frame | (virtual_address & (HUGE_PAGE_SIZE - 1)));
+        }
+
+        self.entry(virtual_address)
+            .filter(|entry| entry.is_present())
+            .map(|entry| entry.address() | (virtual_address & (PAGE_SIZE - 1)))
+    }
+
+    // maps the frames the range touches one after the other from the page `virtual_start` is in.
+    // pages already mapped to the same frame are left alone, ranges overlap, like the kernel image
+    // and the memory region it was loaded in
+    pub fn map_range(
+        &mut self,
+        virtual_start: VirtualAddress,
+        range: Range<PhysicalAddress>,
+        flags: PageFlags,
+    ) -> Result<()> {
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        // the last frame instead of the end, a range can end at the top of the address space
+        let first_frame = range.start & !(PAGE_SIZE - 1);
+        let last_frame = (range.end - 1) & !(PAGE_SIZE - 1);
+        let first_page = virtual_start & !(PAGE_SIZE - 1);
+
+        for (index, frame) in (first_frame..=last_frame).step_by(PAGE_SIZE).enumerate() {
+            let page = first_page + index * PAGE_SIZE;
+
+            match self.map(page, frame, flags) {
+                Err(PagingError::AlreadyMapped(_)) if self.translate(page) == Some(frame) => {}
+                result => result?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // the code doing this, its stack and the tables have to be mapped
+    pub unsafe fn activate(&self) {
+        write_cr3(self.address);
+        enable_paging();
+    }
+}
@@ -0,0 +1,124 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+use thiserror::Error;
+
+use crate::{
+    memory::{physical::global_alloc::ALLOCATOR, virtual_to_physical, VirtualAddress},
+    mutex::{Mutex, MutexGuard},
+    x86::interrupts::PageFaultErrorCode,
+};
+
+use super::{entry::PageFlags, PagingError, Result, KERNEL_PAGE_DIRECTORY, PAGE_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    // a frame is allocated and zeroed on the first access to each page
+    DemandPaged(PageFlags),
+    // never mapped, touching it means something ran past the region next to it
+    Guard,
+}
+
+// a part of the address space set aside without backing all of it up front
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub name: &'static str,
+    pub range: Range<VirtualAddress>,
+    pub kind: RegionKind,
+}
+
+#[derive(Error, Debug)]
+pub enum PageFaultError {
+    #[error("Null pointer dereference")]
+    NullPointer,
+    #[error("Nothing is reserved at the address")]
+    NotReserved,
+    #[error("Guard page of {0} hit")]
+    GuardPage(&'static str),
+    #[error("Access not allowed in {0}")]
+    ProtectionViolation(&'static str),
+    #[error("Failed to back the page: {0}")]
+    Paging(#[from] PagingError),
+    #[error("The {0} was locked, the fault may have come from inside it")]
+    Locked(&'static str),
+}
+
+static REGIONS: Mutex<Vec<Region>> = Mutex::new(Vec::new());
+
+// the faulting code may hold any lock, waiting for it in the handler would never end
+fn try_lock<'a, T>(
+    mutex: &'a Mutex<T>,
+    name: &'static str,
+) -> core::result::Result<MutexGuard<'a, T>, PageFaultError> {
+    mutex.try_lock().ok_or(PageFaultError::Locked(name))
+}
+
+// demand paged pages are backed from inside the page fault handler, so they must not be touched
+// for the first time while the allocator or the kernel page directory is locked
+pub fn reserve(name: &'static str, range: Range<VirtualAddress>, kind: RegionKind) -> Result<()> {
+    for address in [range.start, range.end] {
+        if address % PAGE_SIZE != 0 {
+            return Err(PagingError::Unaligned(address));
+        }
+    }
+
+    let mut regions = REGIONS.lock();
+    if let Some(region) = regions
+        .iter()
+        .find(|region| region.range.start < range.end && range.start < region.range.end)
+    {
+        return Err(PagingError::Overlapping(region.range.start));
+    }
+
+    regions.push(Region { name, range, kind });
+    Ok(())
+}
+
+// called by the page fault handler, Ok when the access can be retried
+pub fn resolve_fault(
+    address: VirtualAddress,
+    error_code: PageFaultErrorCode,
+) -> core::result::Result<(), PageFaultError> {
+    if address < PAGE_SIZE {
+        return Err(PageFaultError::NullPointer);
+    }
+
+    let region = try_lock(&REGIONS, "region list")?
+        .iter()
+        .find(|region| region.range.contains(&address))
+        .cloned()
+        .ok_or(PageFaultError::NotReserved)?;
+
+    let flags = match region.kind {
+        RegionKind::Guard => return Err(PageFaultError::GuardPage(region.name)),
+        RegionKind::DemandPaged(flags) => flags,
+    };
+
+    // a present page faulted because of its flags, backing it again wouldn't change them
+    let allowed = !error_code
+        .intersects(PageFaultErrorCode::PRESENT | PageFaultErrorCode::RESERVED)
+        && (!error_code.contains(PageFaultErrorCode::WRITE) || flags.contains(PageFlags::WRITABLE))
+        && (!error_code.contains(PageFaultErrorCode::USER) || flags.contains(PageFlags::USER));
+    if !allowed {
+        return Err(PageFaultError::ProtectionViolation(region.name));
+    }
+
+    // the directory is taken first, a frame that was allocated can always be given back.
+    // the allocator is only held around its own calls, map needs it for new tables
+    let mut directory = try_lock(&KERNEL_PAGE_DIRECTORY, "kernel page directory")?;
+    let directory = directory.as_mut().ok_or(PagingError::Uninitialized)?;
+
+    let block = try_lock(&ALLOCATOR, "allocator")?
+        .allocate(PAGE_SIZE)
+        .map_err(PagingError::OutOfMemory)?;
+    unsafe { (block.base_address as *mut u8).write_bytes(0, PAGE_SIZE) };
+
+    let page = address & !(PAGE_SIZE - 1);
+    if let Err(err) = directory.map(page, virtual_to_physical(block.base_address), flags) {
+        // the faulting code doesn't hold the allocator or the allocation above would have failed,
+        // so this can wait for it like map does
+        let _ = ALLOCATOR.lock().free(block);
+        return Err(err.into());
+    }
+
+    Ok(())
+}
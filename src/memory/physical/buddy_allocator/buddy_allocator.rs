@@ -15,11 +15,14 @@ pub struct BuddyAllocator {
 }
 
 impl BuddyAllocator {
+    // we must be biggest block aligned for xor trick to work at buddy pair search
+    pub const ALIGNMENT: usize = BLOCK_SIZES[BLOCK_SIZES.len() - 1];
+
     pub fn new(base_address: usize, size: usize) -> Self {
-        let largest_block_size = *BLOCK_SIZES.last().unwrap();
+        let largest_block_size = Self::ALIGNMENT;
 
-        // we must be biggest block aligned for xor trick to work at buddy pair search
-        let aligned_base_address = base_address + (base_address % largest_block_size);
+        let aligned_base_address =
+            (base_address + largest_block_size - 1) & !(largest_block_size - 1);
 
         let size = size - (aligned_base_address - base_address);
         let base_address = aligned_base_address;
@@ -119,33 +122,40 @@ impl BuddyAllocator {
             return Err(AllocatorError::UnsupportedSize);
         };
 
+        // the memory left can be in blocks too small for the size
+        let base_address = self
+            .inner_alloc(area_index)
+            .ok_or(AllocatorError::OutOfMemory)?;
+
         self.remaining_memory -= self.memory_areas[area_index].block_size;
 
         return Ok(PhysicalMemoryBlock {
-            base_address: self.inner_alloc(area_index),
+            base_address,
             size: self.memory_areas[area_index].block_size,
         });
     }
 
-    fn inner_alloc(&mut self, area_index: usize) -> usize {
-        assert!(area_index < self.memory_areas.len());
+    fn inner_alloc(&mut self, area_index: usize) -> Option<usize> {
+        if area_index >= self.memory_areas.len() {
+            return None;
+        }
 
         if let Some(meme_block) = self.memory_areas[area_index].allocate_block(self.base_address) {
-            meme_block
+            Some(meme_block)
         } else {
-            let meme_block = self.inner_alloc(area_index + 1);
+            let meme_block = self.inner_alloc(area_index + 1)?;
 
             self.memory_areas[area_index]
                 .free_block(meme_block, self.base_address)
                 .expect(
                     "We can't find valid memory block while splitting from largess memory blocks",
                 );
-            meme_block + self.memory_areas[area_index].block_size
+            Some(meme_block + self.memory_areas[area_index].block_size)
         }
     }
 
     pub fn free(&mut self, block_to_free: PhysicalMemoryBlock) -> Result<()> {
-        if block_to_free.base_address > self.memory_areas.last().unwrap().block_size + self.size {
+        if !self.contains(block_to_free.base_address) {
             return Err(AllocatorError::FreeOutOfBounds);
         }
 
@@ -181,6 +191,10 @@ impl BuddyAllocator {
         None
     }
 
+    pub fn contains(&self, address: usize) -> bool {
+        (self.base_address..self.base_address + self.size).contains(&address)
+    }
+
     pub fn smallest_block_size(&self) -> usize {
         self.memory_areas.first().unwrap().block_size
     }
@@ -1,13 +1,13 @@
 use super::{
-    buddy_allocator::buddy_allocator::BuddyAllocator, inline_free_list::InlineFreeList,
-    AllocatorError, PhysicalMemoryBlock, Result,
+    inline_free_list::InlineFreeList, physical_memory_manager::PhysicalMemoryManager,
+    AllocatorError, PhysicalMemoryAllocator, PhysicalMemoryBlock, Result,
 };
 
 const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024];
 
 pub struct FixedBlockAllocator {
     free_lists: [InlineFreeList<()>; BLOCK_SIZES.len()],
-    buddy_allocator: Option<BuddyAllocator>,
+    memory_manager: Option<PhysicalMemoryManager>,
 }
 
 impl FixedBlockAllocator {
@@ -18,12 +18,12 @@ impl FixedBlockAllocator {
 
         FixedBlockAllocator {
             free_lists: FREE_LISTS,
-            buddy_allocator: None,
+            memory_manager: None,
         }
     }
 
-    pub fn init(&mut self, buddy_allocator: BuddyAllocator) {
-        self.buddy_allocator = Some(buddy_allocator);
+    pub fn init(&mut self, memory_manager: PhysicalMemoryManager) {
+        self.memory_manager = Some(memory_manager);
     }
 
     pub fn allocate(&mut self, size: usize) -> Result<PhysicalMemoryBlock> {
@@ -42,7 +42,7 @@ impl FixedBlockAllocator {
                 })
             }
             None => self
-                .buddy_allocator
+                .memory_manager
                 .as_mut()
                 .ok_or(AllocatorError::UninitializedAllocator)?
                 .allocate(size),
@@ -56,7 +56,7 @@ impl FixedBlockAllocator {
                 Ok(())
             }
             None => self
-                .buddy_allocator
+                .memory_manager
                 .as_mut()
                 .ok_or(AllocatorError::UninitializedAllocator)?
                 .free(block_to_free),
@@ -69,11 +69,11 @@ impl FixedBlockAllocator {
 
     fn grow_free_list(&mut self, block_index: usize) -> Result<()> {
         let inner_allocator = self
-            .buddy_allocator
+            .memory_manager
             .as_mut()
             .ok_or(AllocatorError::UninitializedAllocator)?;
 
-        let new_chunk = inner_allocator.allocate(inner_allocator.smallest_block_size())?;
+        let new_chunk = inner_allocator.allocate(inner_allocator.smallest_block_size()?)?;
         let free_list_block_size = BLOCK_SIZES[block_index];
         let free_list = &mut self.free_lists[block_index];
 
@@ -1,15 +1,17 @@
-use super::PhysicalAddress;
+use super::VirtualAddress;
 
 pub mod buddy_allocator;
 mod fixed_block_allocator;
 pub mod global_alloc;
 mod inline_free_list;
+pub mod physical_memory_manager;
 
 pub type Result<T> = core::result::Result<T, AllocatorError>;
 
 #[derive(Debug)]
 pub struct PhysicalMemoryBlock {
-    pub base_address: PhysicalAddress,
+    // where the block is in the direct map, virtual_to_physical gives the frame
+    pub base_address: VirtualAddress,
     pub size: usize,
 }
 
@@ -24,4 +26,7 @@ pub enum AllocatorError {
     UnsupportedSize,
     FreeOutOfBounds,
     UninitializedAllocator,
+    // ranges handed to the physical memory manager after it was populated
+    AlreadyPopulated,
+    TooManyRanges,
 }
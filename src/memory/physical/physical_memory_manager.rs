@@ -0,0 +1,200 @@
+use core::ops::Range;
+
+use crate::{
+    memory::{paging::PAGE_SIZE, physical_to_virtual, PhysicalAddress},
+    println,
+};
+
+use super::{
+    buddy_allocator::buddy_allocator::BuddyAllocator, AllocatorError, PhysicalMemoryAllocator,
+    PhysicalMemoryBlock, Result,
+};
+
+// the memory map of a pc has a handful of usable regions, more than this are left out
+const MAX_REGIONS: usize = 16;
+// reserved ranges split regions, so there can be more arenas than regions
+const MAX_ARENAS: usize = 32;
+// ranges that touch are merged, grub puts the modules right after each other
+const MAX_RESERVED: usize = 32;
+
+// hands out the memory of every usable region, each one is a buddy arena of its own.
+// regions and reserved ranges are collected first, the arenas only exist after `populate`.
+// arenas are tried in the order the regions came in
+#[derive(Default)]
+pub struct PhysicalMemoryManager {
+    arenas: [Option<BuddyAllocator>; MAX_ARENAS],
+    regions: [Range<PhysicalAddress>; MAX_REGIONS],
+    reserved: [Range<PhysicalAddress>; MAX_RESERVED],
+    populated: bool,
+}
+
+impl PhysicalMemoryManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // memory the allocator may use, it has to be in the direct map
+    pub fn add_region(&mut self, region: Range<PhysicalAddress>) -> Result<()> {
+        let populated = self.populated;
+        push_range(&mut self.regions, region, populated)
+    }
+
+    // carves a range out of the regions, for memory that is in use before the allocator is, like the
+    // kernel, the multiboot modules or firmware tables. it doesn't have to be in a region.
+    // the range grows to whole pages, nobody else could use the rest of them anyway
+    pub fn reserve(&mut self, range: Range<PhysicalAddress>) -> Result<()> {
+        if self.populated {
+            return Err(AllocatorError::AlreadyPopulated);
+        }
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let start = range.start & !(PAGE_SIZE - 1);
+        let end = range
+            .end
+            .checked_next_multiple_of(PAGE_SIZE)
+            .unwrap_or(usize::MAX);
+        let mut range = start..end;
+
+        // the ranges in the slots never touch each other, so one pass finds all that touch this one
+        for slot in self
+            .reserved
+            .iter_mut()
+            .filter(|slot| !Range::is_empty(slot))
+        {
+            if slot.start <= range.end && range.start <= slot.end {
+                range = slot.start.min(range.start)..slot.end.max(range.end);
+                *slot = 0..0;
+            }
+        }
+
+        push_range(&mut self.reserved, range, false)
+    }
+
+    // builds the arenas from what is left of the regions, nothing can be added afterwards
+    pub fn populate(&mut self) {
+        self.populated = true;
+
+        let Self {
+            arenas,
+            regions,
+            reserved,
+            ..
+        } = self;
+
+        for region in regions.iter() {
+            carve(region.clone(), reserved, &mut |piece| {
+                add_arena(arenas, piece)
+            });
+        }
+    }
+
+    pub fn remaining_memory(&self) -> usize {
+        self.arenas
+            .iter()
+            .flatten()
+            .map(BuddyAllocator::remaining_memory)
+            .sum()
+    }
+
+    pub fn arena_count(&self) -> usize {
+        self.arenas.iter().flatten().count()
+    }
+
+    pub fn smallest_block_size(&self) -> Result<usize> {
+        self.arenas
+            .iter()
+            .flatten()
+            .next()
+            .map(BuddyAllocator::smallest_block_size)
+            .ok_or(AllocatorError::UninitializedAllocator)
+    }
+}
+
+impl PhysicalMemoryAllocator for PhysicalMemoryManager {
+    fn allocate(&mut self, size: usize) -> Result<PhysicalMemoryBlock> {
+        for arena in self.arenas.iter_mut().flatten() {
+            match arena.allocate(size) {
+                Err(AllocatorError::OutOfMemory) => continue,
+                result => return result,
+            }
+        }
+
+        Err(AllocatorError::OutOfMemory)
+    }
+
+    fn free(&mut self, physical_memory_block: PhysicalMemoryBlock) -> Result<()> {
+        self.arenas
+            .iter_mut()
+            .flatten()
+            .find(|arena| arena.contains(physical_memory_block.base_address))
+            .ok_or(AllocatorError::FreeOutOfBounds)?
+            .free(physical_memory_block)
+    }
+}
+
+fn push_range(
+    ranges: &mut [Range<PhysicalAddress>],
+    range: Range<PhysicalAddress>,
+    populated: bool,
+) -> Result<()> {
+    if populated {
+        return Err(AllocatorError::AlreadyPopulated);
+    }
+    if range.is_empty() {
+        return Ok(());
+    }
+
+    let index = ranges
+        .iter()
+        .position(Range::is_empty)
+        .ok_or(AllocatorError::TooManyRanges)?;
+    ranges[index] = range;
+
+    Ok(())
+}
+
+// calls `add` with whatever is left of the region on both sides of the reserved ranges in it
+fn carve(
+    region: Range<PhysicalAddress>,
+    reserved: &[Range<PhysicalAddress>],
+    add: &mut impl FnMut(Range<PhysicalAddress>),
+) {
+    if region.is_empty() {
+        return;
+    }
+
+    match reserved
+        .iter()
+        .find(|hole| hole.start < region.end && region.start < hole.end)
+    {
+        Some(hole) => {
+            carve(region.start..hole.start.max(region.start), reserved, add);
+            carve(hole.end.min(region.end)..region.end, reserved, add);
+        }
+        None => add(region),
+    }
+}
+
+fn add_arena(arenas: &mut [Option<BuddyAllocator>], region: Range<PhysicalAddress>) {
+    // the arena starts at the next multiple of its largest block, smaller rests are lost
+    let aligned_start =
+        (region.start + BuddyAllocator::ALIGNMENT - 1) & !(BuddyAllocator::ALIGNMENT - 1);
+    if region.end.saturating_sub(aligned_start) < BuddyAllocator::ALIGNMENT {
+        return;
+    }
+
+    match arenas.iter_mut().find(|arena| arena.is_none()) {
+        Some(arena) => {
+            *arena = Some(BuddyAllocator::new(
+                physical_to_virtual(region.start),
+                region.len(),
+            ))
+        }
+        None => println!(
+            "Ignoring physical memory at {:#x}..{:#x}, too many arenas",
+            region.start, region.end
+        ),
+    }
+}
@@ -0,0 +1,22 @@
+use core::{mem::size_of, slice, str};
+
+use super::tags::TagType;
+
+// whatever follows the kernel path on the multiboot2 line in grub.cfg
+#[repr(C)]
+pub struct CommandLineTag {
+    tag_type: TagType,
+    size: u32,
+    // a zero terminated utf-8 string starts here
+    first_byte: u8,
+}
+
+impl CommandLineTag {
+    pub fn command_line(&self) -> &str {
+        // the size covers the header and the terminating zero
+        let length = self.size as usize - 2 * size_of::<u32>() - 1;
+        let bytes = unsafe { slice::from_raw_parts(&self.first_byte as *const u8, length) };
+
+        str::from_utf8(bytes).unwrap_or("")
+    }
+}
@@ -28,7 +28,9 @@ pub struct MemoryMapEntry {
 pub enum MemoryEntryType {
     Available = 1,
     Reserved = 2,
-    AcpiAvailable = 3, // TODO: find out what is Acpi
+    // acpi tables, usable once the os is done reading them
+    AcpiAvailable = 3,
+    // acpi non-volatile storage, it has to survive hibernation
     ReservedHibernate = 4,
     Defective = 5,
 }
@@ -46,6 +48,16 @@ impl MemoryMapTag {
         self.get_memory_map_entries()
             .filter(|entry| entry.memory_type == MemoryEntryType::Available)
     }
+
+    // memory the firmware still has something in, it can overlap the available entries
+    pub fn get_firmware_memory_map_entries(&self) -> impl Iterator<Item = &MemoryMapEntry> {
+        self.get_memory_map_entries().filter(|entry| {
+            matches!(
+                entry.memory_type,
+                MemoryEntryType::AcpiAvailable | MemoryEntryType::ReservedHibernate
+            )
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1,11 +1,15 @@
 // my take on https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#Boot-information-format with a lil inspection from phil
 
 use self::{
+    command_line::CommandLineTag,
     memory_map::MemoryMapTag,
+    module::ModuleTag,
     tags::{Tag, TagIter, TagType},
 };
 
+pub mod command_line;
 pub mod memory_map;
+pub mod module;
 pub mod tags;
 
 pub struct MultiBootInfo {
@@ -25,6 +29,11 @@ impl MultiBootInfo {
         }
     }
 
+    // the whole structure, tags included
+    pub fn size(&self) -> usize {
+        unsafe { (*self.inner).total_size as usize }
+    }
+
     pub fn tags(&self) -> TagIter {
         // tags come right after the inner
         TagIter::new(unsafe { self.inner.offset(1) } as *const _)
@@ -38,4 +47,16 @@ impl MultiBootInfo {
         self.get_tag(TagType::MemoryMap)
             .map(|tag| unsafe { &*(tag as *const Tag as *const MemoryMapTag) })
     }
+
+    pub fn command_line_tag(&self) -> Option<&CommandLineTag> {
+        self.get_tag(TagType::CommandLine)
+            .map(|tag| unsafe { &*(tag as *const Tag as *const CommandLineTag) })
+    }
+
+    // there is a tag for every module, in the order of the module2 lines
+    pub fn module_tags(&self) -> impl Iterator<Item = &ModuleTag> {
+        self.tags()
+            .filter(|tag| tag.tag_type == TagType::Module)
+            .map(|tag| unsafe { &*(tag as *const Tag as *const ModuleTag) })
+    }
 }
@@ -0,0 +1,42 @@
+use core::{mem::size_of, slice, str};
+
+use crate::memory::physical_to_virtual;
+
+use super::tags::TagType;
+
+// a file grub loaded next to the kernel, from a module2 line in grub.cfg
+#[repr(C)]
+pub struct ModuleTag {
+    tag_type: TagType,
+    size: u32,
+    module_start: u32,
+    module_end: u32,
+    // a zero terminated utf-8 string with whatever follows the path on the module2 line
+    first_byte: u8,
+}
+
+impl ModuleTag {
+    // physical addresses
+    pub fn start(&self) -> usize {
+        self.module_start as usize
+    }
+
+    // one past the last byte
+    pub fn end(&self) -> usize {
+        self.module_end as usize
+    }
+
+    pub fn name(&self) -> &str {
+        // the tag's size counts type, size, start, end and the terminating zero of the string
+        let length = self.size as usize - 4 * size_of::<u32>() - 1;
+        let bytes = unsafe { slice::from_raw_parts(&self.first_byte as *const u8, length) };
+
+        str::from_utf8(bytes).unwrap_or("")
+    }
+
+    // the memory stays valid as long as nobody allocates over it, so it has to be kept out of the allocator
+    pub fn bytes(&self) -> &'static [u8] {
+        let start = physical_to_virtual(self.start()) as *const u8;
+        unsafe { slice::from_raw_parts(start, self.end() - self.start()) }
+    }
+}
@@ -6,6 +6,8 @@ use core::marker::PhantomData;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TagType {
     End = 0,
+    CommandLine = 1,
+    Module = 3,
     MemoryMap = 6,
 }
 
@@ -11,14 +11,14 @@ use crate::x86::{
     interrupts::{disable_interrupt, enable_interrupt},
 };
 
-pub struct Mutex<T> {
-    data: UnsafeCell<T>,
+pub struct Mutex<T: ?Sized> {
     lock: AtomicBool,
+    data: UnsafeCell<T>,
 }
 
-unsafe impl<T> Sync for Mutex<T> {}
+unsafe impl<T: ?Sized> Sync for Mutex<T> {}
 
-pub struct MutexGuard<'a, T> {
+pub struct MutexGuard<'a, T: ?Sized> {
     data: &'a mut T,
     lock: &'a AtomicBool,
     old_interrupt_flag: AtomicBool,
@@ -32,6 +32,12 @@ impl<T> Mutex<T> {
         }
     }
 
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
     pub fn lock(&self) -> MutexGuard<T> {
         let old_interrupt_flag = unsafe { get_cpu_flags().interrupt_enabled() };
         unsafe { disable_interrupt() };
@@ -52,8 +58,28 @@ impl<T> Mutex<T> {
         }
     }
 
-    pub fn into_inner(self) -> T {
-        self.data.into_inner()
+    // None instead of spinning when the lock is held, for code that may have interrupted the holder
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let old_interrupt_flag = unsafe { get_cpu_flags().interrupt_enabled() };
+        unsafe { disable_interrupt() };
+        if self
+            .lock
+            .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            if old_interrupt_flag {
+                unsafe { enable_interrupt() };
+            }
+            return None;
+        }
+
+        unsafe {
+            Some(MutexGuard {
+                lock: &self.lock,
+                data: &mut *self.data.get(),
+                old_interrupt_flag: AtomicBool::new(old_interrupt_flag),
+            })
+        }
     }
 
     pub unsafe fn get_raw_ptr(&self) -> *const T {
@@ -65,7 +91,7 @@ impl<T> Mutex<T> {
     }
 }
 
-impl<'a, T> core::ops::Deref for MutexGuard<'a, T> {
+impl<'a, T: ?Sized> core::ops::Deref for MutexGuard<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -73,13 +99,13 @@ impl<'a, T> core::ops::Deref for MutexGuard<'a, T> {
     }
 }
 
-impl<'a, T> core::ops::DerefMut for MutexGuard<'a, T> {
+impl<'a, T: ?Sized> core::ops::DerefMut for MutexGuard<'a, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         self.data
     }
 }
 
-impl<'a, T> Drop for MutexGuard<'a, T> {
+impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
     fn drop(&mut self) {
         self.lock.store(true, Ordering::SeqCst);
 
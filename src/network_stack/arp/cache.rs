@@ -0,0 +1,54 @@
+use alloc::vec::Vec;
+
+use crate::{
+    mutex::Mutex,
+    network_stack::{
+        ethernet::{EitherType, EthernetAddress},
+        interface::InterfaceId,
+        ipv4::Ipv4Address,
+        neighbor_cache::NeighborCache,
+        packet_buffer::PacketBuffer,
+    },
+    x86::pit::TICKS_PER_SECOND,
+};
+
+// entries are forgotten after a minute so a host that moved gets asked again
+const REACHABLE_TIMEOUT: usize = 60 * TICKS_PER_SECOND;
+const REQUEST_INTERVAL: usize = TICKS_PER_SECOND;
+const MAX_REQUESTS: u8 = 3;
+
+pub struct PendingPacket {
+    pub ether_type: EitherType,
+    pub packet: PacketBuffer,
+}
+
+static NEIGHBORS: Mutex<NeighborCache<Ipv4Address, PendingPacket>> = Mutex::new(
+    NeighborCache::new("ARP", REACHABLE_TIMEOUT, REQUEST_INTERVAL, MAX_REQUESTS),
+);
+
+pub fn lookup(interface_id: InterfaceId, address: Ipv4Address) -> Option<EthernetAddress> {
+    NEIGHBORS.lock().lookup(interface_id, address)
+}
+
+pub(super) fn queue_packet(
+    interface_id: InterfaceId,
+    address: Ipv4Address,
+    packet: PendingPacket,
+) -> bool {
+    NEIGHBORS.lock().queue_packet(interface_id, address, packet)
+}
+
+pub(super) fn update(
+    interface_id: InterfaceId,
+    address: Ipv4Address,
+    ethernet_address: EthernetAddress,
+    create: bool,
+) -> Vec<PendingPacket> {
+    NEIGHBORS
+        .lock()
+        .update(interface_id, address, ethernet_address, create)
+}
+
+pub(super) fn expire() -> Vec<(InterfaceId, Ipv4Address)> {
+    NEIGHBORS.lock().expire()
+}
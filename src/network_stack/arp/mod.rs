@@ -0,0 +1,406 @@
+// https://datatracker.ietf.org/doc/html/rfc826
+// duplicate address detection follows https://datatracker.ietf.org/doc/html/rfc5227
+
+use alloc::vec::Vec;
+use thiserror::Error;
+
+use crate::{
+    mutex::Mutex,
+    pci::drivers::network::Result,
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::{
+    ethernet::{EitherType, EthernetAddress},
+    interface::{get_device, ipv4_address, set_ipv4_cidr, transmit_packet, InterfaceId},
+    ipv4::{Ipv4Address, Ipv4Cidr},
+    packet_buffer::PacketBuffer,
+};
+
+use self::cache::PendingPacket;
+
+pub mod cache;
+
+pub const ARP_PACKET_SIZE: usize = 28;
+const ETHERNET_HARDWARE_TYPE: u16 = 1;
+const ETHERNET_ADDRESS_LENGTH: u8 = 6;
+const IPV4_ADDRESS_LENGTH: u8 = 4;
+
+const PROBE_COUNT: u8 = 3;
+const PROBE_INTERVAL: usize = TICKS_PER_SECOND;
+// how long we keep listening for a conflict after the last probe
+const ANNOUNCE_WAIT: usize = 2 * TICKS_PER_SECOND;
+
+#[derive(Error, Debug)]
+pub enum ArpError {
+    #[error("Packet of {0} bytes is too short for an ARP packet")]
+    TooShort(usize),
+    #[error("Unsupported hardware type {0}")]
+    UnsupportedHardwareType(u16),
+    #[error("Unsupported protocol type {0:?}")]
+    UnsupportedProtocolType(EitherType),
+    #[error("Unsupported address lengths, hardware: {hardware}, protocol: {protocol}")]
+    UnsupportedAddressLength { hardware: u8, protocol: u8 },
+    #[error("Unknown operation {0}")]
+    UnknownOperation(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Request,
+    Reply,
+}
+
+impl TryFrom<u16> for Operation {
+    type Error = ArpError;
+
+    fn try_from(value: u16) -> core::result::Result<Self, ArpError> {
+        match value {
+            1 => Ok(Self::Request),
+            2 => Ok(Self::Reply),
+            value => Err(ArpError::UnknownOperation(value)),
+        }
+    }
+}
+
+impl From<Operation> for u16 {
+    fn from(value: Operation) -> Self {
+        match value {
+            Operation::Request => 1,
+            Operation::Reply => 2,
+        }
+    }
+}
+
+// we only speak ARP for IPv4 over ethernet, which is all anyone uses it for
+#[derive(Debug, Clone, Copy)]
+pub struct ArpPacket {
+    pub operation: Operation,
+    pub sender_hardware_address: EthernetAddress,
+    pub sender_protocol_address: Ipv4Address,
+    pub target_hardware_address: EthernetAddress,
+    pub target_protocol_address: Ipv4Address,
+}
+
+impl ArpPacket {
+    pub fn parse(bytes: &[u8]) -> core::result::Result<Self, ArpError> {
+        if bytes.len() < ARP_PACKET_SIZE {
+            return Err(ArpError::TooShort(bytes.len()));
+        }
+
+        let hardware_type = u16::from_be_bytes([bytes[0], bytes[1]]);
+        if hardware_type != ETHERNET_HARDWARE_TYPE {
+            return Err(ArpError::UnsupportedHardwareType(hardware_type));
+        }
+
+        let protocol_type = EitherType::from(u16::from_be_bytes([bytes[2], bytes[3]]));
+        if protocol_type != EitherType::Ipv4 {
+            return Err(ArpError::UnsupportedProtocolType(protocol_type));
+        }
+
+        if bytes[4] != ETHERNET_ADDRESS_LENGTH || bytes[5] != IPV4_ADDRESS_LENGTH {
+            return Err(ArpError::UnsupportedAddressLength {
+                hardware: bytes[4],
+                protocol: bytes[5],
+            });
+        }
+
+        Ok(Self {
+            operation: Operation::try_from(u16::from_be_bytes([bytes[6], bytes[7]]))?,
+            sender_hardware_address: EthernetAddress::from_slice(&bytes[8..14]),
+            sender_protocol_address: Ipv4Address::from_slice(&bytes[14..18]),
+            target_hardware_address: EthernetAddress::from_slice(&bytes[18..24]),
+            target_protocol_address: Ipv4Address::from_slice(&bytes[24..28]),
+        })
+    }
+
+    pub fn to_packet(self) -> PacketBuffer {
+        let mut packet = PacketBuffer::from_payload(&[]);
+
+        packet.extend_from_slice(&ETHERNET_HARDWARE_TYPE.to_be_bytes());
+        packet.extend_from_slice(&u16::from(EitherType::Ipv4).to_be_bytes());
+        packet.extend_from_slice(&[ETHERNET_ADDRESS_LENGTH, IPV4_ADDRESS_LENGTH]);
+        packet.extend_from_slice(&u16::from(self.operation).to_be_bytes());
+        packet.extend_from_slice(&self.sender_hardware_address.bytes);
+        packet.extend_from_slice(&self.sender_protocol_address.bytes);
+        packet.extend_from_slice(&self.target_hardware_address.bytes);
+        packet.extend_from_slice(&self.target_protocol_address.bytes);
+
+        packet
+    }
+}
+
+struct AddressProbe {
+    interface_id: InterfaceId,
+    cidr: Ipv4Cidr,
+    probes_sent: u8,
+    last_probe_at: usize,
+    // ticks until the next probe, or until the address is assigned after the last one
+    wait: usize,
+}
+
+// addresses that are still being checked for duplicates, they are not assigned to the interface yet
+static ADDRESS_PROBES: Mutex<Vec<AddressProbe>> = Mutex::new(Vec::new());
+
+enum ProbeAction {
+    Probe(InterfaceId, Ipv4Address),
+    Assign(InterfaceId, Ipv4Cidr),
+}
+
+// starts duplicate address detection, the address is assigned once nobody claims it
+pub fn configure_address(interface_id: InterfaceId, cidr: Ipv4Cidr) {
+    set_ipv4_cidr(interface_id, None);
+
+    let mut probes = ADDRESS_PROBES.lock();
+    probes.retain(|probe| probe.interface_id != interface_id);
+    probes.push(AddressProbe {
+        interface_id,
+        cidr,
+        probes_sent: 0,
+        last_probe_at: pit::ticks(),
+        wait: 0,
+    });
+}
+
+// sends the packet to a neighbor on the link, it waits in the cache while the address is resolved
+pub fn send_packet(
+    interface_id: InterfaceId,
+    next_hop: Ipv4Address,
+    ether_type: EitherType,
+    packet: PacketBuffer,
+) -> Result<()> {
+    if next_hop == Ipv4Address::BROADCAST {
+        return transmit_packet(
+            interface_id,
+            EthernetAddress::broadcast(),
+            ether_type,
+            packet,
+        );
+    }
+
+    if let Some(ethernet_address) = cache::lookup(interface_id, next_hop) {
+        return transmit_packet(interface_id, ethernet_address, ether_type, packet);
+    }
+
+    if cache::queue_packet(interface_id, next_hop, PendingPacket { ether_type, packet }) {
+        send_request(interface_id, next_hop)?;
+    }
+
+    Ok(())
+}
+
+pub fn handle_packet(interface_id: InterfaceId, data: &[u8]) {
+    let packet = match ArpPacket::parse(data) {
+        Ok(packet) => packet,
+        Err(err) => {
+            println!("Dropped ARP packet on interface {}: {}", interface_id, err);
+            return;
+        }
+    };
+
+    let Some(device) = get_device(interface_id) else {
+        return;
+    };
+    let our_ethernet_address = device.lock().ethernet_address();
+
+    // our own packets can be looped back to us, they tell us nothing
+    if packet.sender_hardware_address == our_ethernet_address {
+        return;
+    }
+
+    if detect_conflict(interface_id, &packet) {
+        return;
+    }
+
+    // probes come from hosts without an address yet
+    if packet.sender_protocol_address.is_unspecified() {
+        return;
+    }
+
+    let our_address = ipv4_address(interface_id);
+    let targets_us = our_address == Some(packet.target_protocol_address);
+
+    // the merge step from rfc 826, only hosts talking to us get a new entry
+    let pending = cache::update(
+        interface_id,
+        packet.sender_protocol_address,
+        packet.sender_hardware_address,
+        targets_us,
+    );
+
+    for pending_packet in pending {
+        if let Err(err) = transmit_packet(
+            interface_id,
+            packet.sender_hardware_address,
+            pending_packet.ether_type,
+            pending_packet.packet,
+        ) {
+            println!("Failed to send a packet waiting on ARP: {}", err);
+        }
+    }
+
+    if let (true, Operation::Request, Some(our_address)) =
+        (targets_us, packet.operation, our_address)
+    {
+        let reply = ArpPacket {
+            operation: Operation::Reply,
+            sender_hardware_address: our_ethernet_address,
+            sender_protocol_address: our_address,
+            target_hardware_address: packet.sender_hardware_address,
+            target_protocol_address: packet.sender_protocol_address,
+        };
+
+        if let Err(err) = transmit_packet(
+            interface_id,
+            packet.sender_hardware_address,
+            EitherType::Arp,
+            reply.to_packet(),
+        ) {
+            println!("Failed to send ARP reply: {}", err);
+        }
+    }
+}
+
+// returns true when the packet claims an address that we use or are probing for
+fn detect_conflict(interface_id: InterfaceId, packet: &ArpPacket) -> bool {
+    {
+        let mut probes = ADDRESS_PROBES.lock();
+
+        if let Some(position) = probes.iter().position(|probe| {
+            probe.interface_id == interface_id
+                && (packet.sender_protocol_address == probe.cidr.address
+                    // someone else probing for the same address at the same time
+                    || (packet.sender_protocol_address.is_unspecified()
+                        && packet.target_protocol_address == probe.cidr.address))
+        }) {
+            let probe = probes.remove(position);
+            println!(
+                "Address {} on interface {} is already used by {}, giving up on it",
+                probe.cidr.address, interface_id, packet.sender_hardware_address
+            );
+            return true;
+        }
+    }
+
+    if ipv4_address(interface_id) == Some(packet.sender_protocol_address) {
+        println!(
+            "Address {} on interface {} is also claimed by {}, defending it",
+            packet.sender_protocol_address, interface_id, packet.sender_hardware_address
+        );
+
+        if let Err(err) = announce(interface_id) {
+            println!("Failed to defend address: {}", err);
+        }
+        return true;
+    }
+
+    false
+}
+
+// drives duplicate address detection and the neighbor cache timers, called from the network stack poll loop
+pub fn poll_timers() {
+    let mut actions = Vec::new();
+
+    {
+        let mut probes = ADDRESS_PROBES.lock();
+
+        probes.retain_mut(|probe| {
+            if !pit::elapsed(probe.last_probe_at, probe.wait) {
+                return true;
+            }
+
+            if probe.probes_sent == PROBE_COUNT {
+                actions.push(ProbeAction::Assign(probe.interface_id, probe.cidr));
+                return false;
+            }
+
+            probe.probes_sent += 1;
+            probe.last_probe_at = pit::ticks();
+            probe.wait = if probe.probes_sent == PROBE_COUNT {
+                ANNOUNCE_WAIT
+            } else {
+                PROBE_INTERVAL
+            };
+            actions.push(ProbeAction::Probe(probe.interface_id, probe.cidr.address));
+            true
+        });
+    }
+
+    // the locks above are released before we transmit
+    for action in actions {
+        let result = match action {
+            ProbeAction::Probe(interface_id, address) => send_probe(interface_id, address),
+            ProbeAction::Assign(interface_id, cidr) => {
+                set_ipv4_cidr(interface_id, Some(cidr));
+                println!("Interface {} is now {}", interface_id, cidr);
+                announce(interface_id)
+            }
+        };
+
+        if let Err(err) = result {
+            println!("Failed to send ARP packet: {}", err);
+        }
+    }
+
+    for (interface_id, address) in cache::expire() {
+        if let Err(err) = send_request(interface_id, address) {
+            println!("Failed to send ARP request: {}", err);
+        }
+    }
+}
+
+fn send_request(interface_id: InterfaceId, address: Ipv4Address) -> Result<()> {
+    // we can't ask anything before we have an address to be answered at
+    let Some(our_address) = ipv4_address(interface_id) else {
+        return Ok(());
+    };
+
+    send_broadcast(interface_id, Operation::Request, our_address, address)
+}
+
+// a probe carries no sender address so it can't pollute caches if the address turns out taken
+fn send_probe(interface_id: InterfaceId, address: Ipv4Address) -> Result<()> {
+    send_broadcast(
+        interface_id,
+        Operation::Request,
+        Ipv4Address::UNSPECIFIED,
+        address,
+    )
+}
+
+// gratuitous ARP, tells everyone on the link which ethernet address our IP lives at
+pub fn announce(interface_id: InterfaceId) -> Result<()> {
+    let Some(our_address) = ipv4_address(interface_id) else {
+        return Ok(());
+    };
+
+    send_broadcast(interface_id, Operation::Request, our_address, our_address)
+}
+
+fn send_broadcast(
+    interface_id: InterfaceId,
+    operation: Operation,
+    sender_protocol_address: Ipv4Address,
+    target_protocol_address: Ipv4Address,
+) -> Result<()> {
+    let Some(device) = get_device(interface_id) else {
+        return Ok(());
+    };
+    let sender_hardware_address = device.lock().ethernet_address();
+
+    let packet = ArpPacket {
+        operation,
+        sender_hardware_address,
+        sender_protocol_address,
+        target_hardware_address: EthernetAddress { bytes: [0; 6] },
+        target_protocol_address,
+    };
+
+    transmit_packet(
+        interface_id,
+        EthernetAddress::broadcast(),
+        EitherType::Arp,
+        packet.to_packet(),
+    )
+}
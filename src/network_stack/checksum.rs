@@ -0,0 +1,60 @@
+// the ones' complement sum shared by IPv4, ICMP, UDP and TCP, https://datatracker.ietf.org/doc/html/rfc1071
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Checksum {
+    sum: u32,
+    // an odd byte left over from the previous slice, it pairs with the first byte of the next one
+    pending_byte: Option<u8>,
+}
+
+impl Checksum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_bytes(&mut self, mut bytes: &[u8]) {
+        if let Some(high) = self.pending_byte.take() {
+            let Some((&low, rest)) = bytes.split_first() else {
+                self.pending_byte = Some(high);
+                return;
+            };
+
+            self.add_u16(u16::from_be_bytes([high, low]));
+            bytes = rest;
+        }
+
+        let (words, rest) = bytes.as_chunks::<2>();
+        for word in words {
+            self.add_u16(u16::from_be_bytes(*word));
+        }
+
+        if let [last] = rest {
+            self.pending_byte = Some(*last);
+        }
+    }
+
+    pub fn add_u16(&mut self, value: u16) {
+        self.sum += value as u32;
+        // fold early so the sum never overflows no matter how much we feed it
+        self.sum = (self.sum & 0xFFFF) + (self.sum >> 16);
+    }
+
+    pub fn finish(self) -> u16 {
+        let mut sum = self.sum;
+
+        if let Some(high) = self.pending_byte {
+            sum += (high as u32) << 8;
+        }
+
+        while sum >> 16 != 0 {
+            sum = (sum & 0xFFFF) + (sum >> 16);
+        }
+
+        !(sum as u16)
+    }
+}
+
+pub fn internet_checksum(bytes: &[u8]) -> u16 {
+    let mut checksum = Checksum::new();
+    checksum.add_bytes(bytes);
+    checksum.finish()
+}
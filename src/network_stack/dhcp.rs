@@ -0,0 +1,891 @@
+// https://datatracker.ietf.org/doc/html/rfc2131
+// https://datatracker.ietf.org/doc/html/rfc2132
+
+use alloc::vec::Vec;
+use thiserror::Error;
+
+use crate::{
+    mutex::Mutex,
+    pci::drivers::network::NetworkError,
+    println,
+    x86::{
+        pit::{self, TICKS_PER_SECOND},
+        read_timestamp_counter,
+    },
+};
+
+use super::{
+    arp,
+    ethernet::EthernetAddress,
+    interface::{get_device, ipv4_cidr, set_dns_servers, set_ipv4_cidr, InterfaceId},
+    ipv4::{self, routing, Ipv4Address, Ipv4Cidr, SendOptions, SocketAddressV4},
+    udp::{self, UdpError, UdpSocket},
+};
+
+const SERVER_PORT: u16 = 67;
+const CLIENT_PORT: u16 = 68;
+
+// the fixed BOOTP part, the options follow the magic cookie
+const MESSAGE_HEADER_SIZE: usize = 236;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+const OPERATION_BOOT_REQUEST: u8 = 1;
+const OPERATION_BOOT_REPLY: u8 = 2;
+const HARDWARE_TYPE_ETHERNET: u8 = 1;
+// asks the server to broadcast its replies, we can't receive unicasts before we have an address
+const FLAG_BROADCAST: u16 = 0x8000;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_SUBNET_MASK: u8 = 1;
+const OPTION_ROUTER: u8 = 3;
+const OPTION_DNS_SERVERS: u8 = 6;
+const OPTION_REQUESTED_ADDRESS: u8 = 50;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_IDENTIFIER: u8 = 54;
+const OPTION_PARAMETER_REQUEST_LIST: u8 = 55;
+const OPTION_RENEWAL_TIME: u8 = 58;
+const OPTION_REBINDING_TIME: u8 = 59;
+const OPTION_END: u8 = 255;
+
+const INFINITE_LEASE: u32 = u32::MAX;
+
+// rfc 2131 4.1, the wait starts at 4 seconds and doubles up to 64, give or take a second
+const INITIAL_RETRANSMISSION_TIMEOUT: usize = 4 * TICKS_PER_SECOND;
+const MAX_RETRANSMISSION_TIMEOUT: usize = 64 * TICKS_PER_SECOND;
+// while renewing or rebinding we wait half of what is left, but never less than this
+const MIN_RENEWAL_RETRANSMISSION_TIMEOUT: usize = 60 * TICKS_PER_SECOND;
+// requests that go unanswered send us back to discovering
+const MAX_REQUEST_ATTEMPTS: u8 = 4;
+// pit::elapsed compares wrapping differences, so longer timers have to be clamped
+const MAX_TIMER: usize = usize::MAX / 2;
+
+#[derive(Error, Debug)]
+pub enum DhcpError {
+    #[error("Message of {0} bytes is too short for DHCP")]
+    TooShort(usize),
+    #[error("Message is not DHCP, the magic cookie is missing")]
+    InvalidMagicCookie,
+    #[error(transparent)]
+    Udp(#[from] UdpError),
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+}
+
+pub type Result<T> = core::result::Result<T, DhcpError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+    Unknown(u8),
+}
+
+impl From<u8> for MessageType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Discover,
+            2 => Self::Offer,
+            3 => Self::Request,
+            4 => Self::Decline,
+            5 => Self::Ack,
+            6 => Self::Nak,
+            7 => Self::Release,
+            8 => Self::Inform,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(value: MessageType) -> Self {
+        match value {
+            MessageType::Discover => 1,
+            MessageType::Offer => 2,
+            MessageType::Request => 3,
+            MessageType::Decline => 4,
+            MessageType::Ack => 5,
+            MessageType::Nak => 6,
+            MessageType::Release => 7,
+            MessageType::Inform => 8,
+            MessageType::Unknown(value) => value,
+        }
+    }
+}
+
+// the rfc 2132 options the client acts on, other codes are skipped while parsing
+#[derive(Debug, Default, Clone)]
+pub struct DhcpOptions {
+    pub message_type: Option<MessageType>,
+    pub subnet_mask: Option<Ipv4Address>,
+    pub routers: Vec<Ipv4Address>,
+    pub dns_servers: Vec<Ipv4Address>,
+    pub requested_address: Option<Ipv4Address>,
+    // all times are in seconds
+    pub lease_time: Option<u32>,
+    pub renewal_time: Option<u32>,
+    pub rebinding_time: Option<u32>,
+    pub server_identifier: Option<Ipv4Address>,
+    pub parameter_request_list: Vec<u8>,
+}
+
+impl DhcpOptions {
+    fn parse(mut bytes: &[u8]) -> Self {
+        let mut options = Self::default();
+
+        while let Some((&code, rest)) = bytes.split_first() {
+            match code {
+                OPTION_END => break,
+                OPTION_PAD => {
+                    bytes = rest;
+                    continue;
+                }
+                _ => (),
+            }
+
+            // every other option carries its length, without the code and length bytes
+            let Some((&length, rest)) = rest.split_first() else {
+                break;
+            };
+            let length = length as usize;
+            if length > rest.len() {
+                break;
+            }
+
+            let data = &rest[..length];
+
+            match (code, data.len()) {
+                (OPTION_MESSAGE_TYPE, 1) => options.message_type = Some(MessageType::from(data[0])),
+                (OPTION_SUBNET_MASK, 4) => {
+                    options.subnet_mask = Some(Ipv4Address::from_slice(data))
+                }
+                (OPTION_ROUTER, length) if length % 4 == 0 => {
+                    options.routers = read_addresses(data)
+                }
+                (OPTION_DNS_SERVERS, length) if length % 4 == 0 => {
+                    options.dns_servers = read_addresses(data)
+                }
+                (OPTION_REQUESTED_ADDRESS, 4) => {
+                    options.requested_address = Some(Ipv4Address::from_slice(data))
+                }
+                (OPTION_LEASE_TIME, 4) => {
+                    options.lease_time = Some(u32::from_be_bytes(data.try_into().unwrap()))
+                }
+                (OPTION_RENEWAL_TIME, 4) => {
+                    options.renewal_time = Some(u32::from_be_bytes(data.try_into().unwrap()))
+                }
+                (OPTION_REBINDING_TIME, 4) => {
+                    options.rebinding_time = Some(u32::from_be_bytes(data.try_into().unwrap()))
+                }
+                (OPTION_SERVER_IDENTIFIER, 4) => {
+                    options.server_identifier = Some(Ipv4Address::from_slice(data))
+                }
+                (OPTION_PARAMETER_REQUEST_LIST, _) => {
+                    options.parameter_request_list = data.to_vec()
+                }
+                _ => (),
+            }
+
+            bytes = &rest[length..];
+        }
+
+        options
+    }
+
+    // only what a client sends
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        if let Some(message_type) = self.message_type {
+            bytes.extend_from_slice(&[OPTION_MESSAGE_TYPE, 1, u8::from(message_type)]);
+        }
+
+        if let Some(requested_address) = self.requested_address {
+            bytes.extend_from_slice(&[OPTION_REQUESTED_ADDRESS, 4]);
+            bytes.extend_from_slice(&requested_address.bytes);
+        }
+
+        if let Some(server_identifier) = self.server_identifier {
+            bytes.extend_from_slice(&[OPTION_SERVER_IDENTIFIER, 4]);
+            bytes.extend_from_slice(&server_identifier.bytes);
+        }
+
+        if !self.parameter_request_list.is_empty() {
+            bytes.extend_from_slice(&[
+                OPTION_PARAMETER_REQUEST_LIST,
+                self.parameter_request_list.len() as u8,
+            ]);
+            bytes.extend_from_slice(&self.parameter_request_list);
+        }
+
+        bytes.push(OPTION_END);
+    }
+}
+
+// a list option is a run of addresses, the length was checked to be a multiple of 4
+fn read_addresses(data: &[u8]) -> Vec<Ipv4Address> {
+    let (addresses, _) = data.as_chunks::<4>();
+    addresses
+        .iter()
+        .map(|&bytes| Ipv4Address { bytes })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct DhcpMessage {
+    pub operation: u8,
+    pub transaction_id: u32,
+    // since the client began the exchange
+    pub seconds: u16,
+    pub flags: u16,
+    // ours once we are bound, otherwise unspecified
+    pub client_address: Ipv4Address,
+    // what the server offers or assigns
+    pub your_address: Ipv4Address,
+    pub client_hardware_address: EthernetAddress,
+    pub options: DhcpOptions,
+}
+
+impl DhcpMessage {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < MESSAGE_HEADER_SIZE + MAGIC_COOKIE.len() {
+            return Err(DhcpError::TooShort(bytes.len()));
+        }
+
+        if bytes[MESSAGE_HEADER_SIZE..MESSAGE_HEADER_SIZE + MAGIC_COOKIE.len()] != MAGIC_COOKIE {
+            return Err(DhcpError::InvalidMagicCookie);
+        }
+
+        Ok(Self {
+            operation: bytes[0],
+            transaction_id: u32::from_be_bytes(bytes[4..8].try_into().unwrap()),
+            seconds: u16::from_be_bytes([bytes[8], bytes[9]]),
+            flags: u16::from_be_bytes([bytes[10], bytes[11]]),
+            client_address: Ipv4Address::from_slice(&bytes[12..16]),
+            your_address: Ipv4Address::from_slice(&bytes[16..20]),
+            client_hardware_address: EthernetAddress {
+                bytes: bytes[28..34].try_into().unwrap(),
+            },
+            options: DhcpOptions::parse(&bytes[MESSAGE_HEADER_SIZE + MAGIC_COOKIE.len()..]),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(MESSAGE_HEADER_SIZE + 64);
+
+        bytes.extend_from_slice(&[self.operation, HARDWARE_TYPE_ETHERNET, 6, 0]);
+        bytes.extend_from_slice(&self.transaction_id.to_be_bytes());
+        bytes.extend_from_slice(&self.seconds.to_be_bytes());
+        bytes.extend_from_slice(&self.flags.to_be_bytes());
+        bytes.extend_from_slice(&self.client_address.bytes);
+        bytes.extend_from_slice(&self.your_address.bytes);
+        // the server and relay addresses are only filled in by servers and relays
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&self.client_hardware_address.bytes);
+        // the rest of the hardware address, the server name and the boot file name
+        bytes.resize(MESSAGE_HEADER_SIZE, 0);
+
+        bytes.extend_from_slice(&MAGIC_COOKIE);
+        self.options.write_to(&mut bytes);
+
+        bytes
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub cidr: Ipv4Cidr,
+    pub server: Ipv4Address,
+    pub routers: Vec<Ipv4Address>,
+    pub dns_servers: Vec<Ipv4Address>,
+    // seconds, None for an infinite lease
+    pub lease_time: Option<u32>,
+    renewal_time: Option<u32>,
+    rebinding_time: Option<u32>,
+}
+
+impl Lease {
+    fn from_ack(message: &DhcpMessage, server: Ipv4Address) -> Self {
+        let options = &message.options;
+
+        let cidr = match options.subnet_mask {
+            Some(netmask) => Ipv4Cidr::from_netmask(message.your_address, netmask),
+            None => classful_cidr(message.your_address),
+        };
+
+        let lease_time = options
+            .lease_time
+            .filter(|&lease_time| lease_time != INFINITE_LEASE);
+
+        // rfc 2131 4.4.5, half the lease and seven eighths of it unless the server says otherwise
+        let renewal_time = lease_time.map(|lease_time| {
+            options
+                .renewal_time
+                .unwrap_or(lease_time / 2)
+                .min(lease_time)
+        });
+        let rebinding_time = lease_time.map(|lease_time| {
+            options
+                .rebinding_time
+                .unwrap_or((lease_time as u64 * 7 / 8) as u32)
+                .min(lease_time)
+        });
+
+        Self {
+            cidr,
+            server,
+            routers: options.routers.clone(),
+            dns_servers: options.dns_servers.clone(),
+            lease_time,
+            renewal_time,
+            rebinding_time,
+        }
+    }
+}
+
+// rfc 1122 3.3.1.1, what the address class implies when the server sends no subnet mask
+fn classful_cidr(address: Ipv4Address) -> Ipv4Cidr {
+    let prefix_length = match address.bytes[0] {
+        0..=127 => 8,
+        128..=191 => 16,
+        _ => 24,
+    };
+
+    Ipv4Cidr::new(address, prefix_length)
+}
+
+fn seconds_to_ticks(seconds: u32) -> usize {
+    (seconds as usize)
+        .saturating_mul(TICKS_PER_SECOND)
+        .min(MAX_TIMER)
+}
+
+#[derive(Debug, Clone)]
+enum ClientState {
+    // broadcasting discovers and taking the first offer
+    Selecting,
+    Requesting {
+        server: Ipv4Address,
+        address: Ipv4Address,
+        attempts: u8,
+    },
+    Bound,
+    // asking the server that gave us the lease to extend it
+    Renewing,
+    // that server didn't answer, asking any server
+    Rebinding,
+}
+
+struct Client {
+    interface_id: InterfaceId,
+    ethernet_address: EthernetAddress,
+    state: ClientState,
+    transaction_id: u32,
+    // when the exchange began, servers see it in the seconds field
+    started_at: usize,
+    last_sent_at: usize,
+    retransmission_timeout: usize,
+    // the timeout with jitter, drawn when the message went out
+    retransmission_wait: usize,
+    lease: Option<Lease>,
+    // the lease timers run from when the acknowledged request was sent
+    lease_started_at: usize,
+}
+
+struct OutgoingMessage {
+    interface_id: InterfaceId,
+    source_address: Ipv4Address,
+    destination_address: Ipv4Address,
+    message: DhcpMessage,
+}
+
+// the interface changes go through the arp and routing locks, so they are made after ours is released
+enum Action {
+    Configure(InterfaceId, Lease),
+    Unconfigure(InterfaceId),
+}
+
+struct Dhcp {
+    socket: UdpSocket,
+    clients: Vec<Client>,
+}
+
+static DHCP: Mutex<Option<Dhcp>> = Mutex::new(None);
+
+// a second either way, so clients that booted together don't retransmit in lockstep
+fn jitter(timeout: usize) -> usize {
+    let random = read_timestamp_counter() as usize % (2 * TICKS_PER_SECOND);
+    (timeout + random).saturating_sub(TICKS_PER_SECOND)
+}
+
+impl Client {
+    fn new(interface_id: InterfaceId, ethernet_address: EthernetAddress) -> Self {
+        let mut client = Self {
+            interface_id,
+            ethernet_address,
+            state: ClientState::Selecting,
+            transaction_id: 0,
+            started_at: 0,
+            last_sent_at: 0,
+            retransmission_timeout: 0,
+            retransmission_wait: 0,
+            lease: None,
+            lease_started_at: 0,
+        };
+        client.begin_exchange();
+        client
+    }
+
+    fn begin_exchange(&mut self) {
+        let ethernet_address = &self.ethernet_address.bytes;
+        self.transaction_id = read_timestamp_counter() as u32
+            ^ u32::from_be_bytes(ethernet_address[2..6].try_into().unwrap());
+        self.started_at = pit::ticks();
+        self.retransmission_timeout = INITIAL_RETRANSMISSION_TIMEOUT;
+    }
+
+    fn restart(&mut self, outbox: &mut Vec<OutgoingMessage>) {
+        self.state = ClientState::Selecting;
+        self.lease = None;
+        self.begin_exchange();
+        self.send(outbox);
+    }
+
+    fn is_bound(&self) -> bool {
+        matches!(
+            self.state,
+            ClientState::Bound | ClientState::Renewing | ClientState::Rebinding
+        )
+    }
+
+    fn bound_address(&self) -> Ipv4Address {
+        self.lease
+            .as_ref()
+            .map_or(Ipv4Address::UNSPECIFIED, |lease| lease.cidr.address)
+    }
+
+    fn elapsed_seconds(&self) -> u16 {
+        (pit::ticks().wrapping_sub(self.started_at) / TICKS_PER_SECOND).min(u16::MAX as usize)
+            as u16
+    }
+
+    fn lease_timer_elapsed(&self, seconds: Option<u32>) -> bool {
+        seconds
+            .is_some_and(|seconds| pit::elapsed(self.lease_started_at, seconds_to_ticks(seconds)))
+    }
+
+    // rfc 2131 4.4.5, half the time left until `seconds` into the lease
+    fn renewal_retransmission_timeout(&self, seconds: Option<u32>) -> usize {
+        let remaining = seconds.map_or(MAX_TIMER, |seconds| {
+            seconds_to_ticks(seconds)
+                .saturating_sub(pit::ticks().wrapping_sub(self.lease_started_at))
+        });
+
+        (remaining / 2).max(MIN_RENEWAL_RETRANSMISSION_TIMEOUT)
+    }
+
+    // the message for the current state, nothing is sent while bound
+    fn send(&mut self, outbox: &mut Vec<OutgoingMessage>) {
+        let mut options = DhcpOptions::default();
+        let mut client_address = Ipv4Address::UNSPECIFIED;
+        let mut source_address = Ipv4Address::UNSPECIFIED;
+        let mut destination_address = Ipv4Address::BROADCAST;
+
+        match self.state {
+            ClientState::Selecting => options.message_type = Some(MessageType::Discover),
+            ClientState::Requesting {
+                server, address, ..
+            } => {
+                options.message_type = Some(MessageType::Request);
+                options.requested_address = Some(address);
+                options.server_identifier = Some(server);
+            }
+            ClientState::Bound => return,
+            ClientState::Renewing | ClientState::Rebinding => {
+                options.message_type = Some(MessageType::Request);
+                client_address = self.bound_address();
+                source_address = client_address;
+
+                if let (ClientState::Renewing, Some(lease)) = (&self.state, &self.lease) {
+                    destination_address = lease.server;
+                }
+            }
+        }
+
+        options.parameter_request_list = alloc::vec![
+            OPTION_SUBNET_MASK,
+            OPTION_ROUTER,
+            OPTION_DNS_SERVERS,
+            OPTION_LEASE_TIME,
+            OPTION_RENEWAL_TIME,
+            OPTION_REBINDING_TIME,
+        ];
+
+        outbox.push(OutgoingMessage {
+            interface_id: self.interface_id,
+            source_address,
+            destination_address,
+            message: DhcpMessage {
+                operation: OPERATION_BOOT_REQUEST,
+                transaction_id: self.transaction_id,
+                seconds: self.elapsed_seconds(),
+                flags: if client_address.is_unspecified() {
+                    FLAG_BROADCAST
+                } else {
+                    0
+                },
+                client_address,
+                your_address: Ipv4Address::UNSPECIFIED,
+                client_hardware_address: self.ethernet_address,
+                options,
+            },
+        });
+
+        self.last_sent_at = pit::ticks();
+        self.retransmission_wait = jitter(self.retransmission_timeout);
+    }
+
+    fn handle_message(
+        &mut self,
+        message: &DhcpMessage,
+        outbox: &mut Vec<OutgoingMessage>,
+        actions: &mut Vec<Action>,
+    ) {
+        let Some(message_type) = message.options.message_type else {
+            return;
+        };
+
+        match (&self.state, message_type) {
+            (ClientState::Selecting, MessageType::Offer) => {
+                let Some(server) = message.options.server_identifier else {
+                    return;
+                };
+
+                if message.your_address.is_unspecified() {
+                    return;
+                }
+
+                println!(
+                    "DHCP server {} offered {} on interface {}",
+                    server, message.your_address, self.interface_id
+                );
+
+                self.state = ClientState::Requesting {
+                    server,
+                    address: message.your_address,
+                    attempts: 1,
+                };
+                self.retransmission_timeout = INITIAL_RETRANSMISSION_TIMEOUT;
+                self.send(outbox);
+            }
+            (
+                ClientState::Requesting { .. } | ClientState::Renewing | ClientState::Rebinding,
+                MessageType::Ack,
+            ) => {
+                let server = match (&self.state, &self.lease) {
+                    (ClientState::Requesting { server, .. }, _) => *server,
+                    (_, Some(lease)) => message.options.server_identifier.unwrap_or(lease.server),
+                    (_, None) => return,
+                };
+
+                let lease = Lease::from_ack(message, server);
+
+                // a renewal that hands out a different address is a new lease
+                if self.is_bound() && lease.cidr != self.lease.as_ref().unwrap().cidr {
+                    actions.push(Action::Unconfigure(self.interface_id));
+                }
+
+                println!(
+                    "DHCP lease for {} on interface {} from {}, {:?} seconds",
+                    lease.cidr, self.interface_id, server, lease.lease_time
+                );
+
+                self.state = ClientState::Bound;
+                self.lease_started_at = self.last_sent_at;
+                self.lease = Some(lease.clone());
+                actions.push(Action::Configure(self.interface_id, lease));
+            }
+            (
+                ClientState::Requesting { .. } | ClientState::Renewing | ClientState::Rebinding,
+                MessageType::Nak,
+            ) => {
+                println!(
+                    "DHCP server refused our request on interface {}",
+                    self.interface_id
+                );
+
+                if self.is_bound() {
+                    actions.push(Action::Unconfigure(self.interface_id));
+                }
+
+                self.restart(outbox);
+            }
+            _ => (),
+        }
+    }
+
+    fn poll_timers(&mut self, outbox: &mut Vec<OutgoingMessage>, actions: &mut Vec<Action>) {
+        let Some(lease) = self.lease.clone() else {
+            // discovering and requesting back off exponentially
+            if !pit::elapsed(self.last_sent_at, self.retransmission_wait) {
+                return;
+            }
+
+            self.retransmission_timeout =
+                (self.retransmission_timeout * 2).min(MAX_RETRANSMISSION_TIMEOUT);
+
+            if let ClientState::Requesting { attempts, .. } = &mut self.state {
+                if *attempts == MAX_REQUEST_ATTEMPTS {
+                    self.restart(outbox);
+                    return;
+                }
+
+                *attempts += 1;
+            }
+
+            self.send(outbox);
+            return;
+        };
+
+        if self.lease_timer_elapsed(lease.lease_time) {
+            println!(
+                "DHCP lease for {} on interface {} expired",
+                lease.cidr, self.interface_id
+            );
+            actions.push(Action::Unconfigure(self.interface_id));
+            self.restart(outbox);
+            return;
+        }
+
+        match self.state {
+            ClientState::Bound if self.lease_timer_elapsed(lease.renewal_time) => {
+                self.state = ClientState::Renewing;
+                self.begin_exchange();
+                self.send(outbox);
+            }
+            ClientState::Renewing if self.lease_timer_elapsed(lease.rebinding_time) => {
+                self.state = ClientState::Rebinding;
+                self.send(outbox);
+            }
+            ClientState::Renewing
+                if pit::elapsed(
+                    self.last_sent_at,
+                    self.renewal_retransmission_timeout(lease.rebinding_time),
+                ) =>
+            {
+                self.send(outbox);
+            }
+            ClientState::Rebinding
+                if pit::elapsed(
+                    self.last_sent_at,
+                    self.renewal_retransmission_timeout(lease.lease_time),
+                ) =>
+            {
+                self.send(outbox);
+            }
+            _ => (),
+        }
+    }
+}
+
+// starts discovering on the interface, the lease is applied to it once a server acknowledges
+pub fn start(interface_id: InterfaceId) -> Result<()> {
+    let ethernet_address = get_device(interface_id)
+        .ok_or(NetworkError::UnknownInterface(interface_id))?
+        .lock()
+        .ethernet_address();
+
+    let mut outbox = Vec::new();
+
+    {
+        let mut dhcp = DHCP.lock();
+
+        if dhcp.is_none() {
+            *dhcp = Some(Dhcp {
+                socket: UdpSocket::bind(
+                    SocketAddressV4::new(Ipv4Address::UNSPECIFIED, CLIENT_PORT).into(),
+                )?,
+                clients: Vec::new(),
+            });
+        }
+
+        let clients = &mut dhcp.as_mut().unwrap().clients;
+        clients.retain(|client| client.interface_id != interface_id);
+
+        let mut client = Client::new(interface_id, ethernet_address);
+        client.send(&mut outbox);
+        clients.push(client);
+    }
+
+    send_messages(outbox);
+
+    Ok(())
+}
+
+// forgets the client, whatever it configured stays on the interface
+pub fn stop(interface_id: InterfaceId) {
+    let mut dhcp = DHCP.lock();
+
+    let Some(state) = dhcp.as_mut() else {
+        return;
+    };
+
+    state
+        .clients
+        .retain(|client| client.interface_id != interface_id);
+
+    // frees the port once nobody needs it
+    if state.clients.is_empty() {
+        *dhcp = None;
+    }
+}
+
+// the current lease, also while it is being renewed
+pub fn lease(interface_id: InterfaceId) -> Option<Lease> {
+    DHCP.lock()
+        .as_ref()?
+        .clients
+        .iter()
+        .find(|client| client.interface_id == interface_id && client.is_bound())?
+        .lease
+        .clone()
+}
+
+// handles the replies that came in and runs the lease timers, called from the network stack poll loop
+pub fn poll() {
+    let mut outbox = Vec::new();
+    let mut actions = Vec::new();
+
+    {
+        let mut dhcp = DHCP.lock();
+
+        let Some(dhcp) = dhcp.as_mut() else {
+            return;
+        };
+
+        while let Ok(datagram) = dhcp.socket.try_recv_from() {
+            let message = match DhcpMessage::parse(&datagram.data) {
+                Ok(message) => message,
+                Err(err) => {
+                    println!("Dropped DHCP message from {}: {}", datagram.source, err);
+                    continue;
+                }
+            };
+
+            if message.operation != OPERATION_BOOT_REPLY {
+                continue;
+            }
+
+            if let Some(client) = dhcp.clients.iter_mut().find(|client| {
+                client.interface_id == datagram.interface_id
+                    && client.transaction_id == message.transaction_id
+                    && client.ethernet_address == message.client_hardware_address
+            }) {
+                client.handle_message(&message, &mut outbox, &mut actions);
+            }
+        }
+
+        for client in &mut dhcp.clients {
+            client.poll_timers(&mut outbox, &mut actions);
+        }
+    }
+
+    for action in actions {
+        match action {
+            Action::Configure(interface_id, lease) => configure(interface_id, &lease),
+            Action::Unconfigure(interface_id) => unconfigure(interface_id),
+        }
+    }
+
+    send_messages(outbox);
+}
+
+fn send_messages(outbox: Vec<OutgoingMessage>) {
+    for outgoing in outbox {
+        // broadcasts have no route, they go out of the interface the client runs on
+        let options = SendOptions {
+            interface_id: (outgoing.destination_address == Ipv4Address::BROADCAST)
+                .then_some(outgoing.interface_id),
+            source_address: Some(outgoing.source_address),
+            ..SendOptions::default()
+        };
+
+        let result = udp::send_from(
+            SocketAddressV4::new(outgoing.source_address, CLIENT_PORT),
+            &outgoing.message.to_bytes(),
+            SocketAddressV4::new(outgoing.destination_address, SERVER_PORT),
+            options,
+        );
+
+        if let Err(err) = result {
+            println!("Failed to send DHCP message: {}", err);
+        }
+    }
+}
+
+fn configure(interface_id: InterfaceId, lease: &Lease) {
+    // a renewal of the same address keeps it, probing again would take it away for a while
+    if ipv4_cidr(interface_id) != Some(lease.cidr) {
+        arp::configure_address(interface_id, lease.cidr);
+    }
+
+    if let Some(&router) = lease.routers.first() {
+        routing::set_default_gateway(interface_id, router);
+    }
+
+    set_dns_servers(interface_id, lease.dns_servers.clone());
+}
+
+fn unconfigure(interface_id: InterfaceId) {
+    set_ipv4_cidr(interface_id, None);
+    routing::remove_interface_routes(interface_id);
+    set_dns_servers(interface_id, Vec::new());
+}
+
+// the fallback when no server answers, from `ip=10.0.2.15/24 gateway=10.0.2.2 dns=10.0.2.3` on the kernel command line
+#[derive(Debug, Clone)]
+pub struct StaticConfiguration {
+    pub cidr: Ipv4Cidr,
+    pub gateway: Option<Ipv4Address>,
+    pub dns_servers: Vec<Ipv4Address>,
+}
+
+impl StaticConfiguration {
+    // None when the command line has no address, dns may be given more than once
+    pub fn from_command_line(command_line: &str) -> ipv4::Result<Option<Self>> {
+        let mut cidr = None;
+        let mut gateway = None;
+        let mut dns_servers = Vec::new();
+
+        for (key, value) in command_line
+            .split_whitespace()
+            .filter_map(|argument| argument.split_once('='))
+        {
+            match key {
+                "ip" => cidr = Some(value.parse()?),
+                "gateway" => gateway = Some(value.parse()?),
+                "dns" => dns_servers.push(value.parse()?),
+                _ => (),
+            }
+        }
+
+        Ok(cidr.map(|cidr| Self {
+            cidr,
+            gateway,
+            dns_servers,
+        }))
+    }
+
+    pub fn apply(&self, interface_id: InterfaceId) {
+        arp::configure_address(interface_id, self.cidr);
+
+        if let Some(gateway) = self.gateway {
+            routing::set_default_gateway(interface_id, gateway);
+        }
+
+        set_dns_servers(interface_id, self.dns_servers.clone());
+    }
+}
@@ -0,0 +1,82 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use crate::{
+    mutex::Mutex,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::message::{RecordData, RecordType};
+
+// the entry that expires first makes room once it is full
+const MAX_ENTRIES: usize = 256;
+// a misconfigured server shouldn't pin an answer for days
+const MAX_TIME_TO_LIVE: u32 = 24 * 60 * 60;
+
+struct CacheEntry {
+    records: Vec<RecordData>,
+    cached_at: usize,
+    // ticks
+    time_to_live: usize,
+}
+
+impl CacheEntry {
+    fn is_expired(&self) -> bool {
+        pit::elapsed(self.cached_at, self.time_to_live)
+    }
+}
+
+// names are case insensitive, the keys are lowercase
+static CACHE: Mutex<BTreeMap<(String, RecordType), CacheEntry>> = Mutex::new(BTreeMap::new());
+
+fn key(name: &str, record_type: RecordType) -> (String, RecordType) {
+    (name.trim_end_matches('.').to_ascii_lowercase(), record_type)
+}
+
+pub fn lookup(name: &str, record_type: RecordType) -> Option<Vec<RecordData>> {
+    let mut cache = CACHE.lock();
+    let key = key(name, record_type);
+
+    if cache.get(&key)?.is_expired() {
+        cache.remove(&key);
+        return None;
+    }
+
+    Some(cache[&key].records.clone())
+}
+
+// the records of an rrset share a ttl, the lowest one is used if they don't.
+// answers with a ttl of zero are only good for the query that asked
+pub fn insert(name: &str, record_type: RecordType, records: Vec<RecordData>, time_to_live: u32) {
+    if time_to_live == 0 || records.is_empty() {
+        return;
+    }
+
+    let mut cache = CACHE.lock();
+
+    cache.retain(|_, entry| !entry.is_expired());
+
+    if cache.len() >= MAX_ENTRIES {
+        let now = pit::ticks();
+        let soonest = cache
+            .iter()
+            .min_by_key(|(_, entry)| {
+                entry
+                    .time_to_live
+                    .saturating_sub(now.wrapping_sub(entry.cached_at))
+            })
+            .map(|(key, _)| key.clone());
+
+        if let Some(soonest) = soonest {
+            cache.remove(&soonest);
+        }
+    }
+
+    cache.insert(
+        key(name, record_type),
+        CacheEntry {
+            records,
+            cached_at: pit::ticks(),
+            time_to_live: time_to_live.min(MAX_TIME_TO_LIVE) as usize * TICKS_PER_SECOND,
+        },
+    );
+}
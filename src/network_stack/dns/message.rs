@@ -0,0 +1,333 @@
+// https://datatracker.ietf.org/doc/html/rfc1035#section-4
+// https://datatracker.ietf.org/doc/html/rfc3596
+
+use alloc::{string::String, vec::Vec};
+use bitflags::bitflags;
+
+use crate::network_stack::{ipv4::Ipv4Address, ipv6::Ipv6Address};
+
+use super::{DnsError, Result};
+
+const HEADER_SIZE: usize = 12;
+const CLASS_INTERNET: u16 = 1;
+const MAX_LABEL_LENGTH: usize = 63;
+// in wire format, length bytes and the root label included
+const MAX_NAME_LENGTH: usize = 255;
+// the top two bits of a length byte mark a pointer to a name earlier in the message
+const POINTER_MASK: u8 = 0xC0;
+// a name can't have more labels than this, so more jumps than that means the pointers loop
+const MAX_POINTERS: usize = MAX_NAME_LENGTH / 2;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct DnsFlags: u16 {
+        const RESPONSE = 1 << 15;
+        const AUTHORITATIVE = 1 << 10;
+        const TRUNCATED = 1 << 9;
+        const RECURSION_DESIRED = 1 << 8;
+        const RECURSION_AVAILABLE = 1 << 7;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RecordType {
+    A,
+    Cname,
+    Ptr,
+    Aaaa,
+    Unknown(u16),
+}
+
+impl From<u16> for RecordType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => Self::A,
+            5 => Self::Cname,
+            12 => Self::Ptr,
+            28 => Self::Aaaa,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<RecordType> for u16 {
+    fn from(value: RecordType) -> Self {
+        match value {
+            RecordType::A => 1,
+            RecordType::Cname => 5,
+            RecordType::Ptr => 12,
+            RecordType::Aaaa => 28,
+            RecordType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseCode {
+    NoError,
+    FormatError,
+    ServerFailure,
+    NameError,
+    NotImplemented,
+    Refused,
+    Unknown(u8),
+}
+
+impl From<u8> for ResponseCode {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::NoError,
+            1 => Self::FormatError,
+            2 => Self::ServerFailure,
+            3 => Self::NameError,
+            4 => Self::NotImplemented,
+            5 => Self::Refused,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    A(Ipv4Address),
+    Aaaa(Ipv6Address),
+    Cname(String),
+    Ptr(String),
+    Unknown(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Question {
+    pub name: String,
+    pub record_type: RecordType,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResourceRecord {
+    pub name: String,
+    pub record_type: RecordType,
+    // seconds the answer may be cached
+    pub time_to_live: u32,
+    pub data: RecordData,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub flags: DnsFlags,
+    pub response_code: ResponseCode,
+    pub questions: Vec<Question>,
+    // the authority and additional sections are not needed by a stub resolver
+    pub answers: Vec<ResourceRecord>,
+}
+
+impl DnsMessage {
+    // a recursive query for a single name
+    pub fn query(id: u16, name: &str, record_type: RecordType) -> Self {
+        Self {
+            id,
+            flags: DnsFlags::RECURSION_DESIRED,
+            response_code: ResponseCode::NoError,
+            questions: alloc::vec![Question {
+                name: String::from(name),
+                record_type,
+            }],
+            answers: Vec::new(),
+        }
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(DnsError::TooShort(bytes.len()));
+        }
+
+        let flags = u16::from_be_bytes([bytes[2], bytes[3]]);
+        let question_count = u16::from_be_bytes([bytes[4], bytes[5]]);
+        let answer_count = u16::from_be_bytes([bytes[6], bytes[7]]);
+
+        let mut offset = HEADER_SIZE;
+
+        let mut questions = Vec::new();
+        for _ in 0..question_count {
+            let name = read_name(bytes, &mut offset)?;
+            let record_type = RecordType::from(read_u16(bytes, &mut offset)?);
+            let _class = read_u16(bytes, &mut offset)?;
+
+            questions.push(Question { name, record_type });
+        }
+
+        let mut answers = Vec::new();
+        for _ in 0..answer_count {
+            if let Some(record) = read_record(bytes, &mut offset)? {
+                answers.push(record);
+            }
+        }
+
+        Ok(Self {
+            id: u16::from_be_bytes([bytes[0], bytes[1]]),
+            flags: DnsFlags::from_bits_truncate(flags),
+            response_code: ResponseCode::from((flags & 0xF) as u8),
+            questions,
+            answers,
+        })
+    }
+
+    // only queries are sent, so the names are written without compression
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + 64);
+
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        bytes.extend_from_slice(&self.flags.bits().to_be_bytes());
+        bytes.extend_from_slice(&(self.questions.len() as u16).to_be_bytes());
+        // no answer, authority or additional records
+        bytes.extend_from_slice(&[0; 6]);
+
+        for question in &self.questions {
+            write_name(&mut bytes, &question.name)?;
+            bytes.extend_from_slice(&u16::from(question.record_type).to_be_bytes());
+            bytes.extend_from_slice(&CLASS_INTERNET.to_be_bytes());
+        }
+
+        Ok(bytes)
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: &mut usize) -> Result<u16> {
+    let value = bytes
+        .get(*offset..*offset + 2)
+        .ok_or(DnsError::TooShort(bytes.len()))?;
+    *offset += 2;
+
+    Ok(u16::from_be_bytes([value[0], value[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Result<u32> {
+    let value = bytes
+        .get(*offset..*offset + 4)
+        .ok_or(DnsError::TooShort(bytes.len()))?;
+    *offset += 4;
+
+    Ok(u32::from_be_bytes(value.try_into().unwrap()))
+}
+
+// follows compression pointers, `offset` ends up after the name as it appears at that spot
+fn read_name(bytes: &[u8], offset: &mut usize) -> Result<String> {
+    let mut name = String::new();
+    let mut position = *offset;
+    let mut pointers = 0;
+
+    loop {
+        let &length = bytes.get(position).ok_or(DnsError::TooShort(bytes.len()))?;
+
+        if length & POINTER_MASK == POINTER_MASK {
+            let &low = bytes
+                .get(position + 1)
+                .ok_or(DnsError::TooShort(bytes.len()))?;
+
+            if pointers == 0 {
+                *offset = position + 2;
+            }
+
+            pointers += 1;
+            if pointers > MAX_POINTERS {
+                return Err(DnsError::PointerLoop);
+            }
+
+            position = (((length & !POINTER_MASK) as usize) << 8) | low as usize;
+            continue;
+        }
+
+        // the other two combinations of the top bits are reserved
+        if length & POINTER_MASK != 0 {
+            return Err(DnsError::InvalidName);
+        }
+
+        position += 1;
+
+        if length == 0 {
+            break;
+        }
+
+        let label = bytes
+            .get(position..position + length as usize)
+            .ok_or(DnsError::TooShort(bytes.len()))?;
+        position += length as usize;
+
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(core::str::from_utf8(label).map_err(|_| DnsError::InvalidName)?);
+
+        if name.len() > MAX_NAME_LENGTH {
+            return Err(DnsError::InvalidName);
+        }
+    }
+
+    if pointers == 0 {
+        *offset = position;
+    }
+
+    Ok(name)
+}
+
+fn write_name(bytes: &mut Vec<u8>, name: &str) -> Result<()> {
+    let start = bytes.len();
+
+    // a trailing dot only marks the name as fully qualified
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > MAX_LABEL_LENGTH {
+            return Err(DnsError::InvalidName);
+        }
+
+        bytes.push(label.len() as u8);
+        bytes.extend_from_slice(label.as_bytes());
+    }
+
+    bytes.push(0);
+
+    if bytes.len() - start > MAX_NAME_LENGTH {
+        return Err(DnsError::InvalidName);
+    }
+
+    Ok(())
+}
+
+// None for records outside the internet class
+fn read_record(bytes: &[u8], offset: &mut usize) -> Result<Option<ResourceRecord>> {
+    let name = read_name(bytes, offset)?;
+    let record_type = RecordType::from(read_u16(bytes, offset)?);
+    let class = read_u16(bytes, offset)?;
+    let time_to_live = read_u32(bytes, offset)?;
+    let data_length = read_u16(bytes, offset)? as usize;
+
+    let data_start = *offset;
+    let data = bytes
+        .get(data_start..data_start + data_length)
+        .ok_or(DnsError::TooShort(bytes.len()))?;
+    *offset += data_length;
+
+    if class != CLASS_INTERNET {
+        return Ok(None);
+    }
+
+    let data = match (record_type, data_length) {
+        (RecordType::A, 4) => RecordData::A(Ipv4Address::from_slice(data)),
+        (RecordType::Aaaa, 16) => RecordData::Aaaa(Ipv6Address::from_slice(data)),
+        // the names may point anywhere in the message, so they are read from the whole of it
+        (RecordType::Cname, _) => RecordData::Cname(read_name(bytes, &mut data_start.clone())?),
+        (RecordType::Ptr, _) => RecordData::Ptr(read_name(bytes, &mut data_start.clone())?),
+        _ => RecordData::Unknown(data.to_vec()),
+    };
+
+    Ok(Some(ResourceRecord {
+        name,
+        record_type,
+        // rfc 2181 8, values with the top bit set are treated as zero
+        time_to_live: if time_to_live > i32::MAX as u32 {
+            0
+        } else {
+            time_to_live
+        },
+        data,
+    }))
+}
@@ -0,0 +1,339 @@
+// https://datatracker.ietf.org/doc/html/rfc1035
+// https://datatracker.ietf.org/doc/html/rfc1123#section-6.1.3
+
+use alloc::{format, string::String, vec, vec::Vec};
+use thiserror::Error;
+
+use crate::{
+    mutex::Mutex,
+    println,
+    x86::{
+        pit::{self, TICKS_PER_SECOND},
+        read_timestamp_counter,
+    },
+};
+
+use self::message::{DnsFlags, DnsMessage, RecordData, RecordType, ResourceRecord, ResponseCode};
+
+use super::{
+    interface,
+    ipv4::{Ipv4Address, SocketAddressV4},
+    ports::{EPHEMERAL_PORT_END, EPHEMERAL_PORT_START},
+    tcp::{TcpError, TcpStream},
+    udp::{UdpError, UdpSocket},
+};
+
+pub mod cache;
+pub mod message;
+
+const DNS_PORT: u16 = 53;
+// per server and attempt
+const QUERY_TIMEOUT: usize = 2 * TICKS_PER_SECOND;
+// every server is asked this many times before we give up
+const MAX_ROUNDS: usize = 2;
+// longer chains are treated as loops
+const MAX_CNAME_DEPTH: usize = 8;
+// random ports that are taken are retried, after this many an ephemeral one is used
+const MAX_BIND_ATTEMPTS: usize = 8;
+
+#[derive(Error, Debug)]
+pub enum DnsError {
+    #[error("Message of {0} bytes is too short")]
+    TooShort(usize),
+    #[error("Invalid domain name")]
+    InvalidName,
+    #[error("Compression pointers form a loop")]
+    PointerLoop,
+    #[error("{0} does not exist")]
+    NameNotFound(String),
+    #[error("{0} has no records of the requested type")]
+    NoRecords(String),
+    #[error("CNAME chain for {0} is too long")]
+    CnameLoop(String),
+    #[error("Server {0} answered {1:?}")]
+    ServerFailure(Ipv4Address, ResponseCode),
+    #[error("No DNS servers are configured")]
+    NoServers,
+    #[error("Timed out waiting for an answer")]
+    TimedOut,
+    #[error(transparent)]
+    Udp(#[from] UdpError),
+    #[error(transparent)]
+    Tcp(#[from] TcpError),
+}
+
+pub type Result<T> = core::result::Result<T, DnsError>;
+
+// the addresses of `name`, the lookups drive the stack so they must not be called from a protocol handler
+pub fn resolve(name: &str) -> Result<Vec<Ipv4Address>> {
+    if let Ok(address) = name.parse() {
+        return Ok(vec![address]);
+    }
+
+    Ok(lookup(name, RecordType::A)?
+        .into_iter()
+        .filter_map(|record| match record {
+            RecordData::A(address) => Some(address),
+            _ => None,
+        })
+        .collect())
+}
+
+// the names behind `address`, from its PTR records under in-addr.arpa
+pub fn reverse_lookup(address: Ipv4Address) -> Result<Vec<String>> {
+    let [a, b, c, d] = address.bytes;
+    let name = format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a);
+
+    Ok(lookup(&name, RecordType::Ptr)?
+        .into_iter()
+        .filter_map(|record| match record {
+            RecordData::Ptr(name) => Some(name),
+            _ => None,
+        })
+        .collect())
+}
+
+// the records of `record_type` for `name`, following CNAMEs. the cache is asked before the servers
+pub fn lookup(name: &str, record_type: RecordType) -> Result<Vec<RecordData>> {
+    let mut name = String::from(name);
+    // the last response is searched first, its records may have been too short lived to cache
+    let mut answers = Vec::new();
+    let mut queried = None;
+    let mut depth = 0;
+
+    loop {
+        if let Some(records) =
+            find_records(&answers, &name, record_type).or_else(|| cache::lookup(&name, record_type))
+        {
+            return Ok(records);
+        }
+
+        let alias = find_records(&answers, &name, RecordType::Cname)
+            .or_else(|| cache::lookup(&name, RecordType::Cname))
+            .and_then(|records| records.into_iter().next());
+
+        if let (Some(RecordData::Cname(target)), false) = (alias, record_type == RecordType::Cname)
+        {
+            depth += 1;
+            if depth > MAX_CNAME_DEPTH {
+                return Err(DnsError::CnameLoop(name));
+            }
+
+            name = target;
+            continue;
+        }
+
+        // the server already told us what it knows about this name
+        if queried.as_ref() == Some(&name) {
+            return Err(DnsError::NoRecords(name));
+        }
+
+        answers = query(&name, record_type)?;
+        queried = Some(name.clone());
+    }
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+fn find_records(
+    answers: &[ResourceRecord],
+    name: &str,
+    record_type: RecordType,
+) -> Option<Vec<RecordData>> {
+    let records: Vec<_> = answers
+        .iter()
+        .filter(|record| record.record_type == record_type && same_name(&record.name, name))
+        .map(|record| record.data.clone())
+        .collect();
+
+    (!records.is_empty()).then_some(records)
+}
+
+// every rrset in the answer section goes in on its own
+fn cache_answers(answers: &[ResourceRecord]) {
+    let mut cached: Vec<(&str, RecordType)> = Vec::new();
+
+    for record in answers {
+        if cached.iter().any(|&(name, record_type)| {
+            record_type == record.record_type && same_name(name, &record.name)
+        }) {
+            continue;
+        }
+
+        let rrset = answers.iter().filter(|other| {
+            other.record_type == record.record_type && same_name(&other.name, &record.name)
+        });
+
+        cache::insert(
+            &record.name,
+            record.record_type,
+            rrset.clone().map(|other| other.data.clone()).collect(),
+            rrset.map(|other| other.time_to_live).min().unwrap_or(0),
+        );
+
+        cached.push((&record.name, record.record_type));
+    }
+}
+
+// asks every configured server in turn, twice around before giving up
+fn query(name: &str, record_type: RecordType) -> Result<Vec<ResourceRecord>> {
+    let servers = interface::dns_servers();
+    if servers.is_empty() {
+        return Err(DnsError::NoServers);
+    }
+
+    let mut last_error = DnsError::TimedOut;
+
+    for _ in 0..MAX_ROUNDS {
+        for &server in &servers {
+            let query = DnsMessage::query(random() as u16, name, record_type);
+
+            let response = match exchange_udp(server, &query) {
+                Ok(response) => response,
+                Err(err) => {
+                    last_error = err;
+                    continue;
+                }
+            };
+
+            match response.response_code {
+                ResponseCode::NoError => {
+                    cache_answers(&response.answers);
+                    return Ok(response.answers);
+                }
+                // an authoritative no, other servers would say the same
+                ResponseCode::NameError => return Err(DnsError::NameNotFound(String::from(name))),
+                code => last_error = DnsError::ServerFailure(server, code),
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+fn is_response_to(response: &DnsMessage, query: &DnsMessage) -> bool {
+    response.flags.contains(DnsFlags::RESPONSE)
+        && response.id == query.id
+        && response.questions.len() == query.questions.len()
+        && response
+            .questions
+            .iter()
+            .zip(&query.questions)
+            .all(|(answered, asked)| {
+                answered.record_type == asked.record_type && same_name(&answered.name, &asked.name)
+            })
+}
+
+// every query goes out from a socket of its own, see bind_random_port
+fn exchange_udp(server: Ipv4Address, query: &DnsMessage) -> Result<DnsMessage> {
+    let socket = bind_random_port()?;
+    let server_address = SocketAddressV4::new(server, DNS_PORT).into();
+    socket.send_to(&query.to_bytes()?, server_address)?;
+
+    // anything that doesn't match what we asked is ignored, it may be spoofed or a late answer
+    let sent_at = pit::ticks();
+    let response = loop {
+        let remaining = QUERY_TIMEOUT.saturating_sub(pit::ticks().wrapping_sub(sent_at));
+        let datagram = match socket.recv_from(Some(remaining)) {
+            Ok(datagram) => datagram,
+            Err(UdpError::TimedOut) => return Err(DnsError::TimedOut),
+            Err(err) => return Err(err.into()),
+        };
+
+        if datagram.source != server_address {
+            continue;
+        }
+
+        match DnsMessage::parse(&datagram.data) {
+            Ok(response) if is_response_to(&response, query) => break response,
+            Ok(_) => (),
+            Err(err) => println!("Dropped DNS message from {}: {}", server, err),
+        }
+    };
+
+    // the answer didn't fit in a datagram, it has to be asked again over TCP
+    if response.flags.contains(DnsFlags::TRUNCATED) {
+        return exchange_tcp(server, query);
+    }
+
+    Ok(response)
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7766, every message is preceded by its length
+fn exchange_tcp(server: Ipv4Address, query: &DnsMessage) -> Result<DnsMessage> {
+    let stream = TcpStream::connect(
+        SocketAddressV4::new(server, DNS_PORT).into(),
+        Some(QUERY_TIMEOUT),
+    )?;
+
+    let query_bytes = query.to_bytes()?;
+    let mut framed = Vec::with_capacity(2 + query_bytes.len());
+    framed.extend_from_slice(&(query_bytes.len() as u16).to_be_bytes());
+    framed.extend_from_slice(&query_bytes);
+    stream.write_all(&framed, Some(QUERY_TIMEOUT))?;
+
+    let mut length = [0; 2];
+    read_exact(&stream, &mut length)?;
+
+    let mut response = vec![0; u16::from_be_bytes(length) as usize];
+    read_exact(&stream, &mut response)?;
+
+    let response = DnsMessage::parse(&response)?;
+    if !is_response_to(&response, query) {
+        return Err(DnsError::ServerFailure(server, response.response_code));
+    }
+
+    Ok(response)
+}
+
+fn read_exact(stream: &TcpStream, buffer: &mut [u8]) -> Result<()> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        match stream.read(&mut buffer[filled..], Some(QUERY_TIMEOUT))? {
+            // the server closed before the whole message came
+            0 => return Err(DnsError::TooShort(filled)),
+            length => filled += length,
+        }
+    }
+
+    Ok(())
+}
+
+// rfc 5452, a forged answer has to guess the port as well as the id. the ephemeral ports are
+// handed out in order, so the port is picked at random from the same range
+fn bind_random_port() -> Result<UdpSocket> {
+    let port_count = (EPHEMERAL_PORT_END - EPHEMERAL_PORT_START) as u64 + 1;
+
+    for _ in 0..MAX_BIND_ATTEMPTS {
+        let port = EPHEMERAL_PORT_START + (random() % port_count) as u16;
+
+        match UdpSocket::bind(SocketAddressV4::new(Ipv4Address::UNSPECIFIED, port).into()) {
+            Err(UdpError::AddressInUse(_)) => continue,
+            result => return Ok(result?),
+        }
+    }
+
+    Ok(UdpSocket::bind(
+        SocketAddressV4::new(Ipv4Address::UNSPECIFIED, 0).into(),
+    )?)
+}
+
+// there is no entropy source, so every call stirs the timestamp counter into a state that is
+// scrambled with splitmix64. the value then depends on the timing of every earlier query too
+static RANDOM_STATE: Mutex<u64> = Mutex::new(0);
+
+fn random() -> u64 {
+    let mut state = RANDOM_STATE.lock();
+    *state = state
+        .wrapping_add(read_timestamp_counter())
+        .wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+    let mut value = *state;
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
@@ -1,9 +1,29 @@
-use core::{fmt::Formatter, mem::size_of};
+use core::fmt::Formatter;
 
-use alloc::vec::{self, Vec};
 use crc::Crc;
+use thiserror::Error;
 
-#[derive(Debug, Clone, Copy)]
+use super::packet_buffer::PacketBuffer;
+
+pub const ETHERNET_HEADER_SIZE: usize = 14;
+pub const VLAN_TAG_SIZE: usize = 4;
+pub const FRAME_CHECK_SEQUENCE_SIZE: usize = 4;
+// the largest payload an untagged frame can carry
+pub const MAX_PAYLOAD_SIZE: usize = 1500;
+
+const ETHERNET_CRC: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+#[derive(Error, Debug)]
+pub enum EthernetError {
+    #[error("Frame of {0} bytes is too short for an ethernet header")]
+    TooShort(usize),
+    #[error("Frame of {0} bytes is larger than the ethernet maximum")]
+    TooLong(usize),
+    #[error("Frame check sequence mismatch, expected {expected:#X}, found {found:#X}")]
+    BadFrameCheckSequence { expected: u32, found: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(C, packed)]
 pub struct EthernetAddress {
     pub bytes: [u8; 6],
@@ -13,6 +33,17 @@ impl EthernetAddress {
     pub fn broadcast() -> Self {
         Self { bytes: [0xff; 6] }
     }
+
+    // the group bit is the lowest bit of the first byte, broadcast counts as multicast too
+    pub fn is_multicast(&self) -> bool {
+        self.bytes[0] & 1 != 0
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut address = Self { bytes: [0; 6] };
+        address.bytes.copy_from_slice(&bytes[..6]);
+        address
+    }
 }
 
 impl core::fmt::Display for EthernetAddress {
@@ -31,37 +62,161 @@ impl core::fmt::Display for EthernetAddress {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-#[repr(u16)]
+// any 16 bit value can show up on the wire, so unknown types are kept instead of transmuted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EitherType {
-    Ipv4 = 0x0800,
-    Arp = 0x0806,
+    Ipv4,
+    Arp,
+    Vlan,
+    Ipv6,
+    Unknown(u16),
 }
 
-#[repr(C, packed)]
-pub struct EthernetFrame<'a> {
+impl From<u16> for EitherType {
+    fn from(value: u16) -> Self {
+        match value {
+            0x0800 => Self::Ipv4,
+            0x0806 => Self::Arp,
+            0x8100 => Self::Vlan,
+            0x86DD => Self::Ipv6,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<EitherType> for u16 {
+    fn from(value: EitherType) -> Self {
+        match value {
+            EitherType::Ipv4 => 0x0800,
+            EitherType::Arp => 0x0806,
+            EitherType::Vlan => 0x8100,
+            EitherType::Ipv6 => 0x86DD,
+            EitherType::Unknown(value) => value,
+        }
+    }
+}
+
+// https://en.wikipedia.org/wiki/IEEE_802.1Q
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VlanTag {
+    pub priority: u8,
+    pub drop_eligible: bool,
+    pub id: u16,
+}
+
+impl VlanTag {
+    fn from_bytes(bytes: [u8; 2]) -> Self {
+        let control = u16::from_be_bytes(bytes);
+        Self {
+            priority: (control >> 13) as u8,
+            drop_eligible: control & (1 << 12) != 0,
+            id: control & 0xFFF,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 2] {
+        (((self.priority as u16 & 0b111) << 13)
+            | ((self.drop_eligible as u16) << 12)
+            | (self.id & 0xFFF))
+            .to_be_bytes()
+    }
+}
+
+// the frame check sequence goes on the wire least significant byte first
+pub fn frame_check_sequence(frame: &[u8]) -> u32 {
+    ETHERNET_CRC.checksum(frame)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EthernetHeader {
     pub destination_address: EthernetAddress,
     pub source_address: EthernetAddress,
+    pub vlan: Option<VlanTag>,
     pub ether_type: EitherType,
+}
+
+impl EthernetHeader {
+    pub fn size(&self) -> usize {
+        ETHERNET_HEADER_SIZE + self.vlan.map_or(0, |_| VLAN_TAG_SIZE)
+    }
+
+    // the frame check sequence is left to the card
+    pub fn prepend_to(&self, packet: &mut PacketBuffer) {
+        let header = packet.prepend(self.size());
+
+        header[0..6].copy_from_slice(&self.destination_address.bytes);
+        header[6..12].copy_from_slice(&self.source_address.bytes);
+
+        let mut offset = 12;
+        if let Some(vlan) = self.vlan {
+            header[offset..offset + 2].copy_from_slice(&u16::from(EitherType::Vlan).to_be_bytes());
+            header[offset + 2..offset + 4].copy_from_slice(&vlan.to_bytes());
+            offset += VLAN_TAG_SIZE;
+        }
+
+        header[offset..offset + 2].copy_from_slice(&u16::from(self.ether_type).to_be_bytes());
+    }
+}
+
+// a received frame, the payload borrows from the buffer the card handed us
+#[derive(Debug, Clone, Copy)]
+pub struct EthernetFrame<'a> {
+    pub header: EthernetHeader,
     pub data: &'a [u8],
 }
 
-impl <'a> EthernetFrame <'a> {
-    pub fn to_bytes(&self, add_crc: bool) -> Vec<u8> {
-        let mut vec = Vec::with_capacity(size_of::<EthernetFrame>() + self.data.len());
+impl<'a> EthernetFrame<'a> {
+    // frames may come in shorter than the 60 byte minimum since not every card pads them,
+    // `has_frame_check_sequence` is for cards that leave the FCS at the end of the frame
+    pub fn parse(bytes: &'a [u8], has_frame_check_sequence: bool) -> Result<Self, EthernetError> {
+        let mut bytes = bytes;
+
+        if has_frame_check_sequence {
+            if bytes.len() < ETHERNET_HEADER_SIZE + FRAME_CHECK_SEQUENCE_SIZE {
+                return Err(EthernetError::TooShort(bytes.len()));
+            }
 
-        vec.extend_from_slice(&self.destination_address.bytes);
-        vec.extend_from_slice(&self.source_address.bytes);
-        vec.extend_from_slice(&(self.ether_type as u16).to_be_bytes());
-        vec.extend_from_slice(self.data);
+            let (frame, sequence) = bytes.split_at(bytes.len() - FRAME_CHECK_SEQUENCE_SIZE);
+            let expected = frame_check_sequence(frame);
+            let found = u32::from_le_bytes(sequence.try_into().unwrap());
+
+            if expected != found {
+                return Err(EthernetError::BadFrameCheckSequence { expected, found });
+            }
+
+            bytes = frame;
+        }
+
+        if bytes.len() < ETHERNET_HEADER_SIZE {
+            return Err(EthernetError::TooShort(bytes.len()));
+        }
+
+        let mut ether_type = EitherType::from(u16::from_be_bytes([bytes[12], bytes[13]]));
+        let mut vlan = None;
+        let mut header_size = ETHERNET_HEADER_SIZE;
+
+        if ether_type == EitherType::Vlan {
+            if bytes.len() < ETHERNET_HEADER_SIZE + VLAN_TAG_SIZE {
+                return Err(EthernetError::TooShort(bytes.len()));
+            }
+
+            vlan = Some(VlanTag::from_bytes([bytes[14], bytes[15]]));
+            ether_type = EitherType::from(u16::from_be_bytes([bytes[16], bytes[17]]));
+            header_size += VLAN_TAG_SIZE;
+        }
 
-        if add_crc {
-            let crc = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
-            let mut digest = crc.digest();
-            digest.update(&vec);
-            vec.extend_from_slice(&digest.finalize().to_be_bytes());
+        if bytes.len() > header_size + MAX_PAYLOAD_SIZE {
+            return Err(EthernetError::TooLong(bytes.len()));
         }
 
-        vec
+        Ok(Self {
+            header: EthernetHeader {
+                destination_address: EthernetAddress::from_slice(&bytes[0..6]),
+                source_address: EthernetAddress::from_slice(&bytes[6..12]),
+                vlan,
+                ether_type,
+            },
+            data: &bytes[header_size..],
+        })
     }
 }
@@ -0,0 +1,90 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::println;
+
+use super::{request::Request, response::Response, Handler};
+
+// runs around a handler, it can answer by itself, change the request or change the response.
+// `next` is the rest of the chain
+pub trait Middleware {
+    fn handle(&self, request: Request, next: &dyn Handler) -> Response;
+}
+
+impl<F: Fn(Request, &dyn Handler) -> Response> Middleware for F {
+    fn handle(&self, request: Request, next: &dyn Handler) -> Response {
+        self(request, next)
+    }
+}
+
+// a handler behind middleware, the first one added sees the request first
+pub struct Chain {
+    middleware: Vec<Box<dyn Middleware>>,
+    handler: Box<dyn Handler>,
+}
+
+impl Chain {
+    pub fn new(handler: impl Handler + 'static) -> Self {
+        Self {
+            middleware: Vec::new(),
+            handler: Box::new(handler),
+        }
+    }
+
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+}
+
+// the part of the chain after `index`
+struct Next<'a> {
+    chain: &'a Chain,
+    index: usize,
+}
+
+impl Handler for Next<'_> {
+    fn handle(&self, request: Request) -> Response {
+        match self.chain.middleware.get(self.index) {
+            Some(middleware) => middleware.handle(
+                request,
+                &Next {
+                    chain: self.chain,
+                    index: self.index + 1,
+                },
+            ),
+            None => self.chain.handler.handle(request),
+        }
+    }
+}
+
+impl Handler for Chain {
+    fn handle(&self, request: Request) -> Response {
+        Next {
+            chain: self,
+            index: 0,
+        }
+        .handle(request)
+    }
+}
+
+// prints a line for every request and the status it got
+pub fn log_requests(request: Request, next: &dyn Handler) -> Response {
+    let method = request.method.clone();
+    let path = request.path.clone();
+    let peer_address = request.peer_address;
+
+    let response = next.handle(request);
+
+    match peer_address {
+        Some(peer_address) => println!(
+            "{} {} {} -> {}",
+            peer_address,
+            method,
+            path,
+            u16::from(response.status)
+        ),
+        None => println!("{} {} -> {}", method, path, u16::from(response.status)),
+    }
+
+    response
+}
@@ -0,0 +1,228 @@
+// https://datatracker.ietf.org/doc/html/rfc9110
+// https://datatracker.ietf.org/doc/html/rfc9112
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Formatter;
+use thiserror::Error;
+
+use super::tcp::TcpError;
+
+use self::{request::Request, response::Response, response::StatusCode};
+
+pub mod middleware;
+pub mod request;
+pub mod response;
+pub mod router;
+pub mod server;
+pub mod static_files;
+pub mod websocket;
+
+#[derive(Error, Debug)]
+pub enum HttpError {
+    #[error("Malformed request line")]
+    InvalidRequestLine,
+    #[error("Malformed header line")]
+    InvalidHeader,
+    #[error("HTTP/1.1 request without a Host header")]
+    MissingHost,
+    #[error("Request head is larger than {0} bytes")]
+    HeadTooLarge(usize),
+    #[error("Body is larger than {0} bytes")]
+    BodyTooLarge(usize),
+    #[error("Invalid Content-Length")]
+    InvalidContentLength,
+    #[error("Malformed chunk")]
+    InvalidChunk,
+    #[error("Unsupported transfer coding {0}")]
+    UnsupportedTransferCoding(String),
+    #[error("Unsupported version {0}")]
+    UnsupportedVersion(String),
+    #[error(transparent)]
+    Tcp(#[from] TcpError),
+}
+
+impl HttpError {
+    // what the client is told before the connection is closed
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::HeadTooLarge(_) => StatusCode::HeaderFieldsTooLarge,
+            Self::BodyTooLarge(_) => StatusCode::PayloadTooLarge,
+            Self::UnsupportedTransferCoding(_) => StatusCode::NotImplemented,
+            Self::UnsupportedVersion(_) => StatusCode::VersionNotSupported,
+            Self::Tcp(_) => StatusCode::InternalServerError,
+            _ => StatusCode::BadRequest,
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, HttpError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Options,
+    Patch,
+    Other(String),
+}
+
+impl From<&str> for Method {
+    // methods are case sensitive
+    fn from(value: &str) -> Self {
+        match value {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "OPTIONS" => Self::Options,
+            "PATCH" => Self::Patch,
+            value => Self::Other(value.to_string()),
+        }
+    }
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Options => "OPTIONS",
+            Self::Patch => "PATCH",
+            Self::Other(value) => value,
+        }
+    }
+}
+
+impl core::fmt::Display for Method {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Version {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 => "HTTP/1.1",
+        }
+    }
+}
+
+// field names are case insensitive, the order and duplicates are kept as received
+#[derive(Debug, Clone, Default)]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // for list fields like Connection, every occurrence is split on commas
+    pub fn contains_token(&self, name: &str, token: &str) -> bool {
+        self.get_all(name)
+            .flat_map(|value| value.split(','))
+            .any(|item| item.trim().eq_ignore_ascii_case(token))
+    }
+
+    // replaces every field of that name
+    pub fn set(&mut self, name: &str, value: impl Into<String>) {
+        self.remove(name);
+        self.append(name, value);
+    }
+
+    pub fn append(&mut self, name: &str, value: impl Into<String>) {
+        self.fields.push((name.to_string(), value.into()));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.fields
+            .retain(|(field, _)| !field.eq_ignore_ascii_case(name));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+// what the server calls for every request, closures work as handlers too
+pub trait Handler {
+    fn handle(&self, request: Request) -> Response;
+}
+
+impl<F: Fn(Request) -> Response> Handler for F {
+    fn handle(&self, request: Request) -> Response {
+        self(request)
+    }
+}
+
+// %XX escapes of paths and queries, `+` is a space only in queries.
+// invalid escapes are kept as they are
+pub fn percent_decode(value: &str, plus_as_space: bool) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let escaped = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| core::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+                match escaped {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
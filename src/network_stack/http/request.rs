@@ -0,0 +1,399 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::network_stack::ip::SocketAddress;
+
+use super::{percent_decode, Headers, HttpError, Method, Result, Version};
+
+// the request line and headers have to fit in this, anything larger is answered with 431
+pub const MAX_HEAD_SIZE: usize = 8 * 1024;
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+// a chunk size line is a hex number and extensions we ignore
+const MAX_CHUNK_LINE_SIZE: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: Method,
+    // percent-decoded, without the query
+    pub path: String,
+    // decoded name and value pairs in the order they came
+    pub query: Vec<(String, String)>,
+    pub version: Version,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+    // filled by the router from the `:name` and `*name` segments of the matched pattern
+    pub params: Vec<(String, String)>,
+    pub peer_address: Option<SocketAddress>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    pub fn query(&self, name: &str) -> Option<&str> {
+        find_value(&self.query, name)
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        find_value(&self.params, name)
+    }
+
+    // rfc 9112 9.3, HTTP/1.1 stays open unless told otherwise and HTTP/1.0 is the other way around
+    pub fn keep_alive(&self) -> bool {
+        match self.version {
+            Version::Http11 => !self.headers.contains_token("Connection", "close"),
+            Version::Http10 => self.headers.contains_token("Connection", "keep-alive"),
+        }
+    }
+}
+
+fn find_value<'a>(pairs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name, true), percent_decode(value, true))
+        })
+        .collect()
+}
+
+fn parse_head(head: &[u8]) -> Result<Request> {
+    let head = core::str::from_utf8(head).map_err(|_| HttpError::InvalidHeader)?;
+    let mut lines = head.split("\r\n");
+
+    let request_line = lines.next().ok_or(HttpError::InvalidRequestLine)?;
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(HttpError::InvalidRequestLine);
+    };
+
+    if method.is_empty() {
+        return Err(HttpError::InvalidRequestLine);
+    }
+
+    let version = match version {
+        "HTTP/1.1" => Version::Http11,
+        "HTTP/1.0" => Version::Http10,
+        version if version.starts_with("HTTP/") => {
+            return Err(HttpError::UnsupportedVersion(version.to_string()))
+        }
+        _ => return Err(HttpError::InvalidRequestLine),
+    };
+
+    // the absolute form proxies get is reduced to its path
+    let target = match target.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |start| &rest[start..]),
+        None => target,
+    };
+
+    if !target.starts_with('/') && target != "*" {
+        return Err(HttpError::InvalidRequestLine);
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = Headers::new();
+    for line in lines {
+        let (name, value) = line.split_once(':').ok_or(HttpError::InvalidHeader)?;
+
+        // rfc 9112 5.1, no whitespace before the colon and no folded lines
+        if name.is_empty() || name.contains(|c: char| c.is_ascii_whitespace()) {
+            return Err(HttpError::InvalidHeader);
+        }
+
+        headers.append(name, value.trim());
+    }
+
+    if version == Version::Http11 && !headers.contains("Host") {
+        return Err(HttpError::MissingHost);
+    }
+
+    Ok(Request {
+        method: Method::from(method),
+        path: percent_decode(path, false),
+        query: parse_query(query),
+        version,
+        headers,
+        body: Vec::new(),
+        params: Vec::new(),
+        peer_address: None,
+    })
+}
+
+// rfc 9112 6.3, how the body after the head is delimited
+enum BodyLength {
+    Fixed(usize),
+    Chunked,
+}
+
+fn body_length(headers: &Headers) -> Result<BodyLength> {
+    // Content-Length is ignored next to Transfer-Encoding, rfc 9112 6.3 3
+    if headers.contains("Transfer-Encoding") {
+        let codings: Vec<&str> = headers
+            .get_all("Transfer-Encoding")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty())
+            .collect();
+
+        // we don't decompress anything, so chunked is the only coding we take
+        return match codings.as_slice() {
+            [coding] if coding.eq_ignore_ascii_case("chunked") => Ok(BodyLength::Chunked),
+            codings => Err(HttpError::UnsupportedTransferCoding(codings.join(", "))),
+        };
+    }
+
+    let mut length = None;
+    for value in headers.get_all("Content-Length") {
+        let parsed = value
+            .bytes()
+            .all(|byte| byte.is_ascii_digit())
+            .then(|| value.parse::<usize>().ok())
+            .flatten()
+            .ok_or(HttpError::InvalidContentLength)?;
+
+        // repeated fields have to agree or the request could be read two ways
+        if length.is_some_and(|length| length != parsed) {
+            return Err(HttpError::InvalidContentLength);
+        }
+        length = Some(parsed);
+    }
+
+    let length = length.unwrap_or(0);
+    if length > MAX_BODY_SIZE {
+        return Err(HttpError::BodyTooLarge(MAX_BODY_SIZE));
+    }
+
+    Ok(BodyLength::Fixed(length))
+}
+
+enum ChunkState {
+    Size,
+    Data(usize),
+    // the CRLF after the chunk data
+    DataEnd,
+    Trailers,
+}
+
+enum ParserState {
+    Head,
+    Body { request: Request, remaining: usize },
+    Chunked { request: Request, chunk: ChunkState },
+}
+
+// fed with whatever the connection receives, hands out requests once they are complete.
+// pipelined requests stay buffered until they are asked for
+pub struct RequestParser {
+    buffer: Vec<u8>,
+    state: ParserState,
+    // set when the head asked for 100 Continue, cleared by take_continue
+    continue_requested: bool,
+}
+
+impl RequestParser {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            state: ParserState::Head,
+            continue_requested: false,
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    // whatever was received past the last request, for a connection that switches protocols
+    pub fn take_buffer(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.buffer)
+    }
+
+    // rfc 9110 10.1.1, the client waits for an interim response before it sends the body
+    pub fn take_continue(&mut self) -> bool {
+        core::mem::take(&mut self.continue_requested)
+    }
+
+    // the parser can't recover from an error, the connection has to be closed
+    pub fn next_request(&mut self) -> Result<Option<Request>> {
+        loop {
+            let state = core::mem::replace(&mut self.state, ParserState::Head);
+
+            let (state, request) = match state {
+                ParserState::Head => match self.parse_head()? {
+                    Some(state) => (state, None),
+                    None => return Ok(None),
+                },
+                ParserState::Body {
+                    mut request,
+                    remaining,
+                } => {
+                    let length = remaining.min(self.buffer.len());
+                    request.body.extend(self.buffer.drain(..length));
+
+                    match remaining - length {
+                        0 => (ParserState::Head, Some(request)),
+                        remaining => {
+                            self.state = ParserState::Body { request, remaining };
+                            return Ok(None);
+                        }
+                    }
+                }
+                ParserState::Chunked { request, chunk } => {
+                    match self.parse_chunked(request, chunk)? {
+                        Ok(request) => (ParserState::Head, Some(request)),
+                        Err(state) => {
+                            self.state = state;
+                            return Ok(None);
+                        }
+                    }
+                }
+            };
+
+            self.state = state;
+
+            if request.is_some() {
+                return Ok(request);
+            }
+        }
+    }
+
+    // returns the state the body is read in, None while the head is incomplete
+    fn parse_head(&mut self) -> Result<Option<ParserState>> {
+        // rfc 9112 2.2, empty lines in front of a request are ignored
+        let leading = self
+            .buffer
+            .iter()
+            .take_while(|&&byte| byte == b'\r' || byte == b'\n')
+            .count();
+        self.buffer.drain(..leading);
+
+        let Some(end) = self
+            .buffer
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+        else {
+            if self.buffer.len() > MAX_HEAD_SIZE {
+                return Err(HttpError::HeadTooLarge(MAX_HEAD_SIZE));
+            }
+            return Ok(None);
+        };
+
+        if end > MAX_HEAD_SIZE {
+            return Err(HttpError::HeadTooLarge(MAX_HEAD_SIZE));
+        }
+
+        let request = parse_head(&self.buffer[..end])?;
+        self.buffer.drain(..end + 4);
+
+        let state = match body_length(&request.headers)? {
+            BodyLength::Fixed(remaining) => ParserState::Body { request, remaining },
+            BodyLength::Chunked => ParserState::Chunked {
+                request,
+                chunk: ChunkState::Size,
+            },
+        };
+
+        self.continue_requested = match &state {
+            ParserState::Body { request, remaining } => {
+                *remaining > self.buffer.len() && expects_continue(request)
+            }
+            ParserState::Chunked { request, .. } => {
+                self.buffer.is_empty() && expects_continue(request)
+            }
+            ParserState::Head => false,
+        };
+
+        Ok(Some(state))
+    }
+
+    fn take_line(&mut self, limit: usize) -> Result<Option<String>> {
+        let Some(end) = self.buffer.windows(2).position(|window| window == b"\r\n") else {
+            if self.buffer.len() > limit {
+                return Err(HttpError::InvalidChunk);
+            }
+            return Ok(None);
+        };
+
+        let line = core::str::from_utf8(&self.buffer[..end])
+            .map_err(|_| HttpError::InvalidChunk)?
+            .to_string();
+        self.buffer.drain(..end + 2);
+
+        Ok(Some(line))
+    }
+
+    // Ok(Err(state)) means more bytes are needed
+    fn parse_chunked(
+        &mut self,
+        mut request: Request,
+        mut chunk: ChunkState,
+    ) -> Result<core::result::Result<Request, ParserState>> {
+        loop {
+            chunk = match chunk {
+                ChunkState::Size => {
+                    let Some(line) = self.take_line(MAX_CHUNK_LINE_SIZE)? else {
+                        return Ok(Err(ParserState::Chunked { request, chunk }));
+                    };
+
+                    let size = line.split(';').next().unwrap_or("").trim();
+                    let size =
+                        usize::from_str_radix(size, 16).map_err(|_| HttpError::InvalidChunk)?;
+
+                    if request.body.len().saturating_add(size) > MAX_BODY_SIZE {
+                        return Err(HttpError::BodyTooLarge(MAX_BODY_SIZE));
+                    }
+
+                    match size {
+                        0 => ChunkState::Trailers,
+                        size => ChunkState::Data(size),
+                    }
+                }
+                ChunkState::Data(remaining) => {
+                    let length = remaining.min(self.buffer.len());
+                    request.body.extend(self.buffer.drain(..length));
+
+                    match remaining - length {
+                        0 => ChunkState::DataEnd,
+                        remaining => {
+                            return Ok(Err(ParserState::Chunked {
+                                request,
+                                chunk: ChunkState::Data(remaining),
+                            }))
+                        }
+                    }
+                }
+                ChunkState::DataEnd => match self.take_line(2)? {
+                    Some(line) if line.is_empty() => ChunkState::Size,
+                    Some(_) => return Err(HttpError::InvalidChunk),
+                    None => return Ok(Err(ParserState::Chunked { request, chunk })),
+                },
+                // trailer fields are read and dropped, nothing we serve looks at them
+                ChunkState::Trailers => match self.take_line(MAX_HEAD_SIZE)? {
+                    Some(line) if line.is_empty() => return Ok(Ok(request)),
+                    Some(_) => ChunkState::Trailers,
+                    None => return Ok(Err(ParserState::Chunked { request, chunk })),
+                },
+            };
+        }
+    }
+}
+
+fn expects_continue(request: &Request) -> bool {
+    request.version == Version::Http11
+        && request
+            .header("Expect")
+            .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"))
+}
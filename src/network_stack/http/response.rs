@@ -0,0 +1,240 @@
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+
+use super::{websocket::Session, Headers, Version};
+
+const SERVER_NAME: &str = "MonkaOS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusCode {
+    Continue,
+    SwitchingProtocols,
+    Ok,
+    Created,
+    NoContent,
+    PartialContent,
+    MovedPermanently,
+    Found,
+    NotModified,
+    BadRequest,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    RequestTimeout,
+    PayloadTooLarge,
+    RangeNotSatisfiable,
+    UpgradeRequired,
+    HeaderFieldsTooLarge,
+    InternalServerError,
+    NotImplemented,
+    BadGateway,
+    ServiceUnavailable,
+    GatewayTimeout,
+    VersionNotSupported,
+    Unknown(u16),
+}
+
+impl From<u16> for StatusCode {
+    fn from(value: u16) -> Self {
+        match value {
+            100 => Self::Continue,
+            101 => Self::SwitchingProtocols,
+            200 => Self::Ok,
+            201 => Self::Created,
+            204 => Self::NoContent,
+            206 => Self::PartialContent,
+            301 => Self::MovedPermanently,
+            302 => Self::Found,
+            304 => Self::NotModified,
+            400 => Self::BadRequest,
+            403 => Self::Forbidden,
+            404 => Self::NotFound,
+            405 => Self::MethodNotAllowed,
+            408 => Self::RequestTimeout,
+            413 => Self::PayloadTooLarge,
+            416 => Self::RangeNotSatisfiable,
+            426 => Self::UpgradeRequired,
+            431 => Self::HeaderFieldsTooLarge,
+            500 => Self::InternalServerError,
+            501 => Self::NotImplemented,
+            502 => Self::BadGateway,
+            503 => Self::ServiceUnavailable,
+            504 => Self::GatewayTimeout,
+            505 => Self::VersionNotSupported,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(value: StatusCode) -> Self {
+        match value {
+            StatusCode::Continue => 100,
+            StatusCode::SwitchingProtocols => 101,
+            StatusCode::Ok => 200,
+            StatusCode::Created => 201,
+            StatusCode::NoContent => 204,
+            StatusCode::PartialContent => 206,
+            StatusCode::MovedPermanently => 301,
+            StatusCode::Found => 302,
+            StatusCode::NotModified => 304,
+            StatusCode::BadRequest => 400,
+            StatusCode::Forbidden => 403,
+            StatusCode::NotFound => 404,
+            StatusCode::MethodNotAllowed => 405,
+            StatusCode::RequestTimeout => 408,
+            StatusCode::PayloadTooLarge => 413,
+            StatusCode::RangeNotSatisfiable => 416,
+            StatusCode::UpgradeRequired => 426,
+            StatusCode::HeaderFieldsTooLarge => 431,
+            StatusCode::InternalServerError => 500,
+            StatusCode::NotImplemented => 501,
+            StatusCode::BadGateway => 502,
+            StatusCode::ServiceUnavailable => 503,
+            StatusCode::GatewayTimeout => 504,
+            StatusCode::VersionNotSupported => 505,
+            StatusCode::Unknown(value) => value,
+        }
+    }
+}
+
+impl StatusCode {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::Continue => "Continue",
+            Self::SwitchingProtocols => "Switching Protocols",
+            Self::Ok => "OK",
+            Self::Created => "Created",
+            Self::NoContent => "No Content",
+            Self::PartialContent => "Partial Content",
+            Self::MovedPermanently => "Moved Permanently",
+            Self::Found => "Found",
+            Self::NotModified => "Not Modified",
+            Self::BadRequest => "Bad Request",
+            Self::Forbidden => "Forbidden",
+            Self::NotFound => "Not Found",
+            Self::MethodNotAllowed => "Method Not Allowed",
+            Self::RequestTimeout => "Request Timeout",
+            Self::PayloadTooLarge => "Content Too Large",
+            Self::RangeNotSatisfiable => "Range Not Satisfiable",
+            Self::UpgradeRequired => "Upgrade Required",
+            Self::HeaderFieldsTooLarge => "Request Header Fields Too Large",
+            Self::InternalServerError => "Internal Server Error",
+            Self::NotImplemented => "Not Implemented",
+            Self::BadGateway => "Bad Gateway",
+            Self::ServiceUnavailable => "Service Unavailable",
+            Self::GatewayTimeout => "Gateway Timeout",
+            Self::VersionNotSupported => "HTTP Version Not Supported",
+            Self::Unknown(_) => "",
+        }
+    }
+
+    // rfc 9110 6.4.1, these never carry content even when a handler sets some
+    pub fn allows_body(&self) -> bool {
+        let code = u16::from(*self);
+        code >= 200 && code != 204 && code != 304
+    }
+}
+
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+    // takes the connection over once this response is sent, see websocket::accept
+    pub upgrade: Option<Box<dyn Session>>,
+}
+
+impl Response {
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: Headers::new(),
+            body: Vec::new(),
+            upgrade: None,
+        }
+    }
+
+    pub fn text(status: StatusCode, text: &str) -> Self {
+        Self::new(status)
+            .with_header("Content-Type", "text/plain; charset=utf-8")
+            .with_body(text.as_bytes())
+    }
+
+    // a plain text body with the reason phrase, for errors nobody wrote a page for
+    pub fn error(status: StatusCode) -> Self {
+        Self::text(
+            status,
+            &format!("{} {}\n", u16::from(status), status.reason()),
+        )
+    }
+
+    pub fn redirect(status: StatusCode, location: &str) -> Self {
+        Self::new(status).with_header("Location", location)
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.set(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn with_upgrade(mut self, session: impl Session + 'static) -> Self {
+        self.upgrade = Some(Box::new(session));
+        self
+    }
+
+    // whether the handler wants the connection closed after this response
+    pub fn closes_connection(&self) -> bool {
+        self.headers.contains_token("Connection", "close")
+    }
+
+    // the framing headers are ours, whatever the handler set for them is replaced.
+    // a response to HEAD has the length of the body it leaves out. a switch of protocols keeps
+    // the Connection: Upgrade it was given
+    pub fn to_bytes(&self, version: Version, keep_alive: bool, head_only: bool) -> Vec<u8> {
+        let mut headers = self.headers.clone();
+        headers.remove("Transfer-Encoding");
+
+        if self.status.allows_body() {
+            headers.set("Content-Length", format!("{}", self.body.len()));
+        } else {
+            headers.remove("Content-Length");
+        }
+
+        if !headers.contains("Server") {
+            headers.set("Server", SERVER_NAME);
+        }
+
+        match (self.status, keep_alive, version) {
+            (StatusCode::SwitchingProtocols, _, _) => {}
+            (_, false, _) => headers.set("Connection", "close"),
+            (_, true, Version::Http10) => headers.set("Connection", "keep-alive"),
+            (_, true, Version::Http11) => headers.remove("Connection"),
+        }
+
+        let mut bytes = Vec::with_capacity(256 + self.body.len());
+        bytes.extend_from_slice(
+            format!(
+                "{} {} {}\r\n",
+                version.as_str(),
+                u16::from(self.status),
+                self.status.reason()
+            )
+            .as_bytes(),
+        );
+
+        for (name, value) in headers.iter() {
+            bytes.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        bytes.extend_from_slice(b"\r\n");
+
+        if self.status.allows_body() && !head_only {
+            bytes.extend_from_slice(&self.body);
+        }
+
+        bytes
+    }
+}
@@ -0,0 +1,147 @@
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use super::{
+    request::Request,
+    response::{Response, StatusCode},
+    Handler, Method,
+};
+
+// one part of a route pattern between slashes
+enum Segment {
+    Literal(String),
+    // `:name` matches a single segment
+    Parameter(String),
+    // `*name` matches the rest of the path, slashes included, and has to come last
+    Wildcard(String),
+}
+
+struct Route {
+    // None takes any method
+    method: Option<Method>,
+    segments: Vec<Segment>,
+    handler: Box<dyn Handler>,
+}
+
+impl Route {
+    // the captured parameters when the path matches
+    fn matches(&self, path: &[&str]) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Wildcard(name) => {
+                    params.push((name.clone(), path.get(i..).unwrap_or(&[]).join("/")));
+                    return Some(params);
+                }
+                Segment::Literal(literal) => {
+                    if path.get(i) != Some(&literal.as_str()) {
+                        return None;
+                    }
+                }
+                Segment::Parameter(name) => {
+                    params.push((name.clone(), path.get(i)?.to_string()));
+                }
+            }
+        }
+
+        (path.len() == self.segments.len()).then_some(params)
+    }
+
+    fn allows(&self, method: &Method) -> bool {
+        match &self.method {
+            None => true,
+            // rfc 9110 9.3.2, HEAD is answered like GET without the body
+            Some(Method::Get) => *method == Method::Get || *method == Method::Head,
+            Some(allowed) => allowed == method,
+        }
+    }
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+// dispatches on method and path, routes are tried in the order they were added.
+// a path that matches with the wrong method is answered with 405
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self { routes: Vec::new() }
+    }
+
+    // `pattern` is like /users/:id/files/*path
+    pub fn route(
+        mut self,
+        method: Option<Method>,
+        pattern: &str,
+        handler: impl Handler + 'static,
+    ) -> Self {
+        let segments = split_path(pattern)
+            .into_iter()
+            .map(|segment| {
+                if let Some(name) = segment.strip_prefix(':') {
+                    Segment::Parameter(name.to_string())
+                } else if let Some(name) = segment.strip_prefix('*') {
+                    Segment::Wildcard(name.to_string())
+                } else {
+                    Segment::Literal(segment.to_string())
+                }
+            })
+            .collect();
+
+        self.routes.push(Route {
+            method,
+            segments,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn get(self, pattern: &str, handler: impl Handler + 'static) -> Self {
+        self.route(Some(Method::Get), pattern, handler)
+    }
+}
+
+impl Handler for Router {
+    fn handle(&self, mut request: Request) -> Response {
+        // the request moves into the handler, so the segments can't borrow from it
+        let path = request.path.clone();
+        let path = split_path(&path);
+        let mut allowed = Vec::new();
+
+        for route in &self.routes {
+            let Some(params) = route.matches(&path) else {
+                continue;
+            };
+
+            if !route.allows(&request.method) {
+                allowed.extend(route.method.clone());
+                continue;
+            }
+
+            request.params = params;
+            return route.handler.handle(request);
+        }
+
+        if !allowed.is_empty() {
+            let allow = allowed
+                .iter()
+                .map(Method::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            return Response::error(StatusCode::MethodNotAllowed).with_header("Allow", allow);
+        }
+
+        Response::error(StatusCode::NotFound)
+    }
+}
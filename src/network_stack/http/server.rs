@@ -0,0 +1,226 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    network_stack::{
+        ip::SocketAddress,
+        tcp::{TcpError, TcpListener, TcpStream},
+    },
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::{
+    request::RequestParser, response::Response, websocket::connection::WebSocketConnection,
+    Handler, Method, Result, Version,
+};
+
+const BACKLOG: usize = 16;
+// further connections wait in the listen backlog until one of these closes
+const MAX_CONNECTIONS: usize = 32;
+// idle keep-alive connections and peers that stall halfway are closed after this
+const IDLE_TIMEOUT: usize = 15 * TICKS_PER_SECOND;
+const READ_BUFFER_SIZE: usize = 4096;
+// pipelined requests wait while this much is still to be sent, so a peer that doesn't read can't
+// make us buffer without end
+const MAX_PENDING_OUTPUT: usize = 64 * 1024;
+
+struct Connection {
+    stream: TcpStream,
+    peer_address: SocketAddress,
+    parser: RequestParser,
+    // set once a request was upgraded, the rest of the connection is websocket frames
+    websocket: Option<WebSocketConnection>,
+    output: Vec<u8>,
+    written: usize,
+    // the connection closes once what is queued is sent, after a response or a websocket close
+    closing: bool,
+    // the peer sent FIN, nothing more is coming
+    peer_closed: bool,
+    last_activity_at: usize,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            peer_address: stream.peer_address(),
+            stream,
+            parser: RequestParser::new(),
+            websocket: None,
+            output: Vec::new(),
+            written: 0,
+            closing: false,
+            peer_closed: false,
+            last_activity_at: pit::ticks(),
+        }
+    }
+
+    fn pending_output(&self) -> usize {
+        self.output.len() - self.written
+    }
+
+    // returns false once the connection is done, dropping the stream closes it gracefully
+    fn poll(&mut self, handler: &dyn Handler) -> bool {
+        let result = self.receive().and_then(|_| {
+            self.process(handler);
+            self.flush()
+        });
+
+        if let Err(err) = result {
+            println!(
+                "Dropped HTTP connection from {}: {}",
+                self.peer_address, err
+            );
+            return false;
+        }
+
+        if self.pending_output() == 0 && (self.closing || self.peer_closed) {
+            return false;
+        }
+
+        // a websocket stays open as long as the peer answers pings
+        self.websocket.is_some() || !pit::elapsed(self.last_activity_at, IDLE_TIMEOUT)
+    }
+
+    fn receive(&mut self) -> Result<()> {
+        let mut buffer = [0; READ_BUFFER_SIZE];
+
+        while !self.closing && !self.peer_closed && self.pending_output() < MAX_PENDING_OUTPUT {
+            match self.stream.try_read(&mut buffer) {
+                Ok(0) => self.peer_closed = true,
+                Ok(length) => {
+                    match &mut self.websocket {
+                        Some(websocket) => websocket.push(&buffer[..length]),
+                        None => self.parser.push(&buffer[..length]),
+                    }
+                    self.last_activity_at = pit::ticks();
+                }
+                Err(TcpError::WouldBlock) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self, handler: &dyn Handler) {
+        if self.websocket.is_none() {
+            self.process_requests(handler);
+        }
+
+        let unsent = self.pending_output();
+        if let Some(websocket) = &mut self.websocket {
+            let open = websocket.process(unsent);
+            self.output.extend(websocket.take_output());
+            self.closing = !open;
+        }
+    }
+
+    // answers every complete request in the order they came in
+    fn process_requests(&mut self, handler: &dyn Handler) {
+        while !self.closing && self.pending_output() < MAX_PENDING_OUTPUT {
+            let mut request = match self.parser.next_request() {
+                Ok(Some(request)) => request,
+                Ok(None) => {
+                    if self.parser.take_continue() {
+                        self.output
+                            .extend_from_slice(b"HTTP/1.1 100 Continue\r\n\r\n");
+                    }
+                    break;
+                }
+                Err(err) => {
+                    println!("Bad HTTP request from {}: {}", self.peer_address, err);
+
+                    // the parser lost track of where requests start, so the connection can't go on
+                    let response = Response::error(err.status());
+                    self.output
+                        .extend(response.to_bytes(Version::Http11, false, false));
+                    self.closing = true;
+                    break;
+                }
+            };
+
+            let version = request.version;
+            let head_only = request.method == Method::Head;
+            let keep_alive = request.keep_alive();
+            request.peer_address = Some(self.peer_address);
+
+            let mut response = handler.handle(request);
+            let keep_alive = keep_alive && !response.closes_connection();
+
+            self.output
+                .extend(response.to_bytes(version, keep_alive, head_only));
+
+            // requests pipelined after the handshake would be frames of the new protocol
+            if let Some(session) = response.upgrade.take() {
+                self.websocket = Some(WebSocketConnection::new(
+                    session,
+                    self.peer_address,
+                    self.parser.take_buffer(),
+                ));
+                break;
+            }
+
+            self.closing = !keep_alive;
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        while self.pending_output() > 0 {
+            match self.stream.try_write(&self.output[self.written..]) {
+                Ok(0) | Err(TcpError::WouldBlock) => break,
+                Ok(length) => {
+                    self.written += length;
+                    self.last_activity_at = pit::ticks();
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if self.pending_output() == 0 {
+            self.output.clear();
+            self.written = 0;
+        }
+
+        Ok(())
+    }
+}
+
+// serves HTTP/1.1 with keep-alive, pipelining and websocket upgrades. it never blocks, `poll` has to be called
+// between polls of the network stack
+pub struct HttpServer {
+    listener: TcpListener,
+    handler: Box<dyn Handler>,
+    connections: Vec<Connection>,
+}
+
+impl HttpServer {
+    pub fn bind(local: SocketAddress, handler: impl Handler + 'static) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(local, BACKLOG)?,
+            handler: Box::new(handler),
+            connections: Vec::new(),
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddress {
+        self.listener.local_address()
+    }
+
+    // accepts new connections and answers whatever requests are complete
+    pub fn poll(&mut self) {
+        while self.connections.len() < MAX_CONNECTIONS {
+            match self.listener.try_accept() {
+                Ok(stream) => self.connections.push(Connection::new(stream)),
+                Err(TcpError::WouldBlock) => break,
+                Err(err) => {
+                    println!("Failed to accept HTTP connection: {}", err);
+                    break;
+                }
+            }
+        }
+
+        let handler = &*self.handler;
+        self.connections
+            .retain_mut(|connection| connection.poll(handler));
+    }
+}
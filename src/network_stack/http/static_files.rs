@@ -0,0 +1,170 @@
+// conditional requests https://datatracker.ietf.org/doc/html/rfc9110#section-13
+// range requests https://datatracker.ietf.org/doc/html/rfc9110#section-14
+
+use alloc::{format, string::String};
+
+use crate::initrd::{Archive, Entry, EntryKind};
+
+use super::{
+    request::Request,
+    response::{Response, StatusCode},
+    Handler,
+};
+
+// tried in order when a directory is asked for
+const INDEX_FILES: [&str; 2] = ["index.html", "index.htm"];
+
+// serves the files of an archive. behind a `*path` route the wildcard is the file path,
+// otherwise the whole request path is
+pub struct StaticFiles {
+    archive: Archive,
+}
+
+impl StaticFiles {
+    pub fn new(archive: Archive) -> Self {
+        Self { archive }
+    }
+
+    // the file and the path it was found at, directories resolve to their index file
+    fn find(&self, path: &str) -> Option<(String, &Entry)> {
+        if self.archive.is_directory(path) {
+            return INDEX_FILES.iter().find_map(|index| {
+                let path = format!("{}/{}", path, index);
+                let entry = self.archive.get(&path)?;
+                Some((path, entry))
+            });
+        }
+
+        self.archive
+            .get(path)
+            .filter(|entry| entry.kind == EntryKind::File)
+            .map(|entry| (String::from(path), entry))
+    }
+}
+
+impl Handler for StaticFiles {
+    fn handle(&self, request: Request) -> Response {
+        let path = request.param("path").unwrap_or(request.path.as_str());
+
+        // relative links in an index page only work below the directory
+        if self.archive.is_directory(path) && !request.path.ends_with('/') {
+            return Response::redirect(StatusCode::MovedPermanently, &format!("{}/", request.path));
+        }
+
+        match self.find(path) {
+            Some((path, entry)) => serve(&request, &path, entry),
+            None => Response::error(StatusCode::NotFound),
+        }
+    }
+}
+
+fn serve(request: &Request, path: &str, entry: &Entry) -> Response {
+    let size = entry.data.len();
+    let etag = entity_tag(entry);
+
+    let response = Response::new(StatusCode::Ok)
+        .with_header("Content-Type", content_type(path))
+        .with_header("ETag", etag.as_str())
+        .with_header("Accept-Ranges", "bytes");
+
+    if request
+        .header("If-None-Match")
+        .is_some_and(|tags| matches_tag_list(tags, &etag))
+    {
+        return Response {
+            status: StatusCode::NotModified,
+            ..response
+        };
+    }
+
+    // a different validator in If-Range means the client has an older version, it gets all of it
+    let range = request
+        .header("Range")
+        .filter(|_| request.header("If-Range").is_none_or(|tag| tag == etag))
+        .and_then(|range| parse_range(range, size));
+
+    match range {
+        None => response.with_body(entry.data),
+        Some(None) => Response {
+            status: StatusCode::RangeNotSatisfiable,
+            ..response
+        }
+        .with_header("Content-Range", format!("bytes */{}", size)),
+        Some(Some((first, last))) => Response {
+            status: StatusCode::PartialContent,
+            ..response
+        }
+        .with_header(
+            "Content-Range",
+            format!("bytes {}-{}/{}", first, last, size),
+        )
+        .with_body(&entry.data[first..=last]),
+    }
+}
+
+// the archive never changes, so the modification time and size tell versions apart
+fn entity_tag(entry: &Entry) -> String {
+    format!("\"{:x}-{:x}\"", entry.modified, entry.data.len())
+}
+
+// rfc 9110 13.1.2, If-None-Match uses the weak comparison
+fn matches_tag_list(tags: &str, etag: &str) -> bool {
+    tags.split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag.trim_start_matches("W/"))
+}
+
+// None when the header is ignored, which includes multiple ranges since we don't send multipart.
+// Some(None) when nothing of the file is in the range, otherwise the first and last byte
+fn parse_range(header: &str, size: usize) -> Option<Option<(usize, usize)>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+
+    // bytes=-n is the last n bytes
+    if first.is_empty() {
+        let suffix: usize = last.parse().ok()?;
+        return Some((suffix > 0 && size > 0).then(|| (size - suffix.min(size), size - 1)));
+    }
+
+    let first: usize = first.parse().ok()?;
+    let last = match last {
+        "" => usize::MAX,
+        last => last.parse().ok()?,
+    };
+
+    if last < first {
+        return None;
+    }
+
+    Some((first < size).then(|| (first, last.min(size - 1))))
+}
+
+fn content_type(path: &str) -> &'static str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,216 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use crate::{
+    network_stack::ip::SocketAddress,
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::{
+    frame::{Frame, FrameParser, Opcode},
+    CloseCode, Message, Result, Session, WebSocket, WebSocketError, MAX_MESSAGE_SIZE,
+};
+
+// a quiet peer is pinged to find out whether it is still there
+const PING_INTERVAL: usize = 30 * TICKS_PER_SECOND;
+// how long our ping or close frame waits for an answer
+const RESPONSE_TIMEOUT: usize = 10 * TICKS_PER_SECOND;
+
+// the websocket side of an upgraded HTTP connection, the server moves bytes in and out
+pub struct WebSocketConnection {
+    socket: WebSocket,
+    session: Box<dyn Session>,
+    parser: FrameParser,
+    // a fragmented message until its final frame, rfc 6455 5.4
+    message: Option<(Opcode, Vec<u8>)>,
+    opened: bool,
+    // the session was told about the close, nothing happens after that
+    closed: bool,
+    last_received_at: usize,
+    ping_sent_at: Option<usize>,
+}
+
+impl WebSocketConnection {
+    // `buffered` is what the client sent after the handshake request
+    pub fn new(session: Box<dyn Session>, peer_address: SocketAddress, buffered: Vec<u8>) -> Self {
+        Self {
+            socket: WebSocket::new(peer_address),
+            session,
+            parser: FrameParser::new(buffered),
+            message: None,
+            opened: false,
+            closed: false,
+            last_received_at: pit::ticks(),
+            ping_sent_at: None,
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.parser.push(data);
+        self.last_received_at = pit::ticks();
+    }
+
+    // handles the frames that came in and lets the session push. `unsent` is what the server
+    // still has to send. returns false once the connection is over and can be closed
+    pub fn process(&mut self, unsent: usize) -> bool {
+        self.socket.unsent = unsent;
+
+        if !self.opened {
+            self.opened = true;
+            self.session.on_open(&mut self.socket);
+        }
+
+        while !self.closed {
+            let result = self.parser.next_frame().and_then(|frame| match frame {
+                Some(frame) => self.handle_frame(frame).map(|_| true),
+                None => Ok(false),
+            });
+
+            match result {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => self.fail(err),
+            }
+        }
+
+        if self.closed {
+            return false;
+        }
+
+        if !self.socket.is_closing() {
+            self.session.poll(&mut self.socket);
+        }
+
+        let waiting_since = self.socket.close_sent_at.or(self.ping_sent_at);
+        match waiting_since {
+            Some(sent_at) if pit::elapsed(sent_at, RESPONSE_TIMEOUT) => {
+                self.finish(CloseCode::Abnormal, "");
+                return false;
+            }
+            None if pit::elapsed(self.last_received_at, PING_INTERVAL) => {
+                self.socket.send_frame(Frame::new(Opcode::Ping, Vec::new()));
+                self.ping_sent_at = Some(pit::ticks());
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.socket.output)
+    }
+
+    fn handle_frame(&mut self, frame: Frame) -> Result<()> {
+        match frame.opcode {
+            Opcode::Ping => {
+                if !self.socket.is_closing() {
+                    self.socket
+                        .send_frame(Frame::new(Opcode::Pong, frame.payload));
+                }
+            }
+            // unsolicited pongs are allowed and mean the peer is alive just the same
+            Opcode::Pong => self.ping_sent_at = None,
+            Opcode::Close => {
+                let (code, reason) = parse_close(&frame.payload)?;
+
+                // rfc 6455 5.5.1, the close is echoed unless we started it
+                if !self.socket.is_closing() {
+                    self.socket.close(code, "");
+                }
+                self.finish(code, &reason);
+            }
+            Opcode::Text | Opcode::Binary => {
+                if self.message.is_some() {
+                    return Err(WebSocketError::ExpectedContinuation);
+                }
+
+                if frame.fin {
+                    self.deliver(frame.opcode, frame.payload)?;
+                } else {
+                    self.message = Some((frame.opcode, frame.payload));
+                }
+            }
+            Opcode::Continuation => {
+                let Some((opcode, mut data)) = self.message.take() else {
+                    return Err(WebSocketError::UnexpectedContinuation);
+                };
+
+                if data.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                    return Err(WebSocketError::MessageTooLarge(MAX_MESSAGE_SIZE));
+                }
+                data.extend(frame.payload);
+
+                if frame.fin {
+                    self.deliver(opcode, data)?;
+                } else {
+                    self.message = Some((opcode, data));
+                }
+            }
+            Opcode::Unknown(value) => return Err(WebSocketError::UnknownOpcode(value)),
+        }
+
+        Ok(())
+    }
+
+    // messages that come in after our close frame are checked and dropped
+    fn deliver(&mut self, opcode: Opcode, data: Vec<u8>) -> Result<()> {
+        let message = match opcode {
+            Opcode::Text => {
+                Message::Text(String::from_utf8(data).map_err(|_| WebSocketError::InvalidUtf8)?)
+            }
+            _ => Message::Binary(data),
+        };
+
+        if !self.socket.is_closing() {
+            self.session.on_message(&mut self.socket, message);
+        }
+
+        Ok(())
+    }
+
+    // rfc 6455 7.1.7, the peer is told why and the connection is closed without waiting
+    fn fail(&mut self, err: WebSocketError) {
+        println!(
+            "Failing WebSocket connection from {}: {}",
+            self.socket.peer_address, err
+        );
+
+        let code = err.close_code();
+        self.socket.close(code, "");
+        self.finish(code, "");
+    }
+
+    fn finish(&mut self, code: CloseCode, reason: &str) {
+        if !self.closed {
+            self.closed = true;
+            self.session.on_close(code, reason);
+        }
+    }
+}
+
+// the session hears about connections that were dropped without a close handshake too
+impl Drop for WebSocketConnection {
+    fn drop(&mut self) {
+        self.finish(CloseCode::Abnormal, "");
+    }
+}
+
+// an empty close frame is fine, otherwise it starts with a code and may have a utf-8 reason
+fn parse_close(payload: &[u8]) -> Result<(CloseCode, String)> {
+    if payload.is_empty() {
+        return Ok((CloseCode::NoStatus, String::new()));
+    }
+
+    let [first, second, reason @ ..] = payload else {
+        return Err(WebSocketError::InvalidCloseFrame);
+    };
+
+    let code = CloseCode::from(u16::from_be_bytes([*first, *second]));
+    if !code.is_sendable() {
+        return Err(WebSocketError::InvalidCloseFrame);
+    }
+
+    let reason = core::str::from_utf8(reason).map_err(|_| WebSocketError::InvalidUtf8)?;
+    Ok((code, String::from(reason)))
+}
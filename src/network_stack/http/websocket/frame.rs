@@ -0,0 +1,191 @@
+// https://datatracker.ietf.org/doc/html/rfc6455#section-5.2
+
+use alloc::vec::Vec;
+
+use super::{Result, WebSocketError, MAX_MESSAGE_SIZE};
+
+const FIN: u8 = 0x80;
+// no extensions are negotiated, so these have to be zero
+const RESERVED: u8 = 0x70;
+const OPCODE: u8 = 0x0F;
+const MASKED: u8 = 0x80;
+const LENGTH: u8 = 0x7F;
+
+// control frames can't be fragmented and carry at most this much, rfc 6455 5.5
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+    Unknown(u8),
+}
+
+impl From<u8> for Opcode {
+    fn from(value: u8) -> Self {
+        match value {
+            0x0 => Self::Continuation,
+            0x1 => Self::Text,
+            0x2 => Self::Binary,
+            0x8 => Self::Close,
+            0x9 => Self::Ping,
+            0xA => Self::Pong,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(value: Opcode) -> Self {
+        match value {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+            Opcode::Unknown(value) => value,
+        }
+    }
+}
+
+impl Opcode {
+    pub fn is_control(&self) -> bool {
+        u8::from(*self) & 0x8 != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    // the last frame of a message
+    pub fin: bool,
+    pub opcode: Opcode,
+    // already unmasked
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(opcode: Opcode, payload: Vec<u8>) -> Self {
+        Self {
+            fin: true,
+            opcode,
+            payload,
+        }
+    }
+
+    // frames from the server are never masked, rfc 6455 5.1
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let length = self.payload.len();
+        let mut bytes = Vec::with_capacity(10 + length);
+
+        let fin = if self.fin { FIN } else { 0 };
+        bytes.push(fin | u8::from(self.opcode));
+
+        match length {
+            0..=125 => bytes.push(length as u8),
+            126..=0xFFFF => {
+                bytes.push(126);
+                bytes.extend_from_slice(&(length as u16).to_be_bytes());
+            }
+            _ => {
+                bytes.push(127);
+                bytes.extend_from_slice(&(length as u64).to_be_bytes());
+            }
+        }
+
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+}
+
+// fed with whatever the connection receives, hands out frames once they are complete
+pub struct FrameParser {
+    buffer: Vec<u8>,
+}
+
+impl FrameParser {
+    // `buffer` is what came after the handshake request
+    pub fn new(buffer: Vec<u8>) -> Self {
+        Self { buffer }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    // the parser can't recover from an error, the connection has to be failed
+    pub fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let [first, second, ..] = self.buffer[..] else {
+            return Ok(None);
+        };
+
+        if first & RESERVED != 0 {
+            return Err(WebSocketError::ReservedBits);
+        }
+
+        let fin = first & FIN != 0;
+        let opcode = Opcode::from(first & OPCODE);
+        if let Opcode::Unknown(value) = opcode {
+            return Err(WebSocketError::UnknownOpcode(value));
+        }
+
+        // clients have to mask everything they send, rfc 6455 5.1
+        if second & MASKED == 0 {
+            return Err(WebSocketError::UnmaskedFrame);
+        }
+
+        let (length, mut offset) = match second & LENGTH {
+            126 => match self.buffer.get(2..4) {
+                Some(bytes) => (u16::from_be_bytes([bytes[0], bytes[1]]) as u64, 4),
+                None => return Ok(None),
+            },
+            127 => match self.buffer.get(2..10) {
+                Some(bytes) => {
+                    let mut length = [0; 8];
+                    length.copy_from_slice(bytes);
+                    (u64::from_be_bytes(length), 10)
+                }
+                None => return Ok(None),
+            },
+            length => (length as u64, 2),
+        };
+
+        if opcode.is_control() && (!fin || length > MAX_CONTROL_PAYLOAD as u64) {
+            return Err(WebSocketError::InvalidControlFrame);
+        }
+
+        // checked before the payload is buffered, a peer can't make us wait for gigabytes
+        if length > MAX_MESSAGE_SIZE as u64 {
+            return Err(WebSocketError::MessageTooLarge(MAX_MESSAGE_SIZE));
+        }
+        let length = length as usize;
+
+        let Some(key) = self.buffer.get(offset..offset + 4) else {
+            return Ok(None);
+        };
+        let key = [key[0], key[1], key[2], key[3]];
+        offset += 4;
+
+        if self.buffer.len() < offset + length {
+            return Ok(None);
+        }
+
+        let payload = self
+            .buffer
+            .drain(..offset + length)
+            .skip(offset)
+            .enumerate()
+            .map(|(i, byte)| byte ^ key[i % 4])
+            .collect();
+
+        Ok(Some(Frame {
+            fin,
+            opcode,
+            payload,
+        }))
+    }
+}
@@ -0,0 +1,279 @@
+// https://datatracker.ietf.org/doc/html/rfc6455
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use thiserror::Error;
+
+use crate::{
+    network_stack::ip::SocketAddress,
+    util::{base64, sha1::sha1},
+    x86::pit,
+};
+
+use self::frame::{Frame, Opcode};
+
+use super::{
+    request::Request,
+    response::{Response, StatusCode},
+    Method, Version,
+};
+
+pub mod connection;
+pub mod frame;
+
+// the only version there is, rfc 6455 4.1
+const VERSION: &str = "13";
+// appended to the client's key before hashing, rfc 6455 1.3
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// a message, with all its fragments, can't be larger than this
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+// the close code takes 2 of the 125 bytes a control frame can carry
+const MAX_CLOSE_REASON_SIZE: usize = 123;
+
+#[derive(Error, Debug)]
+pub enum WebSocketError {
+    #[error("Frame with reserved bits set")]
+    ReservedBits,
+    #[error("Unknown opcode {0:#x}")]
+    UnknownOpcode(u8),
+    #[error("Frame from the client is not masked")]
+    UnmaskedFrame,
+    #[error("Fragmented or oversized control frame")]
+    InvalidControlFrame,
+    #[error("Message is larger than {0} bytes")]
+    MessageTooLarge(usize),
+    #[error("Continuation frame without a message to continue")]
+    UnexpectedContinuation,
+    #[error("New message before the last one was finished")]
+    ExpectedContinuation,
+    #[error("Text message is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("Malformed close frame")]
+    InvalidCloseFrame,
+    #[error("The connection is closing")]
+    Closed,
+}
+
+impl WebSocketError {
+    // what the peer is told when we fail the connection over this, rfc 6455 7.4.1
+    pub fn close_code(&self) -> CloseCode {
+        match self {
+            Self::MessageTooLarge(_) => CloseCode::MessageTooBig,
+            Self::InvalidUtf8 => CloseCode::InvalidPayload,
+            Self::Closed => CloseCode::Normal,
+            _ => CloseCode::ProtocolError,
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, WebSocketError>;
+
+// rfc 6455 7.4.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseCode {
+    Normal,
+    GoingAway,
+    ProtocolError,
+    UnsupportedData,
+    // the close frame had no code, never sent
+    NoStatus,
+    // the connection ended without a close frame, never sent
+    Abnormal,
+    InvalidPayload,
+    PolicyViolation,
+    MessageTooBig,
+    InternalError,
+    Unknown(u16),
+}
+
+impl From<u16> for CloseCode {
+    fn from(value: u16) -> Self {
+        match value {
+            1000 => Self::Normal,
+            1001 => Self::GoingAway,
+            1002 => Self::ProtocolError,
+            1003 => Self::UnsupportedData,
+            1005 => Self::NoStatus,
+            1006 => Self::Abnormal,
+            1007 => Self::InvalidPayload,
+            1008 => Self::PolicyViolation,
+            1009 => Self::MessageTooBig,
+            1011 => Self::InternalError,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<CloseCode> for u16 {
+    fn from(value: CloseCode) -> Self {
+        match value {
+            CloseCode::Normal => 1000,
+            CloseCode::GoingAway => 1001,
+            CloseCode::ProtocolError => 1002,
+            CloseCode::UnsupportedData => 1003,
+            CloseCode::NoStatus => 1005,
+            CloseCode::Abnormal => 1006,
+            CloseCode::InvalidPayload => 1007,
+            CloseCode::PolicyViolation => 1008,
+            CloseCode::MessageTooBig => 1009,
+            CloseCode::InternalError => 1011,
+            CloseCode::Unknown(value) => value,
+        }
+    }
+}
+
+impl CloseCode {
+    // whether a peer may put the code in a close frame, rfc 6455 7.4.2.
+    // 3000 to 4999 belong to libraries and applications
+    pub fn is_sendable(&self) -> bool {
+        matches!(u16::from(*self), 1000..=1003 | 1007..=1011 | 3000..=4999)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+// the application side of one websocket connection. the server calls it from its poll,
+// pings, pongs and the close handshake are answered without it
+pub trait Session {
+    fn on_open(&mut self, _socket: &mut WebSocket) {}
+
+    fn on_message(&mut self, socket: &mut WebSocket, message: Message);
+
+    // called on every poll of the server while the connection is open, for pushing updates
+    fn poll(&mut self, _socket: &mut WebSocket) {}
+
+    // called once at the end. NoStatus when the peer's close frame had no code,
+    // Abnormal when the connection ended without a close handshake
+    fn on_close(&mut self, _code: CloseCode, _reason: &str) {}
+}
+
+// closures work as sessions that only answer messages
+impl<F: FnMut(&mut WebSocket, Message)> Session for F {
+    fn on_message(&mut self, socket: &mut WebSocket, message: Message) {
+        self(socket, message)
+    }
+}
+
+// what a session sends through, frames are queued and the server sends them after the session
+// returns
+pub struct WebSocket {
+    peer_address: SocketAddress,
+    output: Vec<u8>,
+    // what the connection still has to send from earlier polls
+    unsent: usize,
+    close_sent_at: Option<usize>,
+}
+
+impl WebSocket {
+    fn new(peer_address: SocketAddress) -> Self {
+        Self {
+            peer_address,
+            output: Vec::new(),
+            unsent: 0,
+            close_sent_at: None,
+        }
+    }
+
+    pub fn peer_address(&self) -> SocketAddress {
+        self.peer_address
+    }
+
+    pub fn send(&mut self, message: Message) -> Result<()> {
+        if self.is_closing() {
+            return Err(WebSocketError::Closed);
+        }
+
+        let frame = match message {
+            Message::Text(text) => Frame::new(Opcode::Text, text.into_bytes()),
+            Message::Binary(data) => Frame::new(Opcode::Binary, data),
+        };
+        self.send_frame(frame);
+
+        Ok(())
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<()> {
+        self.send(Message::Text(text.to_string()))
+    }
+
+    // starts the close handshake, messages from the peer are dropped until it answers.
+    // codes that can't be sent go out as a close frame without a code
+    pub fn close(&mut self, code: CloseCode, reason: &str) {
+        if self.is_closing() {
+            return;
+        }
+
+        let mut payload = Vec::new();
+        if code.is_sendable() {
+            let mut end = reason.len().min(MAX_CLOSE_REASON_SIZE);
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            payload.extend_from_slice(&u16::from(code).to_be_bytes());
+            payload.extend_from_slice(&reason.as_bytes()[..end]);
+        }
+
+        self.send_frame(Frame::new(Opcode::Close, payload));
+        self.close_sent_at = Some(pit::ticks());
+    }
+
+    // our close frame is out, nothing but the peer's answer is expected anymore
+    pub fn is_closing(&self) -> bool {
+        self.close_sent_at.is_some()
+    }
+
+    // bytes queued but not sent yet, a session can skip updates when the peer doesn't keep up
+    pub fn buffered_amount(&self) -> usize {
+        self.unsent + self.output.len()
+    }
+
+    fn send_frame(&mut self, frame: Frame) {
+        self.output.extend(frame.to_bytes());
+    }
+}
+
+// rfc 6455 4.2.2, answers the opening handshake. once the response is sent the session takes
+// the connection over, a request that isn't a valid handshake gets an error instead
+pub fn accept(request: &Request, session: impl Session + 'static) -> Response {
+    if !request.headers.contains_token("Upgrade", "websocket") {
+        return Response::error(StatusCode::UpgradeRequired).with_header("Upgrade", "websocket");
+    }
+
+    if request.header("Sec-WebSocket-Version") != Some(VERSION) {
+        return Response::error(StatusCode::UpgradeRequired)
+            .with_header("Sec-WebSocket-Version", VERSION);
+    }
+
+    let key = request
+        .header("Sec-WebSocket-Key")
+        .filter(|key| base64::decode(key).is_some_and(|key| key.len() == 16));
+
+    let valid = request.method == Method::Get
+        && request.version == Version::Http11
+        && request.headers.contains_token("Connection", "Upgrade");
+
+    let Some(key) = key.filter(|_| valid) else {
+        return Response::error(StatusCode::BadRequest);
+    };
+
+    Response::new(StatusCode::SwitchingProtocols)
+        .with_header("Upgrade", "websocket")
+        .with_header("Connection", "Upgrade")
+        .with_header("Sec-WebSocket-Accept", accept_key(key))
+        .with_upgrade(session)
+}
+
+// proves to the client that the server understood the handshake
+fn accept_key(key: &str) -> String {
+    let mut input = String::from(key);
+    input.push_str(ACCEPT_GUID);
+
+    base64::encode(&sha1(input.as_bytes()))
+}
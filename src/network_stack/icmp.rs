@@ -0,0 +1,415 @@
+// https://datatracker.ietf.org/doc/html/rfc792
+
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use alloc::{format, vec::Vec};
+use thiserror::Error;
+
+use crate::{
+    mutex::Mutex,
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::{
+    checksum::internet_checksum,
+    interface::InterfaceId,
+    ipv4::{self, header::Ipv4Header, IpProtocol, Ipv4Address},
+    packet_buffer::PacketBuffer,
+    poll_until,
+};
+
+const ICMP_HEADER_SIZE: usize = 8;
+// errors quote the offending header and this much of its payload
+const QUOTED_PAYLOAD_SIZE: usize = 8;
+const ECHO_IDENTIFIER: u16 = 0x4D4F;
+const PING_PAYLOAD: &[u8] = b"monkaos ping monkaos ping monkaos";
+
+#[derive(Error, Debug)]
+pub enum IcmpError {
+    #[error("Packet of {0} bytes is too short for an ICMP header")]
+    TooShort(usize),
+    #[error("Checksum mismatch")]
+    BadChecksum,
+    #[error("Unknown code {0}")]
+    UnknownCode(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    EchoReply,
+    DestinationUnreachable,
+    EchoRequest,
+    TimeExceeded,
+    Unknown(u8),
+}
+
+impl From<u8> for MessageType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::EchoReply,
+            3 => Self::DestinationUnreachable,
+            8 => Self::EchoRequest,
+            11 => Self::TimeExceeded,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(value: MessageType) -> Self {
+        match value {
+            MessageType::EchoReply => 0,
+            MessageType::DestinationUnreachable => 3,
+            MessageType::EchoRequest => 8,
+            MessageType::TimeExceeded => 11,
+            MessageType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum UnreachableCode {
+    Network = 0,
+    Host = 1,
+    Protocol = 2,
+    Port = 3,
+    FragmentationNeeded = 4,
+}
+
+impl TryFrom<u8> for UnreachableCode {
+    type Error = IcmpError;
+
+    fn try_from(value: u8) -> Result<Self, IcmpError> {
+        match value {
+            0 => Ok(Self::Network),
+            1 => Ok(Self::Host),
+            2 => Ok(Self::Protocol),
+            3 => Ok(Self::Port),
+            4 => Ok(Self::FragmentationNeeded),
+            value => Err(IcmpError::UnknownCode(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TimeExceededCode {
+    TimeToLive = 0,
+    FragmentReassembly = 1,
+}
+
+impl TryFrom<u8> for TimeExceededCode {
+    type Error = IcmpError;
+
+    fn try_from(value: u8) -> Result<Self, IcmpError> {
+        match value {
+            0 => Ok(Self::TimeToLive),
+            1 => Ok(Self::FragmentReassembly),
+            value => Err(IcmpError::UnknownCode(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IcmpMessage<'a> {
+    pub message_type: MessageType,
+    pub code: u8,
+    // identifier and sequence for echoes, the next hop mtu for fragmentation needed, unused otherwise
+    pub rest_of_header: [u8; 4],
+    pub data: &'a [u8],
+}
+
+impl<'a> IcmpMessage<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, IcmpError> {
+        if bytes.len() < ICMP_HEADER_SIZE {
+            return Err(IcmpError::TooShort(bytes.len()));
+        }
+
+        if internet_checksum(bytes) != 0 {
+            return Err(IcmpError::BadChecksum);
+        }
+
+        Ok(Self {
+            message_type: MessageType::from(bytes[0]),
+            code: bytes[1],
+            rest_of_header: bytes[4..8].try_into().unwrap(),
+            data: &bytes[ICMP_HEADER_SIZE..],
+        })
+    }
+
+    pub fn to_packet(self) -> PacketBuffer {
+        let mut packet = PacketBuffer::from_payload(self.data);
+
+        let header = packet.prepend(ICMP_HEADER_SIZE);
+        header[0] = u8::from(self.message_type);
+        header[1] = self.code;
+        header[2..4].copy_from_slice(&[0, 0]);
+        header[4..8].copy_from_slice(&self.rest_of_header);
+
+        let checksum = internet_checksum(&packet);
+        packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+        packet
+    }
+
+    fn echo_fields(&self) -> (u16, u16) {
+        (
+            u16::from_be_bytes([self.rest_of_header[0], self.rest_of_header[1]]),
+            u16::from_be_bytes([self.rest_of_header[2], self.rest_of_header[3]]),
+        )
+    }
+}
+
+struct PendingEcho {
+    destination: Ipv4Address,
+    sequence: u16,
+    sent_at: usize,
+    round_trip_ticks: Option<usize>,
+}
+
+static PENDING_ECHOES: Mutex<Vec<PendingEcho>> = Mutex::new(Vec::new());
+static NEXT_SEQUENCE: AtomicU16 = AtomicU16::new(1);
+
+pub fn init() {
+    ipv4::register_protocol_handler(IpProtocol::Icmp, handle_packet);
+}
+
+fn handle_packet(interface_id: InterfaceId, header: &Ipv4Header, payload: &[u8]) {
+    let message = match IcmpMessage::parse(payload) {
+        Ok(message) => message,
+        Err(err) => {
+            println!("Dropped ICMP packet on interface {}: {}", interface_id, err);
+            return;
+        }
+    };
+
+    match message.message_type {
+        MessageType::EchoRequest => {
+            // answering broadcast pings turns us into an amplifier
+            if header.destination_address == Ipv4Address::BROADCAST
+                || header.destination_address.is_multicast()
+            {
+                return;
+            }
+
+            let reply = IcmpMessage {
+                message_type: MessageType::EchoReply,
+                ..message
+            };
+
+            if let Err(err) = ipv4::send(header.source_address, IpProtocol::Icmp, reply.to_packet())
+            {
+                println!("Failed to send echo reply: {}", err);
+            }
+        }
+        MessageType::EchoReply => handle_echo_reply(header, &message),
+        MessageType::DestinationUnreachable | MessageType::TimeExceeded => {
+            handle_error(header, &message)
+        }
+        MessageType::Unknown(_) => (),
+    }
+}
+
+fn handle_error(header: &Ipv4Header, message: &IcmpMessage) {
+    // the quoted header tells us which of our datagrams didn't make it
+    let destination = Ipv4Header::parse(message.data)
+        .map(|(quoted, _)| quoted.destination_address)
+        .ok();
+
+    let reason = match message.message_type {
+        MessageType::DestinationUnreachable => match UnreachableCode::try_from(message.code) {
+            // rfc 1191, the mtu of the next hop is in the low half of the unused field
+            Ok(UnreachableCode::FragmentationNeeded) => format!(
+                "fragmentation needed, next hop mtu {}",
+                u16::from_be_bytes([message.rest_of_header[2], message.rest_of_header[3]])
+            ),
+            Ok(code) => format!("{:?}", code),
+            Err(err) => format!("{}", err),
+        },
+        _ => match TimeExceededCode::try_from(message.code) {
+            Ok(code) => format!("{:?}", code),
+            Err(err) => format!("{}", err),
+        },
+    };
+
+    println!(
+        "{:?} ({}) from {} about {:?}",
+        message.message_type, reason, header.source_address, destination
+    );
+}
+
+fn handle_echo_reply(header: &Ipv4Header, message: &IcmpMessage) {
+    let (identifier, sequence) = message.echo_fields();
+    if identifier != ECHO_IDENTIFIER {
+        return;
+    }
+
+    let mut pending = PENDING_ECHOES.lock();
+
+    if let Some(echo) = pending.iter_mut().find(|echo| {
+        echo.sequence == sequence
+            && echo.destination == header.source_address
+            && echo.round_trip_ticks.is_none()
+    }) {
+        let round_trip_ticks = pit::ticks().wrapping_sub(echo.sent_at);
+        echo.round_trip_ticks = Some(round_trip_ticks);
+
+        println!(
+            "{} bytes from {}: icmp_seq={} ttl={} time={} ms",
+            message.data.len() + ICMP_HEADER_SIZE,
+            header.source_address,
+            sequence,
+            header.time_to_live,
+            ticks_to_milliseconds(round_trip_ticks)
+        );
+    }
+}
+
+fn ticks_to_milliseconds(ticks: usize) -> usize {
+    ticks * 1000 / TICKS_PER_SECOND
+}
+
+// sends an echo request and returns its sequence number, the reply is matched in the background
+pub fn send_echo_request(destination: Ipv4Address) -> ipv4::Result<u16> {
+    let sequence = NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+
+    let mut rest_of_header = [0; 4];
+    rest_of_header[..2].copy_from_slice(&ECHO_IDENTIFIER.to_be_bytes());
+    rest_of_header[2..].copy_from_slice(&sequence.to_be_bytes());
+
+    let request = IcmpMessage {
+        message_type: MessageType::EchoRequest,
+        code: 0,
+        rest_of_header,
+        data: PING_PAYLOAD,
+    };
+
+    PENDING_ECHOES.lock().push(PendingEcho {
+        destination,
+        sequence,
+        sent_at: pit::ticks(),
+        round_trip_ticks: None,
+    });
+
+    ipv4::send(destination, IpProtocol::Icmp, request.to_packet())?;
+
+    Ok(sequence)
+}
+
+// pings and waits for the reply, returns the round trip time in milliseconds or None on timeout
+pub fn ping(destination: Ipv4Address, timeout: usize) -> ipv4::Result<Option<usize>> {
+    let sequence = send_echo_request(destination)?;
+
+    let round_trip_ticks = poll_until(Some(timeout), || {
+        PENDING_ECHOES
+            .lock()
+            .iter()
+            .find(|echo| echo.sequence == sequence)
+            .and_then(|echo| echo.round_trip_ticks)
+    });
+
+    PENDING_ECHOES
+        .lock()
+        .retain(|echo| echo.sequence != sequence);
+
+    if round_trip_ticks.is_none() {
+        println!("Request to {} icmp_seq={} timed out", destination, sequence);
+    }
+
+    Ok(round_trip_ticks.map(ticks_to_milliseconds))
+}
+
+// rfc 1122 3.2.2, errors are never sent about errors, broadcasts or anything but the first fragment
+fn may_send_error(header: &Ipv4Header, payload: &[u8]) -> bool {
+    if header.destination_address == Ipv4Address::BROADCAST
+        || header.destination_address.is_multicast()
+        || header.source_address.is_unspecified()
+        || header.source_address == Ipv4Address::BROADCAST
+        || header.source_address.is_multicast()
+        || header.fragment_offset != 0
+    {
+        return false;
+    }
+
+    if header.protocol == IpProtocol::Icmp {
+        return payload.first().is_some_and(|&message_type| {
+            matches!(
+                MessageType::from(message_type),
+                MessageType::EchoRequest | MessageType::EchoReply
+            )
+        });
+    }
+
+    true
+}
+
+fn quote(header: &Ipv4Header, payload: &[u8]) -> Vec<u8> {
+    let mut quoted = Vec::with_capacity(header.header_length + QUOTED_PAYLOAD_SIZE);
+    quoted.extend_from_slice(&header.to_bytes());
+    quoted.extend_from_slice(&payload[..payload.len().min(QUOTED_PAYLOAD_SIZE)]);
+    quoted
+}
+
+fn send_error(
+    message_type: MessageType,
+    code: u8,
+    rest_of_header: [u8; 4],
+    header: &Ipv4Header,
+    payload: &[u8],
+) {
+    if !may_send_error(header, payload) {
+        return;
+    }
+
+    let quoted = quote(header, payload);
+    let message = IcmpMessage {
+        message_type,
+        code,
+        rest_of_header,
+        data: &quoted,
+    };
+
+    if let Err(err) = ipv4::send(header.source_address, IpProtocol::Icmp, message.to_packet()) {
+        println!("Failed to send ICMP {:?}: {}", message_type, err);
+    }
+}
+
+// `header` and `payload` are the datagram that caused the error
+pub fn send_destination_unreachable(code: UnreachableCode, header: &Ipv4Header, payload: &[u8]) {
+    send_error(
+        MessageType::DestinationUnreachable,
+        code as u8,
+        [0; 4],
+        header,
+        payload,
+    );
+}
+
+// we are not a router, so the datagram is one of ours. the error goes straight to our own handler,
+// like ip_local_error in linux, since there is nobody on the wire to send it to
+pub fn send_fragmentation_needed(mtu: u16, header: &Ipv4Header, payload: &[u8]) {
+    let mut rest_of_header = [0; 4];
+    rest_of_header[2..].copy_from_slice(&mtu.to_be_bytes());
+
+    let quoted = quote(header, payload);
+    let message = IcmpMessage {
+        message_type: MessageType::DestinationUnreachable,
+        code: UnreachableCode::FragmentationNeeded as u8,
+        rest_of_header,
+        data: &quoted,
+    };
+
+    handle_error(header, &message);
+}
+
+pub fn send_time_exceeded(code: TimeExceededCode, header: &Ipv4Header, payload: &[u8]) {
+    send_error(
+        MessageType::TimeExceeded,
+        code as u8,
+        [0; 4],
+        header,
+        payload,
+    );
+}
@@ -0,0 +1,371 @@
+// https://datatracker.ietf.org/doc/html/rfc4443
+
+use alloc::{format, vec::Vec};
+use thiserror::Error;
+
+use crate::println;
+
+use super::{
+    interface::InterfaceId,
+    ipv4::IpProtocol,
+    ipv6::{
+        self,
+        header::{Ipv6Header, IPV6_HEADER_SIZE},
+        pseudo_header_checksum, Ipv6Address, SendOptions, MINIMUM_MTU,
+    },
+    ndp,
+    packet_buffer::PacketBuffer,
+};
+
+// type, code and checksum, the rest of the first word belongs to the message
+pub const ICMPV6_HEADER_SIZE: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum Icmpv6Error {
+    #[error("Packet of {0} bytes is too short for an ICMPv6 header")]
+    TooShort(usize),
+    #[error("Checksum mismatch")]
+    BadChecksum,
+    #[error("Unknown code {0}")]
+    UnknownCode(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    DestinationUnreachable,
+    PacketTooBig,
+    TimeExceeded,
+    ParameterProblem,
+    EchoRequest,
+    EchoReply,
+    RouterSolicitation,
+    RouterAdvertisement,
+    NeighborSolicitation,
+    NeighborAdvertisement,
+    Redirect,
+    Unknown(u8),
+}
+
+impl MessageType {
+    // error messages have the high bit clear, informational ones have it set
+    pub fn is_error(&self) -> bool {
+        u8::from(*self) < 128
+    }
+}
+
+impl From<u8> for MessageType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::DestinationUnreachable,
+            2 => Self::PacketTooBig,
+            3 => Self::TimeExceeded,
+            4 => Self::ParameterProblem,
+            128 => Self::EchoRequest,
+            129 => Self::EchoReply,
+            133 => Self::RouterSolicitation,
+            134 => Self::RouterAdvertisement,
+            135 => Self::NeighborSolicitation,
+            136 => Self::NeighborAdvertisement,
+            137 => Self::Redirect,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(value: MessageType) -> Self {
+        match value {
+            MessageType::DestinationUnreachable => 1,
+            MessageType::PacketTooBig => 2,
+            MessageType::TimeExceeded => 3,
+            MessageType::ParameterProblem => 4,
+            MessageType::EchoRequest => 128,
+            MessageType::EchoReply => 129,
+            MessageType::RouterSolicitation => 133,
+            MessageType::RouterAdvertisement => 134,
+            MessageType::NeighborSolicitation => 135,
+            MessageType::NeighborAdvertisement => 136,
+            MessageType::Redirect => 137,
+            MessageType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum UnreachableCode {
+    NoRoute = 0,
+    AddressUnreachable = 3,
+    Port = 4,
+}
+
+impl TryFrom<u8> for UnreachableCode {
+    type Error = Icmpv6Error;
+
+    fn try_from(value: u8) -> Result<Self, Icmpv6Error> {
+        match value {
+            0 => Ok(Self::NoRoute),
+            3 => Ok(Self::AddressUnreachable),
+            4 => Ok(Self::Port),
+            value => Err(Icmpv6Error::UnknownCode(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ParameterProblemCode {
+    ErroneousHeaderField = 0,
+    UnrecognizedNextHeader = 1,
+}
+
+impl TryFrom<u8> for ParameterProblemCode {
+    type Error = Icmpv6Error;
+
+    fn try_from(value: u8) -> Result<Self, Icmpv6Error> {
+        match value {
+            0 => Ok(Self::ErroneousHeaderField),
+            1 => Ok(Self::UnrecognizedNextHeader),
+            value => Err(Icmpv6Error::UnknownCode(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Icmpv6Message<'a> {
+    pub message_type: MessageType,
+    pub code: u8,
+    // everything after the checksum
+    pub body: &'a [u8],
+}
+
+impl<'a> Icmpv6Message<'a> {
+    // the checksum covers a pseudo header, so the addresses from the ip header are needed
+    pub fn parse(header: &Ipv6Header, bytes: &'a [u8]) -> Result<Self, Icmpv6Error> {
+        if bytes.len() < ICMPV6_HEADER_SIZE {
+            return Err(Icmpv6Error::TooShort(bytes.len()));
+        }
+
+        let mut sum = pseudo_header_checksum(
+            header.source_address,
+            header.destination_address,
+            IpProtocol::Icmpv6,
+            bytes.len(),
+        );
+        sum.add_bytes(bytes);
+
+        if sum.finish() != 0 {
+            return Err(Icmpv6Error::BadChecksum);
+        }
+
+        Ok(Self {
+            message_type: MessageType::from(bytes[0]),
+            code: bytes[1],
+            body: &bytes[ICMPV6_HEADER_SIZE..],
+        })
+    }
+
+    pub fn to_packet(
+        self,
+        source_address: Ipv6Address,
+        destination_address: Ipv6Address,
+    ) -> PacketBuffer {
+        let mut packet = PacketBuffer::from_payload(self.body);
+
+        let header = packet.prepend(ICMPV6_HEADER_SIZE);
+        header[0] = u8::from(self.message_type);
+        header[1] = self.code;
+        header[2..4].copy_from_slice(&[0, 0]);
+
+        let mut sum = pseudo_header_checksum(
+            source_address,
+            destination_address,
+            IpProtocol::Icmpv6,
+            packet.len(),
+        );
+        sum.add_bytes(&packet);
+        packet[2..4].copy_from_slice(&sum.finish().to_be_bytes());
+
+        packet
+    }
+}
+
+pub fn init() {
+    ipv6::register_protocol_handler(IpProtocol::Icmpv6, handle_packet);
+}
+
+// picks the source address first, the checksum depends on it
+pub fn send(
+    destination: Ipv6Address,
+    message: &Icmpv6Message,
+    mut options: SendOptions,
+) -> ipv6::Result<()> {
+    let source_address = ipv6::source_address_for(destination, &options)?;
+    options.source_address = Some(source_address);
+
+    ipv6::send_with_options(
+        destination,
+        IpProtocol::Icmpv6,
+        message.to_packet(source_address, destination),
+        options,
+    )
+}
+
+fn handle_packet(interface_id: InterfaceId, header: &Ipv6Header, payload: &[u8]) {
+    let message = match Icmpv6Message::parse(header, payload) {
+        Ok(message) => message,
+        Err(err) => {
+            println!(
+                "Dropped ICMPv6 packet on interface {}: {}",
+                interface_id, err
+            );
+            return;
+        }
+    };
+
+    match message.message_type {
+        MessageType::EchoRequest => {
+            let reply = Icmpv6Message {
+                message_type: MessageType::EchoReply,
+                ..message
+            };
+
+            // rfc 4443 4.2, the reply to a multicast comes from one of our unicast addresses.
+            // link-local senders can only be reached through the interface they came in on
+            let options = SendOptions {
+                interface_id: header
+                    .source_address
+                    .is_link_local()
+                    .then_some(interface_id),
+                source_address: (!header.destination_address.is_multicast())
+                    .then_some(header.destination_address),
+                ..SendOptions::default()
+            };
+
+            if let Err(err) = send(header.source_address, &reply, options) {
+                println!("Failed to send echo reply: {}", err);
+            }
+        }
+        MessageType::RouterSolicitation
+        | MessageType::RouterAdvertisement
+        | MessageType::NeighborSolicitation
+        | MessageType::NeighborAdvertisement
+        | MessageType::Redirect => ndp::handle_message(interface_id, header, &message),
+        MessageType::DestinationUnreachable
+        | MessageType::PacketTooBig
+        | MessageType::TimeExceeded
+        | MessageType::ParameterProblem => handle_error(header, &message),
+        MessageType::EchoReply | MessageType::Unknown(_) => (),
+    }
+}
+
+fn handle_error(header: &Ipv6Header, message: &Icmpv6Message) {
+    // the quoted header after the first word tells us which of our packets didn't make it
+    let destination = message
+        .body
+        .get(4..)
+        .and_then(|quoted| Ipv6Header::parse(quoted).ok())
+        .map(|(quoted, _)| quoted.destination_address);
+
+    // the first word is the mtu for packet too big and the offending offset for parameter problems
+    let word = message
+        .body
+        .get(..4)
+        .map_or(0, |word| u32::from_be_bytes(word.try_into().unwrap()));
+
+    let reason = match message.message_type {
+        MessageType::DestinationUnreachable => match UnreachableCode::try_from(message.code) {
+            Ok(code) => format!("{:?}", code),
+            Err(err) => format!("{}", err),
+        },
+        MessageType::PacketTooBig => format!("mtu {}", word),
+        MessageType::ParameterProblem => match ParameterProblemCode::try_from(message.code) {
+            Ok(code) => format!("{:?} at offset {}", code, word),
+            Err(err) => format!("{}", err),
+        },
+        _ => format!("code {}", message.code),
+    };
+
+    println!(
+        "{:?} ({}) from {} about {:?}",
+        message.message_type, reason, header.source_address, destination
+    );
+}
+
+// rfc 4443 2.4 e, no errors about errors, about packets sent to multicast or from nobody in particular
+fn may_send_error(header: &Ipv6Header, payload: &[u8]) -> bool {
+    if header.destination_address.is_multicast()
+        || header.source_address.is_unspecified()
+        || header.source_address.is_multicast()
+    {
+        return false;
+    }
+
+    if header.next_header == IpProtocol::Icmpv6 {
+        return payload
+            .first()
+            .is_some_and(|&message_type| !MessageType::from(message_type).is_error());
+    }
+
+    true
+}
+
+fn send_error(
+    message_type: MessageType,
+    code: u8,
+    rest_of_header: [u8; 4],
+    header: &Ipv6Header,
+    payload: &[u8],
+) {
+    if !may_send_error(header, payload) {
+        return;
+    }
+
+    // as much of the offending packet as fits in the minimum mtu
+    let quoted_payload_size = payload
+        .len()
+        .min(MINIMUM_MTU - 2 * IPV6_HEADER_SIZE - ICMPV6_HEADER_SIZE - rest_of_header.len());
+
+    let mut body =
+        Vec::with_capacity(rest_of_header.len() + IPV6_HEADER_SIZE + quoted_payload_size);
+    body.extend_from_slice(&rest_of_header);
+    body.extend_from_slice(&header.to_bytes());
+    body.extend_from_slice(&payload[..quoted_payload_size]);
+
+    let message = Icmpv6Message {
+        message_type,
+        code,
+        body: &body,
+    };
+
+    if let Err(err) = send(header.source_address, &message, SendOptions::default()) {
+        println!("Failed to send ICMPv6 {:?}: {}", message_type, err);
+    }
+}
+
+// `header` and `payload` are the packet that caused the error
+pub fn send_destination_unreachable(code: UnreachableCode, header: &Ipv6Header, payload: &[u8]) {
+    send_error(
+        MessageType::DestinationUnreachable,
+        code as u8,
+        [0; 4],
+        header,
+        payload,
+    );
+}
+
+// `pointer` is the offset of the offending field in the packet
+pub fn send_parameter_problem(
+    code: ParameterProblemCode,
+    pointer: u32,
+    header: &Ipv6Header,
+    payload: &[u8],
+) {
+    send_error(
+        MessageType::ParameterProblem,
+        code as u8,
+        pointer.to_be_bytes(),
+        header,
+        payload,
+    );
+}
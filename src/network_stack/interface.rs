@@ -0,0 +1,227 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use crate::{
+    mutex::Mutex,
+    pci::drivers::network::{NetworkDevice, NetworkError, Result},
+    println,
+    util::event::Event,
+};
+
+use super::{
+    ethernet::{EitherType, EthernetAddress, EthernetHeader},
+    ipv4::{Ipv4Address, Ipv4Cidr},
+    ipv6::{Ipv6Address, Ipv6Cidr},
+    packet_buffer::PacketBuffer,
+};
+
+pub type InterfaceId = usize;
+pub type SharedNetworkDevice = Arc<Mutex<dyn NetworkDevice>>;
+
+pub struct Interface {
+    pub id: InterfaceId,
+    pub device: SharedNetworkDevice,
+    // only set once duplicate address detection let the address through
+    pub ipv4: Option<Ipv4Cidr>,
+    // from DHCP or the kernel command line
+    pub dns_servers: Vec<Ipv4Address>,
+    // the link-local address and the ones from autoconfiguration, once they passed duplicate address detection
+    pub ipv6: Vec<Ipv6Cidr>,
+}
+
+static INTERFACES: Mutex<Vec<Interface>> = Mutex::new(Vec::new());
+
+// raised by the drivers from their interrupt handlers
+pub static RECEIVE_EVENT: Event = Event::new();
+pub static TRANSMIT_EVENT: Event = Event::new();
+
+pub fn register_interface(device: SharedNetworkDevice) -> InterfaceId {
+    let mut interfaces = INTERFACES.lock();
+
+    let id = interfaces.len();
+    interfaces.push(Interface {
+        id,
+        device,
+        ipv4: None,
+        dns_servers: Vec::new(),
+        ipv6: Vec::new(),
+    });
+
+    id
+}
+
+pub fn get_device(id: InterfaceId) -> Option<SharedNetworkDevice> {
+    INTERFACES
+        .lock()
+        .iter()
+        .find(|interface| interface.id == id)
+        .map(|interface| interface.device.clone())
+}
+
+pub fn ipv4_cidr(id: InterfaceId) -> Option<Ipv4Cidr> {
+    INTERFACES
+        .lock()
+        .iter()
+        .find(|interface| interface.id == id)
+        .and_then(|interface| interface.ipv4)
+}
+
+pub fn ipv4_address(id: InterfaceId) -> Option<Ipv4Address> {
+    ipv4_cidr(id).map(|cidr| cidr.address)
+}
+
+pub fn set_ipv4_cidr(id: InterfaceId, cidr: Option<Ipv4Cidr>) {
+    if let Some(interface) = INTERFACES
+        .lock()
+        .iter_mut()
+        .find(|interface| interface.id == id)
+    {
+        interface.ipv4 = cidr;
+    }
+}
+
+pub fn set_dns_servers(id: InterfaceId, dns_servers: Vec<Ipv4Address>) {
+    if let Some(interface) = INTERFACES
+        .lock()
+        .iter_mut()
+        .find(|interface| interface.id == id)
+    {
+        interface.dns_servers = dns_servers;
+    }
+}
+
+// the servers of every interface, in interface order
+pub fn dns_servers() -> Vec<Ipv4Address> {
+    INTERFACES
+        .lock()
+        .iter()
+        .flat_map(|interface| interface.dns_servers.iter().copied())
+        .collect()
+}
+
+pub fn ipv6_addresses(id: InterfaceId) -> Vec<Ipv6Cidr> {
+    INTERFACES
+        .lock()
+        .iter()
+        .find(|interface| interface.id == id)
+        .map_or(Vec::new(), |interface| interface.ipv6.clone())
+}
+
+pub fn has_ipv6_address(id: InterfaceId, address: Ipv6Address) -> bool {
+    ipv6_addresses(id)
+        .iter()
+        .any(|cidr| cidr.address == address)
+}
+
+pub fn add_ipv6_address(id: InterfaceId, cidr: Ipv6Cidr) {
+    if let Some(interface) = INTERFACES
+        .lock()
+        .iter_mut()
+        .find(|interface| interface.id == id)
+    {
+        interface
+            .ipv6
+            .retain(|existing| existing.address != cidr.address);
+        interface.ipv6.push(cidr);
+    }
+}
+
+pub fn remove_ipv6_address(id: InterfaceId, address: Ipv6Address) {
+    if let Some(interface) = INTERFACES
+        .lock()
+        .iter_mut()
+        .find(|interface| interface.id == id)
+    {
+        interface.ipv6.retain(|cidr| cidr.address != address);
+    }
+}
+
+// interfaces that have at least their link-local address
+pub fn ipv6_interfaces() -> Vec<InterfaceId> {
+    INTERFACES
+        .lock()
+        .iter()
+        .filter(|interface| !interface.ipv6.is_empty())
+        .map(|interface| interface.id)
+        .collect()
+}
+
+// every configured interface with its network, these make up the connected routes
+pub fn ipv4_networks() -> Vec<(InterfaceId, Ipv4Cidr)> {
+    INTERFACES
+        .lock()
+        .iter()
+        .filter_map(|interface| Some((interface.id, interface.ipv4?)))
+        .collect()
+}
+
+pub fn devices() -> Vec<(InterfaceId, SharedNetworkDevice)> {
+    INTERFACES
+        .lock()
+        .iter()
+        .map(|interface| (interface.id, interface.device.clone()))
+        .collect()
+}
+
+// the back-pressure path: with the transmit queue full the caller sleeps until the card reports
+// sent frames, instead of getting FullTransmissionsQueue. the device lock is released while we wait
+pub fn transmit_frame_blocking(device: &Mutex<dyn NetworkDevice>, frame: Vec<u8>) -> Result<()> {
+    loop {
+        {
+            let mut device = device.lock();
+
+            if device.can_transmit(&frame) {
+                return device.transmit_frame(frame);
+            }
+        }
+
+        TRANSMIT_EVENT.wait();
+    }
+}
+
+// wraps the packet in an ethernet header from the interface address and sends it,
+// callers must not hold any lock since this may have to wait for the card
+pub fn transmit_packet(
+    id: InterfaceId,
+    destination_address: EthernetAddress,
+    ether_type: EitherType,
+    mut packet: PacketBuffer,
+) -> Result<()> {
+    let device = get_device(id).ok_or(NetworkError::UnknownInterface(id))?;
+    let source_address = device.lock().ethernet_address();
+
+    EthernetHeader {
+        destination_address,
+        source_address,
+        vlan: None,
+        ether_type,
+    }
+    .prepend_to(&mut packet);
+
+    transmit_frame_blocking(&device, packet.into_vec())
+}
+
+pub fn print_interfaces() {
+    for (id, device) in devices() {
+        let address = ipv4_cidr(id);
+        let device = device.lock();
+
+        println!(
+            "Interface {}: {} {} inet: {}, mtu: {}, link: {}, capabilities: {:?}",
+            id,
+            device.name(),
+            device.ethernet_address(),
+            address.map_or(String::from("none"), |address| address.to_string()),
+            device.mtu(),
+            if device.link_up() { "up" } else { "down" },
+            device.capabilities(),
+        );
+        for cidr in ipv6_addresses(id) {
+            println!("    inet6: {}", cidr);
+        }
+        println!("    {:?}", device.statistics());
+    }
+}
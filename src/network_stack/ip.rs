@@ -0,0 +1,199 @@
+// what UDP and TCP need from the network layer without caring which IP version carries them
+
+use core::{fmt::Formatter, str::FromStr};
+
+use thiserror::Error;
+
+use super::{
+    checksum::Checksum,
+    ipv4::{self, IpProtocol, Ipv4Address, Ipv4Error, SocketAddressV4},
+    ipv6::{self, Ipv6Address, Ipv6Error},
+    packet_buffer::PacketBuffer,
+};
+
+#[derive(Error, Debug)]
+pub enum IpError {
+    #[error("Can't send from {0} to {1}, they are different IP versions")]
+    MixedVersions(IpAddress, IpAddress),
+    #[error("Invalid address")]
+    InvalidAddress,
+    #[error(transparent)]
+    Ipv4(#[from] Ipv4Error),
+    #[error(transparent)]
+    Ipv6(#[from] Ipv6Error),
+}
+
+pub type Result<T> = core::result::Result<T, IpError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IpAddress {
+    V4(Ipv4Address),
+    V6(Ipv6Address),
+}
+
+impl IpAddress {
+    pub fn is_unspecified(&self) -> bool {
+        match self {
+            Self::V4(address) => address.is_unspecified(),
+            Self::V6(address) => address.is_unspecified(),
+        }
+    }
+
+    pub fn is_same_version(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::V4(_), Self::V4(_)) | (Self::V6(_), Self::V6(_))
+        )
+    }
+}
+
+impl From<Ipv4Address> for IpAddress {
+    fn from(value: Ipv4Address) -> Self {
+        Self::V4(value)
+    }
+}
+
+impl From<Ipv6Address> for IpAddress {
+    fn from(value: Ipv6Address) -> Self {
+        Self::V6(value)
+    }
+}
+
+impl FromStr for IpAddress {
+    type Err = IpError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(address) = s.parse::<Ipv4Address>() {
+            return Ok(Self::V4(address));
+        }
+
+        s.parse::<Ipv6Address>()
+            .map(Self::V6)
+            .map_err(|_| IpError::InvalidAddress)
+    }
+}
+
+impl core::fmt::Display for IpAddress {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::V4(address) => address.fmt(f),
+            Self::V6(address) => address.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SocketAddress {
+    pub address: IpAddress,
+    pub port: u16,
+}
+
+impl SocketAddress {
+    pub const fn new(address: IpAddress, port: u16) -> Self {
+        Self { address, port }
+    }
+}
+
+impl From<SocketAddressV4> for SocketAddress {
+    fn from(value: SocketAddressV4) -> Self {
+        Self::new(IpAddress::V4(value.address), value.port)
+    }
+}
+
+impl core::fmt::Display for SocketAddress {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        match self.address {
+            IpAddress::V4(address) => write!(f, "{}:{}", address, self.port),
+            // the brackets keep the port apart from the last group
+            IpAddress::V6(address) => write!(f, "[{}]:{}", address, self.port),
+        }
+    }
+}
+
+// both addresses come from one header or one connection, so they are always the same version
+pub fn pseudo_header_checksum(
+    source_address: IpAddress,
+    destination_address: IpAddress,
+    protocol: IpProtocol,
+    length: usize,
+) -> Checksum {
+    match (source_address, destination_address) {
+        (IpAddress::V4(source), IpAddress::V4(destination)) => {
+            ipv4::pseudo_header_checksum(source, destination, protocol, length)
+        }
+        (IpAddress::V6(source), IpAddress::V6(destination)) => {
+            ipv6::pseudo_header_checksum(source, destination, protocol, length)
+        }
+        (source, destination) => unreachable!("pseudo header from {} to {}", source, destination),
+    }
+}
+
+// `local` is what a socket is bound to, an unspecified address of either version
+// takes whatever the destination needs so one socket can talk to both
+pub fn source_address_for(local: IpAddress, destination: IpAddress) -> Result<IpAddress> {
+    if local.is_unspecified() {
+        return default_source_address(destination);
+    }
+
+    match local.is_same_version(&destination) {
+        true => Ok(local),
+        false => Err(IpError::MixedVersions(local, destination)),
+    }
+}
+
+// the address of the interface the destination is routed through
+pub fn default_source_address(destination: IpAddress) -> Result<IpAddress> {
+    Ok(match destination {
+        IpAddress::V4(destination) => {
+            ipv4::source_address_for(destination, &ipv4::SendOptions::default())?.into()
+        }
+        IpAddress::V6(destination) => {
+            ipv6::source_address_for(destination, &ipv6::SendOptions::default())?.into()
+        }
+    })
+}
+
+// the largest packet the network layer takes on the way to `destination`, its own header excluded
+pub fn payload_mtu_for(destination: IpAddress) -> Result<usize> {
+    Ok(match destination {
+        IpAddress::V4(destination) => {
+            ipv4::mtu_for(destination, &ipv4::SendOptions::default())?
+                - ipv4::header::IPV4_HEADER_SIZE
+        }
+        IpAddress::V6(destination) => {
+            ipv6::mtu_for(destination, &ipv6::SendOptions::default())?
+                - ipv6::header::IPV6_HEADER_SIZE
+        }
+    })
+}
+
+pub fn send(
+    source_address: IpAddress,
+    destination: IpAddress,
+    protocol: IpProtocol,
+    packet: PacketBuffer,
+) -> Result<()> {
+    match (source_address, destination) {
+        (IpAddress::V4(source), IpAddress::V4(destination)) => ipv4::send_with_options(
+            destination,
+            protocol,
+            packet,
+            ipv4::SendOptions {
+                source_address: Some(source),
+                ..Default::default()
+            },
+        )?,
+        (IpAddress::V6(source), IpAddress::V6(destination)) => ipv6::send_with_options(
+            destination,
+            protocol,
+            packet,
+            ipv6::SendOptions {
+                source_address: Some(source),
+                ..Default::default()
+            },
+        )?,
+        (source, destination) => return Err(IpError::MixedVersions(source, destination)),
+    }
+
+    Ok(())
+}
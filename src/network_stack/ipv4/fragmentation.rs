@@ -0,0 +1,252 @@
+use alloc::{vec, vec::Vec};
+use core::slice;
+
+use lazy_static::lazy_static;
+
+use crate::{
+    memory::{
+        paging::{self, entry::PageFlags, PAGE_SIZE},
+        VirtualAddress,
+    },
+    mutex::Mutex,
+    network_stack::{icmp, packet_buffer::PacketBuffer},
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::{
+    header::{FragmentFlags, Ipv4Header, FRAGMENT_OFFSET_UNIT, IPV4_HEADER_SIZE},
+    IpProtocol, Ipv4Address, Ipv4Error, Result,
+};
+
+// rfc 791 suggests 15 seconds as the lower bound, linux uses 30
+const REASSEMBLY_TIMEOUT: usize = 30 * TICKS_PER_SECOND;
+const MAX_REASSEMBLIES: usize = 16;
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+// room for the payload of any datagram, in whole pages
+const SLOT_SIZE: usize = (MAX_DATAGRAM_SIZE + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+
+lazy_static! {
+    // a slot for every datagram in reassembly. only the pages fragments land in get frames, and
+    // they stay for the next datagram in the slot
+    static ref SLOTS: VirtualAddress = paging::reserve_demand_paged(
+        "IPv4 reassembly",
+        MAX_REASSEMBLIES * SLOT_SIZE,
+        PageFlags::WRITABLE
+    )
+    .expect("Failed to reserve the IPv4 reassembly slots");
+}
+
+// splits the payload into datagrams that fit the mtu and writes their headers
+pub fn fragment(
+    header: Ipv4Header,
+    mut packet: PacketBuffer,
+    mtu: usize,
+) -> Result<Vec<PacketBuffer>> {
+    let size = IPV4_HEADER_SIZE + packet.len();
+
+    if size > MAX_DATAGRAM_SIZE {
+        return Err(Ipv4Error::DatagramTooLarge(size));
+    }
+
+    if size <= mtu {
+        header.prepend_to(&mut packet);
+        return Ok(vec![packet]);
+    }
+
+    if header.flags.contains(FragmentFlags::DONT_FRAGMENT) {
+        // size fits in 16 bits after the check above, and the mtu is smaller still
+        let header = Ipv4Header {
+            total_length: size as u16,
+            ..header
+        };
+        icmp::send_fragmentation_needed(mtu as u16, &header, &packet);
+        return Err(Ipv4Error::FragmentationNeeded { size, mtu });
+    }
+
+    // without room for one unit after the header no fragment could carry any data
+    if mtu < IPV4_HEADER_SIZE + FRAGMENT_OFFSET_UNIT {
+        return Err(Ipv4Error::MtuTooSmall(mtu));
+    }
+
+    // every fragment but the last has to carry a multiple of 8 bytes
+    let fragment_size = (mtu - IPV4_HEADER_SIZE) & !(FRAGMENT_OFFSET_UNIT - 1);
+    let fragment_count = packet.len().div_ceil(fragment_size);
+
+    Ok(packet
+        .chunks(fragment_size)
+        .enumerate()
+        .map(|(index, data)| {
+            let mut fragment_header = header;
+            fragment_header.fragment_offset += index * fragment_size;
+            if index + 1 < fragment_count {
+                fragment_header.flags |= FragmentFlags::MORE_FRAGMENTS;
+            }
+
+            let mut fragment = PacketBuffer::from_payload(data);
+            fragment_header.prepend_to(&mut fragment);
+            fragment
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DatagramKey {
+    source_address: Ipv4Address,
+    destination_address: Ipv4Address,
+    protocol: IpProtocol,
+    identification: u16,
+}
+
+impl DatagramKey {
+    fn new(header: &Ipv4Header) -> Self {
+        Self {
+            source_address: header.source_address,
+            destination_address: header.destination_address,
+            protocol: header.protocol,
+            identification: header.identification,
+        }
+    }
+}
+
+struct Reassembly {
+    key: DatagramKey,
+    // the header of the fragment at offset 0, it becomes the header of the whole datagram
+    first_header: Option<Ipv4Header>,
+    // where the payload goes, an index into SLOTS
+    slot: usize,
+    // byte ranges received so far, sorted and merged so a complete datagram is a single range
+    received: Vec<(usize, usize)>,
+    // known once the fragment without MORE_FRAGMENTS came in
+    payload_length: Option<usize>,
+    started_at: usize,
+}
+
+impl Reassembly {
+    // only the received ranges hold anything, the rest is whatever the slot had before
+    fn data(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((*SLOTS + self.slot * SLOT_SIZE) as *const u8, SLOT_SIZE) }
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut((*SLOTS + self.slot * SLOT_SIZE) as *mut u8, SLOT_SIZE) }
+    }
+
+    fn add_range(&mut self, start: usize, end: usize) {
+        self.received.push((start, end));
+        self.received.sort_unstable();
+
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(self.received.len());
+        for &(start, end) in &self.received {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        self.received = merged;
+    }
+
+    fn is_complete(&self) -> bool {
+        self.first_header.is_some()
+            && self
+                .payload_length
+                .is_some_and(|length| self.received == [(0, length)])
+    }
+}
+
+static REASSEMBLIES: Mutex<Vec<Reassembly>> = Mutex::new(Vec::new());
+
+// stores the fragment and returns the whole datagram once every part of it arrived
+pub fn reassemble(header: &Ipv4Header, payload: &[u8]) -> Option<(Ipv4Header, Vec<u8>)> {
+    let key = DatagramKey::new(header);
+    let start = header.fragment_offset;
+    let end = start + payload.len();
+
+    if IPV4_HEADER_SIZE + end > MAX_DATAGRAM_SIZE {
+        println!("Dropped IPv4 fragment reaching past the maximum datagram size");
+        return None;
+    }
+
+    let mut reassemblies = REASSEMBLIES.lock();
+
+    let index = match reassemblies.iter().position(|entry| entry.key == key) {
+        Some(index) => index,
+        None => {
+            if reassemblies.len() == MAX_REASSEMBLIES {
+                let dropped = reassemblies.remove(0);
+                println!(
+                    "Too many IPv4 datagrams in reassembly, dropped one from {}",
+                    dropped.key.source_address
+                );
+            }
+
+            // there is one since at most MAX_REASSEMBLIES - 1 are left
+            let slot = (0..MAX_REASSEMBLIES)
+                .find(|&slot| reassemblies.iter().all(|entry| entry.slot != slot))
+                .unwrap();
+
+            reassemblies.push(Reassembly {
+                key,
+                first_header: None,
+                slot,
+                received: Vec::new(),
+                payload_length: None,
+                started_at: pit::ticks(),
+            });
+            reassemblies.len() - 1
+        }
+    };
+
+    let reassembly = &mut reassemblies[index];
+
+    reassembly.data_mut()[start..end].copy_from_slice(payload);
+    reassembly.add_range(start, end);
+
+    if !header.flags.contains(FragmentFlags::MORE_FRAGMENTS) {
+        reassembly.payload_length = Some(end);
+    }
+
+    if start == 0 {
+        reassembly.first_header = Some(*header);
+    }
+
+    if !reassembly.is_complete() {
+        return None;
+    }
+
+    let reassembly = reassemblies.remove(index);
+    let payload_length = reassembly.payload_length.unwrap();
+
+    let mut header = reassembly.first_header.unwrap();
+    header.flags.remove(FragmentFlags::MORE_FRAGMENTS);
+    header.total_length = (header.header_length + payload_length) as u16;
+
+    Some((header, reassembly.data()[..payload_length].to_vec()))
+}
+
+// drops datagrams whose fragments stopped coming, returns the first fragment of those that had one
+// so the sender can be told with an ICMP time exceeded
+pub fn expire() -> Vec<(Ipv4Header, Vec<u8>)> {
+    let mut expired_datagrams = Vec::new();
+
+    REASSEMBLIES.lock().retain(|reassembly| {
+        let expired = pit::elapsed(reassembly.started_at, REASSEMBLY_TIMEOUT);
+
+        if expired {
+            println!(
+                "IPv4 reassembly of datagram {} from {} timed out",
+                reassembly.key.identification, reassembly.key.source_address
+            );
+
+            if let Some(header) = reassembly.first_header {
+                let first_fragment_end = reassembly.received[0].1;
+                expired_datagrams.push((header, reassembly.data()[..first_fragment_end].to_vec()));
+            }
+        }
+
+        !expired
+    });
+
+    expired_datagrams
+}
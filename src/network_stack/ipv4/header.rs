@@ -0,0 +1,121 @@
+use bitflags::bitflags;
+
+use crate::network_stack::{checksum::internet_checksum, packet_buffer::PacketBuffer};
+
+use super::{IpProtocol, Ipv4Address, Ipv4Error, Result};
+
+pub const IPV4_HEADER_SIZE: usize = 20;
+const IPV4_VERSION: u8 = 4;
+const FRAGMENT_OFFSET_MASK: u16 = 0x1FFF;
+// fragment offsets are counted in 8 byte blocks
+pub const FRAGMENT_OFFSET_UNIT: usize = 8;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct FragmentFlags: u16 {
+        const MORE_FRAGMENTS = 1 << 13;
+        const DONT_FRAGMENT = 1 << 14;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ipv4Header {
+    pub type_of_service: u8,
+    // filled in when the header is written
+    pub total_length: u16,
+    pub identification: u16,
+    pub flags: FragmentFlags,
+    // in bytes, not in the 8 byte units used on the wire
+    pub fragment_offset: usize,
+    pub time_to_live: u8,
+    pub protocol: IpProtocol,
+    pub source_address: Ipv4Address,
+    pub destination_address: Ipv4Address,
+    // includes the options, which we skip when parsing and never send
+    pub header_length: usize,
+}
+
+impl Ipv4Header {
+    // returns the header and the payload, anything past the total length is link layer padding
+    pub fn parse(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        if bytes.len() < IPV4_HEADER_SIZE {
+            return Err(Ipv4Error::TooShort(bytes.len()));
+        }
+
+        let version = bytes[0] >> 4;
+        if version != IPV4_VERSION {
+            return Err(Ipv4Error::UnsupportedVersion(version));
+        }
+
+        let header_length = (bytes[0] & 0xF) as usize * 4;
+        if header_length < IPV4_HEADER_SIZE || header_length > bytes.len() {
+            return Err(Ipv4Error::InvalidHeaderLength(header_length));
+        }
+
+        let total_length = u16::from_be_bytes([bytes[2], bytes[3]]);
+        if (total_length as usize) < header_length || total_length as usize > bytes.len() {
+            return Err(Ipv4Error::InvalidTotalLength(total_length as usize));
+        }
+
+        // summing a header together with its checksum gives zero when it is intact
+        if internet_checksum(&bytes[..header_length]) != 0 {
+            return Err(Ipv4Error::BadChecksum);
+        }
+
+        let flags_and_offset = u16::from_be_bytes([bytes[6], bytes[7]]);
+
+        Ok((
+            Self {
+                type_of_service: bytes[1],
+                total_length,
+                identification: u16::from_be_bytes([bytes[4], bytes[5]]),
+                flags: FragmentFlags::from_bits_truncate(flags_and_offset),
+                fragment_offset: (flags_and_offset & FRAGMENT_OFFSET_MASK) as usize
+                    * FRAGMENT_OFFSET_UNIT,
+                time_to_live: bytes[8],
+                protocol: IpProtocol::from(bytes[9]),
+                source_address: Ipv4Address::from_slice(&bytes[12..16]),
+                destination_address: Ipv4Address::from_slice(&bytes[16..20]),
+                header_length,
+            },
+            &bytes[header_length..total_length as usize],
+        ))
+    }
+
+    pub fn is_fragment(&self) -> bool {
+        self.flags.contains(FragmentFlags::MORE_FRAGMENTS) || self.fragment_offset != 0
+    }
+
+    // writes the header in front of the payload, the total length and checksum are computed here
+    pub fn prepend_to(&self, packet: &mut PacketBuffer) {
+        let header = Self {
+            total_length: (IPV4_HEADER_SIZE + packet.len()) as u16,
+            ..*self
+        };
+
+        packet.prepend_slice(&header.to_bytes());
+    }
+
+    // the header as it goes on the wire with the current total length, options are dropped
+    pub fn to_bytes(self) -> [u8; IPV4_HEADER_SIZE] {
+        let flags_and_offset =
+            self.flags.bits() | (self.fragment_offset / FRAGMENT_OFFSET_UNIT) as u16;
+
+        let mut header = [0; IPV4_HEADER_SIZE];
+
+        header[0] = (IPV4_VERSION << 4) | (IPV4_HEADER_SIZE / 4) as u8;
+        header[1] = self.type_of_service;
+        header[2..4].copy_from_slice(&self.total_length.to_be_bytes());
+        header[4..6].copy_from_slice(&self.identification.to_be_bytes());
+        header[6..8].copy_from_slice(&flags_and_offset.to_be_bytes());
+        header[8] = self.time_to_live;
+        header[9] = u8::from(self.protocol);
+        header[12..16].copy_from_slice(&self.source_address.bytes);
+        header[16..20].copy_from_slice(&self.destination_address.bytes);
+
+        let checksum = internet_checksum(&header);
+        header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+        header
+    }
+}
@@ -0,0 +1,437 @@
+// https://datatracker.ietf.org/doc/html/rfc791
+
+use core::{
+    fmt::Formatter,
+    str::FromStr,
+    sync::atomic::{AtomicU16, Ordering},
+};
+
+use thiserror::Error;
+
+use crate::{mutex::Mutex, pci::drivers::network::NetworkError, println};
+
+use self::{
+    header::{FragmentFlags, Ipv4Header, IPV4_HEADER_SIZE},
+    routing::Route,
+};
+
+use super::{
+    arp,
+    checksum::Checksum,
+    ethernet::EitherType,
+    icmp::{self, TimeExceededCode, UnreachableCode},
+    interface::{get_device, ipv4_cidr, InterfaceId},
+    packet_buffer::PacketBuffer,
+};
+
+pub mod fragmentation;
+pub mod header;
+pub mod routing;
+
+pub const DEFAULT_TIME_TO_LIVE: u8 = 64;
+// every link has to carry a 60 byte header and an 8 byte fragment, rfc 791 section 3.2
+pub const MINIMUM_MTU: usize = 68;
+
+#[derive(Error, Debug)]
+pub enum Ipv4Error {
+    #[error("Packet of {0} bytes is too short for an IPv4 header")]
+    TooShort(usize),
+    #[error("Unsupported IP version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid header length {0}")]
+    InvalidHeaderLength(usize),
+    #[error("Total length {0} does not match the packet")]
+    InvalidTotalLength(usize),
+    #[error("Header checksum mismatch")]
+    BadChecksum,
+    #[error("No route to {0}")]
+    NoRoute(Ipv4Address),
+    #[error("Interface {0} has no IPv4 address")]
+    NoSourceAddress(InterfaceId),
+    #[error("Datagram of {size} bytes needs fragmentation but don't fragment is set, mtu: {mtu}")]
+    FragmentationNeeded { size: usize, mtu: usize },
+    #[error("Datagram of {0} bytes is larger than IPv4 allows")]
+    DatagramTooLarge(usize),
+    #[error("MTU of {0} bytes is too small for IPv4")]
+    MtuTooSmall(usize),
+    #[error("Invalid address")]
+    InvalidAddress,
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+}
+
+pub type Result<T> = core::result::Result<T, Ipv4Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ipv4Address {
+    pub bytes: [u8; 4],
+}
+
+impl Ipv4Address {
+    pub const UNSPECIFIED: Self = Self::new(0, 0, 0, 0);
+    pub const BROADCAST: Self = Self::new(255, 255, 255, 255);
+
+    pub const fn new(a: u8, b: u8, c: u8, d: u8) -> Self {
+        Self {
+            bytes: [a, b, c, d],
+        }
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut address = Self::UNSPECIFIED;
+        address.bytes.copy_from_slice(&bytes[..4]);
+        address
+    }
+
+    pub fn to_u32(self) -> u32 {
+        u32::from_be_bytes(self.bytes)
+    }
+
+    pub fn from_u32(value: u32) -> Self {
+        Self {
+            bytes: value.to_be_bytes(),
+        }
+    }
+
+    pub fn is_unspecified(&self) -> bool {
+        *self == Self::UNSPECIFIED
+    }
+
+    pub fn is_multicast(&self) -> bool {
+        self.bytes[0] & 0xF0 == 224
+    }
+}
+
+// dotted decimal, 10.0.2.15
+impl FromStr for Ipv4Address {
+    type Err = Ipv4Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut address = Self::UNSPECIFIED;
+        let mut parts = s.split('.');
+
+        for byte in &mut address.bytes {
+            *byte = parts
+                .next()
+                .and_then(|part| part.parse().ok())
+                .ok_or(Ipv4Error::InvalidAddress)?;
+        }
+
+        if parts.next().is_some() {
+            return Err(Ipv4Error::InvalidAddress);
+        }
+
+        Ok(address)
+    }
+}
+
+impl core::fmt::Display for Ipv4Address {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.bytes[0], self.bytes[1], self.bytes[2], self.bytes[3]
+        )
+    }
+}
+
+// an address together with the length of its network prefix, 10.0.2.15/24
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Cidr {
+    pub address: Ipv4Address,
+    pub prefix_length: u8,
+}
+
+impl Ipv4Cidr {
+    pub const fn new(address: Ipv4Address, prefix_length: u8) -> Self {
+        Self {
+            address,
+            prefix_length,
+        }
+    }
+
+    pub fn from_netmask(address: Ipv4Address, netmask: Ipv4Address) -> Self {
+        Self::new(address, netmask.to_u32().leading_ones() as u8)
+    }
+
+    pub fn netmask(&self) -> Ipv4Address {
+        Ipv4Address::from_u32(
+            u32::MAX
+                .checked_shl(32 - self.prefix_length as u32)
+                .unwrap_or(0),
+        )
+    }
+
+    pub fn network(&self) -> Ipv4Address {
+        Ipv4Address::from_u32(self.address.to_u32() & self.netmask().to_u32())
+    }
+
+    pub fn broadcast(&self) -> Ipv4Address {
+        Ipv4Address::from_u32(self.address.to_u32() | !self.netmask().to_u32())
+    }
+
+    pub fn contains(&self, address: Ipv4Address) -> bool {
+        address.to_u32() & self.netmask().to_u32() == self.network().to_u32()
+    }
+}
+
+impl FromStr for Ipv4Cidr {
+    type Err = Ipv4Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, prefix_length) = s.split_once('/').ok_or(Ipv4Error::InvalidAddress)?;
+
+        let prefix_length = prefix_length
+            .parse()
+            .ok()
+            .filter(|&prefix_length| prefix_length <= 32)
+            .ok_or(Ipv4Error::InvalidAddress)?;
+
+        Ok(Self::new(address.parse()?, prefix_length))
+    }
+}
+
+impl core::fmt::Display for Ipv4Cidr {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_length)
+    }
+}
+
+// an address and a transport layer port, 10.0.2.15:68
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketAddressV4 {
+    pub address: Ipv4Address,
+    pub port: u16,
+}
+
+impl SocketAddressV4 {
+    pub const fn new(address: Ipv4Address, port: u16) -> Self {
+        Self { address, port }
+    }
+}
+
+impl core::fmt::Display for SocketAddressV4 {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}:{}", self.address, self.port)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpProtocol {
+    Icmp,
+    Tcp,
+    Udp,
+    Icmpv6,
+    Unknown(u8),
+}
+
+impl From<u8> for IpProtocol {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Icmp,
+            6 => Self::Tcp,
+            17 => Self::Udp,
+            58 => Self::Icmpv6,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<IpProtocol> for u8 {
+    fn from(value: IpProtocol) -> Self {
+        match value {
+            IpProtocol::Icmp => 1,
+            IpProtocol::Tcp => 6,
+            IpProtocol::Udp => 17,
+            IpProtocol::Icmpv6 => 58,
+            IpProtocol::Unknown(value) => value,
+        }
+    }
+}
+
+// upper layers get the header of the (reassembled) datagram and its payload
+pub type ProtocolHandler = fn(InterfaceId, &Ipv4Header, &[u8]);
+
+static PROTOCOL_HANDLERS: Mutex<[Option<ProtocolHandler>; 256]> = Mutex::new([None; 256]);
+
+static NEXT_IDENTIFICATION: AtomicU16 = AtomicU16::new(1);
+
+pub fn register_protocol_handler(protocol: IpProtocol, handler: ProtocolHandler) {
+    PROTOCOL_HANDLERS.lock()[u8::from(protocol) as usize] = Some(handler);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SendOptions {
+    pub time_to_live: u8,
+    pub dont_fragment: bool,
+    // sends out of this interface instead of asking the routing table, needed for broadcasts
+    pub interface_id: Option<InterfaceId>,
+    // overrides the interface address, DHCP sends from 0.0.0.0 before it has one
+    pub source_address: Option<Ipv4Address>,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            time_to_live: DEFAULT_TIME_TO_LIVE,
+            dont_fragment: false,
+            interface_id: None,
+            source_address: None,
+        }
+    }
+}
+
+fn route_for(destination: Ipv4Address, options: &SendOptions) -> Result<Route> {
+    match options.interface_id {
+        Some(interface_id) => Ok(Route {
+            destination: Ipv4Cidr::new(destination, 32),
+            gateway: None,
+            interface_id,
+        }),
+        None => routing::lookup(destination).ok_or(Ipv4Error::NoRoute(destination)),
+    }
+}
+
+// the address we send from when talking to `destination`, upper layers need it for their pseudo headers
+pub fn source_address_for(destination: Ipv4Address, options: &SendOptions) -> Result<Ipv4Address> {
+    if let Some(source_address) = options.source_address {
+        return Ok(source_address);
+    }
+
+    let route = route_for(destination, options)?;
+
+    ipv4_cidr(route.interface_id)
+        .map(|cidr| cidr.address)
+        .ok_or(Ipv4Error::NoSourceAddress(route.interface_id))
+}
+
+fn interface_mtu(interface_id: InterfaceId) -> Result<usize> {
+    let mtu = get_device(interface_id)
+        .ok_or(NetworkError::UnknownInterface(interface_id))?
+        .lock()
+        .mtu();
+
+    if mtu < MINIMUM_MTU {
+        return Err(Ipv4Error::MtuTooSmall(mtu));
+    }
+
+    Ok(mtu)
+}
+
+// the largest datagram that goes out towards `destination` without fragmenting, TCP derives its mss from it
+pub fn mtu_for(destination: Ipv4Address, options: &SendOptions) -> Result<usize> {
+    interface_mtu(route_for(destination, options)?.interface_id)
+}
+
+// the part of the UDP and TCP checksums that covers the addresses from the IP header
+pub fn pseudo_header_checksum(
+    source_address: Ipv4Address,
+    destination_address: Ipv4Address,
+    protocol: IpProtocol,
+    length: usize,
+) -> Checksum {
+    let mut checksum = Checksum::new();
+    checksum.add_bytes(&source_address.bytes);
+    checksum.add_bytes(&destination_address.bytes);
+    checksum.add_u16(u8::from(protocol) as u16);
+    checksum.add_u16(length as u16);
+    checksum
+}
+
+pub fn send(destination: Ipv4Address, protocol: IpProtocol, packet: PacketBuffer) -> Result<()> {
+    send_with_options(destination, protocol, packet, SendOptions::default())
+}
+
+pub fn send_with_options(
+    destination: Ipv4Address,
+    protocol: IpProtocol,
+    packet: PacketBuffer,
+    options: SendOptions,
+) -> Result<()> {
+    let route = route_for(destination, &options)?;
+
+    let cidr = ipv4_cidr(route.interface_id);
+    let source_address = options
+        .source_address
+        .or(cidr.map(|cidr| cidr.address))
+        .ok_or(Ipv4Error::NoSourceAddress(route.interface_id))?;
+
+    // nobody answers ARP for broadcasts, and we don't join multicast groups so those go to everyone too
+    let next_hop =
+        if destination.is_multicast() || cidr.is_some_and(|cidr| cidr.broadcast() == destination) {
+            Ipv4Address::BROADCAST
+        } else {
+            route.gateway.unwrap_or(destination)
+        };
+
+    let mtu = interface_mtu(route.interface_id)?;
+
+    let mut flags = FragmentFlags::empty();
+    flags.set(FragmentFlags::DONT_FRAGMENT, options.dont_fragment);
+
+    let header = Ipv4Header {
+        type_of_service: 0,
+        total_length: 0,
+        identification: NEXT_IDENTIFICATION.fetch_add(1, Ordering::Relaxed),
+        flags,
+        fragment_offset: 0,
+        time_to_live: options.time_to_live,
+        protocol,
+        source_address,
+        destination_address: destination,
+        header_length: IPV4_HEADER_SIZE,
+    };
+
+    for datagram in fragmentation::fragment(header, packet, mtu)? {
+        arp::send_packet(route.interface_id, next_hop, EitherType::Ipv4, datagram)?;
+    }
+
+    Ok(())
+}
+
+pub fn handle_packet(interface_id: InterfaceId, data: &[u8]) {
+    let (header, payload) = match Ipv4Header::parse(data) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            println!("Dropped IPv4 packet on interface {}: {}", interface_id, err);
+            return;
+        }
+    };
+
+    let accepted = header.destination_address == Ipv4Address::BROADCAST
+        || header.destination_address.is_multicast()
+        || ipv4_cidr(interface_id).is_some_and(|cidr| {
+            header.destination_address == cidr.address
+                || header.destination_address == cidr.broadcast()
+        });
+
+    // we are not a router
+    if !accepted {
+        return;
+    }
+
+    if header.is_fragment() {
+        if let Some((header, payload)) = fragmentation::reassemble(&header, payload) {
+            deliver(interface_id, &header, &payload);
+        }
+    } else {
+        deliver(interface_id, &header, payload);
+    }
+}
+
+fn deliver(interface_id: InterfaceId, header: &Ipv4Header, payload: &[u8]) {
+    // the handler runs without the table locked so it is free to send
+    let handler = PROTOCOL_HANDLERS.lock()[u8::from(header.protocol) as usize];
+
+    match handler {
+        Some(handler) => handler(interface_id, header, payload),
+        None => icmp::send_destination_unreachable(UnreachableCode::Protocol, header, payload),
+    }
+}
+
+pub fn poll_timers() {
+    // the errors go out here, after the reassembly table was unlocked
+    for (header, payload) in fragmentation::expire() {
+        icmp::send_time_exceeded(TimeExceededCode::FragmentReassembly, &header, &payload);
+    }
+}
@@ -0,0 +1,65 @@
+use alloc::vec::Vec;
+
+use crate::{
+    mutex::Mutex,
+    network_stack::interface::{ipv4_networks, InterfaceId},
+};
+
+use super::{Ipv4Address, Ipv4Cidr};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Route {
+    pub destination: Ipv4Cidr,
+    // None for networks that are directly on the link
+    pub gateway: Option<Ipv4Address>,
+    pub interface_id: InterfaceId,
+}
+
+// the networks of the interfaces themselves are not stored here, they come from the interface addresses
+static ROUTES: Mutex<Vec<Route>> = Mutex::new(Vec::new());
+
+pub fn add_route(route: Route) {
+    let mut routes = ROUTES.lock();
+
+    routes.retain(|existing| existing.destination != route.destination);
+    routes.push(route);
+}
+
+pub fn set_default_gateway(interface_id: InterfaceId, gateway: Ipv4Address) {
+    add_route(Route {
+        destination: Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0),
+        gateway: Some(gateway),
+        interface_id,
+    });
+}
+
+pub fn remove_interface_routes(interface_id: InterfaceId) {
+    ROUTES
+        .lock()
+        .retain(|route| route.interface_id != interface_id);
+}
+
+fn connected_routes() -> impl Iterator<Item = Route> {
+    ipv4_networks()
+        .into_iter()
+        .map(|(interface_id, cidr)| Route {
+            destination: Ipv4Cidr::new(cidr.network(), cidr.prefix_length),
+            gateway: None,
+            interface_id,
+        })
+}
+
+// longest prefix match, a connected network wins over a static route of the same length
+pub fn lookup(destination: Ipv4Address) -> Option<Route> {
+    let routes = ROUTES.lock().clone();
+
+    connected_routes()
+        .chain(routes)
+        .filter(|route| route.destination.contains(destination))
+        .fold(None, |best: Option<Route>, route| match best {
+            Some(best) if best.destination.prefix_length >= route.destination.prefix_length => {
+                Some(best)
+            }
+            _ => Some(route),
+        })
+}
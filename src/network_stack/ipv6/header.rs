@@ -0,0 +1,123 @@
+use crate::network_stack::{ipv4::IpProtocol, packet_buffer::PacketBuffer};
+
+use super::{Ipv6Address, Ipv6Error, Result};
+
+pub const IPV6_HEADER_SIZE: usize = 40;
+const IPV6_VERSION: u8 = 6;
+// where the next header field sits in the fixed header, parameter problems point at it
+pub const NEXT_HEADER_OFFSET: u32 = 6;
+
+// https://datatracker.ietf.org/doc/html/rfc8200#section-4
+const HOP_BY_HOP_OPTIONS: u8 = 0;
+const ROUTING: u8 = 43;
+const FRAGMENT: u8 = 44;
+pub const NO_NEXT_HEADER: u8 = 59;
+const DESTINATION_OPTIONS: u8 = 60;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ipv6Header {
+    pub traffic_class: u8,
+    pub flow_label: u32,
+    // set from the payload when the packet is serialized
+    pub payload_length: u16,
+    pub next_header: IpProtocol,
+    pub hop_limit: u8,
+    pub source_address: Ipv6Address,
+    pub destination_address: Ipv6Address,
+}
+
+impl Ipv6Header {
+    // returns the header and the payload, anything past the payload length is link layer padding
+    pub fn parse(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        if bytes.len() < IPV6_HEADER_SIZE {
+            return Err(Ipv6Error::TooShort(bytes.len()));
+        }
+
+        let version = bytes[0] >> 4;
+        if version != IPV6_VERSION {
+            return Err(Ipv6Error::UnsupportedVersion(version));
+        }
+
+        let payload_length = u16::from_be_bytes([bytes[4], bytes[5]]);
+        if IPV6_HEADER_SIZE + payload_length as usize > bytes.len() {
+            return Err(Ipv6Error::InvalidPayloadLength(payload_length as usize));
+        }
+
+        let first_word = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+
+        Ok((
+            Self {
+                traffic_class: (first_word >> 20) as u8,
+                flow_label: first_word & 0xF_FFFF,
+                payload_length,
+                next_header: IpProtocol::from(bytes[6]),
+                hop_limit: bytes[7],
+                source_address: Ipv6Address::from_slice(&bytes[8..24]),
+                destination_address: Ipv6Address::from_slice(&bytes[24..40]),
+            },
+            &bytes[IPV6_HEADER_SIZE..IPV6_HEADER_SIZE + payload_length as usize],
+        ))
+    }
+
+    pub fn to_bytes(self) -> [u8; IPV6_HEADER_SIZE] {
+        let mut bytes = [0; IPV6_HEADER_SIZE];
+
+        let first_word = ((IPV6_VERSION as u32) << 28)
+            | ((self.traffic_class as u32) << 20)
+            | (self.flow_label & 0xF_FFFF);
+
+        bytes[0..4].copy_from_slice(&first_word.to_be_bytes());
+        bytes[4..6].copy_from_slice(&self.payload_length.to_be_bytes());
+        bytes[6] = u8::from(self.next_header);
+        bytes[7] = self.hop_limit;
+        bytes[8..24].copy_from_slice(&self.source_address.bytes);
+        bytes[24..40].copy_from_slice(&self.destination_address.bytes);
+
+        bytes
+    }
+
+    // writes the header in front of the payload, the payload length is taken from the packet
+    pub fn prepend_to(&self, packet: &mut PacketBuffer) {
+        let header = Self {
+            payload_length: packet.len() as u16,
+            ..*self
+        };
+
+        packet.prepend_slice(&header.to_bytes());
+    }
+}
+
+// walks past the extension headers meant for us and returns the upper layer protocol with its payload.
+// fragments are not reassembled, hosts size their packets to the path mtu so they are rare
+pub fn skip_extension_headers(
+    mut next_header: IpProtocol,
+    mut payload: &[u8],
+) -> Result<(IpProtocol, &[u8])> {
+    loop {
+        let kind = u8::from(next_header);
+
+        let length = match kind {
+            // the length counts 8 byte units past the first 8 bytes
+            HOP_BY_HOP_OPTIONS | ROUTING | DESTINATION_OPTIONS => {
+                let &length = payload
+                    .get(1)
+                    .ok_or(Ipv6Error::InvalidExtensionHeader(kind))?;
+                (length as usize + 1) * 8
+            }
+            FRAGMENT => return Err(Ipv6Error::Fragmented),
+            _ => return Ok((next_header, payload)),
+        };
+
+        if length > payload.len() {
+            return Err(Ipv6Error::InvalidExtensionHeader(kind));
+        }
+
+        // a routing header with segments left would have us forward the packet
+        if kind == ROUTING && payload[3] != 0 {
+            return Err(Ipv6Error::InvalidExtensionHeader(kind));
+        }
+
+        next_header = IpProtocol::from(payload[0]);
+        payload = &payload[length..];
+    }
+}
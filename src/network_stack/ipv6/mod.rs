@@ -0,0 +1,485 @@
+// https://datatracker.ietf.org/doc/html/rfc8200
+// https://datatracker.ietf.org/doc/html/rfc4291
+
+use core::{fmt::Formatter, str::FromStr};
+
+use alloc::vec::Vec;
+use thiserror::Error;
+
+use crate::{mutex::Mutex, pci::drivers::network::NetworkError, println};
+
+use self::{
+    header::{skip_extension_headers, Ipv6Header, IPV6_HEADER_SIZE},
+    routing::Route,
+};
+
+use super::{
+    checksum::Checksum,
+    ethernet::EthernetAddress,
+    icmpv6::{self, ParameterProblemCode},
+    interface::{get_device, has_ipv6_address, ipv6_addresses, ipv6_interfaces, InterfaceId},
+    ipv4::IpProtocol,
+    ndp,
+    packet_buffer::PacketBuffer,
+};
+
+pub mod header;
+pub mod routing;
+
+pub const DEFAULT_HOP_LIMIT: u8 = 64;
+// every link has to carry this much, so errors quoting a packet never need fragmenting
+pub const MINIMUM_MTU: usize = 1280;
+
+#[derive(Error, Debug)]
+pub enum Ipv6Error {
+    #[error("Packet of {0} bytes is too short for an IPv6 header")]
+    TooShort(usize),
+    #[error("Unsupported IP version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Payload length {0} does not match the packet")]
+    InvalidPayloadLength(usize),
+    #[error("Malformed extension header {0}")]
+    InvalidExtensionHeader(u8),
+    #[error("Fragmented packets are not supported")]
+    Fragmented,
+    #[error("No route to {0}")]
+    NoRoute(Ipv6Address),
+    #[error("Interface {0} has no usable IPv6 address")]
+    NoSourceAddress(InterfaceId),
+    #[error("Packet of {size} bytes does not fit the mtu of {mtu}")]
+    PacketTooLarge { size: usize, mtu: usize },
+    #[error("Invalid address")]
+    InvalidAddress,
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+}
+
+pub type Result<T> = core::result::Result<T, Ipv6Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ipv6Address {
+    pub bytes: [u8; 16],
+}
+
+impl Ipv6Address {
+    pub const UNSPECIFIED: Self = Self::new([0, 0, 0, 0, 0, 0, 0, 0]);
+    pub const ALL_NODES: Self = Self::new([0xFF02, 0, 0, 0, 0, 0, 0, 1]);
+    pub const ALL_ROUTERS: Self = Self::new([0xFF02, 0, 0, 0, 0, 0, 0, 2]);
+
+    pub const fn new(segments: [u16; 8]) -> Self {
+        let mut bytes = [0; 16];
+        let mut i = 0;
+
+        while i < 8 {
+            bytes[2 * i] = (segments[i] >> 8) as u8;
+            bytes[2 * i + 1] = segments[i] as u8;
+            i += 1;
+        }
+
+        Self { bytes }
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut address = Self::UNSPECIFIED;
+        address.bytes.copy_from_slice(&bytes[..16]);
+        address
+    }
+
+    pub fn to_u128(self) -> u128 {
+        u128::from_be_bytes(self.bytes)
+    }
+
+    pub fn from_u128(value: u128) -> Self {
+        Self {
+            bytes: value.to_be_bytes(),
+        }
+    }
+
+    pub fn segments(&self) -> [u16; 8] {
+        core::array::from_fn(|i| u16::from_be_bytes([self.bytes[2 * i], self.bytes[2 * i + 1]]))
+    }
+
+    pub fn is_unspecified(&self) -> bool {
+        *self == Self::UNSPECIFIED
+    }
+
+    pub fn is_multicast(&self) -> bool {
+        self.bytes[0] == 0xFF
+    }
+
+    // fe80::/10
+    pub fn is_link_local(&self) -> bool {
+        self.bytes[0] == 0xFE && self.bytes[1] & 0xC0 == 0x80
+    }
+
+    // multicasts that never leave the link, ff02::/16
+    pub fn is_link_local_multicast(&self) -> bool {
+        self.is_multicast() && self.bytes[1] & 0xF == 2
+    }
+
+    // ff02::1:ffXX:XXXX, neighbor solicitations for us go here instead of to every node
+    pub fn solicited_node(&self) -> Self {
+        let mut address = Self::new([0xFF02, 0, 0, 0, 0, 1, 0xFF00, 0]);
+        address.bytes[13..].copy_from_slice(&self.bytes[13..]);
+        address
+    }
+
+    // rfc 2464 7, multicasts map to 33:33 followed by the low 32 bits
+    pub fn multicast_ethernet_address(&self) -> EthernetAddress {
+        let mut address = EthernetAddress {
+            bytes: [0x33, 0x33, 0, 0, 0, 0],
+        };
+        address.bytes[2..].copy_from_slice(&self.bytes[12..]);
+        address
+    }
+
+    // the upper 64 bits of `prefix` followed by `interface_identifier`
+    pub fn from_prefix(prefix: Ipv6Address, interface_identifier: [u8; 8]) -> Self {
+        let mut address = prefix;
+        address.bytes[8..].copy_from_slice(&interface_identifier);
+        address
+    }
+}
+
+// rfc 4291 appendix A, the modified EUI-64 identifier stuffs ff:fe into the middle of the MAC
+// and flips the universal/local bit
+pub fn interface_identifier(ethernet_address: EthernetAddress) -> [u8; 8] {
+    let mac = ethernet_address.bytes;
+    [
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xFF,
+        0xFE,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]
+}
+
+// the fe80::/64 address every interface gets before anything else
+pub fn link_local_address(ethernet_address: EthernetAddress) -> Ipv6Address {
+    Ipv6Address::from_prefix(
+        Ipv6Address::new([0xFE80, 0, 0, 0, 0, 0, 0, 0]),
+        interface_identifier(ethernet_address),
+    )
+}
+
+// groups of hex digits, a single :: stands for a run of zero groups
+impl FromStr for Ipv6Address {
+    type Err = Ipv6Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        fn parse_groups(part: &str) -> Result<Vec<u16>> {
+            if part.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            part.split(':')
+                .map(|group| {
+                    if group.is_empty() || group.len() > 4 {
+                        return Err(Ipv6Error::InvalidAddress);
+                    }
+
+                    u16::from_str_radix(group, 16).map_err(|_| Ipv6Error::InvalidAddress)
+                })
+                .collect()
+        }
+
+        let mut segments = [0; 8];
+
+        match s.split_once("::") {
+            Some((head, tail)) => {
+                let head = parse_groups(head)?;
+                let tail = parse_groups(tail)?;
+
+                if head.len() + tail.len() > 7 {
+                    return Err(Ipv6Error::InvalidAddress);
+                }
+
+                segments[..head.len()].copy_from_slice(&head);
+                segments[8 - tail.len()..].copy_from_slice(&tail);
+            }
+            None => {
+                let groups = parse_groups(s)?;
+                if groups.len() != 8 {
+                    return Err(Ipv6Error::InvalidAddress);
+                }
+
+                segments.copy_from_slice(&groups);
+            }
+        }
+
+        Ok(Self::new(segments))
+    }
+}
+
+// rfc 5952, lowercase with the longest run of two or more zero groups shortened to ::
+impl core::fmt::Display for Ipv6Address {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        let segments = self.segments();
+
+        let mut longest = (0, 0);
+        let mut i = 0;
+        while i < segments.len() {
+            let start = i;
+            while i < segments.len() && segments[i] == 0 {
+                i += 1;
+            }
+
+            if i - start > longest.1 {
+                longest = (start, i - start);
+            }
+
+            i = i.max(start + 1);
+        }
+
+        let write_groups = |f: &mut Formatter, groups: &[u16]| -> core::fmt::Result {
+            for (i, group) in groups.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ":")?;
+                }
+                write!(f, "{:x}", group)?;
+            }
+            Ok(())
+        };
+
+        match longest {
+            (start, length) if length >= 2 => {
+                write_groups(f, &segments[..start])?;
+                write!(f, "::")?;
+                write_groups(f, &segments[start + length..])
+            }
+            _ => write_groups(f, &segments),
+        }
+    }
+}
+
+// an address together with the length of its prefix, 2001:db8::1/64
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv6Cidr {
+    pub address: Ipv6Address,
+    pub prefix_length: u8,
+}
+
+impl Ipv6Cidr {
+    pub const fn new(address: Ipv6Address, prefix_length: u8) -> Self {
+        Self {
+            address,
+            prefix_length,
+        }
+    }
+
+    fn mask(&self) -> u128 {
+        u128::MAX
+            .checked_shl(128 - self.prefix_length as u32)
+            .unwrap_or(0)
+    }
+
+    pub fn network(&self) -> Ipv6Address {
+        Ipv6Address::from_u128(self.address.to_u128() & self.mask())
+    }
+
+    pub fn contains(&self, address: Ipv6Address) -> bool {
+        address.to_u128() & self.mask() == self.network().to_u128()
+    }
+}
+
+impl FromStr for Ipv6Cidr {
+    type Err = Ipv6Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, prefix_length) = s.split_once('/').ok_or(Ipv6Error::InvalidAddress)?;
+
+        let prefix_length = prefix_length
+            .parse()
+            .ok()
+            .filter(|&prefix_length| prefix_length <= 128)
+            .ok_or(Ipv6Error::InvalidAddress)?;
+
+        Ok(Self::new(address.parse()?, prefix_length))
+    }
+}
+
+impl core::fmt::Display for Ipv6Cidr {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_length)
+    }
+}
+
+// upper layers get the fixed header, with the protocol found after the extension headers, and the payload
+pub type ProtocolHandler = fn(InterfaceId, &Ipv6Header, &[u8]);
+
+static PROTOCOL_HANDLERS: Mutex<[Option<ProtocolHandler>; 256]> = Mutex::new([None; 256]);
+
+pub fn register_protocol_handler(protocol: IpProtocol, handler: ProtocolHandler) {
+    PROTOCOL_HANDLERS.lock()[u8::from(protocol) as usize] = Some(handler);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SendOptions {
+    pub hop_limit: u8,
+    // sends out of this interface instead of asking the routing table, needed for link-local destinations
+    pub interface_id: Option<InterfaceId>,
+    // overrides the address selection, duplicate address detection sends from ::
+    pub source_address: Option<Ipv6Address>,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            hop_limit: DEFAULT_HOP_LIMIT,
+            interface_id: None,
+            source_address: None,
+        }
+    }
+}
+
+fn route_for(destination: Ipv6Address, options: &SendOptions) -> Result<Route> {
+    if let Some(interface_id) = options.interface_id {
+        return Ok(Route {
+            interface_id,
+            next_hop: destination,
+        });
+    }
+
+    // link scoped addresses are the same on every link, we take the first one that runs IPv6
+    if destination.is_link_local() || destination.is_link_local_multicast() {
+        return ipv6_interfaces()
+            .first()
+            .map(|&interface_id| Route {
+                interface_id,
+                next_hop: destination,
+            })
+            .ok_or(Ipv6Error::NoRoute(destination));
+    }
+
+    routing::lookup(destination).ok_or(Ipv6Error::NoRoute(destination))
+}
+
+// rfc 6724 boiled down, link-local destinations get our link-local address and everything else a global one
+pub fn source_address_for(destination: Ipv6Address, options: &SendOptions) -> Result<Ipv6Address> {
+    if let Some(source_address) = options.source_address {
+        return Ok(source_address);
+    }
+
+    let interface_id = route_for(destination, options)?.interface_id;
+    let addresses = ipv6_addresses(interface_id);
+    let wants_link_local = destination.is_link_local() || destination.is_link_local_multicast();
+
+    addresses
+        .iter()
+        .find(|cidr| cidr.address.is_link_local() == wants_link_local)
+        .or(addresses.first())
+        .map(|cidr| cidr.address)
+        .ok_or(Ipv6Error::NoSourceAddress(interface_id))
+}
+
+fn interface_mtu(interface_id: InterfaceId) -> Result<usize> {
+    Ok(get_device(interface_id)
+        .ok_or(NetworkError::UnknownInterface(interface_id))?
+        .lock()
+        .mtu())
+}
+
+pub fn mtu_for(destination: Ipv6Address, options: &SendOptions) -> Result<usize> {
+    interface_mtu(route_for(destination, options)?.interface_id)
+}
+
+// the part of the UDP, TCP and ICMPv6 checksums that covers the addresses, rfc 8200 8.1
+pub fn pseudo_header_checksum(
+    source_address: Ipv6Address,
+    destination_address: Ipv6Address,
+    protocol: IpProtocol,
+    length: usize,
+) -> Checksum {
+    let mut checksum = Checksum::new();
+    checksum.add_bytes(&source_address.bytes);
+    checksum.add_bytes(&destination_address.bytes);
+    checksum.add_bytes(&(length as u32).to_be_bytes());
+    checksum.add_u16(u8::from(protocol) as u16);
+    checksum
+}
+
+// we never fragment, the upper layers size their packets from the mtu
+pub fn send_with_options(
+    destination: Ipv6Address,
+    protocol: IpProtocol,
+    mut packet: PacketBuffer,
+    options: SendOptions,
+) -> Result<()> {
+    let route = route_for(destination, &options)?;
+    let source_address = source_address_for(destination, &options)?;
+
+    let size = IPV6_HEADER_SIZE + packet.len();
+    let mtu = interface_mtu(route.interface_id)?;
+    if size > mtu {
+        return Err(Ipv6Error::PacketTooLarge { size, mtu });
+    }
+
+    Ipv6Header {
+        traffic_class: 0,
+        flow_label: 0,
+        payload_length: 0,
+        next_header: protocol,
+        hop_limit: options.hop_limit,
+        source_address,
+        destination_address: destination,
+    }
+    .prepend_to(&mut packet);
+
+    ndp::send_packet(route.interface_id, route.next_hop, packet)?;
+
+    Ok(())
+}
+
+pub fn handle_packet(interface_id: InterfaceId, data: &[u8]) {
+    let (header, payload) = match Ipv6Header::parse(data) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            println!("Dropped IPv6 packet on interface {}: {}", interface_id, err);
+            return;
+        }
+    };
+
+    // the cards are promiscuous, so multicasts for groups we never joined get here too
+    let accepted = if header.destination_address.is_multicast() {
+        header.destination_address.is_link_local_multicast()
+    } else {
+        has_ipv6_address(interface_id, header.destination_address)
+    };
+
+    // packets for other hosts would need forwarding, which a host doesn't do (rfc 8200)
+    if !accepted {
+        return;
+    }
+
+    let (protocol, payload) = match skip_extension_headers(header.next_header, payload) {
+        Ok(found) => found,
+        Err(err) => {
+            println!("Dropped IPv6 packet on interface {}: {}", interface_id, err);
+            return;
+        }
+    };
+
+    let header = Ipv6Header {
+        next_header: protocol,
+        ..header
+    };
+
+    // icmpv6, udp and tcp answer from inside the handler, so it's copied out of the table first
+    let handler = PROTOCOL_HANDLERS.lock()[u8::from(protocol) as usize];
+
+    match handler {
+        Some(handler) => handler(interface_id, &header, payload),
+        // nothing after the extension headers
+        None if protocol == IpProtocol::Unknown(header::NO_NEXT_HEADER) => (),
+        // the pointer is only exact when there were no extension headers
+        None => icmpv6::send_parameter_problem(
+            ParameterProblemCode::UnrecognizedNextHeader,
+            header::NEXT_HEADER_OFFSET,
+            &header,
+            payload,
+        ),
+    }
+}
@@ -0,0 +1,145 @@
+// the prefix list and default router list of https://datatracker.ietf.org/doc/html/rfc4861#section-5.1,
+// filled from router advertisements
+
+use alloc::vec::Vec;
+
+use crate::{
+    mutex::Mutex,
+    network_stack::interface::InterfaceId,
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::{Ipv6Address, Ipv6Cidr};
+
+// pit::elapsed compares wrapping differences, so longer lifetimes count as infinite
+const MAX_LIFETIME: usize = usize::MAX / 2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Route {
+    pub interface_id: InterfaceId,
+    // the destination itself when it is on the link
+    pub next_hop: Ipv6Address,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lifetime {
+    updated_at: usize,
+    // ticks from the router advertisement, None for routes that don't expire
+    ticks: Option<usize>,
+}
+
+impl Lifetime {
+    // all ones in an advertisement means forever
+    fn from_seconds(seconds: u32) -> Self {
+        let ticks = (seconds != u32::MAX).then(|| {
+            (seconds as usize)
+                .saturating_mul(TICKS_PER_SECOND)
+                .min(MAX_LIFETIME)
+        });
+
+        Self {
+            updated_at: pit::ticks(),
+            ticks,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.ticks
+            .is_some_and(|ticks| pit::elapsed(self.updated_at, ticks))
+    }
+}
+
+struct Prefix {
+    interface_id: InterfaceId,
+    cidr: Ipv6Cidr,
+    lifetime: Lifetime,
+}
+
+struct DefaultRouter {
+    interface_id: InterfaceId,
+    address: Ipv6Address,
+    lifetime: Lifetime,
+}
+
+static PREFIXES: Mutex<Vec<Prefix>> = Mutex::new(Vec::new());
+static DEFAULT_ROUTERS: Mutex<Vec<DefaultRouter>> = Mutex::new(Vec::new());
+
+// a lifetime of zero takes the prefix off the link
+pub fn update_prefix(interface_id: InterfaceId, cidr: Ipv6Cidr, valid_lifetime: u32) {
+    let cidr = Ipv6Cidr::new(cidr.network(), cidr.prefix_length);
+    let mut prefixes = PREFIXES.lock();
+
+    prefixes.retain(|prefix| !(prefix.interface_id == interface_id && prefix.cidr == cidr));
+
+    if valid_lifetime != 0 {
+        prefixes.push(Prefix {
+            interface_id,
+            cidr,
+            lifetime: Lifetime::from_seconds(valid_lifetime),
+        });
+    }
+}
+
+// a lifetime of zero means the router stopped being a default router
+pub fn update_default_router(interface_id: InterfaceId, address: Ipv6Address, lifetime: u16) {
+    let mut routers = DEFAULT_ROUTERS.lock();
+
+    routers.retain(|router| !(router.interface_id == interface_id && router.address == address));
+
+    if lifetime != 0 {
+        routers.push(DefaultRouter {
+            interface_id,
+            address,
+            lifetime: Lifetime::from_seconds(lifetime as u32),
+        });
+    }
+}
+
+// longest matching on-link prefix, otherwise the first default router
+pub fn lookup(destination: Ipv6Address) -> Option<Route> {
+    let on_link = PREFIXES
+        .lock()
+        .iter()
+        .filter(|prefix| !prefix.lifetime.is_expired() && prefix.cidr.contains(destination))
+        .max_by_key(|prefix| prefix.cidr.prefix_length)
+        .map(|prefix| Route {
+            interface_id: prefix.interface_id,
+            next_hop: destination,
+        });
+
+    on_link.or_else(|| {
+        DEFAULT_ROUTERS
+            .lock()
+            .iter()
+            .find(|router| !router.lifetime.is_expired())
+            .map(|router| Route {
+                interface_id: router.interface_id,
+                next_hop: router.address,
+            })
+    })
+}
+
+pub fn poll_timers() {
+    PREFIXES.lock().retain(|prefix| {
+        let expired = prefix.lifetime.is_expired();
+        if expired {
+            println!(
+                "Prefix {} on interface {} expired",
+                prefix.cidr, prefix.interface_id
+            );
+        }
+        !expired
+    });
+
+    DEFAULT_ROUTERS.lock().retain(|router| {
+        let expired = router.lifetime.is_expired();
+        if expired {
+            println!(
+                "Default router {} on interface {} expired",
+                router.address, router.interface_id
+            );
+        }
+        !expired
+    });
+}
@@ -1,2 +1,136 @@
+use crate::{
+    pci::drivers::network::DeviceCapabilities,
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use self::{
+    ethernet::{EitherType, EthernetAddress, EthernetFrame},
+    interface::{devices, InterfaceId, RECEIVE_EVENT},
+};
+
+pub mod arp;
+pub mod checksum;
+pub mod dhcp;
+pub mod dns;
 pub mod ethernet;
-pub mod arp;
\ No newline at end of file
+pub mod http;
+pub mod icmp;
+pub mod icmpv6;
+pub mod interface;
+pub mod ip;
+pub mod ipv4;
+pub mod ipv6;
+pub mod ndp;
+pub mod neighbor_cache;
+pub mod packet_buffer;
+pub mod ports;
+pub mod tcp;
+pub mod udp;
+
+// how often the main loop wakes up for the stack timers when no frames come in
+pub const TIMER_POLL_INTERVAL: usize = TICKS_PER_SECOND / 10;
+
+// registers the upper layer protocols with IPv4 and IPv6
+pub fn init() {
+    icmp::init();
+    icmpv6::init();
+    udp::init();
+    tcp::init();
+}
+
+// drives the stack until `condition` returns something, gives up after `timeout` ticks or never with None.
+// it polls the stack itself, so it must not be called from a protocol handler
+pub fn poll_until<T>(
+    timeout: Option<usize>,
+    mut condition: impl FnMut() -> Option<T>,
+) -> Option<T> {
+    let start = pit::ticks();
+
+    loop {
+        if let Some(value) = condition() {
+            return Some(value);
+        }
+
+        if timeout.is_some_and(|timeout| pit::elapsed(start, timeout)) {
+            return None;
+        }
+
+        RECEIVE_EVENT.wait_timeout(TIMER_POLL_INTERVAL);
+        poll();
+    }
+}
+
+// drain every frame the cards received and pass it up the stack, then run the timers
+// we only hold a device lock while pulling a single frame so handlers are free to transmit
+pub fn poll() {
+    for (interface_id, device) in devices() {
+        let (ethernet_address, has_frame_check_sequence) = {
+            let device = device.lock();
+            (
+                device.ethernet_address(),
+                device
+                    .capabilities()
+                    .contains(DeviceCapabilities::RECEIVES_FCS),
+            )
+        };
+
+        loop {
+            let received = device.lock().receive_frame();
+
+            match received {
+                Ok(Some(frame)) => handle_frame(
+                    interface_id,
+                    ethernet_address,
+                    &frame,
+                    has_frame_check_sequence,
+                ),
+                Ok(None) => break,
+                Err(err) => println!("Dropped received frame: {}", err),
+            }
+        }
+    }
+
+    arp::poll_timers();
+    ndp::poll_timers();
+    ipv4::poll_timers();
+    tcp::poll_timers();
+    dhcp::poll();
+}
+
+fn handle_frame(
+    interface_id: InterfaceId,
+    ethernet_address: EthernetAddress,
+    bytes: &[u8],
+    has_frame_check_sequence: bool,
+) {
+    let frame = match EthernetFrame::parse(bytes, has_frame_check_sequence) {
+        Ok(frame) => frame,
+        Err(err) => {
+            println!(
+                "Dropped malformed frame on interface {}: {}",
+                interface_id, err
+            );
+            return;
+        }
+    };
+
+    // the cards run in promiscuous mode, so frames for other hosts have to be dropped here
+    let destination = frame.header.destination_address;
+    if destination != ethernet_address && !destination.is_multicast() {
+        return;
+    }
+
+    match frame.header.ether_type {
+        EitherType::Arp => arp::handle_packet(interface_id, frame.data),
+        EitherType::Ipv4 => ipv4::handle_packet(interface_id, frame.data),
+        EitherType::Ipv6 => ipv6::handle_packet(interface_id, frame.data),
+        ether_type => println!(
+            "Received {:?} frame of {} bytes from {} on interface {}",
+            ether_type,
+            frame.data.len(),
+            frame.header.source_address,
+            interface_id
+        ),
+    }
+}
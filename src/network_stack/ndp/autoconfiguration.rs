@@ -0,0 +1,252 @@
+// https://datatracker.ietf.org/doc/html/rfc4862
+// router solicitations follow https://datatracker.ietf.org/doc/html/rfc4861#section-6.3.7
+
+use alloc::vec::Vec;
+
+use crate::{
+    mutex::Mutex,
+    network_stack::{
+        icmpv6::MessageType,
+        interface::{ipv6_addresses, remove_ipv6_address, InterfaceId},
+        ipv6::{self, header::Ipv6Header, interface_identifier, routing, Ipv6Address, Ipv6Cidr},
+    },
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::{
+    configure_address, ethernet_address, learn_neighbor, send_message, NdpError, NdpOptions,
+};
+
+// MAX_RTR_SOLICITATIONS and RTR_SOLICITATION_INTERVAL
+const MAX_ROUTER_SOLICITATIONS: u8 = 3;
+const ROUTER_SOLICITATION_INTERVAL: usize = 4 * TICKS_PER_SECOND;
+
+// hop limit, flags, router lifetime, reachable time and retransmission timer
+const ROUTER_ADVERTISEMENT_SIZE: usize = 12;
+// addresses are formed from a prefix and the 64 bit interface identifier
+const AUTOCONFIGURATION_PREFIX_LENGTH: u8 = 64;
+// rfc 4862 5.5.3 e, unauthenticated advertisements can't shorten a lifetime below this
+const TWO_HOURS: u32 = 2 * 60 * 60;
+const MAX_LIFETIME: usize = usize::MAX / 2;
+
+struct RouterSolicitation {
+    interface_id: InterfaceId,
+    sent: u8,
+    last_sent_at: usize,
+}
+
+struct AutoconfiguredAddress {
+    interface_id: InterfaceId,
+    cidr: Ipv6Cidr,
+    updated_at: usize,
+    // the prefix's valid lifetime in ticks, None when it was advertised as infinite
+    valid_ticks: Option<usize>,
+}
+
+impl AutoconfiguredAddress {
+    fn remaining_seconds(&self) -> Option<u32> {
+        let ticks = self.valid_ticks?;
+        let elapsed = pit::ticks().wrapping_sub(self.updated_at);
+        Some((ticks.saturating_sub(elapsed) / TICKS_PER_SECOND).min(u32::MAX as usize - 1) as u32)
+    }
+
+    fn set_lifetime(&mut self, seconds: u32) {
+        self.updated_at = pit::ticks();
+        self.valid_ticks = (seconds != u32::MAX).then(|| {
+            (seconds as usize)
+                .saturating_mul(TICKS_PER_SECOND)
+                .min(MAX_LIFETIME)
+        });
+    }
+
+    fn is_expired(&self) -> bool {
+        self.valid_ticks
+            .is_some_and(|ticks| pit::elapsed(self.updated_at, ticks))
+    }
+}
+
+static SOLICITATIONS: Mutex<Vec<RouterSolicitation>> = Mutex::new(Vec::new());
+static ADDRESSES: Mutex<Vec<AutoconfiguredAddress>> = Mutex::new(Vec::new());
+
+// the first solicitation goes out on the next poll
+pub fn solicit_routers(interface_id: InterfaceId) {
+    let mut solicitations = SOLICITATIONS.lock();
+
+    solicitations.retain(|solicitation| solicitation.interface_id != interface_id);
+    solicitations.push(RouterSolicitation {
+        interface_id,
+        sent: 0,
+        last_sent_at: pit::ticks(),
+    });
+}
+
+fn send_solicitation(interface_id: InterfaceId) -> ipv6::Result<()> {
+    // without an address of our own the source link-layer option is not allowed
+    let source_address = ipv6_addresses(interface_id)
+        .into_iter()
+        .map(|cidr| cidr.address)
+        .find(|address| address.is_link_local())
+        .unwrap_or(Ipv6Address::UNSPECIFIED);
+
+    let mut body = Vec::from([0; 4]);
+    if !source_address.is_unspecified() {
+        NdpOptions {
+            source_link_layer_address: ethernet_address(interface_id),
+            ..NdpOptions::default()
+        }
+        .write_to(&mut body);
+    }
+
+    send_message(
+        interface_id,
+        Ipv6Address::ALL_ROUTERS,
+        Some(source_address),
+        MessageType::RouterSolicitation,
+        &body,
+    )
+}
+
+pub(super) fn handle_router_advertisement(
+    interface_id: InterfaceId,
+    header: &Ipv6Header,
+    body: &[u8],
+) -> Result<(), NdpError> {
+    // rfc 4861 6.1.2, routers always advertise from their link-local address
+    if !header.source_address.is_link_local() {
+        return Ok(());
+    }
+
+    if body.len() < ROUTER_ADVERTISEMENT_SIZE {
+        return Err(NdpError::TooShort(body.len()));
+    }
+
+    let router_lifetime = u16::from_be_bytes([body[2], body[3]]);
+    let options = NdpOptions::parse(&body[ROUTER_ADVERTISEMENT_SIZE..])?;
+
+    SOLICITATIONS
+        .lock()
+        .retain(|solicitation| solicitation.interface_id != interface_id);
+
+    routing::update_default_router(interface_id, header.source_address, router_lifetime);
+
+    if let Some(ethernet_address) = options.source_link_layer_address {
+        learn_neighbor(interface_id, header.source_address, ethernet_address, true);
+    }
+
+    let interface_identifier = match ethernet_address(interface_id) {
+        Some(ethernet_address) => interface_identifier(ethernet_address),
+        None => return Ok(()),
+    };
+
+    for prefix in options.prefixes {
+        if prefix.cidr.address.is_link_local() || prefix.preferred_lifetime > prefix.valid_lifetime
+        {
+            continue;
+        }
+
+        if prefix.on_link {
+            routing::update_prefix(interface_id, prefix.cidr, prefix.valid_lifetime);
+        }
+
+        if prefix.autonomous && prefix.cidr.prefix_length == AUTOCONFIGURATION_PREFIX_LENGTH {
+            let cidr = Ipv6Cidr::new(
+                Ipv6Address::from_prefix(prefix.cidr.network(), interface_identifier),
+                AUTOCONFIGURATION_PREFIX_LENGTH,
+            );
+
+            if update_address(interface_id, cidr, prefix.valid_lifetime) {
+                configure_address(interface_id, cidr);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// returns true when the address is new and has to go through duplicate address detection
+fn update_address(interface_id: InterfaceId, cidr: Ipv6Cidr, valid_lifetime: u32) -> bool {
+    let mut addresses = ADDRESSES.lock();
+
+    let existing = addresses
+        .iter_mut()
+        .find(|address| address.interface_id == interface_id && address.cidr == cidr);
+
+    match existing {
+        Some(address) => {
+            let remaining = address.remaining_seconds();
+
+            // rfc 4862 5.5.3 e, anyone on the link could send a short lifetime to take the address away
+            if valid_lifetime > TWO_HOURS || remaining.is_some_and(|r| valid_lifetime > r) {
+                address.set_lifetime(valid_lifetime);
+            } else if remaining.is_none_or(|r| r > TWO_HOURS) {
+                address.set_lifetime(TWO_HOURS);
+            }
+
+            false
+        }
+        None => {
+            if valid_lifetime == 0 {
+                return false;
+            }
+
+            let mut address = AutoconfiguredAddress {
+                interface_id,
+                cidr,
+                updated_at: 0,
+                valid_ticks: None,
+            };
+            address.set_lifetime(valid_lifetime);
+            addresses.push(address);
+
+            true
+        }
+    }
+}
+
+pub(super) fn poll_timers() {
+    let mut solicitations = Vec::new();
+    let mut expired = Vec::new();
+
+    SOLICITATIONS.lock().retain_mut(|solicitation| {
+        if solicitation.sent > 0
+            && !pit::elapsed(solicitation.last_sent_at, ROUTER_SOLICITATION_INTERVAL)
+        {
+            return true;
+        }
+
+        if solicitation.sent == MAX_ROUTER_SOLICITATIONS {
+            println!(
+                "No router answered on interface {}, IPv6 stays link-local",
+                solicitation.interface_id
+            );
+            return false;
+        }
+
+        solicitation.sent += 1;
+        solicitation.last_sent_at = pit::ticks();
+        solicitations.push(solicitation.interface_id);
+        true
+    });
+
+    ADDRESSES.lock().retain(|address| {
+        let is_expired = address.is_expired();
+        if is_expired {
+            expired.push((address.interface_id, address.cidr));
+        }
+        !is_expired
+    });
+
+    for interface_id in solicitations {
+        if let Err(err) = send_solicitation(interface_id) {
+            println!("Failed to send router solicitation: {}", err);
+        }
+    }
+
+    for (interface_id, cidr) in expired {
+        remove_ipv6_address(interface_id, cidr.address);
+        println!("Address {} on interface {} expired", cidr, interface_id);
+    }
+
+    routing::poll_timers();
+}
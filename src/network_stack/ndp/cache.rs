@@ -0,0 +1,50 @@
+use alloc::vec::Vec;
+
+use crate::{
+    mutex::Mutex,
+    network_stack::{
+        ethernet::EthernetAddress, interface::InterfaceId, ipv6::Ipv6Address,
+        neighbor_cache::NeighborCache, packet_buffer::PacketBuffer,
+    },
+    x86::pit::TICKS_PER_SECOND,
+};
+
+// rfc 4861 10, REACHABLE_TIME. we skip the stale and probe states and ask again once it runs out
+const REACHABLE_TIMEOUT: usize = 30 * TICKS_PER_SECOND;
+// RETRANS_TIMER and MAX_MULTICAST_SOLICIT
+const SOLICITATION_INTERVAL: usize = TICKS_PER_SECOND;
+const MAX_SOLICITATIONS: u8 = 3;
+
+static NEIGHBORS: Mutex<NeighborCache<Ipv6Address, PacketBuffer>> = Mutex::new(NeighborCache::new(
+    "neighbor solicitations",
+    REACHABLE_TIMEOUT,
+    SOLICITATION_INTERVAL,
+    MAX_SOLICITATIONS,
+));
+
+pub fn lookup(interface_id: InterfaceId, address: Ipv6Address) -> Option<EthernetAddress> {
+    NEIGHBORS.lock().lookup(interface_id, address)
+}
+
+pub(super) fn queue_packet(
+    interface_id: InterfaceId,
+    address: Ipv6Address,
+    packet: PacketBuffer,
+) -> bool {
+    NEIGHBORS.lock().queue_packet(interface_id, address, packet)
+}
+
+pub(super) fn update(
+    interface_id: InterfaceId,
+    address: Ipv6Address,
+    ethernet_address: EthernetAddress,
+    create: bool,
+) -> Vec<PacketBuffer> {
+    NEIGHBORS
+        .lock()
+        .update(interface_id, address, ethernet_address, create)
+}
+
+pub(super) fn expire() -> Vec<(InterfaceId, Ipv6Address)> {
+    NEIGHBORS.lock().expire()
+}
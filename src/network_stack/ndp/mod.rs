@@ -0,0 +1,525 @@
+// https://datatracker.ietf.org/doc/html/rfc4861
+// duplicate address detection follows https://datatracker.ietf.org/doc/html/rfc4862#section-5.4
+
+use alloc::vec::Vec;
+use bitflags::bitflags;
+use thiserror::Error;
+
+use crate::{
+    mutex::Mutex,
+    pci::drivers::network::{NetworkError, Result as NetworkResult},
+    println,
+    x86::pit::{self, TICKS_PER_SECOND},
+};
+
+use super::{
+    ethernet::{EitherType, EthernetAddress},
+    icmpv6::{self, Icmpv6Message, MessageType},
+    interface::{add_ipv6_address, get_device, has_ipv6_address, transmit_packet, InterfaceId},
+    ipv6::{self, header::Ipv6Header, link_local_address, Ipv6Address, Ipv6Cidr, SendOptions},
+    packet_buffer::PacketBuffer,
+};
+
+pub mod autoconfiguration;
+pub mod cache;
+
+// rfc 4861 7.1.1, anything that crossed a router can't be neighbor discovery
+const NDP_HOP_LIMIT: u8 = 255;
+
+const OPTION_SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+const OPTION_TARGET_LINK_LAYER_ADDRESS: u8 = 2;
+const OPTION_PREFIX_INFORMATION: u8 = 3;
+const OPTION_MTU: u8 = 5;
+// option lengths count 8 byte units
+const OPTION_UNIT: usize = 8;
+const PREFIX_INFORMATION_SIZE: usize = 32;
+
+// the reserved word and target address in front of the options of solicitations and advertisements
+const NEIGHBOR_MESSAGE_SIZE: usize = 20;
+
+// DupAddrDetectTransmits and RetransTimer
+const DUPLICATE_ADDRESS_PROBES: u8 = 1;
+const PROBE_INTERVAL: usize = TICKS_PER_SECOND;
+
+#[derive(Error, Debug)]
+pub enum NdpError {
+    #[error("Message of {0} bytes is too short")]
+    TooShort(usize),
+    #[error("Option {0} has an invalid length")]
+    InvalidOption(u8),
+    #[error("Hop limit {0} shows the message crossed a router")]
+    InvalidHopLimit(u8),
+    #[error("Invalid code {0}")]
+    InvalidCode(u8),
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct AdvertisementFlags: u8 {
+        const ROUTER = 1 << 7;
+        const SOLICITED = 1 << 6;
+        const OVERRIDE = 1 << 5;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PrefixInformation {
+    pub cidr: Ipv6Cidr,
+    pub on_link: bool,
+    // the prefix may be used for stateless autoconfiguration
+    pub autonomous: bool,
+    // seconds, all ones is forever
+    pub valid_lifetime: u32,
+    pub preferred_lifetime: u32,
+}
+
+// link-layer addresses, prefixes and the mtu, rfc 4861 4.6. other types are skipped
+#[derive(Debug, Default, Clone)]
+pub struct NdpOptions {
+    pub source_link_layer_address: Option<EthernetAddress>,
+    pub target_link_layer_address: Option<EthernetAddress>,
+    pub prefixes: Vec<PrefixInformation>,
+    pub mtu: Option<u32>,
+}
+
+impl NdpOptions {
+    pub fn parse(mut bytes: &[u8]) -> Result<Self, NdpError> {
+        let mut options = Self::default();
+
+        while bytes.len() >= 2 {
+            let kind = bytes[0];
+            let length = bytes[1] as usize * OPTION_UNIT;
+
+            // a zero length would loop forever, rfc 4861 4.6 says to drop the whole message
+            if length == 0 || length > bytes.len() {
+                return Err(NdpError::InvalidOption(kind));
+            }
+
+            let data = &bytes[2..length];
+
+            match kind {
+                OPTION_SOURCE_LINK_LAYER_ADDRESS if data.len() >= 6 => {
+                    options.source_link_layer_address = Some(EthernetAddress::from_slice(data))
+                }
+                OPTION_TARGET_LINK_LAYER_ADDRESS if data.len() >= 6 => {
+                    options.target_link_layer_address = Some(EthernetAddress::from_slice(data))
+                }
+                OPTION_PREFIX_INFORMATION if length == PREFIX_INFORMATION_SIZE => {
+                    options.prefixes.push(PrefixInformation {
+                        cidr: Ipv6Cidr::new(Ipv6Address::from_slice(&data[14..30]), data[0]),
+                        on_link: data[1] & 0x80 != 0,
+                        autonomous: data[1] & 0x40 != 0,
+                        valid_lifetime: u32::from_be_bytes(data[2..6].try_into().unwrap()),
+                        preferred_lifetime: u32::from_be_bytes(data[6..10].try_into().unwrap()),
+                    })
+                }
+                OPTION_MTU if length == OPTION_UNIT => {
+                    options.mtu = Some(u32::from_be_bytes(data[2..6].try_into().unwrap()))
+                }
+                _ => (),
+            }
+
+            bytes = &bytes[length..];
+        }
+
+        Ok(options)
+    }
+
+    // only the link-layer address options, they are all a host ever sends
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        for (kind, address) in [
+            (
+                OPTION_SOURCE_LINK_LAYER_ADDRESS,
+                self.source_link_layer_address,
+            ),
+            (
+                OPTION_TARGET_LINK_LAYER_ADDRESS,
+                self.target_link_layer_address,
+            ),
+        ] {
+            if let Some(address) = address {
+                bytes.extend_from_slice(&[kind, 1]);
+                bytes.extend_from_slice(&address.bytes);
+            }
+        }
+    }
+}
+
+struct AddressProbe {
+    interface_id: InterfaceId,
+    cidr: Ipv6Cidr,
+    probes_sent: u8,
+    last_probe_at: usize,
+}
+
+// tentative addresses, they are not assigned to the interface until nobody claims them
+static ADDRESS_PROBES: Mutex<Vec<AddressProbe>> = Mutex::new(Vec::new());
+
+enum ProbeAction {
+    Probe(InterfaceId, Ipv6Address),
+    Assign(InterfaceId, Ipv6Cidr),
+}
+
+// gives the interface its link-local address, routers are solicited once it passed duplicate address detection
+pub fn start(interface_id: InterfaceId) -> NetworkResult<()> {
+    let ethernet_address = get_device(interface_id)
+        .ok_or(NetworkError::UnknownInterface(interface_id))?
+        .lock()
+        .ethernet_address();
+
+    configure_address(
+        interface_id,
+        Ipv6Cidr::new(link_local_address(ethernet_address), 64),
+    );
+
+    Ok(())
+}
+
+// the address stays tentative while neighbor solicitations for it go unanswered, rfc 4862 5.4
+pub fn configure_address(interface_id: InterfaceId, cidr: Ipv6Cidr) {
+    let mut probes = ADDRESS_PROBES.lock();
+
+    probes.retain(|probe| !(probe.interface_id == interface_id && probe.cidr == cidr));
+    probes.push(AddressProbe {
+        interface_id,
+        cidr,
+        probes_sent: 0,
+        last_probe_at: pit::ticks(),
+    });
+}
+
+fn is_tentative(interface_id: InterfaceId, address: Ipv6Address) -> bool {
+    ADDRESS_PROBES
+        .lock()
+        .iter()
+        .any(|probe| probe.interface_id == interface_id && probe.cidr.address == address)
+}
+
+fn ethernet_address(interface_id: InterfaceId) -> Option<EthernetAddress> {
+    Some(get_device(interface_id)?.lock().ethernet_address())
+}
+
+// packets to an unresolved neighbor are queued in the neighbor cache until an advertisement comes
+pub fn send_packet(
+    interface_id: InterfaceId,
+    next_hop: Ipv6Address,
+    packet: PacketBuffer,
+) -> ipv6::Result<()> {
+    if next_hop.is_multicast() {
+        transmit_packet(
+            interface_id,
+            next_hop.multicast_ethernet_address(),
+            EitherType::Ipv6,
+            packet,
+        )?;
+        return Ok(());
+    }
+
+    if let Some(ethernet_address) = cache::lookup(interface_id, next_hop) {
+        transmit_packet(interface_id, ethernet_address, EitherType::Ipv6, packet)?;
+        return Ok(());
+    }
+
+    if cache::queue_packet(interface_id, next_hop, packet) {
+        send_solicitation(interface_id, next_hop)?;
+    }
+
+    Ok(())
+}
+
+// records the neighbor and sends whatever was waiting on it
+fn learn_neighbor(
+    interface_id: InterfaceId,
+    address: Ipv6Address,
+    ethernet_address: EthernetAddress,
+    create: bool,
+) {
+    for packet in cache::update(interface_id, address, ethernet_address, create) {
+        if let Err(err) = transmit_packet(interface_id, ethernet_address, EitherType::Ipv6, packet)
+        {
+            println!(
+                "Failed to send a packet waiting on neighbor discovery: {}",
+                err
+            );
+        }
+    }
+}
+
+fn send_message(
+    interface_id: InterfaceId,
+    destination: Ipv6Address,
+    source_address: Option<Ipv6Address>,
+    message_type: MessageType,
+    body: &[u8],
+) -> ipv6::Result<()> {
+    let message = Icmpv6Message {
+        message_type,
+        code: 0,
+        body,
+    };
+
+    icmpv6::send(
+        destination,
+        &message,
+        SendOptions {
+            hop_limit: NDP_HOP_LIMIT,
+            interface_id: Some(interface_id),
+            source_address,
+        },
+    )
+}
+
+// asks the solicited-node group of `target` for its link-layer address
+fn send_solicitation(interface_id: InterfaceId, target: Ipv6Address) -> ipv6::Result<()> {
+    let mut body = Vec::with_capacity(NEIGHBOR_MESSAGE_SIZE + OPTION_UNIT);
+    body.extend_from_slice(&[0; 4]);
+    body.extend_from_slice(&target.bytes);
+
+    NdpOptions {
+        source_link_layer_address: ethernet_address(interface_id),
+        ..NdpOptions::default()
+    }
+    .write_to(&mut body);
+
+    // the address we ask from has to be on the same link as the target
+    let source_address = ipv6::source_address_for(
+        target,
+        &SendOptions {
+            interface_id: Some(interface_id),
+            ..SendOptions::default()
+        },
+    )?;
+
+    send_message(
+        interface_id,
+        target.solicited_node(),
+        Some(source_address),
+        MessageType::NeighborSolicitation,
+        &body,
+    )
+}
+
+// a probe comes from :: and carries no link-layer address so it can't pollute caches
+fn send_probe(interface_id: InterfaceId, target: Ipv6Address) -> ipv6::Result<()> {
+    let mut body = Vec::with_capacity(NEIGHBOR_MESSAGE_SIZE);
+    body.extend_from_slice(&[0; 4]);
+    body.extend_from_slice(&target.bytes);
+
+    send_message(
+        interface_id,
+        target.solicited_node(),
+        Some(Ipv6Address::UNSPECIFIED),
+        MessageType::NeighborSolicitation,
+        &body,
+    )
+}
+
+fn send_advertisement(
+    interface_id: InterfaceId,
+    destination: Ipv6Address,
+    target: Ipv6Address,
+    flags: AdvertisementFlags,
+) -> ipv6::Result<()> {
+    let mut body = Vec::with_capacity(NEIGHBOR_MESSAGE_SIZE + OPTION_UNIT);
+    body.extend_from_slice(&[flags.bits(), 0, 0, 0]);
+    body.extend_from_slice(&target.bytes);
+
+    NdpOptions {
+        target_link_layer_address: ethernet_address(interface_id),
+        ..NdpOptions::default()
+    }
+    .write_to(&mut body);
+
+    send_message(
+        interface_id,
+        destination,
+        Some(target),
+        MessageType::NeighborAdvertisement,
+        &body,
+    )
+}
+
+pub fn handle_message(interface_id: InterfaceId, header: &Ipv6Header, message: &Icmpv6Message) {
+    let result = if header.hop_limit != NDP_HOP_LIMIT {
+        Err(NdpError::InvalidHopLimit(header.hop_limit))
+    } else if message.code != 0 {
+        Err(NdpError::InvalidCode(message.code))
+    } else {
+        match message.message_type {
+            MessageType::NeighborSolicitation => {
+                handle_solicitation(interface_id, header, message.body)
+            }
+            MessageType::NeighborAdvertisement => handle_advertisement(interface_id, message.body),
+            MessageType::RouterAdvertisement => {
+                autoconfiguration::handle_router_advertisement(interface_id, header, message.body)
+            }
+            // solicitations are for routers and we don't follow redirects
+            _ => Ok(()),
+        }
+    };
+
+    if let Err(err) = result {
+        println!(
+            "Dropped {:?} on interface {}: {}",
+            message.message_type, interface_id, err
+        );
+    }
+}
+
+fn parse_neighbor_message(body: &[u8]) -> Result<(Ipv6Address, NdpOptions), NdpError> {
+    if body.len() < NEIGHBOR_MESSAGE_SIZE {
+        return Err(NdpError::TooShort(body.len()));
+    }
+
+    Ok((
+        Ipv6Address::from_slice(&body[4..NEIGHBOR_MESSAGE_SIZE]),
+        NdpOptions::parse(&body[NEIGHBOR_MESSAGE_SIZE..])?,
+    ))
+}
+
+fn handle_solicitation(
+    interface_id: InterfaceId,
+    header: &Ipv6Header,
+    body: &[u8],
+) -> Result<(), NdpError> {
+    let (target, options) = parse_neighbor_message(body)?;
+
+    if target.is_multicast() {
+        return Ok(());
+    }
+
+    // someone probing for the address we are probing for, neither of us gets it
+    if is_tentative(interface_id, target) {
+        if header.source_address.is_unspecified() {
+            give_up_address(interface_id, target);
+        }
+        return Ok(());
+    }
+
+    if !has_ipv6_address(interface_id, target) {
+        return Ok(());
+    }
+
+    let result = if header.source_address.is_unspecified() {
+        // a probe for our address, everyone hears that it is taken
+        send_advertisement(
+            interface_id,
+            Ipv6Address::ALL_NODES,
+            target,
+            AdvertisementFlags::OVERRIDE,
+        )
+    } else {
+        if let Some(ethernet_address) = options.source_link_layer_address {
+            learn_neighbor(interface_id, header.source_address, ethernet_address, true);
+        }
+
+        send_advertisement(
+            interface_id,
+            header.source_address,
+            target,
+            AdvertisementFlags::SOLICITED | AdvertisementFlags::OVERRIDE,
+        )
+    };
+
+    if let Err(err) = result {
+        println!("Failed to send neighbor advertisement: {}", err);
+    }
+
+    Ok(())
+}
+
+fn handle_advertisement(interface_id: InterfaceId, body: &[u8]) -> Result<(), NdpError> {
+    let (target, options) = parse_neighbor_message(body)?;
+
+    if is_tentative(interface_id, target) {
+        give_up_address(interface_id, target);
+        return Ok(());
+    }
+
+    if has_ipv6_address(interface_id, target) {
+        println!(
+            "Address {} on interface {} is also claimed by {:?}",
+            target, interface_id, options.target_link_layer_address
+        );
+        return Ok(());
+    }
+
+    // only neighbors we asked about get an entry
+    if let Some(ethernet_address) = options.target_link_layer_address {
+        learn_neighbor(interface_id, target, ethernet_address, false);
+    }
+
+    Ok(())
+}
+
+fn give_up_address(interface_id: InterfaceId, address: Ipv6Address) {
+    ADDRESS_PROBES
+        .lock()
+        .retain(|probe| !(probe.interface_id == interface_id && probe.cidr.address == address));
+
+    // rfc 4862 5.4.5, without its link-local address the interface can't do IPv6 at all
+    if address.is_link_local() {
+        println!(
+            "Link-local address {} on interface {} is already in use, IPv6 stays off",
+            address, interface_id
+        );
+    } else {
+        println!(
+            "Address {} on interface {} is already in use, giving up on it",
+            address, interface_id
+        );
+    }
+}
+
+// drives duplicate address detection, router solicitation and the neighbor cache timers,
+// called from the network stack poll loop
+pub fn poll_timers() {
+    let mut actions = Vec::new();
+
+    {
+        let mut probes = ADDRESS_PROBES.lock();
+
+        probes.retain_mut(|probe| {
+            if probe.probes_sent > 0 && !pit::elapsed(probe.last_probe_at, PROBE_INTERVAL) {
+                return true;
+            }
+
+            if probe.probes_sent == DUPLICATE_ADDRESS_PROBES {
+                actions.push(ProbeAction::Assign(probe.interface_id, probe.cidr));
+                return false;
+            }
+
+            probe.probes_sent += 1;
+            probe.last_probe_at = pit::ticks();
+            actions.push(ProbeAction::Probe(probe.interface_id, probe.cidr.address));
+            true
+        });
+    }
+
+    // solicitations go out after the probe table is unlocked, sending takes the interface locks
+    for action in actions {
+        match action {
+            ProbeAction::Probe(interface_id, address) => {
+                if let Err(err) = send_probe(interface_id, address) {
+                    println!("Failed to send neighbor solicitation: {}", err);
+                }
+            }
+            ProbeAction::Assign(interface_id, cidr) => {
+                add_ipv6_address(interface_id, cidr);
+                println!("Interface {} is now {}", interface_id, cidr);
+
+                if cidr.address.is_link_local() {
+                    autoconfiguration::solicit_routers(interface_id);
+                }
+            }
+        }
+    }
+
+    for (interface_id, address) in cache::expire() {
+        if let Err(err) = send_solicitation(interface_id, address) {
+            println!("Failed to send neighbor solicitation: {}", err);
+        }
+    }
+
+    autoconfiguration::poll_timers();
+}
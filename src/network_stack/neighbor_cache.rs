@@ -0,0 +1,166 @@
+use alloc::{collections::BTreeMap, collections::VecDeque, vec::Vec};
+use core::fmt::Display;
+
+use crate::{println, x86::pit};
+
+use super::{ethernet::EthernetAddress, interface::InterfaceId};
+
+// packets waiting on a single neighbor, the oldest is dropped when it fills up
+const MAX_PENDING_PACKETS: usize = 8;
+
+enum NeighborState {
+    Incomplete {
+        requests_sent: u8,
+        last_request_at: usize,
+    },
+    Reachable {
+        ethernet_address: EthernetAddress,
+        updated_at: usize,
+    },
+}
+
+struct Neighbor<P> {
+    state: NeighborState,
+    pending: VecDeque<P>,
+}
+
+// the ethernet addresses of the neighbors of one protocol, ARP for IPv4 and NDP for IPv6.
+// `A` is the protocol address and `P` a packet that waits for the neighbor to answer
+pub struct NeighborCache<A, P> {
+    neighbors: BTreeMap<(InterfaceId, A), Neighbor<P>>,
+    // what the neighbor is asked with, for the message when it never answers
+    request_name: &'static str,
+    reachable_timeout: usize,
+    request_interval: usize,
+    max_requests: u8,
+}
+
+impl<A: Ord + Copy + Display, P> NeighborCache<A, P> {
+    pub const fn new(
+        request_name: &'static str,
+        reachable_timeout: usize,
+        request_interval: usize,
+        max_requests: u8,
+    ) -> Self {
+        Self {
+            neighbors: BTreeMap::new(),
+            request_name,
+            reachable_timeout,
+            request_interval,
+            max_requests,
+        }
+    }
+
+    pub fn lookup(&self, interface_id: InterfaceId, address: A) -> Option<EthernetAddress> {
+        match self.neighbors.get(&(interface_id, address))?.state {
+            NeighborState::Reachable {
+                ethernet_address, ..
+            } => Some(ethernet_address),
+            NeighborState::Incomplete { .. } => None,
+        }
+    }
+
+    // returns true when this is the first packet for the neighbor and a request has to go out
+    pub fn queue_packet(&mut self, interface_id: InterfaceId, address: A, packet: P) -> bool {
+        let mut created = false;
+        let neighbor = self
+            .neighbors
+            .entry((interface_id, address))
+            .or_insert_with(|| {
+                created = true;
+                Neighbor {
+                    state: NeighborState::Incomplete {
+                        requests_sent: 1,
+                        last_request_at: pit::ticks(),
+                    },
+                    pending: VecDeque::new(),
+                }
+            });
+
+        if neighbor.pending.len() == MAX_PENDING_PACKETS {
+            neighbor.pending.pop_front();
+        }
+        neighbor.pending.push_back(packet);
+
+        created
+    }
+
+    // records where the address lives and hands back the packets that were waiting on it,
+    // unknown addresses are only added when `create` is set
+    pub fn update(
+        &mut self,
+        interface_id: InterfaceId,
+        address: A,
+        ethernet_address: EthernetAddress,
+        create: bool,
+    ) -> Vec<P> {
+        let state = NeighborState::Reachable {
+            ethernet_address,
+            updated_at: pit::ticks(),
+        };
+
+        match self.neighbors.get_mut(&(interface_id, address)) {
+            Some(neighbor) => {
+                neighbor.state = state;
+                neighbor.pending.drain(..).collect()
+            }
+            None => {
+                if create {
+                    self.neighbors.insert(
+                        (interface_id, address),
+                        Neighbor {
+                            state,
+                            pending: VecDeque::new(),
+                        },
+                    );
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    // ages the cache, returns the addresses that need another request
+    pub fn expire(&mut self) -> Vec<(InterfaceId, A)> {
+        let Self {
+            neighbors,
+            request_name,
+            reachable_timeout,
+            request_interval,
+            max_requests,
+        } = self;
+        let mut retries = Vec::new();
+
+        neighbors.retain(
+            |&(interface_id, address), neighbor| match &mut neighbor.state {
+                NeighborState::Reachable { updated_at, .. } => {
+                    !pit::elapsed(*updated_at, *reachable_timeout)
+                }
+                NeighborState::Incomplete {
+                    requests_sent,
+                    last_request_at,
+                } => {
+                    if !pit::elapsed(*last_request_at, *request_interval) {
+                        return true;
+                    }
+
+                    if *requests_sent == *max_requests {
+                        println!(
+                            "{} did not answer {}, dropping {} packets",
+                            address,
+                            request_name,
+                            neighbor.pending.len()
+                        );
+                        return false;
+                    }
+
+                    *requests_sent += 1;
+                    *last_request_at = pit::ticks();
+                    retries.push((interface_id, address));
+                    true
+                }
+            },
+        );
+
+        retries
+    }
+}
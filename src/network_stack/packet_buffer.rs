@@ -0,0 +1,80 @@
+use core::ops::{Deref, DerefMut};
+
+use alloc::vec::Vec;
+
+// room kept in front of the payload for every header down to ethernet,
+// enough for ethernet with a vlan tag, ipv6 and a tcp header with all of its options
+pub const DEFAULT_HEADROOM: usize = 128;
+
+// a packet that is built back to front, every layer prepends its header into the headroom
+// so the payload is written once and not copied again on its way down the stack
+pub struct PacketBuffer {
+    buffer: Vec<u8>,
+    start: usize,
+}
+
+impl PacketBuffer {
+    pub fn new(headroom: usize, capacity: usize) -> Self {
+        let mut buffer = Vec::with_capacity(headroom + capacity);
+        buffer.resize(headroom, 0);
+
+        Self {
+            buffer,
+            start: headroom,
+        }
+    }
+
+    pub fn from_payload(payload: &[u8]) -> Self {
+        let mut packet = Self::new(DEFAULT_HEADROOM, payload.len());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    // grows the packet at the front and returns the new bytes for the caller to fill in
+    pub fn prepend(&mut self, length: usize) -> &mut [u8] {
+        if length > self.start {
+            self.grow_headroom(length - self.start);
+        }
+
+        self.start -= length;
+        &mut self.buffer[self.start..self.start + length]
+    }
+
+    pub fn prepend_slice(&mut self, header: &[u8]) {
+        self.prepend(header.len()).copy_from_slice(header);
+    }
+
+    // only happens when a caller picked a headroom too small for the layers below it
+    fn grow_headroom(&mut self, extra: usize) {
+        let mut buffer = Vec::with_capacity(self.buffer.capacity() + extra);
+        buffer.resize(extra, 0);
+        buffer.extend_from_slice(&self.buffer);
+
+        self.buffer = buffer;
+        self.start += extra;
+    }
+
+    // the drivers take a plain vec, this moves the packet to the front of its allocation once
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.buffer.drain(..self.start);
+        self.buffer
+    }
+}
+
+impl Deref for PacketBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[self.start..]
+    }
+}
+
+impl DerefMut for PacketBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer[self.start..]
+    }
+}
@@ -0,0 +1,33 @@
+// the dynamic port range from rfc 6335, UDP and TCP each hand out their own
+pub const EPHEMERAL_PORT_START: u16 = 49152;
+pub const EPHEMERAL_PORT_END: u16 = 65535;
+
+pub struct EphemeralPorts {
+    next: u16,
+}
+
+impl EphemeralPorts {
+    pub const fn new() -> Self {
+        Self {
+            next: EPHEMERAL_PORT_START,
+        }
+    }
+
+    // walks the range round robin so a port that was just released isn't reused right away
+    pub fn allocate(&mut self, in_use: impl Fn(u16) -> bool) -> Option<u16> {
+        for _ in EPHEMERAL_PORT_START..=EPHEMERAL_PORT_END {
+            let port = self.next;
+            self.next = if port == EPHEMERAL_PORT_END {
+                EPHEMERAL_PORT_START
+            } else {
+                port + 1
+            };
+
+            if !in_use(port) {
+                return Some(port);
+            }
+        }
+
+        None
+    }
+}
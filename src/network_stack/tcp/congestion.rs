@@ -0,0 +1,99 @@
+// https://datatracker.ietf.org/doc/html/rfc5681
+// https://datatracker.ietf.org/doc/html/rfc6582
+
+use alloc::boxed::Box;
+
+// the connection does the loss detection and retransmissions, the algorithm only sizes the window.
+// all sizes are in bytes
+pub trait CongestionControl {
+    // how much may be in flight
+    fn window(&self) -> usize;
+    // new data was acknowledged outside of fast recovery
+    fn on_ack(&mut self, acknowledged: usize);
+    // three duplicate acks, a segment is lost and fast recovery begins
+    fn on_fast_retransmit(&mut self, in_flight: usize);
+    // another segment left the network while we are recovering
+    fn on_recovery_duplicate_ack(&mut self);
+    // the retransmission got through but the ack shows another hole
+    fn on_partial_ack(&mut self, acknowledged: usize);
+    // everything outstanding when the loss was detected is acknowledged
+    fn on_recovery_exit(&mut self);
+    fn on_retransmission_timeout(&mut self, in_flight: usize);
+}
+
+// every connection gets its own instance, another algorithm only has to be picked here
+pub fn new_congestion_control(maximum_segment_size: usize) -> Box<dyn CongestionControl> {
+    Box::new(NewReno::new(maximum_segment_size))
+}
+
+pub struct NewReno {
+    maximum_segment_size: usize,
+    congestion_window: usize,
+    slow_start_threshold: usize,
+}
+
+impl NewReno {
+    pub fn new(maximum_segment_size: usize) -> Self {
+        // rfc 5681 3.1, the initial window
+        let congestion_window = if maximum_segment_size > 2190 {
+            2 * maximum_segment_size
+        } else if maximum_segment_size > 1095 {
+            3 * maximum_segment_size
+        } else {
+            4 * maximum_segment_size
+        };
+
+        Self {
+            maximum_segment_size,
+            congestion_window,
+            slow_start_threshold: usize::MAX,
+        }
+    }
+
+    // rfc 5681 equation 4
+    fn reduced_threshold(&self, in_flight: usize) -> usize {
+        (in_flight / 2).max(2 * self.maximum_segment_size)
+    }
+}
+
+impl CongestionControl for NewReno {
+    fn window(&self) -> usize {
+        self.congestion_window
+    }
+
+    fn on_ack(&mut self, acknowledged: usize) {
+        let increase = if self.congestion_window < self.slow_start_threshold {
+            acknowledged.min(self.maximum_segment_size)
+        } else {
+            // about one segment per round trip
+            (self.maximum_segment_size * self.maximum_segment_size / self.congestion_window).max(1)
+        };
+
+        self.congestion_window += increase;
+    }
+
+    fn on_fast_retransmit(&mut self, in_flight: usize) {
+        self.slow_start_threshold = self.reduced_threshold(in_flight);
+        // the three duplicate acks stand for segments that already left the network
+        self.congestion_window = self.slow_start_threshold + 3 * self.maximum_segment_size;
+    }
+
+    fn on_recovery_duplicate_ack(&mut self) {
+        self.congestion_window += self.maximum_segment_size;
+    }
+
+    fn on_partial_ack(&mut self, acknowledged: usize) {
+        self.congestion_window = (self.congestion_window.saturating_sub(acknowledged)
+            + self.maximum_segment_size)
+            .max(self.maximum_segment_size);
+    }
+
+    fn on_recovery_exit(&mut self) {
+        self.congestion_window = self.slow_start_threshold;
+    }
+
+    fn on_retransmission_timeout(&mut self, in_flight: usize) {
+        self.slow_start_threshold = self.reduced_threshold(in_flight);
+        self.congestion_window = self.maximum_segment_size;
+    }
+}
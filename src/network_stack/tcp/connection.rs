@@ -0,0 +1,1231 @@
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+
+use crate::{
+    network_stack::{
+        ip::{IpAddress, SocketAddress},
+        packet_buffer::PacketBuffer,
+    },
+    x86::{
+        pit::{self, TICKS_PER_SECOND},
+        read_timestamp_counter,
+    },
+};
+
+use super::{
+    congestion::{new_congestion_control, CongestionControl},
+    segment::{TcpFlags, TcpHeader, TcpOptions, TIMESTAMPS_OPTION_SIZE},
+    Result, TcpError,
+};
+
+pub const SEND_BUFFER_SIZE: usize = 256 * 1024;
+pub const RECEIVE_BUFFER_SIZE: usize = 256 * 1024;
+// rfc 7323 2, shifted by this the receive buffer fits the 16 bit window field
+const RECEIVE_WINDOW_SHIFT: u8 = 2;
+const MAX_WINDOW_SHIFT: u8 = 14;
+// rfc 1122 4.2.2.6, assumed when the peer doesn't send the option
+pub const DEFAULT_MAXIMUM_SEGMENT_SIZE: usize = 536;
+// linux's floor for the peer's option, below it segments would be mostly headers or carry nothing
+const MIN_MAXIMUM_SEGMENT_SIZE: usize = 64;
+// rfc 6298, linux goes lower than the 1 second minimum and so do we
+const INITIAL_RETRANSMISSION_TIMEOUT: usize = TICKS_PER_SECOND;
+const MIN_RETRANSMISSION_TIMEOUT: usize = TICKS_PER_SECOND / 5;
+const MAX_RETRANSMISSION_TIMEOUT: usize = 60 * TICKS_PER_SECOND;
+const MAX_RETRANSMISSIONS: u8 = 8;
+// twice the maximum segment lifetime
+const TIME_WAIT_TIMEOUT: usize = 60 * TICKS_PER_SECOND;
+const MAX_OUT_OF_ORDER_SEGMENTS: usize = 32;
+const DUPLICATE_ACK_THRESHOLD: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+    Closed,
+}
+
+// why a connection ended without a graceful close
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    Refused,
+    Reset,
+    TimedOut,
+}
+
+impl From<CloseReason> for TcpError {
+    fn from(value: CloseReason) -> Self {
+        match value {
+            CloseReason::Refused => Self::ConnectionRefused,
+            CloseReason::Reset => Self::ConnectionReset,
+            CloseReason::TimedOut => Self::TimedOut,
+        }
+    }
+}
+
+// segments are collected while the connection table is locked and sent once it is released
+pub struct OutgoingSegment {
+    pub source: IpAddress,
+    pub destination: IpAddress,
+    pub packet: PacketBuffer,
+}
+
+impl OutgoingSegment {
+    fn new(local: SocketAddress, remote: SocketAddress, header: TcpHeader, payload: &[u8]) -> Self {
+        let mut packet = PacketBuffer::from_payload(payload);
+        header.prepend_to(&mut packet, local.address, remote.address);
+
+        Self {
+            source: local.address,
+            destination: remote.address,
+            packet,
+        }
+    }
+}
+
+// sequence numbers wrap, so they are compared by their distance
+pub fn sequence_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+pub fn sequence_le(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) <= 0
+}
+
+pub fn sequence_gt(a: u32, b: u32) -> bool {
+    sequence_lt(b, a)
+}
+
+pub fn sequence_ge(a: u32, b: u32) -> bool {
+    sequence_le(b, a)
+}
+
+// sorts the ranges by their distance from `base` and joins the ones that touch
+fn merge_ranges(ranges: &mut Vec<(u32, u32)>, base: u32) {
+    ranges.sort_unstable_by_key(|&(left, _)| left.wrapping_sub(base));
+
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+    for &(left, right) in ranges.iter() {
+        match merged.last_mut() {
+            Some(last) if sequence_le(left, last.1) => {
+                if sequence_gt(right, last.1) {
+                    last.1 = right;
+                }
+            }
+            _ => merged.push((left, right)),
+        }
+    }
+
+    *ranges = merged;
+}
+
+// our clock for the timestamps option, a tick is within the 1 ms to 1 s rfc 7323 asks for
+fn timestamp_now() -> u32 {
+    pit::ticks() as u32
+}
+
+// rfc 6528 adds a keyed hash of the addresses to the clock, the clock alone keeps old segments apart
+fn initial_sequence_number() -> u32 {
+    (read_timestamp_counter() >> 8) as u32
+}
+
+// the answer to a segment that belongs to no connection, rfc 9293 3.10.7.1
+pub fn reset_for(
+    local: SocketAddress,
+    remote: SocketAddress,
+    header: &TcpHeader,
+    payload_length: usize,
+) -> OutgoingSegment {
+    let (sequence_number, acknowledgment_number, flags) = if header.flags.contains(TcpFlags::ACK) {
+        (header.acknowledgment_number, 0, TcpFlags::RST)
+    } else {
+        (
+            0,
+            header
+                .sequence_number
+                .wrapping_add(header.sequence_length(payload_length)),
+            TcpFlags::RST | TcpFlags::ACK,
+        )
+    };
+
+    let reset = TcpHeader {
+        source_port: local.port,
+        destination_port: remote.port,
+        sequence_number,
+        acknowledgment_number,
+        flags,
+        window: 0,
+        urgent_pointer: 0,
+        options: TcpOptions::default(),
+    };
+
+    OutgoingSegment::new(local, remote, reset, &[])
+}
+
+pub struct Connection {
+    pub state: State,
+    pub local: SocketAddress,
+    pub remote: SocketAddress,
+    // the listening port that accepted the connection, cleared once it is queued for accept
+    pub listener_port: Option<u16>,
+    // whether a TcpStream holds the connection, unowned connections are dropped once closed
+    pub owned: bool,
+    pub close_reason: Option<CloseReason>,
+
+    initial_send_sequence: u32,
+    send_unacknowledged: u32,
+    send_next: u32,
+    // the highest sequence number sent, send_next falls back behind it on a retransmission
+    send_maximum: u32,
+    send_window: u32,
+    // the segment that last updated the send window, older ones must not change it
+    send_window_sequence: u32,
+    send_window_acknowledgment: u32,
+    // data from send_unacknowledged on, sent or not
+    send_buffer: VecDeque<u8>,
+    // the user closed its side, a FIN follows the last byte of the buffer
+    fin_queued: bool,
+    // where our FIN went out, cleared when it has to be sent again
+    fin_sequence: Option<u32>,
+    local_maximum_segment_size: u16,
+    // the largest segment the peer takes
+    maximum_segment_size: usize,
+
+    // the extensions are offered in our SYN and stay on if the peer's SYN carries them too
+    window_scaling: bool,
+    // applied to the windows the peer advertises
+    send_window_shift: u8,
+    timestamps: bool,
+    // the peer's clock from the last in order segment, echoed back in ours
+    recent_timestamp: u32,
+    last_acknowledgment_sent: u32,
+    selective_acknowledgments: bool,
+
+    receive_next: u32,
+    receive_buffer: VecDeque<u8>,
+    // segments past a hole, waiting for the missing data
+    out_of_order: Vec<(u32, Vec<u8>)>,
+    // the start of the out of order segment that came in last, its block is reported first
+    latest_out_of_order: Option<u32>,
+    // the peer's FIN when it came in ahead of missing data
+    remote_fin_sequence: Option<u32>,
+    fin_received: bool,
+    // receive_next plus the window we last advertised
+    advertised_window_end: u32,
+
+    smoothed_round_trip_time: Option<usize>,
+    round_trip_time_variance: usize,
+    retransmission_timeout: usize,
+    // the sequence number that acknowledges the timed segment and when it went out
+    round_trip_time_sample: Option<(u32, usize)>,
+    // None while nothing is outstanding
+    retransmit_started_at: Option<usize>,
+    retransmissions: u8,
+    time_wait_started_at: usize,
+
+    congestion_control: Box<dyn CongestionControl>,
+    duplicate_acks: u8,
+    // rfc 6582, set while in fast recovery to what was outstanding when the loss was detected
+    recovery_point: Option<u32>,
+    // the end of what fast recovery retransmitted so far
+    highest_retransmitted: u32,
+    // ranges above send_unacknowledged that the peer reported with SACK, sorted and merged
+    sacked: Vec<(u32, u32)>,
+}
+
+impl Connection {
+    fn new(
+        local: SocketAddress,
+        remote: SocketAddress,
+        state: State,
+        local_maximum_segment_size: u16,
+    ) -> Self {
+        let initial_send_sequence = initial_sequence_number();
+
+        Self {
+            state,
+            local,
+            remote,
+            listener_port: None,
+            owned: false,
+            close_reason: None,
+            initial_send_sequence,
+            send_unacknowledged: initial_send_sequence,
+            send_next: initial_send_sequence,
+            send_maximum: initial_send_sequence,
+            send_window: 0,
+            send_window_sequence: 0,
+            send_window_acknowledgment: 0,
+            send_buffer: VecDeque::new(),
+            fin_queued: false,
+            fin_sequence: None,
+            local_maximum_segment_size,
+            maximum_segment_size: DEFAULT_MAXIMUM_SEGMENT_SIZE,
+            window_scaling: true,
+            send_window_shift: 0,
+            timestamps: true,
+            recent_timestamp: 0,
+            last_acknowledgment_sent: 0,
+            selective_acknowledgments: true,
+            receive_next: 0,
+            receive_buffer: VecDeque::new(),
+            out_of_order: Vec::new(),
+            latest_out_of_order: None,
+            remote_fin_sequence: None,
+            fin_received: false,
+            advertised_window_end: 0,
+            smoothed_round_trip_time: None,
+            round_trip_time_variance: 0,
+            retransmission_timeout: INITIAL_RETRANSMISSION_TIMEOUT,
+            round_trip_time_sample: None,
+            retransmit_started_at: None,
+            retransmissions: 0,
+            time_wait_started_at: 0,
+            congestion_control: new_congestion_control(DEFAULT_MAXIMUM_SEGMENT_SIZE),
+            duplicate_acks: 0,
+            recovery_point: None,
+            highest_retransmitted: initial_send_sequence,
+            sacked: Vec::new(),
+        }
+    }
+
+    // active open, the SYN goes out right away
+    pub fn connect(
+        local: SocketAddress,
+        remote: SocketAddress,
+        local_maximum_segment_size: u16,
+        outbox: &mut Vec<OutgoingSegment>,
+    ) -> Self {
+        let mut connection = Self::new(local, remote, State::SynSent, local_maximum_segment_size);
+        connection.send_syn(outbox);
+        connection
+    }
+
+    // passive open from a SYN that hit a listener, answers with a SYN-ACK
+    pub fn accept(
+        local: SocketAddress,
+        remote: SocketAddress,
+        syn: &TcpHeader,
+        local_maximum_segment_size: u16,
+        outbox: &mut Vec<OutgoingSegment>,
+    ) -> Self {
+        let mut connection = Self::new(
+            local,
+            remote,
+            State::SynReceived,
+            local_maximum_segment_size,
+        );
+
+        connection.listener_port = Some(local.port);
+        connection.receive_next = syn.sequence_number.wrapping_add(1);
+        connection.apply_syn_options(syn);
+        connection.update_send_window(syn);
+        connection.send_syn(outbox);
+        connection
+    }
+
+    fn apply_syn_options(&mut self, syn: &TcpHeader) {
+        let options = &syn.options;
+
+        // never larger than what our own interface takes either
+        if let Some(maximum_segment_size) = options.maximum_segment_size {
+            self.maximum_segment_size = (maximum_segment_size as usize)
+                .max(MIN_MAXIMUM_SEGMENT_SIZE)
+                .min(self.local_maximum_segment_size as usize);
+        }
+
+        // we always offer every extension, so the peer's SYN decides
+        self.window_scaling = options.window_scale.is_some();
+        self.send_window_shift = options.window_scale.unwrap_or(0).min(MAX_WINDOW_SHIFT);
+        self.selective_acknowledgments = options.sack_permitted;
+        self.timestamps = options.timestamps.is_some();
+        if let Some((value, _)) = options.timestamps {
+            self.recent_timestamp = value;
+        }
+
+        self.congestion_control = new_congestion_control(self.segment_size());
+    }
+
+    // the data that fits in a segment next to the options every segment carries
+    fn segment_size(&self) -> usize {
+        if self.timestamps {
+            self.maximum_segment_size
+                .saturating_sub(TIMESTAMPS_OPTION_SIZE)
+                .max(1)
+        } else {
+            self.maximum_segment_size
+        }
+    }
+
+    // the window in a SYN is never scaled
+    fn scaled_window(&self, header: &TcpHeader) -> u32 {
+        if header.flags.contains(TcpFlags::SYN) {
+            header.window as u32
+        } else {
+            (header.window as u32) << self.send_window_shift
+        }
+    }
+
+    fn update_send_window(&mut self, header: &TcpHeader) {
+        self.send_window = self.scaled_window(header);
+        self.send_window_sequence = header.sequence_number;
+        self.send_window_acknowledgment = header.acknowledgment_number;
+    }
+
+    fn receive_window_shift(&self) -> u8 {
+        if self.window_scaling {
+            RECEIVE_WINDOW_SHIFT
+        } else {
+            0
+        }
+    }
+
+    fn receive_window(&self) -> usize {
+        (RECEIVE_BUFFER_SIZE - self.receive_buffer.len())
+            .min((u16::MAX as usize) << self.receive_window_shift())
+    }
+
+    fn segment(
+        &mut self,
+        sequence_number: u32,
+        flags: TcpFlags,
+        payload: &[u8],
+        mut options: TcpOptions,
+    ) -> OutgoingSegment {
+        let window_shift = if flags.contains(TcpFlags::SYN) {
+            0
+        } else {
+            self.receive_window_shift()
+        };
+        let window = (self.receive_window() >> window_shift).min(u16::MAX as usize);
+        self.advertised_window_end = self
+            .receive_next
+            .wrapping_add((window << window_shift) as u32);
+
+        if flags.contains(TcpFlags::ACK) {
+            self.last_acknowledgment_sent = self.receive_next;
+
+            if self.selective_acknowledgments {
+                options.sack_blocks = self.sack_blocks();
+            }
+        }
+
+        if self.timestamps && !flags.contains(TcpFlags::RST) {
+            options.timestamps = Some((timestamp_now(), self.recent_timestamp));
+        }
+
+        let header = TcpHeader {
+            source_port: self.local.port,
+            destination_port: self.remote.port,
+            sequence_number,
+            acknowledgment_number: if flags.contains(TcpFlags::ACK) {
+                self.receive_next
+            } else {
+                0
+            },
+            flags,
+            window: window as u16,
+            urgent_pointer: 0,
+            options,
+        };
+
+        OutgoingSegment::new(self.local, self.remote, header, payload)
+    }
+
+    fn start_retransmit_timer(&mut self) {
+        if self.retransmit_started_at.is_none() {
+            self.retransmit_started_at = Some(pit::ticks());
+        }
+    }
+
+    // our SYN, or SYN-ACK when the peer opened first
+    fn send_syn(&mut self, outbox: &mut Vec<OutgoingSegment>) {
+        let flags = match self.state {
+            State::SynReceived => TcpFlags::SYN | TcpFlags::ACK,
+            _ => TcpFlags::SYN,
+        };
+
+        let options = TcpOptions {
+            maximum_segment_size: Some(self.local_maximum_segment_size),
+            window_scale: self.window_scaling.then_some(RECEIVE_WINDOW_SHIFT),
+            sack_permitted: self.selective_acknowledgments,
+            ..Default::default()
+        };
+
+        let segment = self.segment(self.initial_send_sequence, flags, &[], options);
+        outbox.push(segment);
+
+        self.send_next = self.initial_send_sequence.wrapping_add(1);
+        self.send_maximum = self.send_next;
+
+        if self.retransmissions == 0 {
+            self.round_trip_time_sample = Some((self.send_next, pit::ticks()));
+        }
+        self.start_retransmit_timer();
+    }
+
+    fn send_ack(&mut self, outbox: &mut Vec<OutgoingSegment>) {
+        let segment = self.segment(self.send_next, TcpFlags::ACK, &[], TcpOptions::default());
+        outbox.push(segment);
+    }
+
+    // tears the connection down without the closing handshake
+    pub fn abort(&mut self, reason: Option<CloseReason>, outbox: &mut Vec<OutgoingSegment>) {
+        if !matches!(self.state, State::SynSent | State::TimeWait | State::Closed) {
+            let segment = self.segment(self.send_next, TcpFlags::RST, &[], TcpOptions::default());
+            outbox.push(segment);
+        }
+
+        self.close_reason = reason;
+        self.state = State::Closed;
+    }
+
+    // rfc 9293 3.10.7.4, the checks for segments that arrive on a synchronized connection
+    pub fn handle_segment(
+        &mut self,
+        header: &TcpHeader,
+        payload: &[u8],
+        outbox: &mut Vec<OutgoingSegment>,
+    ) {
+        match self.state {
+            State::SynSent => return self.handle_syn_sent(header, outbox),
+            State::Closed => return,
+            _ => (),
+        }
+
+        let sequence_length = header.sequence_length(payload.len());
+        let timestamps = header.options.timestamps.filter(|_| self.timestamps);
+
+        // rfc 7323 5, an older clock than we saw before means a segment from a previous wrap
+        if let Some((value, _)) = timestamps {
+            if !header.flags.contains(TcpFlags::RST) && sequence_lt(value, self.recent_timestamp) {
+                self.send_ack(outbox);
+                return;
+            }
+        }
+
+        if !self.is_acceptable(header.sequence_number, sequence_length) {
+            if !header.flags.contains(TcpFlags::RST) {
+                self.send_ack(outbox);
+            }
+            return;
+        }
+
+        if let Some((value, _)) = timestamps {
+            if sequence_le(header.sequence_number, self.last_acknowledgment_sent) {
+                self.recent_timestamp = value;
+            }
+        }
+
+        if header.flags.contains(TcpFlags::RST) {
+            // rfc 5961, only a reset right at receive_next is believed, the rest get a challenge ack
+            if header.sequence_number != self.receive_next {
+                self.send_ack(outbox);
+                return;
+            }
+
+            self.close_reason = match self.state {
+                // the listener goes on as if the SYN never came
+                State::SynReceived if self.listener_port.is_some() => None,
+                State::SynReceived => Some(CloseReason::Refused),
+                State::Closing | State::LastAck | State::TimeWait => None,
+                _ => Some(CloseReason::Reset),
+            };
+            self.state = State::Closed;
+            return;
+        }
+
+        // rfc 5961 again, a SYN on an open connection is answered with a challenge ack
+        if header.flags.contains(TcpFlags::SYN) {
+            self.send_ack(outbox);
+            return;
+        }
+
+        if !header.flags.contains(TcpFlags::ACK) {
+            return;
+        }
+
+        let acknowledgment_number = header.acknowledgment_number;
+
+        if self.state == State::SynReceived {
+            if sequence_gt(acknowledgment_number, self.send_unacknowledged)
+                && sequence_le(acknowledgment_number, self.send_maximum)
+            {
+                self.state = State::Established;
+            } else {
+                outbox.push(reset_for(self.local, self.remote, header, payload.len()));
+                return;
+            }
+        }
+
+        if sequence_gt(acknowledgment_number, self.send_maximum) {
+            self.send_ack(outbox);
+            return;
+        }
+
+        // checked before the window update as a duplicate ack must not change the window
+        let is_duplicate_ack = self.is_duplicate_ack(header, payload);
+
+        if sequence_gt(acknowledgment_number, self.send_unacknowledged) {
+            let acknowledged =
+                acknowledgment_number.wrapping_sub(self.send_unacknowledged) as usize;
+
+            self.acknowledge(acknowledgment_number, timestamps);
+            self.record_sack_blocks(&header.options.sack_blocks);
+            self.on_new_ack(acknowledged, outbox);
+        } else if is_duplicate_ack {
+            self.record_sack_blocks(&header.options.sack_blocks);
+            self.on_duplicate_ack(outbox);
+        }
+
+        if sequence_ge(acknowledgment_number, self.send_unacknowledged)
+            && (sequence_lt(self.send_window_sequence, header.sequence_number)
+                || (self.send_window_sequence == header.sequence_number
+                    && sequence_le(self.send_window_acknowledgment, acknowledgment_number)))
+        {
+            self.update_send_window(header);
+
+            // the peer is alive and answering our window probes, that doesn't count as a lost segment
+            if self.send_window == 0 {
+                self.retransmissions = 0;
+            }
+        }
+
+        let fin_acknowledged = self
+            .fin_sequence
+            .is_some_and(|fin_sequence| sequence_gt(self.send_unacknowledged, fin_sequence));
+
+        match self.state {
+            State::FinWait1 if fin_acknowledged => self.state = State::FinWait2,
+            State::Closing if fin_acknowledged => self.enter_time_wait(),
+            State::LastAck if fin_acknowledged => {
+                self.state = State::Closed;
+                return;
+            }
+            _ => (),
+        }
+
+        if !payload.is_empty()
+            && matches!(
+                self.state,
+                State::Established | State::FinWait1 | State::FinWait2
+            )
+        {
+            self.receive_data(header.sequence_number, payload);
+        }
+
+        if header.flags.contains(TcpFlags::FIN) && self.remote_fin_sequence.is_none() {
+            self.remote_fin_sequence =
+                Some(header.sequence_number.wrapping_add(payload.len() as u32));
+        }
+        self.receive_fin();
+
+        // anything that took up sequence space gets acknowledged, data we send carries the ack too
+        if !self.transmit(outbox) && sequence_length > 0 {
+            self.send_ack(outbox);
+        }
+    }
+
+    fn handle_syn_sent(&mut self, header: &TcpHeader, outbox: &mut Vec<OutgoingSegment>) {
+        let has_ack = header.flags.contains(TcpFlags::ACK);
+
+        if has_ack
+            && !(sequence_gt(header.acknowledgment_number, self.initial_send_sequence)
+                && sequence_le(header.acknowledgment_number, self.send_maximum))
+        {
+            if !header.flags.contains(TcpFlags::RST) {
+                outbox.push(reset_for(self.local, self.remote, header, 0));
+            }
+            return;
+        }
+
+        if header.flags.contains(TcpFlags::RST) {
+            if has_ack {
+                self.close_reason = Some(CloseReason::Refused);
+                self.state = State::Closed;
+            }
+            return;
+        }
+
+        if !header.flags.contains(TcpFlags::SYN) {
+            return;
+        }
+
+        self.receive_next = header.sequence_number.wrapping_add(1);
+        self.apply_syn_options(header);
+        self.update_send_window(header);
+
+        if has_ack {
+            self.acknowledge(header.acknowledgment_number, header.options.timestamps);
+            self.state = State::Established;
+
+            if !self.transmit(outbox) {
+                self.send_ack(outbox);
+            }
+        } else {
+            // both sides opened at the same time
+            self.state = State::SynReceived;
+            self.send_syn(outbox);
+        }
+    }
+
+    // rfc 9293 3.10.7.4, the segment has to overlap the receive window
+    fn is_acceptable(&self, sequence_number: u32, sequence_length: u32) -> bool {
+        let window = self.receive_window() as u32;
+        let in_window = |sequence| {
+            sequence_le(self.receive_next, sequence)
+                && sequence_lt(sequence, self.receive_next.wrapping_add(window))
+        };
+
+        match (sequence_length, window) {
+            (0, 0) => sequence_number == self.receive_next,
+            (0, _) => in_window(sequence_number),
+            (_, 0) => false,
+            _ => {
+                in_window(sequence_number)
+                    || in_window(sequence_number.wrapping_add(sequence_length - 1))
+            }
+        }
+    }
+
+    // rfc 5681 2
+    fn is_duplicate_ack(&self, header: &TcpHeader, payload: &[u8]) -> bool {
+        header.acknowledgment_number == self.send_unacknowledged
+            && self.send_unacknowledged != self.send_maximum
+            && payload.is_empty()
+            && !header.flags.intersects(TcpFlags::SYN | TcpFlags::FIN)
+            && self.scaled_window(header) == self.send_window
+    }
+
+    fn acknowledge(&mut self, acknowledgment_number: u32, timestamps: Option<(u32, u32)>) {
+        let mut acknowledged_data =
+            acknowledgment_number.wrapping_sub(self.send_unacknowledged) as usize;
+
+        // the SYN and FIN take up sequence space without being in the buffer
+        if self.send_unacknowledged == self.initial_send_sequence {
+            acknowledged_data -= 1;
+        }
+
+        if let Some(fin_sequence) = self.fin_sequence {
+            if sequence_le(self.send_unacknowledged, fin_sequence)
+                && sequence_gt(acknowledgment_number, fin_sequence)
+            {
+                acknowledged_data -= 1;
+            }
+        }
+
+        let acknowledged_data = acknowledged_data.min(self.send_buffer.len());
+        self.send_buffer.drain(..acknowledged_data);
+
+        match timestamps.filter(|_| self.timestamps) {
+            // the echoed clock times every ack, retransmissions included
+            Some((_, echo_reply)) => {
+                self.update_retransmission_timeout(
+                    timestamp_now().wrapping_sub(echo_reply) as usize
+                );
+                self.round_trip_time_sample = None;
+            }
+            None => {
+                if let Some((sample_sequence, sent_at)) = self.round_trip_time_sample {
+                    if sequence_ge(acknowledgment_number, sample_sequence) {
+                        self.update_retransmission_timeout(pit::ticks().wrapping_sub(sent_at));
+                        self.round_trip_time_sample = None;
+                    }
+                }
+            }
+        }
+
+        self.sacked.retain_mut(|(left, right)| {
+            if sequence_le(*right, acknowledgment_number) {
+                return false;
+            }
+
+            if sequence_lt(*left, acknowledgment_number) {
+                *left = acknowledgment_number;
+            }
+            true
+        });
+
+        self.send_unacknowledged = acknowledgment_number;
+        if sequence_lt(self.send_next, acknowledgment_number) {
+            self.send_next = acknowledgment_number;
+        }
+
+        self.retransmissions = 0;
+        self.retransmit_started_at = if self.send_unacknowledged == self.send_maximum {
+            None
+        } else {
+            Some(pit::ticks())
+        };
+    }
+
+    fn on_new_ack(&mut self, acknowledged: usize, outbox: &mut Vec<OutgoingSegment>) {
+        self.duplicate_acks = 0;
+
+        match self.recovery_point {
+            Some(recovery_point) if sequence_ge(self.send_unacknowledged, recovery_point) => {
+                self.recovery_point = None;
+                self.congestion_control.on_recovery_exit();
+            }
+            // rfc 6582 3.2, a partial ack means the segment after it was lost as well
+            Some(_) => {
+                self.congestion_control.on_partial_ack(acknowledged);
+                self.retransmit_next_hole(outbox);
+            }
+            None => self.congestion_control.on_ack(acknowledged),
+        }
+    }
+
+    fn on_duplicate_ack(&mut self, outbox: &mut Vec<OutgoingSegment>) {
+        self.duplicate_acks = self.duplicate_acks.saturating_add(1);
+
+        if self.recovery_point.is_some() {
+            self.congestion_control.on_recovery_duplicate_ack();
+
+            if self.selective_acknowledgments {
+                self.retransmit_next_hole(outbox);
+            }
+            return;
+        }
+
+        // fast retransmit, the peer keeps acking the same byte because a segment went missing
+        if self.duplicate_acks == DUPLICATE_ACK_THRESHOLD {
+            let in_flight = self.send_maximum.wrapping_sub(self.send_unacknowledged) as usize;
+
+            self.recovery_point = Some(self.send_maximum);
+            self.highest_retransmitted = self.send_unacknowledged;
+            self.congestion_control.on_fast_retransmit(in_flight);
+            self.retransmit_next_hole(outbox);
+        }
+    }
+
+    // the peer's SACK blocks, only ranges inside what is outstanding are believed
+    fn record_sack_blocks(&mut self, blocks: &[(u32, u32)]) {
+        if !self.selective_acknowledgments || blocks.is_empty() {
+            return;
+        }
+
+        for &(left, right) in blocks {
+            if sequence_lt(self.send_unacknowledged, left)
+                && sequence_lt(left, right)
+                && sequence_le(right, self.send_maximum)
+            {
+                self.sacked.push((left, right));
+            }
+        }
+
+        merge_ranges(&mut self.sacked, self.send_unacknowledged);
+    }
+
+    // resends the first segment the peer is missing. without SACK that is whatever follows the ack,
+    // with it the next hole below reported data that wasn't retransmitted yet
+    fn retransmit_next_hole(&mut self, outbox: &mut Vec<OutgoingSegment>) {
+        let mut sequence_number = if self.selective_acknowledgments
+            && sequence_gt(self.highest_retransmitted, self.send_unacknowledged)
+        {
+            self.highest_retransmitted
+        } else {
+            self.send_unacknowledged
+        };
+
+        for &(left, right) in &self.sacked {
+            if sequence_le(left, sequence_number) && sequence_lt(sequence_number, right) {
+                sequence_number = right;
+            }
+        }
+
+        let below_sacked = self
+            .sacked
+            .last()
+            .is_some_and(|&(_, right)| sequence_lt(sequence_number, right));
+
+        if (sequence_number != self.send_unacknowledged && !below_sacked)
+            || !sequence_lt(sequence_number, self.send_maximum)
+        {
+            return;
+        }
+
+        let hole_end = self
+            .sacked
+            .iter()
+            .map(|&(left, _)| left)
+            .find(|&left| sequence_gt(left, sequence_number))
+            .unwrap_or(self.send_maximum);
+
+        let data_end = self
+            .send_unacknowledged
+            .wrapping_add(self.send_buffer.len() as u32);
+
+        let length = (hole_end.wrapping_sub(sequence_number) as usize)
+            .min(data_end.wrapping_sub(sequence_number) as usize)
+            .min(self.segment_size());
+
+        let segment = if length > 0 {
+            self.data_segment(sequence_number, length)
+        } else if self.fin_sequence == Some(sequence_number) {
+            // only our FIN is missing
+            self.segment(
+                sequence_number,
+                TcpFlags::FIN | TcpFlags::ACK,
+                &[],
+                TcpOptions::default(),
+            )
+        } else {
+            return;
+        };
+        outbox.push(segment);
+
+        self.highest_retransmitted = sequence_number.wrapping_add(length.max(1) as u32);
+        self.round_trip_time_sample = None;
+        self.start_retransmit_timer();
+    }
+
+    // rfc 6298 2.2 and 2.3
+    fn update_retransmission_timeout(&mut self, round_trip_time: usize) {
+        match self.smoothed_round_trip_time {
+            None => {
+                self.smoothed_round_trip_time = Some(round_trip_time);
+                self.round_trip_time_variance = round_trip_time / 2;
+            }
+            Some(smoothed) => {
+                self.round_trip_time_variance =
+                    (3 * self.round_trip_time_variance + smoothed.abs_diff(round_trip_time)) / 4;
+                self.smoothed_round_trip_time = Some((7 * smoothed + round_trip_time) / 8);
+            }
+        }
+
+        let smoothed = self.smoothed_round_trip_time.unwrap();
+        self.retransmission_timeout = (smoothed + (4 * self.round_trip_time_variance).max(1))
+            .clamp(MIN_RETRANSMISSION_TIMEOUT, MAX_RETRANSMISSION_TIMEOUT);
+    }
+
+    fn receive_data(&mut self, mut sequence_number: u32, mut payload: &[u8]) {
+        // drop what we already have
+        if sequence_lt(sequence_number, self.receive_next) {
+            let duplicate = self.receive_next.wrapping_sub(sequence_number) as usize;
+            if duplicate >= payload.len() {
+                return;
+            }
+
+            payload = &payload[duplicate..];
+            sequence_number = self.receive_next;
+        }
+
+        // and what doesn't fit the window
+        let offset = sequence_number.wrapping_sub(self.receive_next) as usize;
+        let window = self.receive_window();
+        if offset >= window {
+            return;
+        }
+        payload = &payload[..payload.len().min(window - offset)];
+
+        if sequence_number == self.receive_next {
+            self.receive_buffer.extend(payload);
+            self.receive_next = self.receive_next.wrapping_add(payload.len() as u32);
+            self.reassemble();
+        } else if self.out_of_order.len() < MAX_OUT_OF_ORDER_SEGMENTS {
+            self.out_of_order.push((sequence_number, payload.to_vec()));
+            self.latest_out_of_order = Some(sequence_number);
+        }
+    }
+
+    // rfc 2018 4, what we hold past the hole with the block of the latest segment first
+    fn sack_blocks(&self) -> Vec<(u32, u32)> {
+        let mut blocks: Vec<(u32, u32)> = self
+            .out_of_order
+            .iter()
+            .map(|(sequence_number, data)| {
+                (
+                    *sequence_number,
+                    sequence_number.wrapping_add(data.len() as u32),
+                )
+            })
+            .collect();
+
+        merge_ranges(&mut blocks, self.receive_next);
+
+        if let Some(latest) = self.latest_out_of_order {
+            if let Some(index) = blocks
+                .iter()
+                .position(|&(left, right)| sequence_le(left, latest) && sequence_lt(latest, right))
+            {
+                let block = blocks.remove(index);
+                blocks.insert(0, block);
+            }
+        }
+
+        blocks
+    }
+
+    // moves the out of order segments that the new data reaches into the receive buffer
+    fn reassemble(&mut self) {
+        while let Some(index) = self
+            .out_of_order
+            .iter()
+            .position(|&(sequence_number, _)| sequence_le(sequence_number, self.receive_next))
+        {
+            let (sequence_number, data) = self.out_of_order.swap_remove(index);
+            let duplicate = self.receive_next.wrapping_sub(sequence_number) as usize;
+
+            if duplicate < data.len() {
+                let data = &data[duplicate..];
+                let data = &data[..data.len().min(self.receive_window())];
+
+                self.receive_buffer.extend(data);
+                self.receive_next = self.receive_next.wrapping_add(data.len() as u32);
+            }
+        }
+    }
+
+    // the FIN only counts once all the data before it is in
+    fn receive_fin(&mut self) {
+        if self.fin_received || self.remote_fin_sequence != Some(self.receive_next) {
+            return;
+        }
+
+        self.receive_next = self.receive_next.wrapping_add(1);
+        self.fin_received = true;
+        self.out_of_order.clear();
+
+        match self.state {
+            State::SynReceived | State::Established => self.state = State::CloseWait,
+            State::FinWait1 => self.state = State::Closing,
+            State::FinWait2 => self.enter_time_wait(),
+            _ => (),
+        }
+    }
+
+    fn enter_time_wait(&mut self) {
+        self.state = State::TimeWait;
+        self.time_wait_started_at = pit::ticks();
+        self.retransmit_started_at = None;
+    }
+
+    // sends what the window allows, returns whether anything went out
+    pub fn transmit(&mut self, outbox: &mut Vec<OutgoingSegment>) -> bool {
+        if !matches!(
+            self.state,
+            State::Established
+                | State::CloseWait
+                | State::FinWait1
+                | State::Closing
+                | State::LastAck
+        ) {
+            return false;
+        }
+
+        let mut sent = false;
+
+        // everything including the FIN is out
+        while self.fin_sequence.is_none() {
+            let in_flight = self.send_next.wrapping_sub(self.send_unacknowledged) as usize;
+            let unsent = self.send_buffer.len() - in_flight;
+
+            if unsent == 0 {
+                if self.fin_queued {
+                    self.send_fin(outbox);
+                    sent = true;
+                }
+                break;
+            }
+
+            // a closed window is probed with a single byte, the retransmit timer repeats the probe
+            let usable_window = match self.send_window as usize {
+                0 if in_flight == 0 => 1,
+                window => window
+                    .min(self.congestion_control.window())
+                    .saturating_sub(in_flight),
+            };
+
+            let length = unsent.min(usable_window).min(self.segment_size());
+            if length == 0 {
+                break;
+            }
+
+            let segment = self.data_segment(self.send_next, length);
+            outbox.push(segment);
+
+            let is_new_data = self.send_next == self.send_maximum;
+            self.send_next = self.send_next.wrapping_add(length as u32);
+
+            if is_new_data {
+                self.send_maximum = self.send_next;
+
+                // karn's algorithm, retransmitted segments are never timed
+                if self.round_trip_time_sample.is_none() {
+                    self.round_trip_time_sample = Some((self.send_next, pit::ticks()));
+                }
+            }
+
+            self.start_retransmit_timer();
+            sent = true;
+        }
+
+        sent
+    }
+
+    fn data_segment(&mut self, sequence_number: u32, length: usize) -> OutgoingSegment {
+        let offset = sequence_number.wrapping_sub(self.send_unacknowledged) as usize;
+        let payload: Vec<u8> = self
+            .send_buffer
+            .range(offset..offset + length)
+            .copied()
+            .collect();
+
+        let mut flags = TcpFlags::ACK;
+        if offset + length == self.send_buffer.len() {
+            flags |= TcpFlags::PSH;
+        }
+
+        self.segment(sequence_number, flags, &payload, TcpOptions::default())
+    }
+
+    fn send_fin(&mut self, outbox: &mut Vec<OutgoingSegment>) {
+        let segment = self.segment(
+            self.send_next,
+            TcpFlags::FIN | TcpFlags::ACK,
+            &[],
+            TcpOptions::default(),
+        );
+        outbox.push(segment);
+
+        self.fin_sequence = Some(self.send_next);
+        self.send_next = self.send_next.wrapping_add(1);
+        if sequence_gt(self.send_next, self.send_maximum) {
+            self.send_maximum = self.send_next;
+        }
+
+        match self.state {
+            State::Established => self.state = State::FinWait1,
+            State::CloseWait => self.state = State::LastAck,
+            _ => (),
+        }
+
+        self.start_retransmit_timer();
+    }
+
+    pub fn poll_timers(&mut self, outbox: &mut Vec<OutgoingSegment>) {
+        if self.state == State::TimeWait {
+            if pit::elapsed(self.time_wait_started_at, TIME_WAIT_TIMEOUT) {
+                self.state = State::Closed;
+            }
+            return;
+        }
+
+        let Some(started_at) = self.retransmit_started_at else {
+            return;
+        };
+
+        if !pit::elapsed(started_at, self.retransmission_timeout) {
+            return;
+        }
+
+        if self.retransmissions == MAX_RETRANSMISSIONS {
+            self.abort(Some(CloseReason::TimedOut), outbox);
+            return;
+        }
+
+        self.retransmissions += 1;
+        self.retransmission_timeout =
+            (self.retransmission_timeout * 2).min(MAX_RETRANSMISSION_TIMEOUT);
+        self.round_trip_time_sample = None;
+        self.retransmit_started_at = Some(pit::ticks());
+
+        match self.state {
+            State::SynSent | State::SynReceived => self.send_syn(outbox),
+            _ => {
+                let in_flight = self.send_maximum.wrapping_sub(self.send_unacknowledged) as usize;
+                self.congestion_control.on_retransmission_timeout(in_flight);
+                self.recovery_point = None;
+                self.duplicate_acks = 0;
+                // rfc 2018 8, the peer may have dropped what it reported
+                self.sacked.clear();
+
+                // go back to the first unacknowledged byte and send everything from there again
+                self.send_next = self.send_unacknowledged;
+                if self
+                    .fin_sequence
+                    .is_some_and(|fin_sequence| sequence_ge(fin_sequence, self.send_unacknowledged))
+                {
+                    self.fin_sequence = None;
+                }
+
+                self.transmit(outbox);
+            }
+        }
+    }
+
+    // Ok(0) once the peer closed its side and everything was read
+    pub fn read(&mut self, buffer: &mut [u8], outbox: &mut Vec<OutgoingSegment>) -> Result<usize> {
+        if self.receive_buffer.is_empty() {
+            return match (self.state, self.close_reason) {
+                (_, Some(reason)) => Err(reason.into()),
+                _ if self.fin_received => Ok(0),
+                (State::Closed, None) => Ok(0),
+                _ => Err(TcpError::WouldBlock),
+            };
+        }
+
+        let length = buffer.len().min(self.receive_buffer.len());
+        for (destination, byte) in buffer.iter_mut().zip(self.receive_buffer.drain(..length)) {
+            *destination = byte;
+        }
+
+        // rfc 1122 4.2.3.3, the window is only reopened in big steps to avoid silly windows
+        let window_end = self.receive_next.wrapping_add(self.receive_window() as u32);
+        let window_increase = window_end.wrapping_sub(self.advertised_window_end) as usize;
+
+        if matches!(
+            self.state,
+            State::Established | State::FinWait1 | State::FinWait2
+        ) && window_increase >= (RECEIVE_BUFFER_SIZE / 2).min(self.maximum_segment_size)
+        {
+            self.send_ack(outbox);
+        }
+
+        Ok(length)
+    }
+
+    // queues as much as fits in the send buffer
+    pub fn write(&mut self, data: &[u8], outbox: &mut Vec<OutgoingSegment>) -> Result<usize> {
+        if let Some(reason) = self.close_reason {
+            return Err(reason.into());
+        }
+
+        if self.fin_queued
+            || !matches!(
+                self.state,
+                State::SynSent | State::SynReceived | State::Established | State::CloseWait
+            )
+        {
+            return Err(TcpError::NotConnected);
+        }
+
+        let length = data.len().min(SEND_BUFFER_SIZE - self.send_buffer.len());
+        if length == 0 && !data.is_empty() {
+            return Err(TcpError::WouldBlock);
+        }
+
+        self.send_buffer.extend(&data[..length]);
+        self.transmit(outbox);
+
+        Ok(length)
+    }
+
+    // closes our side, the FIN goes out after the queued data
+    pub fn close(&mut self, outbox: &mut Vec<OutgoingSegment>) {
+        match self.state {
+            State::SynSent => self.state = State::Closed,
+            State::SynReceived | State::Established | State::CloseWait => {
+                self.fin_queued = true;
+                self.transmit(outbox);
+            }
+            _ => (),
+        }
+    }
+}
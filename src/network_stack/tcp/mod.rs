@@ -0,0 +1,471 @@
+// https://datatracker.ietf.org/doc/html/rfc9293
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
+use thiserror::Error;
+
+use crate::{mutex::Mutex, println};
+
+use self::{
+    connection::{reset_for, Connection, OutgoingSegment, State, DEFAULT_MAXIMUM_SEGMENT_SIZE},
+    segment::{TcpFlags, TcpHeader, TCP_HEADER_SIZE},
+};
+
+use super::{
+    interface::{ipv4_cidr, InterfaceId},
+    ip::{self, IpAddress, IpError, SocketAddress},
+    ipv4::{self, header::Ipv4Header, IpProtocol, Ipv4Address},
+    ipv6::{self, header::Ipv6Header},
+    poll_until,
+    ports::EphemeralPorts,
+};
+
+pub mod congestion;
+pub mod connection;
+pub mod segment;
+
+#[derive(Error, Debug)]
+pub enum TcpError {
+    #[error("Packet of {0} bytes is too short for a TCP header")]
+    TooShort(usize),
+    #[error("Invalid data offset {0}")]
+    InvalidDataOffset(usize),
+    #[error("Checksum mismatch")]
+    BadChecksum,
+    #[error("Port {0} is already in use")]
+    AddressInUse(u16),
+    #[error("No free ephemeral ports")]
+    NoFreePorts,
+    #[error("The operation would block")]
+    WouldBlock,
+    #[error("Timed out")]
+    TimedOut,
+    #[error("Connection refused")]
+    ConnectionRefused,
+    #[error("Connection reset by peer")]
+    ConnectionReset,
+    #[error("Not connected")]
+    NotConnected,
+    #[error(transparent)]
+    Ip(#[from] IpError),
+}
+
+pub type Result<T> = core::result::Result<T, TcpError>;
+
+type ConnectionId = usize;
+
+struct Listener {
+    // unspecified accepts connections to any of our addresses, of either IP version
+    local_address: IpAddress,
+    backlog: usize,
+    // established connections nobody accepted yet
+    accept_queue: VecDeque<ConnectionId>,
+}
+
+struct Tcp {
+    connections: BTreeMap<ConnectionId, Connection>,
+    listeners: BTreeMap<u16, Listener>,
+    next_connection_id: ConnectionId,
+}
+
+impl Tcp {
+    const fn new() -> Self {
+        Self {
+            connections: BTreeMap::new(),
+            listeners: BTreeMap::new(),
+            next_connection_id: 0,
+        }
+    }
+
+    fn insert(&mut self, connection: Connection) -> ConnectionId {
+        let id = self.next_connection_id;
+        self.next_connection_id += 1;
+        self.connections.insert(id, connection);
+        id
+    }
+
+    fn port_in_use(&self, port: u16) -> bool {
+        self.listeners.contains_key(&port)
+            || self
+                .connections
+                .values()
+                .any(|connection| connection.local.port == port)
+    }
+
+    // nobody can read a closed connection without a stream, so it goes away
+    fn remove_closed(&mut self) {
+        self.connections
+            .retain(|_, connection| connection.owned || connection.state != State::Closed);
+    }
+
+    fn handle_segment(
+        &mut self,
+        local: SocketAddress,
+        remote: SocketAddress,
+        header: &TcpHeader,
+        payload: &[u8],
+        outbox: &mut Vec<OutgoingSegment>,
+    ) {
+        if let Some((&id, connection)) = self.connections.iter_mut().find(|(_, connection)| {
+            connection.local == local
+                && connection.remote == remote
+                && connection.state != State::Closed
+        }) {
+            connection.handle_segment(header, payload, outbox);
+
+            if !matches!(connection.state, State::SynReceived | State::Closed) {
+                if let Some(listener) = connection
+                    .listener_port
+                    .take()
+                    .and_then(|port| self.listeners.get_mut(&port))
+                {
+                    listener.accept_queue.push_back(id);
+                }
+            }
+
+            self.remove_closed();
+            return;
+        }
+
+        if header.flags.contains(TcpFlags::RST) {
+            return;
+        }
+
+        let Some(listener) = self.listeners.get(&local.port).filter(|listener| {
+            listener.local_address.is_unspecified() || listener.local_address == local.address
+        }) else {
+            outbox.push(reset_for(local, remote, header, payload.len()));
+            return;
+        };
+
+        if header.flags.contains(TcpFlags::ACK) {
+            outbox.push(reset_for(local, remote, header, payload.len()));
+            return;
+        }
+
+        if !header.flags.contains(TcpFlags::SYN) {
+            return;
+        }
+
+        let pending = self
+            .connections
+            .values()
+            .filter(|connection| connection.listener_port == Some(local.port))
+            .count();
+
+        // the peer tries again when its SYN goes unanswered
+        if pending + listener.accept_queue.len() >= listener.backlog {
+            println!(
+                "Dropped SYN from {}, backlog of port {} is full",
+                remote, local.port
+            );
+            return;
+        }
+
+        let connection = Connection::accept(
+            local,
+            remote,
+            header,
+            local_maximum_segment_size(remote.address),
+            outbox,
+        );
+        self.insert(connection);
+    }
+}
+
+static TCP: Mutex<Tcp> = Mutex::new(Tcp::new());
+static EPHEMERAL_PORTS: Mutex<EphemeralPorts> = Mutex::new(EphemeralPorts::new());
+
+pub fn init() {
+    ipv4::register_protocol_handler(IpProtocol::Tcp, handle_ipv4_packet);
+    ipv6::register_protocol_handler(IpProtocol::Tcp, handle_ipv6_packet);
+}
+
+// what fits in a datagram on the way to `destination`, the peer never sends us more than this
+fn local_maximum_segment_size(destination: IpAddress) -> u16 {
+    ip::payload_mtu_for(destination)
+        .map_or(DEFAULT_MAXIMUM_SEGMENT_SIZE, |mtu| mtu - TCP_HEADER_SIZE) as u16
+}
+
+fn send_segments(outbox: Vec<OutgoingSegment>) {
+    for segment in outbox {
+        if let Err(err) = ip::send(
+            segment.source,
+            segment.destination,
+            IpProtocol::Tcp,
+            segment.packet,
+        ) {
+            println!(
+                "Failed to send TCP segment to {}: {}",
+                segment.destination, err
+            );
+        }
+    }
+}
+
+fn handle_ipv4_packet(interface_id: InterfaceId, ip_header: &Ipv4Header, payload: &[u8]) {
+    // TCP is unicast only
+    let destination = ip_header.destination_address;
+    if destination == Ipv4Address::BROADCAST
+        || destination.is_multicast()
+        || ipv4_cidr(interface_id).is_some_and(|cidr| cidr.broadcast() == destination)
+    {
+        return;
+    }
+
+    handle_packet(
+        interface_id,
+        ip_header.source_address.into(),
+        destination.into(),
+        payload,
+    );
+}
+
+fn handle_ipv6_packet(interface_id: InterfaceId, ip_header: &Ipv6Header, payload: &[u8]) {
+    if ip_header.destination_address.is_multicast() {
+        return;
+    }
+
+    handle_packet(
+        interface_id,
+        ip_header.source_address.into(),
+        ip_header.destination_address.into(),
+        payload,
+    );
+}
+
+fn handle_packet(
+    interface_id: InterfaceId,
+    source_address: IpAddress,
+    destination_address: IpAddress,
+    payload: &[u8],
+) {
+    let (header, data) = match TcpHeader::parse(source_address, destination_address, payload) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            println!("Dropped TCP packet on interface {}: {}", interface_id, err);
+            return;
+        }
+    };
+
+    let local = SocketAddress::new(destination_address, header.destination_port);
+    let remote = SocketAddress::new(source_address, header.source_port);
+
+    let mut outbox = Vec::new();
+    TCP.lock()
+        .handle_segment(local, remote, &header, data, &mut outbox);
+    send_segments(outbox);
+}
+
+pub fn poll_timers() {
+    let mut outbox = Vec::new();
+
+    {
+        let mut tcp = TCP.lock();
+        for connection in tcp.connections.values_mut() {
+            connection.poll_timers(&mut outbox);
+        }
+        tcp.remove_closed();
+    }
+
+    send_segments(outbox);
+}
+
+// the port stays taken until the listener is dropped
+#[derive(Debug)]
+pub struct TcpListener {
+    local: SocketAddress,
+}
+
+impl TcpListener {
+    // port 0 picks a free ephemeral port, `backlog` limits the connections waiting for accept
+    pub fn bind(local: SocketAddress, backlog: usize) -> Result<Self> {
+        let mut tcp = TCP.lock();
+
+        let port = match local.port {
+            0 => EPHEMERAL_PORTS
+                .lock()
+                .allocate(|port| tcp.port_in_use(port))
+                .ok_or(TcpError::NoFreePorts)?,
+            port if tcp.listeners.contains_key(&port) => return Err(TcpError::AddressInUse(port)),
+            port => port,
+        };
+
+        tcp.listeners.insert(
+            port,
+            Listener {
+                local_address: local.address,
+                backlog,
+                accept_queue: VecDeque::new(),
+            },
+        );
+
+        Ok(Self {
+            local: SocketAddress::new(local.address, port),
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddress {
+        self.local
+    }
+
+    // returns right away with WouldBlock when no connection is waiting
+    pub fn try_accept(&self) -> Result<TcpStream> {
+        let mut tcp = TCP.lock();
+        let tcp = &mut *tcp;
+
+        let listener = tcp.listeners.get_mut(&self.local.port).unwrap();
+
+        // connections that were reset while queued are gone already
+        while let Some(id) = listener.accept_queue.pop_front() {
+            if let Some(connection) = tcp.connections.get_mut(&id) {
+                connection.owned = true;
+                return Ok(TcpStream { id });
+            }
+        }
+
+        Err(TcpError::WouldBlock)
+    }
+}
+
+impl Drop for TcpListener {
+    // connections that were never accepted are reset
+    fn drop(&mut self) {
+        let mut outbox = Vec::new();
+
+        {
+            let mut tcp = TCP.lock();
+            tcp.listeners.remove(&self.local.port);
+
+            for connection in tcp.connections.values_mut() {
+                if !connection.owned && connection.local.port == self.local.port {
+                    connection.abort(None, &mut outbox);
+                }
+            }
+            tcp.remove_closed();
+        }
+
+        send_segments(outbox);
+    }
+}
+
+// a connection, it is closed gracefully when dropped
+#[derive(Debug)]
+pub struct TcpStream {
+    id: ConnectionId,
+}
+
+impl TcpStream {
+    // active open, blocks until the handshake is done so it must not be called from a protocol handler
+    pub fn connect(remote: SocketAddress, timeout: Option<usize>) -> Result<Self> {
+        let local_address = ip::default_source_address(remote.address)?;
+        let mut outbox = Vec::new();
+
+        let stream = {
+            let mut tcp = TCP.lock();
+
+            let port = EPHEMERAL_PORTS
+                .lock()
+                .allocate(|port| tcp.port_in_use(port))
+                .ok_or(TcpError::NoFreePorts)?;
+
+            let mut connection = Connection::connect(
+                SocketAddress::new(local_address, port),
+                remote,
+                local_maximum_segment_size(remote.address),
+                &mut outbox,
+            );
+            connection.owned = true;
+
+            Self {
+                id: tcp.insert(connection),
+            }
+        };
+
+        send_segments(outbox);
+
+        poll_until(timeout, || {
+            let tcp = TCP.lock();
+            let connection = &tcp.connections[&stream.id];
+
+            match connection.state {
+                State::SynSent | State::SynReceived => None,
+                State::Closed => Some(Err(connection
+                    .close_reason
+                    .map_or(TcpError::NotConnected, TcpError::from))),
+                _ => Some(Ok(())),
+            }
+        })
+        .unwrap_or(Err(TcpError::TimedOut))?;
+
+        Ok(stream)
+    }
+
+    // the connection outlives the lock, the segments it wants to send go out after
+    fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&mut Connection, &mut Vec<OutgoingSegment>) -> T,
+    ) -> T {
+        let mut outbox = Vec::new();
+        let result = f(
+            TCP.lock().connections.get_mut(&self.id).unwrap(),
+            &mut outbox,
+        );
+        send_segments(outbox);
+        result
+    }
+
+    pub fn peer_address(&self) -> SocketAddress {
+        self.with_connection(|connection, _| connection.remote)
+    }
+
+    // returns right away with WouldBlock when nothing was received, Ok(0) means the peer closed
+    pub fn try_read(&self, buffer: &mut [u8]) -> Result<usize> {
+        self.with_connection(|connection, outbox| connection.read(buffer, outbox))
+    }
+
+    // waits in poll_until for data, or for the peer's FIN which reads as 0
+    pub fn read(&self, buffer: &mut [u8], timeout: Option<usize>) -> Result<usize> {
+        poll_until(timeout, || match self.try_read(buffer) {
+            Err(TcpError::WouldBlock) => None,
+            result => Some(result),
+        })
+        .unwrap_or(Err(TcpError::TimedOut))
+    }
+
+    // queues what fits in the send buffer, WouldBlock when it is full
+    pub fn try_write(&self, data: &[u8]) -> Result<usize> {
+        self.with_connection(|connection, outbox| connection.write(data, outbox))
+    }
+
+    // drives the stack until all of `data` is queued, so it must not be called from a protocol handler
+    pub fn write_all(&self, data: &[u8], timeout: Option<usize>) -> Result<()> {
+        let mut written = 0;
+
+        poll_until(timeout, || {
+            while written < data.len() {
+                match self.try_write(&data[written..]) {
+                    Ok(length) => written += length,
+                    Err(TcpError::WouldBlock) => return None,
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+
+            Some(Ok(()))
+        })
+        .unwrap_or(Err(TcpError::TimedOut))
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        self.with_connection(|connection, outbox| {
+            connection.close(outbox);
+            connection.owned = false;
+        });
+
+        TCP.lock().remove_closed();
+    }
+}
@@ -0,0 +1,269 @@
+use alloc::vec::Vec;
+use bitflags::bitflags;
+
+use crate::network_stack::{
+    ip::{pseudo_header_checksum, IpAddress},
+    ipv4::IpProtocol,
+    packet_buffer::PacketBuffer,
+};
+
+use super::{Result, TcpError};
+
+pub const TCP_HEADER_SIZE: usize = 20;
+const MAX_OPTIONS_SIZE: usize = 40;
+
+const OPTION_END: u8 = 0;
+const OPTION_NO_OPERATION: u8 = 1;
+const OPTION_MAXIMUM_SEGMENT_SIZE: u8 = 2;
+const OPTION_WINDOW_SCALE: u8 = 3;
+const OPTION_SACK_PERMITTED: u8 = 4;
+const OPTION_SACK: u8 = 5;
+const OPTION_TIMESTAMPS: u8 = 8;
+// padding included, every segment carries them once they are on
+pub const TIMESTAMPS_OPTION_SIZE: usize = 12;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct TcpFlags: u8 {
+        const FIN = 1 << 0;
+        const SYN = 1 << 1;
+        const RST = 1 << 2;
+        const PSH = 1 << 3;
+        const ACK = 1 << 4;
+        const URG = 1 << 5;
+    }
+}
+
+// mss, window scale, sack and timestamps, unknown kinds are stepped over by their length
+// https://datatracker.ietf.org/doc/html/rfc7323
+// https://datatracker.ietf.org/doc/html/rfc2018
+#[derive(Debug, Default, Clone)]
+pub struct TcpOptions {
+    pub maximum_segment_size: Option<u16>,
+    pub window_scale: Option<u8>,
+    pub sack_permitted: bool,
+    // the sender's clock and the last value it received from us
+    pub timestamps: Option<(u32, u32)>,
+    // the edges of data received past a hole, the right edge is exclusive
+    pub sack_blocks: Vec<(u32, u32)>,
+}
+
+impl TcpOptions {
+    fn parse(mut bytes: &[u8]) -> Self {
+        let mut options = Self::default();
+
+        while let Some((&kind, rest)) = bytes.split_first() {
+            match kind {
+                OPTION_END => break,
+                OPTION_NO_OPERATION => {
+                    bytes = rest;
+                    continue;
+                }
+                _ => (),
+            }
+
+            // every other option carries its own length, kind and length bytes included
+            let Some(&length) = rest.first() else {
+                break;
+            };
+            let length = length as usize;
+            if length < 2 || length > bytes.len() {
+                break;
+            }
+
+            let data = &bytes[2..length];
+
+            match (kind, data.len()) {
+                (OPTION_MAXIMUM_SEGMENT_SIZE, 2) => {
+                    options.maximum_segment_size = Some(u16::from_be_bytes([data[0], data[1]]))
+                }
+                (OPTION_WINDOW_SCALE, 1) => options.window_scale = Some(data[0]),
+                (OPTION_SACK_PERMITTED, 0) => options.sack_permitted = true,
+                (OPTION_SACK, length) if length % 8 == 0 => {
+                    options.sack_blocks = data
+                        .as_chunks::<8>()
+                        .0
+                        .iter()
+                        .map(|block| {
+                            (
+                                u32::from_be_bytes(block[0..4].try_into().unwrap()),
+                                u32::from_be_bytes(block[4..8].try_into().unwrap()),
+                            )
+                        })
+                        .collect()
+                }
+                (OPTION_TIMESTAMPS, 8) => {
+                    options.timestamps = Some((
+                        u32::from_be_bytes(data[0..4].try_into().unwrap()),
+                        u32::from_be_bytes(data[4..8].try_into().unwrap()),
+                    ))
+                }
+                _ => (),
+            }
+
+            bytes = &bytes[length..];
+        }
+
+        options
+    }
+
+    // every option is padded to 4 bytes with no-ops, as the data offset counts in words
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(MAX_OPTIONS_SIZE);
+
+        if let Some(maximum_segment_size) = self.maximum_segment_size {
+            bytes.extend_from_slice(&[OPTION_MAXIMUM_SEGMENT_SIZE, 4]);
+            bytes.extend_from_slice(&maximum_segment_size.to_be_bytes());
+        }
+
+        if let Some(window_scale) = self.window_scale {
+            bytes.extend_from_slice(&[OPTION_NO_OPERATION, OPTION_WINDOW_SCALE, 3, window_scale]);
+        }
+
+        if self.sack_permitted {
+            bytes.extend_from_slice(&[
+                OPTION_NO_OPERATION,
+                OPTION_NO_OPERATION,
+                OPTION_SACK_PERMITTED,
+                2,
+            ]);
+        }
+
+        if let Some((value, echo_reply)) = self.timestamps {
+            bytes.extend_from_slice(&[
+                OPTION_NO_OPERATION,
+                OPTION_NO_OPERATION,
+                OPTION_TIMESTAMPS,
+                10,
+            ]);
+            bytes.extend_from_slice(&value.to_be_bytes());
+            bytes.extend_from_slice(&echo_reply.to_be_bytes());
+        }
+
+        // as many blocks as fit in what is left, the first ones matter most
+        let block_count = self
+            .sack_blocks
+            .len()
+            .min((MAX_OPTIONS_SIZE - bytes.len()).saturating_sub(4) / 8);
+
+        if block_count > 0 {
+            bytes.extend_from_slice(&[
+                OPTION_NO_OPERATION,
+                OPTION_NO_OPERATION,
+                OPTION_SACK,
+                (2 + 8 * block_count) as u8,
+            ]);
+
+            for (left_edge, right_edge) in &self.sack_blocks[..block_count] {
+                bytes.extend_from_slice(&left_edge.to_be_bytes());
+                bytes.extend_from_slice(&right_edge.to_be_bytes());
+            }
+        }
+
+        bytes
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TcpHeader {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub sequence_number: u32,
+    pub acknowledgment_number: u32,
+    pub flags: TcpFlags,
+    pub window: u16,
+    pub urgent_pointer: u16,
+    pub options: TcpOptions,
+}
+
+impl TcpHeader {
+    // the checksum covers the pseudo header, so both ip addresses are needed. returns the header and the
+    // data after the options
+    pub fn parse(
+        source_address: IpAddress,
+        destination_address: IpAddress,
+        bytes: &[u8],
+    ) -> Result<(Self, &[u8])> {
+        if bytes.len() < TCP_HEADER_SIZE {
+            return Err(TcpError::TooShort(bytes.len()));
+        }
+
+        let header_length = (bytes[12] >> 4) as usize * 4;
+        if header_length < TCP_HEADER_SIZE || header_length > bytes.len() {
+            return Err(TcpError::InvalidDataOffset(header_length));
+        }
+
+        let mut sum = pseudo_header_checksum(
+            source_address,
+            destination_address,
+            IpProtocol::Tcp,
+            bytes.len(),
+        );
+        sum.add_bytes(bytes);
+
+        if sum.finish() != 0 {
+            return Err(TcpError::BadChecksum);
+        }
+
+        Ok((
+            Self {
+                source_port: u16::from_be_bytes([bytes[0], bytes[1]]),
+                destination_port: u16::from_be_bytes([bytes[2], bytes[3]]),
+                sequence_number: u32::from_be_bytes(bytes[4..8].try_into().unwrap()),
+                acknowledgment_number: u32::from_be_bytes(bytes[8..12].try_into().unwrap()),
+                flags: TcpFlags::from_bits_truncate(bytes[13]),
+                window: u16::from_be_bytes([bytes[14], bytes[15]]),
+                urgent_pointer: u16::from_be_bytes([bytes[18], bytes[19]]),
+                options: TcpOptions::parse(&bytes[TCP_HEADER_SIZE..header_length]),
+            },
+            &bytes[header_length..],
+        ))
+    }
+
+    // the sequence space the segment occupies, SYN and FIN count as one byte each
+    pub fn sequence_length(&self, payload_length: usize) -> u32 {
+        let mut length = payload_length as u32;
+
+        if self.flags.contains(TcpFlags::SYN) {
+            length += 1;
+        }
+
+        if self.flags.contains(TcpFlags::FIN) {
+            length += 1;
+        }
+
+        length
+    }
+
+    // writes the header in front of the payload, the checksum is computed here
+    pub fn prepend_to(
+        &self,
+        packet: &mut PacketBuffer,
+        source_address: IpAddress,
+        destination_address: IpAddress,
+    ) {
+        let options = self.options.to_bytes();
+        let header_length = TCP_HEADER_SIZE + options.len();
+
+        let header = packet.prepend(header_length);
+        header[0..2].copy_from_slice(&self.source_port.to_be_bytes());
+        header[2..4].copy_from_slice(&self.destination_port.to_be_bytes());
+        header[4..8].copy_from_slice(&self.sequence_number.to_be_bytes());
+        header[8..12].copy_from_slice(&self.acknowledgment_number.to_be_bytes());
+        header[12] = ((header_length / 4) as u8) << 4;
+        header[13] = self.flags.bits();
+        header[14..16].copy_from_slice(&self.window.to_be_bytes());
+        header[16..18].copy_from_slice(&[0, 0]);
+        header[18..20].copy_from_slice(&self.urgent_pointer.to_be_bytes());
+        header[TCP_HEADER_SIZE..].copy_from_slice(&options);
+
+        let mut sum = pseudo_header_checksum(
+            source_address,
+            destination_address,
+            IpProtocol::Tcp,
+            packet.len(),
+        );
+        sum.add_bytes(packet);
+        packet[16..18].copy_from_slice(&sum.finish().to_be_bytes());
+    }
+}
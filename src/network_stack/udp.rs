@@ -0,0 +1,334 @@
+// https://datatracker.ietf.org/doc/html/rfc768
+
+use alloc::{collections::BTreeMap, collections::VecDeque, vec::Vec};
+use thiserror::Error;
+
+use crate::{mutex::Mutex, println};
+
+use super::{
+    icmp::{self, UnreachableCode},
+    icmpv6,
+    interface::InterfaceId,
+    ip::{self, pseudo_header_checksum, IpAddress, IpError, SocketAddress},
+    ipv4::{
+        self,
+        header::{Ipv4Header, IPV4_HEADER_SIZE},
+        IpProtocol, SendOptions, SocketAddressV4,
+    },
+    ipv6::{self, header::Ipv6Header},
+    packet_buffer::PacketBuffer,
+    poll_until,
+    ports::EphemeralPorts,
+};
+
+pub const UDP_HEADER_SIZE: usize = 8;
+// what fits in a single IPv4 datagram, anything past the mtu gets fragmented
+pub const MAX_PAYLOAD_SIZE: usize = u16::MAX as usize - IPV4_HEADER_SIZE - UDP_HEADER_SIZE;
+// datagrams that arrive while the queue is full are dropped, like a full socket buffer
+const MAX_QUEUED_DATAGRAMS: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum UdpError {
+    #[error("Packet of {0} bytes is too short for a UDP header")]
+    TooShort(usize),
+    #[error("Length {0} does not match the packet")]
+    InvalidLength(usize),
+    #[error("Checksum mismatch")]
+    BadChecksum,
+    #[error("Port {0} is already in use")]
+    AddressInUse(u16),
+    #[error("No free ephemeral ports")]
+    NoFreePorts,
+    #[error("Payload of {0} bytes does not fit in a datagram")]
+    PayloadTooLarge(usize),
+    #[error("No datagram is queued")]
+    WouldBlock,
+    #[error("Timed out waiting for a datagram")]
+    TimedOut,
+    #[error(transparent)]
+    Ip(#[from] IpError),
+}
+
+pub type Result<T> = core::result::Result<T, UdpError>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct UdpHeader {
+    pub source_port: u16,
+    pub destination_port: u16,
+}
+
+impl UdpHeader {
+    // returns the header and the payload, the checksum is verified against the ip addresses
+    pub fn parse(
+        source_address: IpAddress,
+        destination_address: IpAddress,
+        bytes: &[u8],
+    ) -> Result<(Self, &[u8])> {
+        if bytes.len() < UDP_HEADER_SIZE {
+            return Err(UdpError::TooShort(bytes.len()));
+        }
+
+        let length = u16::from_be_bytes([bytes[4], bytes[5]]);
+        if (length as usize) < UDP_HEADER_SIZE || length as usize > bytes.len() {
+            return Err(UdpError::InvalidLength(length as usize));
+        }
+
+        let bytes = &bytes[..length as usize];
+        let checksum = u16::from_be_bytes([bytes[6], bytes[7]]);
+
+        // a zero checksum means the sender didn't compute one, IPv6 has no header checksum
+        // to fall back on so rfc 8200 8.1 makes it mandatory there
+        if checksum == 0 && matches!(source_address, IpAddress::V6(_)) {
+            return Err(UdpError::BadChecksum);
+        }
+
+        if checksum != 0 {
+            let mut sum = pseudo_header_checksum(
+                source_address,
+                destination_address,
+                IpProtocol::Udp,
+                bytes.len(),
+            );
+            sum.add_bytes(bytes);
+
+            if sum.finish() != 0 {
+                return Err(UdpError::BadChecksum);
+            }
+        }
+
+        Ok((
+            Self {
+                source_port: u16::from_be_bytes([bytes[0], bytes[1]]),
+                destination_port: u16::from_be_bytes([bytes[2], bytes[3]]),
+            },
+            &bytes[UDP_HEADER_SIZE..],
+        ))
+    }
+
+    // writes the header in front of the payload, the length and checksum are computed here
+    pub fn prepend_to(
+        &self,
+        packet: &mut PacketBuffer,
+        source_address: IpAddress,
+        destination_address: IpAddress,
+    ) {
+        let length = UDP_HEADER_SIZE + packet.len();
+
+        let header = packet.prepend(UDP_HEADER_SIZE);
+        header[0..2].copy_from_slice(&self.source_port.to_be_bytes());
+        header[2..4].copy_from_slice(&self.destination_port.to_be_bytes());
+        header[4..6].copy_from_slice(&(length as u16).to_be_bytes());
+        header[6..8].copy_from_slice(&[0, 0]);
+
+        let mut sum =
+            pseudo_header_checksum(source_address, destination_address, IpProtocol::Udp, length);
+        sum.add_bytes(packet);
+
+        // zero is reserved for no checksum, its ones' complement twin is sent instead
+        let checksum = match sum.finish() {
+            0 => 0xFFFF,
+            checksum => checksum,
+        };
+        packet[6..8].copy_from_slice(&checksum.to_be_bytes());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UdpDatagram {
+    pub source: SocketAddress,
+    pub interface_id: InterfaceId,
+    pub data: Vec<u8>,
+}
+
+struct SocketState {
+    // unspecified accepts datagrams for any of our addresses, of either IP version
+    local_address: IpAddress,
+    receive_queue: VecDeque<UdpDatagram>,
+}
+
+static SOCKETS: Mutex<BTreeMap<u16, SocketState>> = Mutex::new(BTreeMap::new());
+static EPHEMERAL_PORTS: Mutex<EphemeralPorts> = Mutex::new(EphemeralPorts::new());
+
+pub fn init() {
+    ipv4::register_protocol_handler(IpProtocol::Udp, handle_ipv4_packet);
+    ipv6::register_protocol_handler(IpProtocol::Udp, handle_ipv6_packet);
+}
+
+// the port stays taken until the socket is dropped
+#[derive(Debug)]
+pub struct UdpSocket {
+    local: SocketAddress,
+}
+
+impl UdpSocket {
+    // port 0 picks a free ephemeral port
+    pub fn bind(local: SocketAddress) -> Result<Self> {
+        let mut sockets = SOCKETS.lock();
+
+        let port = match local.port {
+            0 => EPHEMERAL_PORTS
+                .lock()
+                .allocate(|port| sockets.contains_key(&port))
+                .ok_or(UdpError::NoFreePorts)?,
+            port if sockets.contains_key(&port) => return Err(UdpError::AddressInUse(port)),
+            port => port,
+        };
+
+        sockets.insert(
+            port,
+            SocketState {
+                local_address: local.address,
+                receive_queue: VecDeque::new(),
+            },
+        );
+
+        Ok(Self {
+            local: SocketAddress::new(local.address, port),
+        })
+    }
+
+    pub fn send_to(&self, data: &[u8], destination: SocketAddress) -> Result<()> {
+        let source_address = ip::source_address_for(self.local.address, destination.address)?;
+        let packet = build_packet(self.local.port, source_address, destination, data)?;
+
+        ip::send(source_address, destination.address, IpProtocol::Udp, packet)?;
+
+        Ok(())
+    }
+
+    // returns right away with WouldBlock when nothing is queued
+    pub fn try_recv_from(&self) -> Result<UdpDatagram> {
+        SOCKETS
+            .lock()
+            .get_mut(&self.local.port)
+            .and_then(|socket| socket.receive_queue.pop_front())
+            .ok_or(UdpError::WouldBlock)
+    }
+
+    // waits in poll_until for the next datagram
+    pub fn recv_from(&self, timeout: Option<usize>) -> Result<UdpDatagram> {
+        poll_until(timeout, || self.try_recv_from().ok()).ok_or(UdpError::TimedOut)
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        SOCKETS.lock().remove(&self.local.port);
+    }
+}
+
+fn build_packet(
+    source_port: u16,
+    source_address: IpAddress,
+    destination: SocketAddress,
+    data: &[u8],
+) -> Result<PacketBuffer> {
+    if data.len() > MAX_PAYLOAD_SIZE {
+        return Err(UdpError::PayloadTooLarge(data.len()));
+    }
+
+    let header = UdpHeader {
+        source_port,
+        destination_port: destination.port,
+    };
+
+    let mut packet = PacketBuffer::from_payload(data);
+    header.prepend_to(&mut packet, source_address, destination.address);
+
+    Ok(packet)
+}
+
+// sends from `local` without a socket, for senders that can't hold one while transmitting.
+// replies only arrive if something is bound to the port
+pub fn send_from(
+    local: SocketAddressV4,
+    data: &[u8],
+    destination: SocketAddressV4,
+    mut options: SendOptions,
+) -> Result<()> {
+    if options.source_address.is_none() && !local.address.is_unspecified() {
+        options.source_address = Some(local.address);
+    }
+
+    let source_address =
+        ipv4::source_address_for(destination.address, &options).map_err(IpError::from)?;
+    options.source_address = Some(source_address);
+
+    let packet = build_packet(local.port, source_address.into(), destination.into(), data)?;
+
+    ipv4::send_with_options(destination.address, IpProtocol::Udp, packet, options)
+        .map_err(IpError::from)?;
+
+    Ok(())
+}
+
+fn handle_ipv4_packet(interface_id: InterfaceId, ip_header: &Ipv4Header, payload: &[u8]) {
+    let port_unreachable = deliver(
+        interface_id,
+        ip_header.source_address.into(),
+        ip_header.destination_address.into(),
+        payload,
+    );
+
+    if port_unreachable {
+        icmp::send_destination_unreachable(UnreachableCode::Port, ip_header, payload);
+    }
+}
+
+fn handle_ipv6_packet(interface_id: InterfaceId, ip_header: &Ipv6Header, payload: &[u8]) {
+    let port_unreachable = deliver(
+        interface_id,
+        ip_header.source_address.into(),
+        ip_header.destination_address.into(),
+        payload,
+    );
+
+    if port_unreachable {
+        icmpv6::send_destination_unreachable(icmpv6::UnreachableCode::Port, ip_header, payload);
+    }
+}
+
+// queues the datagram on its socket, returns true when nobody listens on the port
+fn deliver(
+    interface_id: InterfaceId,
+    source_address: IpAddress,
+    destination_address: IpAddress,
+    payload: &[u8],
+) -> bool {
+    let (header, data) = match UdpHeader::parse(source_address, destination_address, payload) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            println!("Dropped UDP packet on interface {}: {}", interface_id, err);
+            return false;
+        }
+    };
+
+    let delivered = {
+        let mut sockets = SOCKETS.lock();
+
+        match sockets.get_mut(&header.destination_port) {
+            Some(socket)
+                if socket.local_address.is_unspecified()
+                    || socket.local_address == destination_address =>
+            {
+                if socket.receive_queue.len() < MAX_QUEUED_DATAGRAMS {
+                    socket.receive_queue.push_back(UdpDatagram {
+                        source: SocketAddress::new(source_address, header.source_port),
+                        interface_id,
+                        data: data.to_vec(),
+                    });
+                } else {
+                    println!(
+                        "Dropped UDP datagram for full socket on port {}",
+                        header.destination_port
+                    );
+                }
+
+                true
+            }
+            _ => false,
+        }
+    };
+
+    !delivered
+}
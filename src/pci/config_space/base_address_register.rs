@@ -1,3 +1,5 @@
+use crate::{memory::paging, println};
+
 use super::{CommandRegister, PciConfigSpace, BASE_ADDRESS_REGISTERS_COUNT};
 
 const BASE_ADDRESS_REGISTERS_TYPE_MASK: u32 = 0b1;
@@ -66,6 +68,23 @@ impl PciConfigSpace {
         self.set_command_register(command_register);
     }
 
+    // the registers are mapped into the kernel's mmio window, drivers use start_ptr from then on
+    pub(super) fn map_memory_spaces(&mut self) {
+        for register in &mut self.base_address_registers {
+            if let BaseAddressRegister::MemorySpace(memory_space) = register {
+                let start = memory_space.start_ptr.addr();
+
+                match paging::map_mmio(start..start + memory_space.size) {
+                    Ok(address) => memory_space.start_ptr = address as *const u8,
+                    Err(err) => {
+                        println!("Failed to map BAR at {:#x}: {}", start, err);
+                        *register = BaseAddressRegister::EmptyEntry;
+                    }
+                }
+            }
+        }
+    }
+
     fn parse_io_space_bar(&mut self, register_index: usize, original_register_value: u32) {
         self.set_base_address_register(register_index, u32::MAX);
 
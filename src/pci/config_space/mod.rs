@@ -10,12 +10,12 @@ use bitflags::bitflags;
 
 use crate::{
     impl_access_at_offset, print,
-    x86::io::{io_in_u32, io_out_u16, io_out_u32, io_out_u8},
+    x86::io::{io_in_u16, io_in_u32, io_in_u8, io_out_u16, io_out_u32, io_out_u8},
 };
 
 pub use base_address_register::BaseAddressRegister;
 
-use self::base_address_register::MemorySpace;
+use self::base_address_register::{IoSpace, MemorySpace};
 
 use super::{PCI_CONFIG_ADDRESS, PCI_CONFIG_DATA, PCI_INVALID_VENDOR};
 
@@ -74,6 +74,66 @@ impl<T> MemoryMappedRegister<T> {
     }
 }
 
+pub trait PortValue: Sized {
+    unsafe fn read_port(port: u16) -> Self;
+    unsafe fn write_port(port: u16, value: Self);
+}
+
+impl PortValue for u8 {
+    unsafe fn read_port(port: u16) -> Self {
+        io_in_u8(port)
+    }
+
+    unsafe fn write_port(port: u16, value: Self) {
+        io_out_u8(port, value)
+    }
+}
+
+impl PortValue for u16 {
+    unsafe fn read_port(port: u16) -> Self {
+        io_in_u16(port)
+    }
+
+    unsafe fn write_port(port: u16, value: Self) {
+        io_out_u16(port, value)
+    }
+}
+
+impl PortValue for u32 {
+    unsafe fn read_port(port: u16) -> Self {
+        io_in_u32(port)
+    }
+
+    unsafe fn write_port(port: u16, value: Self) {
+        io_out_u32(port, value)
+    }
+}
+
+// same as MemoryMappedRegister but for devices that expose their registers through an io BAR
+pub struct IoSpaceRegister<T> {
+    offset: usize,
+    _pin: PhantomData<T>,
+}
+
+impl<T: PortValue> IoSpaceRegister<T> {
+    pub const fn new(offset: usize) -> Self {
+        Self {
+            offset,
+            _pin: PhantomData,
+        }
+    }
+
+    pub unsafe fn read(&self, io_space: &IoSpace) -> T {
+        assert!(self.offset + size_of::<T>() <= io_space.size);
+        T::read_port((io_space.start_ptr.addr() + self.offset) as u16)
+    }
+
+    pub unsafe fn write(&self, io_space: &mut IoSpace, item: T) {
+        assert!(self.offset + size_of::<T>() <= io_space.size);
+        T::write_port((io_space.start_ptr.addr() + self.offset) as u16, item)
+    }
+}
+
 bitflags! {
     pub struct HeaderType: u8 {
         const GENERAL_DEVICE = 1 << 0;
@@ -243,6 +303,7 @@ impl PciConfigSpace {
         config_space.device_id = config_space.get_device_id();
 
         config_space.init_bar();
+        config_space.map_memory_spaces();
 
         let mut new_config_space = config_space.get_command_register();
         new_config_space.set(CommandRegister::BUS_MASTER, true);
@@ -1,11 +1,17 @@
 use alloc::boxed::Box;
 use thiserror::Error;
 
-use self::network::E1000_DRIVER_ENTRY;
+use self::network::{
+    e1000::E1000_DRIVER_ENTRY,
+    i8255x::{I82557_DRIVER_ENTRY, I82559ER_DRIVER_ENTRY},
+    rtl8139::RTL8139_DRIVER_ENTRY,
+    virtio_net::VIRTIO_NET_DRIVER_ENTRY,
+};
 
 use super::config_space::{BaseAddressRegister, PciConfigSpace};
 
 pub mod network;
+pub mod virtio;
 
 #[derive(Error, Debug)]
 pub enum DriverError {
@@ -26,4 +32,10 @@ pub struct PciDriver {
     pub init_device: PciInitFunction,
 }
 
-pub static PCI_DRIVERS: &'static [PciDriver] = &[E1000_DRIVER_ENTRY];
+pub static PCI_DRIVERS: &[PciDriver] = &[
+    E1000_DRIVER_ENTRY,
+    RTL8139_DRIVER_ENTRY,
+    I82557_DRIVER_ENTRY,
+    I82559ER_DRIVER_ENTRY,
+    VIRTIO_NET_DRIVER_ENTRY,
+];
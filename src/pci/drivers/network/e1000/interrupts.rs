@@ -0,0 +1,33 @@
+use crate::{
+    network_stack::interface::{RECEIVE_EVENT, TRANSMIT_EVENT},
+    println,
+};
+
+use super::{registers::INTERRUPT_CAUSE_READ, E1000Driver};
+
+impl E1000Driver {
+    pub(super) fn handle_interrupt(&mut self) {
+        // reading ICR acknowledges every pending cause so the card stops asserting the line
+        let cause = unsafe { INTERRUPT_CAUSE_READ.read(&self.mmio_space) };
+
+        if cause.receiver_timer_interrupt() || cause.rxdmt0() {
+            RECEIVE_EVENT.signal();
+        }
+
+        if cause.receiver_fifo_overrun() {
+            self.statistics.receive_overruns += 1;
+            println!("e1000 receive overrun, packets were dropped");
+            RECEIVE_EVENT.signal();
+        }
+
+        if cause.transmit_descriptor_written_back() {
+            self.reclaim_transmit_descriptors();
+            TRANSMIT_EVENT.signal();
+        }
+
+        if cause.link_status_change() {
+            unsafe { self.update_link_status() };
+            println!("e1000 link is {}", if self.link_up { "up" } else { "down" });
+        }
+    }
+}
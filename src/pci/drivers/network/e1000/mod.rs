@@ -0,0 +1,518 @@
+// https://pdos.csail.mit.edu/6.828/2011/readings/hardware/8254x_GBe_SDM.pdf
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    hint,
+    mem::size_of,
+    ptr::{read_volatile, write_volatile},
+};
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use crate::{
+    memory::{physical::global_alloc::ALLOCATOR, physical_to_virtual, virtual_to_physical},
+    mutex::Mutex,
+    network_stack::{ethernet::EthernetAddress, interface::register_interface},
+    pci::config_space::{base_address_register::MemorySpace, BaseAddressRegister, PciConfigSpace},
+    println,
+    x86::interrupts::irq::register_irq_handler,
+};
+
+use self::{
+    descriptors::{
+        ReceiveDescriptor, ReceiveErrorRegister, ReceiveStatusRegister,
+        TransmissionCommandRegister, TransmissionDescriptor, TransmissionStatusRegister,
+    },
+    registers::{
+        EepromReadRegister, InterruptMaskRegister, LoopBackMode, ReceiveBufferSize,
+        ReceiveControlRegister, ReceiveDescriptionMinThreshold, ReceiverAddressHighRegister,
+        TransmissionControlRegister, TransmissionIpgRegister, DEVICE_STATUS, EEPROM,
+        EEPROM_ETHERNET_ADDRESS_OFFSET, INTERRUPT_CAUSE_READ, INTERRUPT_MASK, INTERRUPT_MASK_CLEAR,
+        MULTICAST_TABLE_ARRAY, RECEIVE_ADDRESS_HIGH_0, RECEIVE_ADDRESS_LOW_0,
+        RECEIVE_CONTROL_REGISTER, RECEIVE_DESCRIPTOR_BASE_HEAD, RECEIVE_DESCRIPTOR_BASE_HIGH,
+        RECEIVE_DESCRIPTOR_BASE_LOW, RECEIVE_DESCRIPTOR_BASE_TAIL, RECEIVE_DESCRIPTOR_LEN,
+        TRANSMIT_CONTROL_REGISTER, TRANSMIT_DESCRIPTOR_BASE_HEAD, TRANSMIT_DESCRIPTOR_BASE_HIGH,
+        TRANSMIT_DESCRIPTOR_BASE_LOW, TRANSMIT_DESCRIPTOR_BASE_TAIL, TRANSMIT_DESCRIPTOR_LEN,
+        TRANSMIT_IPG_REGISTER,
+    },
+};
+
+use super::{
+    super::{DriverError, PciDriver},
+    DeviceCapabilities, DeviceStatistics, NetworkDevice, NetworkError, Result,
+};
+
+pub(super) mod descriptors;
+mod interrupts;
+mod registers;
+
+const MAX_TRANSMIT_LENGTH: usize = 16384;
+const MAX_RECEIVE_LENGTH: usize = 16384;
+// every transmit descriptor covers at most a single page of the packet
+const TRANSMIT_CHUNK_SIZE: usize = 4096;
+
+pub struct E1000Driver {
+    ethernet_address: EthernetAddress,
+    mmio_space: MemorySpace,
+    transmission_descriptors: Box<TransmissionDescriptorList>,
+    receive_descriptors: Box<ReceiveDescriptorList>,
+    // the next receive descriptor we expect the card to write back
+    receive_index: usize,
+    link_up: bool,
+    // the next transmit descriptor we hand to the card and the oldest one it still owns
+    transmit_tail: usize,
+    transmit_clean_index: usize,
+    // a packet buffer is kept alive on its last descriptor until the card is done reading it
+    transmit_buffers: [Option<Vec<u8>>; TRANSMISSION_DESCRIPTOR_LIST_SIZE],
+    statistics: DeviceStatistics,
+}
+
+// the mmio pointer and the descriptor rings belong to the card, not to whichever context locks the driver
+unsafe impl Send for E1000Driver {}
+
+pub const E1000_DRIVER_ENTRY: PciDriver = PciDriver {
+    vendor_id: 0x8086,
+    device_id: 0x100E,
+    init_device: init_e1000,
+};
+
+const TRANSMISSION_DESCRIPTOR_LIST_SIZE: usize = 1 << 8;
+const RECEIVE_DESCRIPTOR_LIST_SIZE: usize = 1 << 8;
+
+#[repr(C, align(16))]
+struct TransmissionDescriptorList {
+    transmission_descriptor_list: [TransmissionDescriptor; TRANSMISSION_DESCRIPTOR_LIST_SIZE],
+}
+
+#[repr(C, align(16))]
+struct ReceiveDescriptorList {
+    receive_descriptor_list: [ReceiveDescriptor; RECEIVE_DESCRIPTOR_LIST_SIZE],
+}
+
+pub fn init_e1000(pci: &mut PciConfigSpace) -> core::result::Result<(), DriverError> {
+    println!(
+        "Found E1000 like card (vendor_id:{:#X} , device_id: {:#X}), initializing network card...",
+        pci.vendor_id, pci.device_id
+    );
+
+    let driver = Arc::new(Mutex::new(unsafe { E1000Driver::new(pci)? }));
+
+    let interrupt_driver = driver.clone();
+    register_irq_handler(
+        pci.get_interrupt_line(),
+        Box::new(move || interrupt_driver.lock().handle_interrupt()),
+    );
+
+    println!(
+        "E1000 initialized!, my ethernet address is: {}",
+        driver.lock().ethernet_address
+    );
+
+    register_interface(driver);
+
+    Ok(())
+}
+
+impl E1000Driver {
+    unsafe fn new(pci: &mut PciConfigSpace) -> core::result::Result<Self, DriverError> {
+        let BaseAddressRegister::MemorySpace(memory_space) = pci.base_address_registers[0] else {
+            return Err(DriverError::UnexpectedBaseRegisterLayout {
+                register: pci.base_address_registers[0],
+                index: 0,
+            });
+        };
+
+        let mut new_driver = E1000Driver {
+            mmio_space: memory_space,
+            ethernet_address: EthernetAddress { bytes: [0; 6] },
+            transmission_descriptors: Box::new(TransmissionDescriptorList {
+                transmission_descriptor_list: [TransmissionDescriptor::empty();
+                    TRANSMISSION_DESCRIPTOR_LIST_SIZE],
+            }),
+            receive_descriptors: Box::new(ReceiveDescriptorList {
+                receive_descriptor_list: [ReceiveDescriptor::empty(); RECEIVE_DESCRIPTOR_LIST_SIZE],
+            }),
+            receive_index: 0,
+            link_up: false,
+            transmit_tail: 0,
+            transmit_clean_index: 0,
+            transmit_buffers: core::array::from_fn(|_| None),
+            statistics: DeviceStatistics::default(),
+        };
+
+        new_driver.init_transmit();
+        new_driver.init_receive();
+        new_driver.init_interrupts();
+
+        Ok(new_driver)
+    }
+
+    unsafe fn init_transmit(&mut self) {
+        TRANSMIT_DESCRIPTOR_BASE_LOW.write(
+            &mut self.mmio_space,
+            virtual_to_physical(
+                self.transmission_descriptors
+                    .transmission_descriptor_list
+                    .as_ptr()
+                    .addr(),
+            ) as u32,
+        );
+        TRANSMIT_DESCRIPTOR_BASE_HIGH.write(&mut self.mmio_space, 0);
+
+        TRANSMIT_DESCRIPTOR_LEN.write(
+            &mut self.mmio_space,
+            (self
+                .transmission_descriptors
+                .transmission_descriptor_list
+                .len()
+                * size_of::<TransmissionDescriptor>()) as u32,
+        );
+
+        TRANSMIT_DESCRIPTOR_BASE_HEAD.write(&mut self.mmio_space, 0);
+        TRANSMIT_DESCRIPTOR_BASE_TAIL.write(&mut self.mmio_space, 0);
+        TRANSMIT_CONTROL_REGISTER.write(
+            &mut self.mmio_space,
+            TransmissionControlRegister::new()
+                .with_enabled(true)
+                .with_pad_short_packets(true)
+                .with_collision_threshold(0xF)
+                .with_collision_distance_checked(0x40)
+                .unwrap(),
+        );
+
+        TRANSMIT_IPG_REGISTER.write(
+            &mut self.mmio_space,
+            TransmissionIpgRegister::new()
+                .with_ipgt(10)
+                .with_ipgr1(8)
+                .with_ipgr2(6),
+        );
+    }
+
+    unsafe fn init_receive(&mut self) {
+        for byte_index in 0..self.ethernet_address.bytes.len() / 2 {
+            EEPROM.write(
+                &mut self.mmio_space,
+                EepromReadRegister::new()
+                    .with_read_address(EEPROM_ETHERNET_ADDRESS_OFFSET + byte_index as u8)
+                    .with_start_read(true),
+            );
+
+            while !EEPROM.read(&self.mmio_space).done() {
+                hint::spin_loop();
+            }
+
+            let value = EEPROM.read(&self.mmio_space).read_data().to_le_bytes();
+
+            self.ethernet_address.bytes[byte_index * 2] = value[0];
+            self.ethernet_address.bytes[byte_index * 2 + 1] = value[1];
+        }
+
+        RECEIVE_ADDRESS_LOW_0.write(
+            &mut self.mmio_space,
+            u32::from_le_bytes(self.ethernet_address.bytes[..4].try_into().unwrap()),
+        );
+
+        RECEIVE_ADDRESS_HIGH_0.write(
+            &mut self.mmio_space,
+            ReceiverAddressHighRegister::new()
+                .with_receiver_address_high(u16::from_le_bytes(
+                    self.ethernet_address.bytes[4..].try_into().unwrap(),
+                ))
+                .with_address_valid(true),
+        );
+
+        MULTICAST_TABLE_ARRAY.write(&mut self.mmio_space, [0; 4]);
+
+        RECEIVE_DESCRIPTOR_BASE_LOW.write(
+            &mut self.mmio_space,
+            virtual_to_physical(
+                self.receive_descriptors
+                    .receive_descriptor_list
+                    .as_ptr()
+                    .addr(),
+            ) as u32,
+        );
+
+        RECEIVE_DESCRIPTOR_BASE_HIGH.write(&mut self.mmio_space, 0);
+
+        RECEIVE_DESCRIPTOR_LEN.write(
+            &mut self.mmio_space,
+            (self.receive_descriptors.receive_descriptor_list.len()
+                * size_of::<ReceiveDescriptor>()) as u32,
+        );
+
+        RECEIVE_DESCRIPTOR_BASE_HEAD.write(&mut self.mmio_space, 0);
+        RECEIVE_DESCRIPTOR_BASE_TAIL.write(
+            &mut self.mmio_space,
+            (self.receive_descriptors.receive_descriptor_list.len() - 1) as u32,
+        );
+
+        for descriptor in &mut self.receive_descriptors.receive_descriptor_list {
+            let new_page = ALLOCATOR
+                .alloc(Layout::from_size_align(MAX_RECEIVE_LENGTH, MAX_RECEIVE_LENGTH).unwrap());
+            assert!(!new_page.is_null(), "out of memory");
+            descriptor.base_address = virtual_to_physical(new_page.addr()) as u64;
+            descriptor.status = ReceiveStatusRegister::empty();
+        }
+
+        RECEIVE_CONTROL_REGISTER.write(
+            &mut self.mmio_space,
+            ReceiveControlRegister::new()
+                .with_enabled(true)
+                .with_loopback_mod(LoopBackMode::NoLoopBack)
+                .with_store_bad_packets(true)
+                .with_multicast_promiscuous(true)
+                .with_unicast_promiscuous(true)
+                .with_receive_description_min_threshold(ReceiveDescriptionMinThreshold::Quarter)
+                .with_accept_broadcast(true)
+                .with_buffer_size_extension(true)
+                .with_receive_buffer_size(ReceiveBufferSize::Bytes1024)
+                .with_strip_ethernet_crc(true),
+        );
+    }
+
+    unsafe fn init_interrupts(&mut self) {
+        // mask everything and throw away causes left over from before we took the card
+        INTERRUPT_MASK_CLEAR.write(
+            &mut self.mmio_space,
+            InterruptMaskRegister::from_bytes([0xFF; 4]),
+        );
+        INTERRUPT_CAUSE_READ.read(&self.mmio_space);
+
+        self.update_link_status();
+
+        INTERRUPT_MASK.write(
+            &mut self.mmio_space,
+            InterruptMaskRegister::new()
+                .with_receiver_timer_interrupt(true)
+                .with_rxdmt0(true)
+                .with_receiver_fifo_overrun(true)
+                .with_transmit_descriptor_written_back(true)
+                .with_link_status_change(true),
+        );
+    }
+
+    unsafe fn update_link_status(&mut self) {
+        self.link_up = DEVICE_STATUS.read(&self.mmio_space).link_up_indication();
+    }
+
+    fn transmit_descriptors_in_use(&self) -> usize {
+        (self.transmit_tail + TRANSMISSION_DESCRIPTOR_LIST_SIZE - self.transmit_clean_index)
+            % TRANSMISSION_DESCRIPTOR_LIST_SIZE
+    }
+
+    // one descriptor is always left empty so a full ring can be told apart from an empty one
+    fn has_transmit_room(&self, packet: &[u8]) -> bool {
+        self.transmit_descriptors_in_use() + split_at_page_boundaries(packet).count()
+            < TRANSMISSION_DESCRIPTOR_LIST_SIZE
+    }
+
+    fn transmit_packet(&mut self, packet: Vec<u8>) -> Result<()> {
+        if packet.len() > MAX_TRANSMIT_LENGTH {
+            return Err(NetworkError::BufferTooLarge);
+        }
+
+        if packet.is_empty() {
+            return Err(NetworkError::EmptyBuffer);
+        }
+
+        self.reclaim_transmit_descriptors();
+
+        if !self.has_transmit_room(&packet) {
+            return Err(NetworkError::FullTransmissionsQueue);
+        }
+
+        let chunk_count = split_at_page_boundaries(&packet).count();
+
+        for (chunk_index, chunk) in split_at_page_boundaries(&packet).enumerate() {
+            let mut command = TransmissionCommandRegister::REPORT_STATUS;
+            if chunk_index == chunk_count - 1 {
+                // the card appends the frame check sequence, the stack never does
+                command |=
+                    TransmissionCommandRegister::END_OF_PACKET | TransmissionCommandRegister::IFCS;
+            }
+
+            let descriptor = TransmissionDescriptor {
+                base_address: virtual_to_physical(chunk.as_ptr().addr()) as u64,
+                length: chunk.len() as u16,
+                command,
+                status: TransmissionStatusRegister::empty(),
+                ..TransmissionDescriptor::empty()
+            };
+
+            unsafe {
+                write_volatile(
+                    &mut self.transmission_descriptors.transmission_descriptor_list
+                        [self.transmit_tail],
+                    descriptor,
+                );
+            }
+
+            self.transmit_tail = (self.transmit_tail + 1) % TRANSMISSION_DESCRIPTOR_LIST_SIZE;
+        }
+
+        let last_descriptor_index = (self.transmit_tail + TRANSMISSION_DESCRIPTOR_LIST_SIZE - 1)
+            % TRANSMISSION_DESCRIPTOR_LIST_SIZE;
+        self.transmit_buffers[last_descriptor_index] = Some(packet);
+
+        unsafe {
+            TRANSMIT_DESCRIPTOR_BASE_TAIL.write(&mut self.mmio_space, self.transmit_tail as u32);
+        }
+
+        Ok(())
+    }
+
+    // walks the descriptors the card finished with and frees the buffers of fully sent packets
+    fn reclaim_transmit_descriptors(&mut self) {
+        while self.transmit_clean_index != self.transmit_tail {
+            let descriptor = unsafe {
+                read_volatile(
+                    &self.transmission_descriptors.transmission_descriptor_list
+                        [self.transmit_clean_index],
+                )
+            };
+
+            if !descriptor
+                .status
+                .contains(TransmissionStatusRegister::DESCRIPTOR_DONE)
+            {
+                break;
+            }
+
+            if descriptor.status.intersects(
+                TransmissionStatusRegister::EXCESS_COLLISIONS
+                    | TransmissionStatusRegister::LATE_COLLISION
+                    | TransmissionStatusRegister::TRANSMIT_UNDERRUN,
+            ) {
+                self.statistics.transmit_errors += 1;
+            }
+
+            if let Some(packet) = self.transmit_buffers[self.transmit_clean_index].take() {
+                self.statistics.transmitted_frames += 1;
+                self.statistics.transmitted_bytes += packet.len();
+            }
+
+            self.transmit_clean_index =
+                (self.transmit_clean_index + 1) % TRANSMISSION_DESCRIPTOR_LIST_SIZE;
+        }
+    }
+
+    // returns the next full packet the card wrote back, or None if there is nothing ready yet
+    // a packet can span several descriptors, we only consume them once the last one has END_OF_PACKET
+    unsafe fn receive_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        let descriptor_list = &mut self.receive_descriptors.receive_descriptor_list;
+
+        let mut descriptor_count = 0;
+        loop {
+            if descriptor_count == RECEIVE_DESCRIPTOR_LIST_SIZE {
+                return Ok(None);
+            }
+
+            let descriptor = read_volatile(
+                &descriptor_list
+                    [(self.receive_index + descriptor_count) % RECEIVE_DESCRIPTOR_LIST_SIZE],
+            );
+
+            if !descriptor
+                .status
+                .contains(ReceiveStatusRegister::DESCRIPTOR_DONE)
+            {
+                return Ok(None);
+            }
+
+            descriptor_count += 1;
+
+            if descriptor
+                .status
+                .contains(ReceiveStatusRegister::END_OF_PACKET)
+            {
+                break;
+            }
+        }
+
+        let mut packet = Vec::new();
+        let mut errors = ReceiveErrorRegister::empty();
+
+        for _ in 0..descriptor_count {
+            let mut descriptor = read_volatile(&descriptor_list[self.receive_index]);
+
+            errors |= descriptor.errors;
+            packet.extend_from_slice(core::slice::from_raw_parts(
+                physical_to_virtual(descriptor.base_address as usize) as *const u8,
+                descriptor.length as usize,
+            ));
+
+            // recycle the buffer and hand the descriptor back to the card
+            descriptor.status = ReceiveStatusRegister::empty();
+            descriptor.errors = ReceiveErrorRegister::empty();
+            descriptor.length = 0;
+            write_volatile(&mut descriptor_list[self.receive_index], descriptor);
+
+            RECEIVE_DESCRIPTOR_BASE_TAIL.write(&mut self.mmio_space, self.receive_index as u32);
+            self.receive_index = (self.receive_index + 1) % RECEIVE_DESCRIPTOR_LIST_SIZE;
+        }
+
+        if !errors.is_empty() {
+            self.statistics.receive_errors += 1;
+            return Err(NetworkError::E1000ReceiveError(errors));
+        }
+
+        self.statistics.received_frames += 1;
+        self.statistics.received_bytes += packet.len();
+
+        Ok(Some(packet))
+    }
+}
+
+impl NetworkDevice for E1000Driver {
+    fn name(&self) -> &'static str {
+        "e1000"
+    }
+
+    fn ethernet_address(&self) -> EthernetAddress {
+        self.ethernet_address
+    }
+
+    fn link_up(&self) -> bool {
+        self.link_up
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        // the card checks and strips the frame check sequence on its own
+        DeviceCapabilities::empty()
+    }
+
+    fn statistics(&self) -> DeviceStatistics {
+        self.statistics
+    }
+
+    fn can_transmit(&self, frame: &[u8]) -> bool {
+        frame.is_empty() || frame.len() > MAX_TRANSMIT_LENGTH || self.has_transmit_room(frame)
+    }
+
+    fn transmit_frame(&mut self, frame: Vec<u8>) -> Result<()> {
+        self.transmit_packet(frame)
+    }
+
+    fn receive_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        unsafe { self.receive_packet() }
+    }
+}
+
+fn split_at_page_boundaries(packet: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut remaining = packet;
+
+    core::iter::from_fn(move || {
+        if remaining.is_empty() {
+            return None;
+        }
+
+        let page_offset = remaining.as_ptr().addr() % TRANSMIT_CHUNK_SIZE;
+        let (chunk, rest) =
+            remaining.split_at(remaining.len().min(TRANSMIT_CHUNK_SIZE - page_offset));
+        remaining = rest;
+
+        Some(chunk)
+    })
+}
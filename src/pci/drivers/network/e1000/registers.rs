@@ -118,6 +118,9 @@ pub struct InterruptMaskRegister {
     __: B15,
 }
 
+// ICR, ICS, IMS and IMC all share the same layout
+pub type InterruptCauseRegister = InterruptMaskRegister;
+
 #[bitfield]
 #[repr(packed, C)]
 #[derive(Debug)]
@@ -261,8 +264,12 @@ pub const DEVICE_STATUS: MemoryMappedRegister<DeviceStatusRegister> =
 
 pub const EEPROM: MemoryMappedRegister<EepromReadRegister> = MemoryMappedRegister::new(0x00014);
 
+pub const INTERRUPT_CAUSE_READ: MemoryMappedRegister<InterruptCauseRegister> =
+    MemoryMappedRegister::new(0x000C0);
 pub const INTERRUPT_MASK: MemoryMappedRegister<InterruptMaskRegister> =
     MemoryMappedRegister::new(0x000D0);
+pub const INTERRUPT_MASK_CLEAR: MemoryMappedRegister<InterruptMaskRegister> =
+    MemoryMappedRegister::new(0x000D8);
 
 pub const RECEIVE_CONTROL_REGISTER: MemoryMappedRegister<ReceiveControlRegister> =
     MemoryMappedRegister::new(0x00100);
@@ -0,0 +1,130 @@
+#![allow(dead_code)]
+
+use bitflags::bitflags;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct CommandBlockStatus: u16 {
+        const OK = 1 << 13;
+        const COMPLETE = 1 << 15;
+    }
+
+    #[derive(Default)]
+    pub struct CommandBlockCommand: u16 {
+        const INDIVIDUAL_ADDRESS_SETUP = 1;
+        const CONFIGURE = 2;
+        const TRANSMIT = 4;
+        // the transmit data lives in a buffer descriptor array instead of inside the block
+        const FLEXIBLE_MODE = 1 << 3;
+        const INTERRUPT = 1 << 13;
+        const SUSPEND = 1 << 14;
+        const END_OF_LIST = 1 << 15;
+    }
+
+    #[derive(Default)]
+    pub struct ReceiveFrameStatus: u16 {
+        const COLLISION = 1 << 0;
+        const NO_ADDRESS_MATCH = 1 << 1;
+        const RECEIVE_ERROR = 1 << 4;
+        const FRAME_TOO_SHORT = 1 << 7;
+        const DMA_OVERRUN = 1 << 8;
+        const NO_RESOURCES = 1 << 9;
+        const ALIGNMENT_ERROR = 1 << 10;
+        const CRC_ERROR = 1 << 11;
+        const OK = 1 << 13;
+        const COMPLETE = 1 << 15;
+    }
+
+    #[derive(Default)]
+    pub struct ReceiveFrameCommand: u16 {
+        const SUSPEND = 1 << 14;
+        const END_OF_LIST = 1 << 15;
+    }
+}
+
+pub const ACTUAL_COUNT_MASK: u16 = 0x3FFF;
+pub const NO_TRANSMIT_BUFFER_ARRAY: u32 = u32::MAX;
+pub const TRANSMIT_THRESHOLD: u8 = 0xE0;
+
+// the 22 configuration bytes recommended by the 8255x developer manual (6.4.2.3),
+// byte 15 accepts broadcasts and byte 21 accepts every multicast
+pub const CONFIGURATION: [u8; 22] = [
+    0x16, 0x08, 0x00, 0x00, 0x00, 0x80, 0x32, 0x03, 0x01, 0x00, 0x2E, 0x00, 0x60, 0x00, 0xF2, 0xC8,
+    0x00, 0x40, 0xF2, 0x80, 0x3F, 0x0D,
+];
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub struct TransmitBufferDescriptor {
+    pub buffer_address: u32,
+    pub size: u16,
+    pub reserved: u16,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub union CommandBlockParameters {
+    pub individual_address: [u8; 6],
+    pub configuration: [u8; 22],
+    pub transmit: TransmitParameters,
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub struct TransmitParameters {
+    pub buffer_array_address: u32,
+    pub byte_count: u16,
+    pub threshold: u8,
+    pub buffer_count: u8,
+    // we always send a frame from a single buffer so the array lives right inside the block
+    pub buffer: TransmitBufferDescriptor,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+pub struct CommandBlock {
+    pub status: CommandBlockStatus,
+    pub command: CommandBlockCommand,
+    pub link: u32,
+    pub parameters: CommandBlockParameters,
+}
+
+impl CommandBlock {
+    pub const fn empty() -> Self {
+        Self {
+            status: CommandBlockStatus::empty(),
+            command: CommandBlockCommand::empty(),
+            link: 0,
+            parameters: CommandBlockParameters {
+                configuration: [0; 22],
+            },
+        }
+    }
+}
+
+pub const RECEIVE_FRAME_DATA_SIZE: usize = 1518;
+
+#[repr(C, align(16))]
+pub struct ReceiveFrameDescriptor {
+    pub status: ReceiveFrameStatus,
+    pub command: ReceiveFrameCommand,
+    pub link: u32,
+    pub reserved: u32,
+    pub actual_count: u16,
+    pub size: u16,
+    pub data: [u8; RECEIVE_FRAME_DATA_SIZE],
+}
+
+impl ReceiveFrameDescriptor {
+    pub const fn empty() -> Self {
+        Self {
+            status: ReceiveFrameStatus::empty(),
+            command: ReceiveFrameCommand::empty(),
+            link: 0,
+            reserved: u32::MAX,
+            actual_count: 0,
+            size: RECEIVE_FRAME_DATA_SIZE as u16,
+            data: [0; RECEIVE_FRAME_DATA_SIZE],
+        }
+    }
+}
@@ -0,0 +1,535 @@
+// https://www.intel.com/content/dam/doc/manual/8255x-10-100-mbps-ethernet-controller-software-dev-manual.pdf
+// https://wiki.osdev.org/Intel_8255x
+
+use core::{
+    hint,
+    ptr::{addr_of, addr_of_mut, read_volatile, write_volatile},
+    sync::atomic::{fence, Ordering},
+};
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use crate::{
+    memory::virtual_to_physical,
+    mutex::Mutex,
+    network_stack::{
+        ethernet::EthernetAddress,
+        interface::{register_interface, RECEIVE_EVENT, TRANSMIT_EVENT},
+    },
+    pci::config_space::{base_address_register::MemorySpace, BaseAddressRegister, PciConfigSpace},
+    println,
+    x86::interrupts::irq::register_irq_handler,
+};
+
+use self::{
+    descriptors::{
+        CommandBlock, CommandBlockCommand, CommandBlockParameters, CommandBlockStatus,
+        ReceiveFrameCommand, ReceiveFrameDescriptor, ReceiveFrameStatus, TransmitBufferDescriptor,
+        TransmitParameters, ACTUAL_COUNT_MASK, CONFIGURATION, RECEIVE_FRAME_DATA_SIZE,
+        TRANSMIT_THRESHOLD,
+    },
+    registers::{
+        CommandUnitCommand, EepromControlRegister, ReceiveUnitCommand, StatAckRegister,
+        DEFAULT_PHY_ADDRESS, EEPROM_CONTROL, EEPROM_ETHERNET_ADDRESS_OFFSET, EEPROM_READ_OPCODE,
+        MDI_CONTROL, MDI_DATA_MASK, MDI_PHY_ADDRESS_SHIFT, MDI_READY, MDI_READ_OPCODE,
+        MDI_REGISTER_SHIFT, PHY_STATUS_LINK_UP, PHY_STATUS_REGISTER, PORT, PORT_SOFTWARE_RESET,
+        RECEIVE_UNIT_READY, RECEIVE_UNIT_STATUS_MASK, SCB_COMMAND, SCB_GENERAL_POINTER,
+        SCB_INTERRUPT_MASK, SCB_STATUS, SCB_STAT_ACK,
+    },
+};
+
+use super::{
+    super::{DriverError, PciDriver},
+    DeviceCapabilities, DeviceStatistics, NetworkDevice, NetworkError, Result,
+};
+
+pub(super) mod descriptors;
+mod registers;
+
+const COMMAND_BLOCK_LIST_SIZE: usize = 32;
+const RECEIVE_FRAME_AREA_SIZE: usize = 32;
+// a transmit buffer descriptor has 14 bits for the size, but a frame never gets close to that
+const MAX_TRANSMIT_LENGTH: usize = 1514;
+
+// the 82557, 82558, 82559 and most of their variants
+pub const I82557_DRIVER_ENTRY: PciDriver = PciDriver {
+    vendor_id: 0x8086,
+    device_id: 0x1229,
+    init_device: init_i8255x,
+};
+
+// the 82559ER, which is also the id qemu uses for its 82550 and 82551
+pub const I82559ER_DRIVER_ENTRY: PciDriver = PciDriver {
+    vendor_id: 0x8086,
+    device_id: 0x1209,
+    init_device: init_i8255x,
+};
+
+pub struct I8255xDriver {
+    mmio_space: MemorySpace,
+    ethernet_address: EthernetAddress,
+    // the blocks are linked in a ring, the last queued one has SUSPEND set so the command unit stops there
+    command_blocks: Vec<CommandBlock>,
+    command_unit_started: bool,
+    transmit_tail: usize,
+    transmit_clean_index: usize,
+    transmit_buffers: [Option<Vec<u8>>; COMMAND_BLOCK_LIST_SIZE],
+    // the frames are linked in a ring too, the one before receive_index is marked END_OF_LIST
+    receive_frames: Vec<ReceiveFrameDescriptor>,
+    receive_index: usize,
+    link_up: bool,
+    statistics: DeviceStatistics,
+}
+
+// the mmio pointer and the blocks the card links to are only touched with the driver locked
+unsafe impl Send for I8255xDriver {}
+
+pub fn init_i8255x(pci: &mut PciConfigSpace) -> core::result::Result<(), DriverError> {
+    println!(
+        "Found 8255x card (vendor_id:{:#X} , device_id: {:#X}), initializing network card...",
+        pci.vendor_id, pci.device_id
+    );
+
+    let driver = Arc::new(Mutex::new(unsafe { I8255xDriver::new(pci)? }));
+
+    let interrupt_driver = driver.clone();
+    register_irq_handler(
+        pci.get_interrupt_line(),
+        Box::new(move || interrupt_driver.lock().handle_interrupt()),
+    );
+
+    println!(
+        "8255x initialized!, my ethernet address is: {}",
+        driver.lock().ethernet_address
+    );
+
+    register_interface(driver);
+
+    Ok(())
+}
+
+impl I8255xDriver {
+    unsafe fn new(pci: &mut PciConfigSpace) -> core::result::Result<Self, DriverError> {
+        let BaseAddressRegister::MemorySpace(memory_space) = pci.base_address_registers[0] else {
+            return Err(DriverError::UnexpectedBaseRegisterLayout {
+                register: pci.base_address_registers[0],
+                index: 0,
+            });
+        };
+
+        let mut new_driver = I8255xDriver {
+            mmio_space: memory_space,
+            ethernet_address: EthernetAddress { bytes: [0; 6] },
+            command_blocks: (0..COMMAND_BLOCK_LIST_SIZE)
+                .map(|_| CommandBlock::empty())
+                .collect(),
+            command_unit_started: false,
+            transmit_tail: 0,
+            transmit_clean_index: 0,
+            transmit_buffers: core::array::from_fn(|_| None),
+            receive_frames: (0..RECEIVE_FRAME_AREA_SIZE)
+                .map(|_| ReceiveFrameDescriptor::empty())
+                .collect(),
+            receive_index: 0,
+            link_up: false,
+            statistics: DeviceStatistics::default(),
+        };
+
+        new_driver.reset();
+        new_driver.read_ethernet_address();
+        new_driver.setup_card();
+        new_driver.init_transmit();
+        new_driver.init_receive();
+        new_driver.update_link_status();
+
+        // every interrupt we care about is unmasked
+        SCB_INTERRUPT_MASK.write(&mut new_driver.mmio_space, 0);
+
+        Ok(new_driver)
+    }
+
+    unsafe fn reset(&mut self) {
+        PORT.write(&mut self.mmio_space, PORT_SOFTWARE_RESET);
+        // the card needs 10us after a reset, reading a register takes long enough
+        for _ in 0..100 {
+            SCB_STAT_ACK.read(&self.mmio_space);
+        }
+
+        // reset leaves interrupts unmasked, keep them quiet until the rings are ready
+        SCB_INTERRUPT_MASK.write(&mut self.mmio_space, 1);
+
+        // all the pointers we hand the card are physical addresses, so both bases are zero
+        SCB_GENERAL_POINTER.write(&mut self.mmio_space, 0);
+        self.issue_command(CommandUnitCommand::LoadBase as u8);
+        self.issue_command(ReceiveUnitCommand::LoadBase as u8);
+    }
+
+    // the card clears the command byte once it accepted the command
+    unsafe fn issue_command(&mut self, command: u8) {
+        while SCB_COMMAND.read(&self.mmio_space) != 0 {
+            hint::spin_loop();
+        }
+
+        SCB_COMMAND.write(&mut self.mmio_space, command);
+
+        while SCB_COMMAND.read(&self.mmio_space) != 0 {
+            hint::spin_loop();
+        }
+    }
+
+    unsafe fn read_ethernet_address(&mut self) {
+        // the address width depends on the eeprom size, the first read finds it out
+        let mut address_length = 8;
+        self.read_eeprom(EEPROM_ETHERNET_ADDRESS_OFFSET, &mut address_length);
+
+        for word in 0..3 {
+            let value =
+                self.read_eeprom(EEPROM_ETHERNET_ADDRESS_OFFSET + word, &mut address_length);
+            self.ethernet_address.bytes[word as usize * 2..word as usize * 2 + 2]
+                .copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    // the eeprom is a microwire serial device which we clock by hand, section 6.3.4 of the manual
+    unsafe fn read_eeprom(&mut self, address: u8, address_length: &mut u32) -> u16 {
+        let command = ((EEPROM_READ_OPCODE << *address_length) | address as u32) << 16;
+        let mut data: u32 = 0;
+
+        EEPROM_CONTROL.write(
+            &mut self.mmio_space,
+            EepromControlRegister::CHIP_SELECT.bits(),
+        );
+
+        let mut bit = 31;
+        loop {
+            let mut control = EepromControlRegister::CHIP_SELECT;
+            control.set(EepromControlRegister::DATA_IN, command & (1 << bit) != 0);
+
+            EEPROM_CONTROL.write(&mut self.mmio_space, control.bits());
+            EEPROM_CONTROL.write(
+                &mut self.mmio_space,
+                (control | EepromControlRegister::SERIAL_CLOCK).bits(),
+            );
+
+            let data_out =
+                EepromControlRegister::from_bits_truncate(EEPROM_CONTROL.read(&self.mmio_space))
+                    .contains(EepromControlRegister::DATA_OUT);
+
+            // the eeprom drives a dummy zero right after the last address bit it expects
+            if !data_out && bit > 16 {
+                *address_length -= bit - 16;
+                bit = 17;
+            }
+
+            data = (data << 1) | data_out as u32;
+
+            if bit == 0 {
+                break;
+            }
+            bit -= 1;
+        }
+
+        EEPROM_CONTROL.write(&mut self.mmio_space, 0);
+
+        data as u16
+    }
+
+    unsafe fn read_phy_register(&mut self, register: u32) -> u16 {
+        MDI_CONTROL.write(
+            &mut self.mmio_space,
+            MDI_READ_OPCODE
+                | (DEFAULT_PHY_ADDRESS << MDI_PHY_ADDRESS_SHIFT)
+                | (register << MDI_REGISTER_SHIFT),
+        );
+
+        loop {
+            let value = MDI_CONTROL.read(&self.mmio_space);
+            if value & MDI_READY != 0 {
+                return (value & MDI_DATA_MASK) as u16;
+            }
+            hint::spin_loop();
+        }
+    }
+
+    unsafe fn update_link_status(&mut self) {
+        self.link_up = self.read_phy_register(PHY_STATUS_REGISTER) as u32 & PHY_STATUS_LINK_UP != 0;
+    }
+
+    // runs the individual address setup and configure commands before the command unit is used for transmitting
+    unsafe fn setup_card(&mut self) {
+        let mut address_setup = CommandBlock::empty();
+        address_setup.parameters = CommandBlockParameters {
+            individual_address: self.ethernet_address.bytes,
+        };
+        self.run_command(
+            &mut address_setup,
+            CommandBlockCommand::INDIVIDUAL_ADDRESS_SETUP,
+        );
+
+        let mut configure = CommandBlock::empty();
+        configure.parameters = CommandBlockParameters {
+            configuration: CONFIGURATION,
+        };
+        self.run_command(&mut configure, CommandBlockCommand::CONFIGURE);
+    }
+
+    unsafe fn run_command(&mut self, block: &mut CommandBlock, command: CommandBlockCommand) {
+        block.command = command | CommandBlockCommand::END_OF_LIST;
+
+        let block_address = virtual_to_physical(block as *mut CommandBlock as usize);
+        SCB_GENERAL_POINTER.write(&mut self.mmio_space, block_address as u32);
+        self.issue_command(CommandUnitCommand::Start as u8);
+
+        while !read_volatile(addr_of!(block.status)).contains(CommandBlockStatus::COMPLETE) {
+            hint::spin_loop();
+        }
+    }
+
+    fn init_transmit(&mut self) {
+        for index in 0..COMMAND_BLOCK_LIST_SIZE {
+            let next = &self.command_blocks[(index + 1) % COMMAND_BLOCK_LIST_SIZE]
+                as *const CommandBlock as usize;
+            self.command_blocks[index].link = virtual_to_physical(next) as u32;
+        }
+    }
+
+    fn init_receive(&mut self) {
+        for index in 0..RECEIVE_FRAME_AREA_SIZE {
+            let next = &self.receive_frames[(index + 1) % RECEIVE_FRAME_AREA_SIZE]
+                as *const ReceiveFrameDescriptor as usize;
+            self.receive_frames[index].link = virtual_to_physical(next) as u32;
+        }
+
+        self.receive_frames[RECEIVE_FRAME_AREA_SIZE - 1].command = ReceiveFrameCommand::END_OF_LIST;
+
+        unsafe { self.start_receive_unit() };
+    }
+
+    unsafe fn start_receive_unit(&mut self) {
+        let first_frame =
+            &self.receive_frames[self.receive_index] as *const ReceiveFrameDescriptor as usize;
+
+        SCB_GENERAL_POINTER.write(
+            &mut self.mmio_space,
+            virtual_to_physical(first_frame) as u32,
+        );
+        self.issue_command(ReceiveUnitCommand::Start as u8);
+    }
+
+    fn handle_interrupt(&mut self) {
+        let status = unsafe {
+            let status = StatAckRegister::from_bits_truncate(SCB_STAT_ACK.read(&self.mmio_space));
+            // the stat/ack byte is write one to clear
+            SCB_STAT_ACK.write(&mut self.mmio_space, status.bits());
+            status
+        };
+
+        if status.contains(StatAckRegister::FRAME_RECEIVED) {
+            RECEIVE_EVENT.signal();
+        }
+
+        // we ran out of frames, everything received is still waiting in the ring
+        if status.contains(StatAckRegister::RECEIVE_NOT_READY) {
+            self.statistics.receive_overruns += 1;
+            println!("8255x receive unit ran out of frames, packets were dropped");
+            RECEIVE_EVENT.signal();
+        }
+
+        if status.intersects(
+            StatAckRegister::COMMAND_EXECUTED | StatAckRegister::COMMAND_UNIT_NOT_ACTIVE,
+        ) {
+            self.reclaim_command_blocks();
+            TRANSMIT_EVENT.signal();
+        }
+    }
+
+    fn reclaim_command_blocks(&mut self) {
+        while self.transmit_clean_index != self.transmit_tail {
+            let block = &self.command_blocks[self.transmit_clean_index];
+            let status = unsafe { read_volatile(addr_of!(block.status)) };
+
+            if !status.contains(CommandBlockStatus::COMPLETE) {
+                break;
+            }
+
+            if !status.contains(CommandBlockStatus::OK) {
+                self.statistics.transmit_errors += 1;
+            }
+
+            let packet = self.transmit_buffers[self.transmit_clean_index]
+                .take()
+                .unwrap();
+
+            self.statistics.transmitted_frames += 1;
+            self.statistics.transmitted_bytes += packet.len();
+
+            self.transmit_clean_index = (self.transmit_clean_index + 1) % COMMAND_BLOCK_LIST_SIZE;
+        }
+    }
+
+    fn has_transmit_room(&self) -> bool {
+        // one block always stays free, a full ring would look exactly like an empty one
+        (self.transmit_tail + 1) % COMMAND_BLOCK_LIST_SIZE != self.transmit_clean_index
+    }
+
+    unsafe fn transmit_packet(&mut self, frame: Vec<u8>) {
+        let previous = (self.transmit_tail + COMMAND_BLOCK_LIST_SIZE - 1) % COMMAND_BLOCK_LIST_SIZE;
+
+        let block = &mut self.command_blocks[self.transmit_tail];
+        let buffer_array =
+            virtual_to_physical(addr_of!(block.parameters.transmit.buffer).addr()) as u32;
+
+        write_volatile(addr_of_mut!(block.status), CommandBlockStatus::empty());
+        block.parameters = CommandBlockParameters {
+            transmit: TransmitParameters {
+                buffer_array_address: buffer_array,
+                // the whole frame is in the buffer descriptor, nothing is stored inside the block
+                byte_count: 0,
+                threshold: TRANSMIT_THRESHOLD,
+                buffer_count: 1,
+                buffer: TransmitBufferDescriptor {
+                    buffer_address: virtual_to_physical(frame.as_ptr().addr()) as u32,
+                    size: frame.len() as u16,
+                    reserved: 0,
+                },
+            },
+        };
+        write_volatile(
+            addr_of_mut!(block.command),
+            CommandBlockCommand::TRANSMIT
+                | CommandBlockCommand::FLEXIBLE_MODE
+                | CommandBlockCommand::INTERRUPT
+                | CommandBlockCommand::SUSPEND,
+        );
+
+        // the block has to be complete in memory before the card may follow the link into it
+        fence(Ordering::SeqCst);
+
+        // let the command unit carry on from the previous block into the new one
+        let previous_block = &mut self.command_blocks[previous];
+        let previous_command = read_volatile(addr_of!(previous_block.command));
+        write_volatile(
+            addr_of_mut!(previous_block.command),
+            previous_command - CommandBlockCommand::SUSPEND,
+        );
+
+        if self.command_unit_started {
+            // resume is ignored if the unit did not reach the old suspend point yet
+            self.issue_command(CommandUnitCommand::Resume as u8);
+        } else {
+            let first_block =
+                &self.command_blocks[self.transmit_tail] as *const CommandBlock as usize;
+            SCB_GENERAL_POINTER.write(
+                &mut self.mmio_space,
+                virtual_to_physical(first_block) as u32,
+            );
+            self.issue_command(CommandUnitCommand::Start as u8);
+            self.command_unit_started = true;
+        }
+
+        self.transmit_buffers[self.transmit_tail] = Some(frame);
+        self.transmit_tail = (self.transmit_tail + 1) % COMMAND_BLOCK_LIST_SIZE;
+    }
+
+    unsafe fn receive_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        let frame = &mut self.receive_frames[self.receive_index];
+        let status = read_volatile(addr_of!(frame.status));
+
+        if !status.contains(ReceiveFrameStatus::COMPLETE) {
+            return Ok(None);
+        }
+
+        let length = (read_volatile(addr_of!(frame.actual_count)) & ACTUAL_COUNT_MASK) as usize;
+
+        let result = if status.contains(ReceiveFrameStatus::OK) && length <= RECEIVE_FRAME_DATA_SIZE
+        {
+            let packet = frame.data[..length].to_vec();
+
+            self.statistics.received_frames += 1;
+            self.statistics.received_bytes += packet.len();
+
+            Ok(Some(packet))
+        } else {
+            self.statistics.receive_errors += 1;
+            Err(NetworkError::I8255xReceiveError(status))
+        };
+
+        // the consumed frame becomes the new end of the list and the old end is released to the card
+        write_volatile(addr_of_mut!(frame.status), ReceiveFrameStatus::empty());
+        write_volatile(addr_of_mut!(frame.actual_count), 0);
+        write_volatile(
+            addr_of_mut!(frame.command),
+            ReceiveFrameCommand::END_OF_LIST,
+        );
+
+        let previous = (self.receive_index + RECEIVE_FRAME_AREA_SIZE - 1) % RECEIVE_FRAME_AREA_SIZE;
+        let previous_frame = &mut self.receive_frames[previous];
+        write_volatile(
+            addr_of_mut!(previous_frame.command),
+            ReceiveFrameCommand::empty(),
+        );
+
+        self.receive_index = (self.receive_index + 1) % RECEIVE_FRAME_AREA_SIZE;
+
+        // the unit stops once it fills the frame marked END_OF_LIST, restart it once the ring is drained
+        let next_frame = &self.receive_frames[self.receive_index];
+        if !read_volatile(addr_of!(next_frame.status)).contains(ReceiveFrameStatus::COMPLETE)
+            && (SCB_STATUS.read(&self.mmio_space) & RECEIVE_UNIT_STATUS_MASK) != RECEIVE_UNIT_READY
+        {
+            self.start_receive_unit();
+        }
+
+        result
+    }
+}
+
+impl NetworkDevice for I8255xDriver {
+    fn name(&self) -> &'static str {
+        "i8255x"
+    }
+
+    fn ethernet_address(&self) -> EthernetAddress {
+        self.ethernet_address
+    }
+
+    fn link_up(&self) -> bool {
+        self.link_up
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        // the configuration leaves CRC transfer off, so frames come without their FCS
+        DeviceCapabilities::empty()
+    }
+
+    fn statistics(&self) -> DeviceStatistics {
+        self.statistics
+    }
+
+    fn can_transmit(&self, frame: &[u8]) -> bool {
+        frame.is_empty() || frame.len() > MAX_TRANSMIT_LENGTH || self.has_transmit_room()
+    }
+
+    fn transmit_frame(&mut self, frame: Vec<u8>) -> Result<()> {
+        if frame.len() > MAX_TRANSMIT_LENGTH {
+            return Err(NetworkError::BufferTooLarge);
+        }
+
+        if frame.is_empty() {
+            return Err(NetworkError::EmptyBuffer);
+        }
+
+        self.reclaim_command_blocks();
+
+        if !self.has_transmit_room() {
+            return Err(NetworkError::FullTransmissionsQueue);
+        }
+
+        unsafe { self.transmit_packet(frame) };
+
+        Ok(())
+    }
+
+    fn receive_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        unsafe { self.receive_packet() }
+    }
+}
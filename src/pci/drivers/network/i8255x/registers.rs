@@ -0,0 +1,84 @@
+#![allow(dead_code)]
+
+use bitflags::bitflags;
+
+use crate::pci::config_space::MemoryMappedRegister;
+
+bitflags! {
+    // the upper byte of the SCB status word, bits are acknowledged by writing 1 to them
+    pub struct StatAckRegister: u8 {
+        const FLOW_CONTROL_PAUSE = 1 << 0;
+        const SOFTWARE_INTERRUPT = 1 << 2;
+        const MDI_DONE = 1 << 3;
+        const RECEIVE_NOT_READY = 1 << 4;
+        const COMMAND_UNIT_NOT_ACTIVE = 1 << 5;
+        const FRAME_RECEIVED = 1 << 6;
+        const COMMAND_EXECUTED = 1 << 7;
+    }
+
+    // setting a bit masks the matching interrupt
+    pub struct InterruptMaskRegister: u8 {
+        const MASK_ALL = 1 << 0;
+        const SOFTWARE_INTERRUPT = 1 << 1;
+        const FLOW_CONTROL_PAUSE = 1 << 2;
+        const EARLY_RECEIVE = 1 << 3;
+        const RECEIVE_NOT_READY = 1 << 4;
+        const COMMAND_UNIT_NOT_ACTIVE = 1 << 5;
+        const FRAME_RECEIVED = 1 << 6;
+        const COMMAND_EXECUTED = 1 << 7;
+    }
+
+    pub struct EepromControlRegister: u8 {
+        const SERIAL_CLOCK = 1 << 0;
+        const CHIP_SELECT = 1 << 1;
+        const DATA_IN = 1 << 2;
+        const DATA_OUT = 1 << 3;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum CommandUnitCommand {
+    Nop = 0x00,
+    Start = 0x10,
+    Resume = 0x20,
+    LoadBase = 0x60,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum ReceiveUnitCommand {
+    Nop = 0x00,
+    Start = 0x01,
+    Resume = 0x02,
+    Abort = 0x04,
+    LoadBase = 0x06,
+}
+
+// the receive unit state lives in bits 2-5 of the scb status byte
+pub const RECEIVE_UNIT_STATUS_MASK: u8 = 0b1111 << 2;
+pub const RECEIVE_UNIT_READY: u8 = 0b0100 << 2;
+
+pub const PORT_SOFTWARE_RESET: u32 = 0x0;
+
+pub const MDI_READY: u32 = 1 << 28;
+pub const MDI_READ_OPCODE: u32 = 0b10 << 26;
+pub const MDI_PHY_ADDRESS_SHIFT: u32 = 21;
+pub const MDI_REGISTER_SHIFT: u32 = 16;
+pub const MDI_DATA_MASK: u32 = 0xFFFF;
+pub const DEFAULT_PHY_ADDRESS: u32 = 1;
+pub const PHY_STATUS_REGISTER: u32 = 1;
+pub const PHY_STATUS_LINK_UP: u32 = 1 << 2;
+
+// opcode for a serial eeprom read, it is 3 bits wide and starts with the start bit
+pub const EEPROM_READ_OPCODE: u32 = 0b110;
+pub const EEPROM_ETHERNET_ADDRESS_OFFSET: u8 = 0x0;
+
+pub const SCB_STATUS: MemoryMappedRegister<u8> = MemoryMappedRegister::new(0x00);
+pub const SCB_STAT_ACK: MemoryMappedRegister<u8> = MemoryMappedRegister::new(0x01);
+pub const SCB_COMMAND: MemoryMappedRegister<u8> = MemoryMappedRegister::new(0x02);
+pub const SCB_INTERRUPT_MASK: MemoryMappedRegister<u8> = MemoryMappedRegister::new(0x03);
+pub const SCB_GENERAL_POINTER: MemoryMappedRegister<u32> = MemoryMappedRegister::new(0x04);
+pub const PORT: MemoryMappedRegister<u32> = MemoryMappedRegister::new(0x08);
+pub const EEPROM_CONTROL: MemoryMappedRegister<u8> = MemoryMappedRegister::new(0x0E);
+pub const MDI_CONTROL: MemoryMappedRegister<u32> = MemoryMappedRegister::new(0x10);
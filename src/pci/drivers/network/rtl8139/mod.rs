@@ -0,0 +1,415 @@
+// http://realtek.info/pdf/rtl8139d.pdf
+// https://wiki.osdev.org/RTL8139
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    hint,
+    ptr::read_volatile,
+};
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use crate::{
+    memory::{physical::global_alloc::ALLOCATOR, virtual_to_physical},
+    mutex::Mutex,
+    network_stack::{
+        ethernet::EthernetAddress,
+        interface::{register_interface, RECEIVE_EVENT, TRANSMIT_EVENT},
+    },
+    pci::config_space::{base_address_register::IoSpace, BaseAddressRegister, PciConfigSpace},
+    println,
+    x86::interrupts::irq::register_irq_handler,
+};
+
+use self::registers::{
+    CommandRegister, InterruptRegister, MediaStatusRegister, ReceiveConfigurationRegister,
+    ReceivePacketStatus, TransmitConfigurationRegister, TransmitStatusRegister, COMMAND, CONFIG_1,
+    CURRENT_ADDRESS_OF_PACKET_READ, ID_REGISTER_0, ID_REGISTER_4, INTERRUPT_MASK, INTERRUPT_STATUS,
+    MEDIA_STATUS, MULTICAST_REGISTER_0, MULTICAST_REGISTER_4, RECEIVE_BUFFER_START,
+    RECEIVE_CONFIGURATION, TRANSMIT_CONFIGURATION, TRANSMIT_SLOT_COUNT, TRANSMIT_START_ADDRESS,
+    TRANSMIT_STATUS,
+};
+
+use super::{
+    super::{DriverError, PciDriver},
+    DeviceCapabilities, DeviceStatistics, NetworkDevice, NetworkError, Result,
+};
+
+pub(super) mod registers;
+
+const RECEIVE_RING_SIZE: usize = 8192;
+// the card needs 16 bytes of slack after the ring, and since we set WRAP a whole packet may overflow past its end
+const RECEIVE_BUFFER_SIZE: usize = RECEIVE_RING_SIZE + 16 + 1536;
+// ethernet frame sizes with the CRC, anything outside is a broken frame
+const MIN_FRAME_LENGTH: usize = 64;
+const MAX_FRAME_LENGTH: usize = 1518;
+const RECEIVE_PACKET_HEADER_SIZE: usize = 4;
+// the transmit buffers are read straight into the card fifo, they can't be split
+const MAX_TRANSMIT_LENGTH: usize = 1792;
+// the card does not pad short frames on its own
+const MIN_TRANSMIT_LENGTH: usize = 60;
+
+pub const RTL8139_DRIVER_ENTRY: PciDriver = PciDriver {
+    vendor_id: 0x10EC,
+    device_id: 0x8139,
+    init_device: init_rtl8139,
+};
+
+pub struct Rtl8139Driver {
+    io_space: IoSpace,
+    ethernet_address: EthernetAddress,
+    receive_buffer: *mut u8,
+    // offset of the next packet header inside the receive ring
+    receive_offset: usize,
+    link_up: bool,
+    // the card sends the four slots round robin, a buffer is kept until its slot reports done
+    transmit_buffers: [Option<Vec<u8>>; TRANSMIT_SLOT_COUNT],
+    transmit_index: usize,
+    transmit_clean_index: usize,
+    statistics: DeviceStatistics,
+}
+
+// the receive ring is only read through the driver lock, the raw pointer doesn't tie it to a context
+unsafe impl Send for Rtl8139Driver {}
+
+pub fn init_rtl8139(pci: &mut PciConfigSpace) -> core::result::Result<(), DriverError> {
+    println!(
+        "Found RTL8139 card (vendor_id:{:#X} , device_id: {:#X}), initializing network card...",
+        pci.vendor_id, pci.device_id
+    );
+
+    let driver = Arc::new(Mutex::new(unsafe { Rtl8139Driver::new(pci)? }));
+
+    let interrupt_driver = driver.clone();
+    register_irq_handler(
+        pci.get_interrupt_line(),
+        Box::new(move || interrupt_driver.lock().handle_interrupt()),
+    );
+
+    println!(
+        "RTL8139 initialized!, my ethernet address is: {}",
+        driver.lock().ethernet_address
+    );
+
+    register_interface(driver);
+
+    Ok(())
+}
+
+impl Rtl8139Driver {
+    unsafe fn new(pci: &mut PciConfigSpace) -> core::result::Result<Self, DriverError> {
+        let BaseAddressRegister::IoSpace(io_space) = pci.base_address_registers[0] else {
+            return Err(DriverError::UnexpectedBaseRegisterLayout {
+                register: pci.base_address_registers[0],
+                index: 0,
+            });
+        };
+
+        let receive_buffer =
+            ALLOCATOR.alloc(Layout::from_size_align(RECEIVE_BUFFER_SIZE, 4).unwrap());
+        assert!(!receive_buffer.is_null(), "out of memory");
+
+        let mut new_driver = Rtl8139Driver {
+            io_space,
+            ethernet_address: EthernetAddress { bytes: [0; 6] },
+            receive_buffer,
+            receive_offset: 0,
+            link_up: false,
+            transmit_buffers: core::array::from_fn(|_| None),
+            transmit_index: 0,
+            transmit_clean_index: 0,
+            statistics: DeviceStatistics::default(),
+        };
+
+        new_driver.reset();
+        new_driver.read_ethernet_address();
+        new_driver.init_receive_and_transmit();
+        new_driver.update_link_status();
+
+        Ok(new_driver)
+    }
+
+    unsafe fn reset(&mut self) {
+        // wake the card up from its low power state
+        CONFIG_1.write(&mut self.io_space, 0);
+
+        COMMAND.write(&mut self.io_space, CommandRegister::RESET.bits());
+        while CommandRegister::from_bits_truncate(COMMAND.read(&self.io_space))
+            .contains(CommandRegister::RESET)
+        {
+            hint::spin_loop();
+        }
+    }
+
+    unsafe fn read_ethernet_address(&mut self) {
+        self.ethernet_address.bytes[..4]
+            .copy_from_slice(&ID_REGISTER_0.read(&self.io_space).to_le_bytes());
+        self.ethernet_address.bytes[4..]
+            .copy_from_slice(&ID_REGISTER_4.read(&self.io_space).to_le_bytes());
+    }
+
+    // the card starts filling the ring from its beginning again
+    unsafe fn rewind_receive_ring(&mut self) {
+        RECEIVE_BUFFER_START.write(
+            &mut self.io_space,
+            virtual_to_physical(self.receive_buffer.addr()) as u32,
+        );
+        // CAPR trails the real read offset by 16 bytes
+        CURRENT_ADDRESS_OF_PACKET_READ.write(&mut self.io_space, 0u16.wrapping_sub(16));
+        self.receive_offset = 0;
+    }
+
+    unsafe fn configure_receiver(&mut self) {
+        RECEIVE_CONFIGURATION.write(
+            &mut self.io_space,
+            (ReceiveConfigurationRegister::ACCEPT_ALL_PACKETS
+                | ReceiveConfigurationRegister::ACCEPT_PHYSICAL_MATCH
+                | ReceiveConfigurationRegister::ACCEPT_MULTICAST
+                | ReceiveConfigurationRegister::ACCEPT_BROADCAST
+                | ReceiveConfigurationRegister::WRAP
+                | ReceiveConfigurationRegister::UNLIMITED_DMA_BURST)
+                .bits(),
+        );
+    }
+
+    unsafe fn init_receive_and_transmit(&mut self) {
+        self.rewind_receive_ring();
+
+        MULTICAST_REGISTER_0.write(&mut self.io_space, u32::MAX);
+        MULTICAST_REGISTER_4.write(&mut self.io_space, u32::MAX);
+
+        // the configuration registers only stick once the receiver and transmitter are enabled
+        COMMAND.write(
+            &mut self.io_space,
+            (CommandRegister::RECEIVER_ENABLE | CommandRegister::TRANSMITTER_ENABLE).bits(),
+        );
+
+        self.configure_receiver();
+
+        TRANSMIT_CONFIGURATION.write(
+            &mut self.io_space,
+            (TransmitConfigurationRegister::DMA_BURST_2048
+                | TransmitConfigurationRegister::NORMAL_INTERFRAME_GAP)
+                .bits(),
+        );
+
+        // throw away causes left over from before we took the card
+        INTERRUPT_STATUS.write(&mut self.io_space, u16::MAX);
+        INTERRUPT_MASK.write(
+            &mut self.io_space,
+            (InterruptRegister::RECEIVE_OK
+                | InterruptRegister::RECEIVE_ERROR
+                | InterruptRegister::TRANSMIT_OK
+                | InterruptRegister::TRANSMIT_ERROR
+                | InterruptRegister::RECEIVE_BUFFER_OVERFLOW
+                | InterruptRegister::LINK_CHANGE
+                | InterruptRegister::RECEIVE_FIFO_OVERFLOW)
+                .bits(),
+        );
+    }
+
+    unsafe fn update_link_status(&mut self) {
+        self.link_up = !MediaStatusRegister::from_bits_truncate(MEDIA_STATUS.read(&self.io_space))
+            .contains(MediaStatusRegister::LINK_FAIL);
+    }
+
+    fn handle_interrupt(&mut self) {
+        let status = unsafe {
+            let status =
+                InterruptRegister::from_bits_truncate(INTERRUPT_STATUS.read(&self.io_space));
+            // ISR bits are cleared by writing a 1 to them, a 0 leaves them alone
+            INTERRUPT_STATUS.write(&mut self.io_space, status.bits());
+            status
+        };
+
+        if status.intersects(
+            InterruptRegister::RECEIVE_BUFFER_OVERFLOW | InterruptRegister::RECEIVE_FIFO_OVERFLOW,
+        ) {
+            self.statistics.receive_overruns += 1;
+            println!("rtl8139 receive overrun, packets were dropped");
+        }
+
+        if status.intersects(
+            InterruptRegister::RECEIVE_OK
+                | InterruptRegister::RECEIVE_ERROR
+                | InterruptRegister::RECEIVE_BUFFER_OVERFLOW
+                | InterruptRegister::RECEIVE_FIFO_OVERFLOW,
+        ) {
+            RECEIVE_EVENT.signal();
+        }
+
+        if status.intersects(InterruptRegister::TRANSMIT_OK | InterruptRegister::TRANSMIT_ERROR) {
+            self.reclaim_transmit_slots();
+            TRANSMIT_EVENT.signal();
+        }
+
+        if status.contains(InterruptRegister::LINK_CHANGE) {
+            unsafe { self.update_link_status() };
+            println!(
+                "rtl8139 link is {}",
+                if self.link_up { "up" } else { "down" }
+            );
+        }
+    }
+
+    fn reclaim_transmit_slots(&mut self) {
+        while self.transmit_buffers[self.transmit_clean_index].is_some() {
+            let status = TransmitStatusRegister::from_bits_truncate(unsafe {
+                TRANSMIT_STATUS[self.transmit_clean_index].read(&self.io_space)
+            });
+
+            if !status.intersects(
+                TransmitStatusRegister::TRANSMIT_OK | TransmitStatusRegister::TRANSMIT_ABORT,
+            ) {
+                break;
+            }
+
+            if status.intersects(
+                TransmitStatusRegister::TRANSMIT_ABORT
+                    | TransmitStatusRegister::OUT_OF_WINDOW_COLLISION,
+            ) {
+                self.statistics.transmit_errors += 1;
+            }
+
+            let packet = self.transmit_buffers[self.transmit_clean_index]
+                .take()
+                .unwrap();
+
+            self.statistics.transmitted_frames += 1;
+            self.statistics.transmitted_bytes += packet.len();
+
+            self.transmit_clean_index = (self.transmit_clean_index + 1) % TRANSMIT_SLOT_COUNT;
+        }
+    }
+
+    unsafe fn receive_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        if CommandRegister::from_bits_truncate(COMMAND.read(&self.io_space))
+            .contains(CommandRegister::BUFFER_EMPTY)
+        {
+            return Ok(None);
+        }
+
+        let header = self.receive_buffer.add(self.receive_offset);
+        let status = ReceivePacketStatus::from_bits_truncate(read_volatile(header as *const u16));
+        // the length includes the 4 byte CRC at the end of the frame
+        let length = read_volatile(header.add(2) as *const u16) as usize;
+
+        // a broken header's length can't be trusted to find the next packet either
+        if !status.contains(ReceivePacketStatus::RECEIVE_OK)
+            || !(MIN_FRAME_LENGTH..=MAX_FRAME_LENGTH).contains(&length)
+        {
+            self.statistics.receive_errors += 1;
+            self.reset_receiver();
+            return Err(NetworkError::Rtl8139ReceiveError(status));
+        }
+
+        let packet =
+            core::slice::from_raw_parts(header.add(RECEIVE_PACKET_HEADER_SIZE), length).to_vec();
+
+        self.statistics.received_frames += 1;
+        self.statistics.received_bytes += packet.len();
+
+        // packets are dword aligned inside the ring
+        self.receive_offset = ((self.receive_offset + length + RECEIVE_PACKET_HEADER_SIZE + 3)
+            & !3)
+            % RECEIVE_RING_SIZE;
+
+        CURRENT_ADDRESS_OF_PACKET_READ.write(
+            &mut self.io_space,
+            (self.receive_offset as u16).wrapping_sub(16),
+        );
+
+        Ok(Some(packet))
+    }
+
+    // the receiver is stopped and restarted on an empty ring, what was in it is lost.
+    // the same recovery as rtl8139_rx_err in linux
+    unsafe fn reset_receiver(&mut self) {
+        COMMAND.write(
+            &mut self.io_space,
+            CommandRegister::TRANSMITTER_ENABLE.bits(),
+        );
+        while CommandRegister::from_bits_truncate(COMMAND.read(&self.io_space))
+            .contains(CommandRegister::RECEIVER_ENABLE)
+        {
+            hint::spin_loop();
+        }
+
+        self.rewind_receive_ring();
+
+        COMMAND.write(
+            &mut self.io_space,
+            (CommandRegister::RECEIVER_ENABLE | CommandRegister::TRANSMITTER_ENABLE).bits(),
+        );
+        self.configure_receiver();
+    }
+}
+
+impl NetworkDevice for Rtl8139Driver {
+    fn name(&self) -> &'static str {
+        "rtl8139"
+    }
+
+    fn ethernet_address(&self) -> EthernetAddress {
+        self.ethernet_address
+    }
+
+    fn link_up(&self) -> bool {
+        self.link_up
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities::RECEIVES_FCS
+    }
+
+    fn statistics(&self) -> DeviceStatistics {
+        self.statistics
+    }
+
+    fn can_transmit(&self, frame: &[u8]) -> bool {
+        frame.is_empty()
+            || frame.len() > MAX_TRANSMIT_LENGTH
+            || self.transmit_buffers[self.transmit_index].is_none()
+    }
+
+    fn transmit_frame(&mut self, mut frame: Vec<u8>) -> Result<()> {
+        if frame.len() > MAX_TRANSMIT_LENGTH {
+            return Err(NetworkError::BufferTooLarge);
+        }
+
+        if frame.is_empty() {
+            return Err(NetworkError::EmptyBuffer);
+        }
+
+        self.reclaim_transmit_slots();
+
+        if self.transmit_buffers[self.transmit_index].is_some() {
+            return Err(NetworkError::FullTransmissionsQueue);
+        }
+
+        if frame.len() < MIN_TRANSMIT_LENGTH {
+            frame.resize(MIN_TRANSMIT_LENGTH, 0);
+        }
+
+        // the start address must be dword aligned, our allocator never hands out less than 8 byte blocks
+        debug_assert!(frame.as_ptr().addr() % 4 == 0);
+
+        unsafe {
+            TRANSMIT_START_ADDRESS[self.transmit_index].write(
+                &mut self.io_space,
+                virtual_to_physical(frame.as_ptr().addr()) as u32,
+            );
+            // writing the size clears OWN which hands the slot to the card
+            TRANSMIT_STATUS[self.transmit_index].write(&mut self.io_space, frame.len() as u32);
+        }
+
+        self.transmit_buffers[self.transmit_index] = Some(frame);
+        self.transmit_index = (self.transmit_index + 1) % TRANSMIT_SLOT_COUNT;
+
+        Ok(())
+    }
+
+    fn receive_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        unsafe { self.receive_packet() }
+    }
+}
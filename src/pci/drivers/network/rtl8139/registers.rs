@@ -0,0 +1,107 @@
+#![allow(dead_code)]
+
+use bitflags::bitflags;
+
+use crate::pci::config_space::IoSpaceRegister;
+
+bitflags! {
+    pub struct CommandRegister: u8 {
+        const BUFFER_EMPTY = 1 << 0;
+        const TRANSMITTER_ENABLE = 1 << 2;
+        const RECEIVER_ENABLE = 1 << 3;
+        const RESET = 1 << 4;
+    }
+
+    // IMR and ISR share the same layout, ISR bits are cleared by writing 1 to them
+    pub struct InterruptRegister: u16 {
+        const RECEIVE_OK = 1 << 0;
+        const RECEIVE_ERROR = 1 << 1;
+        const TRANSMIT_OK = 1 << 2;
+        const TRANSMIT_ERROR = 1 << 3;
+        const RECEIVE_BUFFER_OVERFLOW = 1 << 4;
+        const LINK_CHANGE = 1 << 5;
+        const RECEIVE_FIFO_OVERFLOW = 1 << 6;
+        const CABLE_LENGTH_CHANGE = 1 << 13;
+        const TIMEOUT = 1 << 14;
+        const SYSTEM_ERROR = 1 << 15;
+    }
+
+    pub struct ReceiveConfigurationRegister: u32 {
+        const ACCEPT_ALL_PACKETS = 1 << 0;
+        const ACCEPT_PHYSICAL_MATCH = 1 << 1;
+        const ACCEPT_MULTICAST = 1 << 2;
+        const ACCEPT_BROADCAST = 1 << 3;
+        const ACCEPT_RUNT = 1 << 4;
+        const ACCEPT_ERROR = 1 << 5;
+        // let a packet overflow past the end of the ring instead of wrapping it to the start
+        const WRAP = 1 << 7;
+        const UNLIMITED_DMA_BURST = 0b111 << 8;
+    }
+
+    pub struct TransmitConfigurationRegister: u32 {
+        const DMA_BURST_2048 = 0b111 << 8;
+        const NO_CRC = 1 << 16;
+        const NORMAL_INTERFRAME_GAP = 0b11 << 24;
+    }
+
+    pub struct TransmitStatusRegister: u32 {
+        const SIZE = 0x1FFF;
+        // set by the card once it copied the buffer into its fifo
+        const OWN = 1 << 13;
+        const TRANSMIT_FIFO_UNDERRUN = 1 << 14;
+        const TRANSMIT_OK = 1 << 15;
+        const OUT_OF_WINDOW_COLLISION = 1 << 29;
+        const TRANSMIT_ABORT = 1 << 30;
+        const CARRIER_SENSE_LOST = 1 << 31;
+    }
+
+    // the header the card writes in front of every packet in the receive ring
+    #[derive(Default)]
+    pub struct ReceivePacketStatus: u16 {
+        const RECEIVE_OK = 1 << 0;
+        const FRAME_ALIGNMENT_ERROR = 1 << 1;
+        const CRC_ERROR = 1 << 2;
+        const LONG_PACKET = 1 << 3;
+        const RUNT_PACKET = 1 << 4;
+        const INVALID_SYMBOL_ERROR = 1 << 5;
+        const BROADCAST = 1 << 13;
+        const PHYSICAL_ADDRESS_MATCHED = 1 << 14;
+        const MULTICAST = 1 << 15;
+    }
+
+    pub struct MediaStatusRegister: u8 {
+        // this bit is set when the link is down
+        const LINK_FAIL = 1 << 2;
+    }
+}
+
+pub const TRANSMIT_SLOT_COUNT: usize = 4;
+
+pub const ID_REGISTER_0: IoSpaceRegister<u32> = IoSpaceRegister::new(0x00);
+pub const ID_REGISTER_4: IoSpaceRegister<u16> = IoSpaceRegister::new(0x04);
+pub const MULTICAST_REGISTER_0: IoSpaceRegister<u32> = IoSpaceRegister::new(0x08);
+pub const MULTICAST_REGISTER_4: IoSpaceRegister<u32> = IoSpaceRegister::new(0x0C);
+
+pub const TRANSMIT_STATUS: [IoSpaceRegister<u32>; TRANSMIT_SLOT_COUNT] = [
+    IoSpaceRegister::new(0x10),
+    IoSpaceRegister::new(0x14),
+    IoSpaceRegister::new(0x18),
+    IoSpaceRegister::new(0x1C),
+];
+pub const TRANSMIT_START_ADDRESS: [IoSpaceRegister<u32>; TRANSMIT_SLOT_COUNT] = [
+    IoSpaceRegister::new(0x20),
+    IoSpaceRegister::new(0x24),
+    IoSpaceRegister::new(0x28),
+    IoSpaceRegister::new(0x2C),
+];
+
+pub const RECEIVE_BUFFER_START: IoSpaceRegister<u32> = IoSpaceRegister::new(0x30);
+pub const COMMAND: IoSpaceRegister<u8> = IoSpaceRegister::new(0x37);
+pub const CURRENT_ADDRESS_OF_PACKET_READ: IoSpaceRegister<u16> = IoSpaceRegister::new(0x38);
+pub const CURRENT_BUFFER_ADDRESS: IoSpaceRegister<u16> = IoSpaceRegister::new(0x3A);
+pub const INTERRUPT_MASK: IoSpaceRegister<u16> = IoSpaceRegister::new(0x3C);
+pub const INTERRUPT_STATUS: IoSpaceRegister<u16> = IoSpaceRegister::new(0x3E);
+pub const TRANSMIT_CONFIGURATION: IoSpaceRegister<u32> = IoSpaceRegister::new(0x40);
+pub const RECEIVE_CONFIGURATION: IoSpaceRegister<u32> = IoSpaceRegister::new(0x44);
+pub const CONFIG_1: IoSpaceRegister<u8> = IoSpaceRegister::new(0x52);
+pub const MEDIA_STATUS: IoSpaceRegister<u8> = IoSpaceRegister::new(0x58);
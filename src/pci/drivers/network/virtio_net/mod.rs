@@ -0,0 +1,308 @@
+// https://docs.oasis-open.org/virtio/virtio/v1.1/csprd01/virtio-v1.1-csprd01.html#x1-1940001
+
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+use bitflags::bitflags;
+
+use crate::{
+    mutex::Mutex,
+    network_stack::{
+        ethernet::EthernetAddress,
+        interface::{register_interface, RECEIVE_EVENT, TRANSMIT_EVENT},
+    },
+    pci::{
+        config_space::PciConfigSpace,
+        drivers::virtio::{
+            registers::InterruptStatus,
+            virtqueue::{Virtqueue, VirtqueueBuffer},
+            VirtioDevice, VIRTIO_VENDOR_ID,
+        },
+    },
+    println,
+    x86::interrupts::irq::register_irq_handler,
+};
+
+use super::{
+    super::{DriverError, PciDriver},
+    DeviceCapabilities, DeviceStatistics, NetworkDevice, NetworkError, Result,
+};
+
+bitflags! {
+    pub struct NetworkFeatures: u32 {
+        const MAC = 1 << 5;
+        const STATUS = 1 << 16;
+    }
+
+    pub struct NetworkStatus: u16 {
+        const LINK_UP = 1 << 0;
+    }
+}
+
+const RECEIVE_QUEUE: u16 = 0;
+const TRANSMIT_QUEUE: u16 = 1;
+
+const CONFIGURATION_MAC_OFFSET: usize = 0x0;
+const CONFIGURATION_STATUS_OFFSET: usize = 0x6;
+
+// every frame is prefixed with a header, we don't use any of the offloads so ours is always zero
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct VirtioNetHeader {
+    flags: u8,
+    gso_type: u8,
+    header_length: u16,
+    gso_size: u16,
+    checksum_start: u16,
+    checksum_offset: u16,
+}
+
+const HEADER_SIZE: usize = core::mem::size_of::<VirtioNetHeader>();
+const MAX_FRAME_LENGTH: usize = 1514;
+const RECEIVE_BUFFER_SIZE: usize = HEADER_SIZE + MAX_FRAME_LENGTH;
+
+// used when the device doesn't tell us its address, the locally administered bit is set
+const FALLBACK_ETHERNET_ADDRESS: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+// the transitional device id, modern only devices are not supported by the legacy transport
+pub const VIRTIO_NET_DRIVER_ENTRY: PciDriver = PciDriver {
+    vendor_id: VIRTIO_VENDOR_ID,
+    device_id: 0x1000,
+    init_device: init_virtio_net,
+};
+
+pub struct VirtioNetDriver {
+    device: VirtioDevice,
+    features: NetworkFeatures,
+    ethernet_address: EthernetAddress,
+    receive_queue: Virtqueue,
+    // indexed by the head descriptor of the chain the buffer was posted with
+    receive_buffers: Vec<Option<Box<[u8; RECEIVE_BUFFER_SIZE]>>>,
+    transmit_queue: Virtqueue,
+    transmit_header: Box<VirtioNetHeader>,
+    transmit_buffers: Vec<Option<Vec<u8>>>,
+    link_up: bool,
+    statistics: DeviceStatistics,
+}
+
+// the queues point into memory shared with the device, which doesn't care who holds the lock
+unsafe impl Send for VirtioNetDriver {}
+
+pub fn init_virtio_net(pci: &mut PciConfigSpace) -> core::result::Result<(), DriverError> {
+    println!(
+        "Found virtio-net card (vendor_id:{:#X} , device_id: {:#X}), initializing network card...",
+        pci.vendor_id, pci.device_id
+    );
+
+    let driver = Arc::new(Mutex::new(unsafe { VirtioNetDriver::new(pci)? }));
+
+    let interrupt_driver = driver.clone();
+    register_irq_handler(
+        pci.get_interrupt_line(),
+        Box::new(move || interrupt_driver.lock().handle_interrupt()),
+    );
+
+    println!(
+        "virtio-net initialized!, my ethernet address is: {}",
+        driver.lock().ethernet_address
+    );
+
+    register_interface(driver);
+
+    Ok(())
+}
+
+impl VirtioNetDriver {
+    unsafe fn new(pci: &mut PciConfigSpace) -> core::result::Result<Self, DriverError> {
+        let mut device = VirtioDevice::new(pci)?;
+
+        let features = NetworkFeatures::from_bits_truncate(
+            device.negotiate_features((NetworkFeatures::MAC | NetworkFeatures::STATUS).bits()),
+        );
+
+        let receive_queue = device.setup_queue(RECEIVE_QUEUE)?;
+        let transmit_queue = device.setup_queue(TRANSMIT_QUEUE)?;
+
+        let mut new_driver = VirtioNetDriver {
+            ethernet_address: EthernetAddress { bytes: [0; 6] },
+            receive_buffers: vec![None; receive_queue.size() as usize],
+            transmit_buffers: vec![None; transmit_queue.size() as usize],
+            device,
+            features,
+            receive_queue,
+            transmit_queue,
+            transmit_header: Box::default(),
+            link_up: false,
+            statistics: DeviceStatistics::default(),
+        };
+
+        new_driver.read_ethernet_address();
+        new_driver.fill_receive_queue();
+        new_driver.update_link_status();
+        new_driver.device.finish_initialization();
+
+        Ok(new_driver)
+    }
+
+    unsafe fn read_ethernet_address(&mut self) {
+        if !self.features.contains(NetworkFeatures::MAC) {
+            self.ethernet_address.bytes = FALLBACK_ETHERNET_ADDRESS;
+            return;
+        }
+
+        for (index, byte) in self.ethernet_address.bytes.iter_mut().enumerate() {
+            *byte = self
+                .device
+                .read_device_configuration(CONFIGURATION_MAC_OFFSET + index);
+        }
+    }
+
+    unsafe fn update_link_status(&mut self) {
+        // without the status feature the link is always considered up
+        self.link_up = !self.features.contains(NetworkFeatures::STATUS)
+            || NetworkStatus::from_bits_truncate(
+                self.device
+                    .read_device_configuration(CONFIGURATION_STATUS_OFFSET),
+            )
+            .contains(NetworkStatus::LINK_UP);
+    }
+
+    unsafe fn fill_receive_queue(&mut self) {
+        while self.receive_queue.free_descriptors() > 0 {
+            self.post_receive_buffer(Box::new([0; RECEIVE_BUFFER_SIZE]));
+        }
+
+        self.device.notify(&self.receive_queue);
+    }
+
+    unsafe fn post_receive_buffer(&mut self, buffer: Box<[u8; RECEIVE_BUFFER_SIZE]>) {
+        let head = self
+            .receive_queue
+            .add_buffers(&[VirtqueueBuffer {
+                address: buffer.as_ptr().addr(),
+                length: RECEIVE_BUFFER_SIZE,
+                device_writable: true,
+            }])
+            .expect("a receive buffer was returned without freeing its descriptor");
+
+        self.receive_buffers[head as usize] = Some(buffer);
+    }
+
+    fn handle_interrupt(&mut self) {
+        // reading the status acknowledges the interrupt
+        let status = unsafe { self.device.read_interrupt_status() };
+
+        if status.contains(InterruptStatus::QUEUE) {
+            RECEIVE_EVENT.signal();
+            self.reclaim_transmit_buffers();
+            TRANSMIT_EVENT.signal();
+        }
+
+        if status.contains(InterruptStatus::CONFIGURATION_CHANGE) {
+            unsafe { self.update_link_status() };
+            println!(
+                "virtio-net link is {}",
+                if self.link_up { "up" } else { "down" }
+            );
+        }
+    }
+
+    fn reclaim_transmit_buffers(&mut self) {
+        while let Some((head, _)) = unsafe { self.transmit_queue.pop_used() } {
+            let packet = self.transmit_buffers[head as usize].take().unwrap();
+
+            self.statistics.transmitted_frames += 1;
+            self.statistics.transmitted_bytes += packet.len();
+        }
+    }
+
+    unsafe fn receive_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        let Some((head, length)) = self.receive_queue.pop_used() else {
+            return Ok(None);
+        };
+
+        let buffer = self.receive_buffers[head as usize].take().unwrap();
+
+        let result = if (HEADER_SIZE..=RECEIVE_BUFFER_SIZE).contains(&length) {
+            let packet = buffer[HEADER_SIZE..length].to_vec();
+
+            self.statistics.received_frames += 1;
+            self.statistics.received_bytes += packet.len();
+
+            Ok(Some(packet))
+        } else {
+            self.statistics.receive_errors += 1;
+            Err(NetworkError::VirtioTruncatedFrame(length))
+        };
+
+        // the same buffer goes straight back to the device
+        self.post_receive_buffer(buffer);
+        self.device.notify(&self.receive_queue);
+
+        result
+    }
+}
+
+impl NetworkDevice for VirtioNetDriver {
+    fn name(&self) -> &'static str {
+        "virtio-net"
+    }
+
+    fn ethernet_address(&self) -> EthernetAddress {
+        self.ethernet_address
+    }
+
+    fn link_up(&self) -> bool {
+        self.link_up
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities::empty()
+    }
+
+    fn statistics(&self) -> DeviceStatistics {
+        self.statistics
+    }
+
+    fn can_transmit(&self, frame: &[u8]) -> bool {
+        frame.is_empty()
+            || frame.len() > MAX_FRAME_LENGTH
+            || self.transmit_queue.free_descriptors() >= 2
+    }
+
+    fn transmit_frame(&mut self, frame: Vec<u8>) -> Result<()> {
+        if frame.len() > MAX_FRAME_LENGTH {
+            return Err(NetworkError::BufferTooLarge);
+        }
+
+        if frame.is_empty() {
+            return Err(NetworkError::EmptyBuffer);
+        }
+
+        self.reclaim_transmit_buffers();
+
+        // the header and the frame go out as a chain of two descriptors
+        let head = unsafe {
+            self.transmit_queue.add_buffers(&[
+                VirtqueueBuffer {
+                    address: (self.transmit_header.as_ref() as *const VirtioNetHeader).addr(),
+                    length: HEADER_SIZE,
+                    device_writable: false,
+                },
+                VirtqueueBuffer {
+                    address: frame.as_ptr().addr(),
+                    length: frame.len(),
+                    device_writable: false,
+                },
+            ])
+        }
+        .ok_or(NetworkError::FullTransmissionsQueue)?;
+
+        self.transmit_buffers[head as usize] = Some(frame);
+        unsafe { self.device.notify(&self.transmit_queue) };
+
+        Ok(())
+    }
+
+    fn receive_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        unsafe { self.receive_packet() }
+    }
+}
@@ -0,0 +1,110 @@
+// legacy virtio over pci, https://docs.oasis-open.org/virtio/virtio/v1.1/csprd01/virtio-v1.1-csprd01.html#x1-1090002
+
+use alloc::boxed::Box;
+use thiserror::Error;
+
+use crate::pci::config_space::{
+    base_address_register::IoSpace, BaseAddressRegister, IoSpaceRegister, PciConfigSpace, PortValue,
+};
+
+use self::{
+    registers::{
+        DeviceStatus, InterruptStatus, DEVICE_CONFIGURATION_OFFSET, DEVICE_FEATURES, DEVICE_STATUS,
+        DRIVER_FEATURES, INTERRUPT_STATUS, QUEUE_ADDRESS, QUEUE_ADDRESS_SHIFT, QUEUE_NOTIFY,
+        QUEUE_SELECT, QUEUE_SIZE,
+    },
+    virtqueue::Virtqueue,
+};
+
+use super::DriverError;
+
+pub mod registers;
+pub mod virtqueue;
+
+pub const VIRTIO_VENDOR_ID: u16 = 0x1AF4;
+
+#[derive(Error, Debug)]
+pub enum VirtioError {
+    #[error("Virtqueue {0} does not exist on the device")]
+    MissingQueue(u16),
+    #[error("Failed to allocate memory for virtqueue {0}")]
+    QueueAllocationFailed(u16),
+}
+
+impl From<VirtioError> for DriverError {
+    fn from(error: VirtioError) -> Self {
+        DriverError::InitializationError(Box::new(error))
+    }
+}
+
+pub struct VirtioDevice {
+    io_space: IoSpace,
+}
+
+impl VirtioDevice {
+    // resets the device and acknowledges it, the caller negotiates features and sets up queues next
+    pub unsafe fn new(pci: &PciConfigSpace) -> Result<Self, DriverError> {
+        let BaseAddressRegister::IoSpace(io_space) = pci.base_address_registers[0] else {
+            return Err(DriverError::UnexpectedBaseRegisterLayout {
+                register: pci.base_address_registers[0],
+                index: 0,
+            });
+        };
+
+        let mut device = Self { io_space };
+
+        DEVICE_STATUS.write(&mut device.io_space, 0);
+        device.add_status(DeviceStatus::ACKNOWLEDGE);
+        device.add_status(DeviceStatus::DRIVER);
+
+        Ok(device)
+    }
+
+    unsafe fn add_status(&mut self, status: DeviceStatus) {
+        let current = DeviceStatus::from_bits_truncate(DEVICE_STATUS.read(&self.io_space));
+        DEVICE_STATUS.write(&mut self.io_space, (current | status).bits());
+    }
+
+    // accepts every feature in `supported` that the device offers and returns them
+    pub unsafe fn negotiate_features(&mut self, supported: u32) -> u32 {
+        let features = DEVICE_FEATURES.read(&self.io_space) & supported;
+        DRIVER_FEATURES.write(&mut self.io_space, features);
+        features
+    }
+
+    // legacy devices decide the queue size on their own
+    pub unsafe fn setup_queue(&mut self, index: u16) -> Result<Virtqueue, VirtioError> {
+        QUEUE_SELECT.write(&mut self.io_space, index);
+
+        let size = QUEUE_SIZE.read(&self.io_space);
+        if size == 0 {
+            return Err(VirtioError::MissingQueue(index));
+        }
+
+        let queue = Virtqueue::new(index, size).ok_or(VirtioError::QueueAllocationFailed(index))?;
+
+        QUEUE_ADDRESS.write(
+            &mut self.io_space,
+            (queue.physical_address() >> QUEUE_ADDRESS_SHIFT) as u32,
+        );
+
+        Ok(queue)
+    }
+
+    // the device starts using the queues once we are done setting it up
+    pub unsafe fn finish_initialization(&mut self) {
+        self.add_status(DeviceStatus::DRIVER_OK);
+    }
+
+    pub unsafe fn notify(&mut self, queue: &Virtqueue) {
+        QUEUE_NOTIFY.write(&mut self.io_space, queue.index());
+    }
+
+    pub unsafe fn read_interrupt_status(&mut self) -> InterruptStatus {
+        InterruptStatus::from_bits_truncate(INTERRUPT_STATUS.read(&self.io_space))
+    }
+
+    pub unsafe fn read_device_configuration<T: PortValue>(&self, offset: usize) -> T {
+        IoSpaceRegister::<T>::new(DEVICE_CONFIGURATION_OFFSET + offset).read(&self.io_space)
+    }
+}
@@ -0,0 +1,39 @@
+#![allow(dead_code)]
+
+use bitflags::bitflags;
+
+use crate::pci::config_space::IoSpaceRegister;
+
+bitflags! {
+    pub struct DeviceStatus: u8 {
+        const ACKNOWLEDGE = 1 << 0;
+        const DRIVER = 1 << 1;
+        const DRIVER_OK = 1 << 2;
+        const FEATURES_OK = 1 << 3;
+        const DEVICE_NEEDS_RESET = 1 << 6;
+        const FAILED = 1 << 7;
+    }
+
+    // reading the register acknowledges the interrupt
+    pub struct InterruptStatus: u8 {
+        const QUEUE = 1 << 0;
+        const CONFIGURATION_CHANGE = 1 << 1;
+    }
+}
+
+// the legacy layout from section 4.1.4.8 of the spec, the device configuration follows the common header
+pub const DEVICE_FEATURES: IoSpaceRegister<u32> = IoSpaceRegister::new(0x00);
+pub const DRIVER_FEATURES: IoSpaceRegister<u32> = IoSpaceRegister::new(0x04);
+pub const QUEUE_ADDRESS: IoSpaceRegister<u32> = IoSpaceRegister::new(0x08);
+pub const QUEUE_SIZE: IoSpaceRegister<u16> = IoSpaceRegister::new(0x0C);
+pub const QUEUE_SELECT: IoSpaceRegister<u16> = IoSpaceRegister::new(0x0E);
+pub const QUEUE_NOTIFY: IoSpaceRegister<u16> = IoSpaceRegister::new(0x10);
+pub const DEVICE_STATUS: IoSpaceRegister<u8> = IoSpaceRegister::new(0x12);
+pub const INTERRUPT_STATUS: IoSpaceRegister<u8> = IoSpaceRegister::new(0x13);
+
+// only true while MSI-X is disabled, which we never enable
+pub const DEVICE_CONFIGURATION_OFFSET: usize = 0x14;
+
+// legacy queues hand the device a page frame number
+pub const QUEUE_ADDRESS_SHIFT: u32 = 12;
+pub const QUEUE_ALIGNMENT: usize = 4096;
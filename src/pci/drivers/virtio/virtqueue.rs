@@ -0,0 +1,207 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    mem::size_of,
+    ptr::{read_volatile, write_volatile},
+    sync::atomic::{fence, Ordering},
+};
+
+use bitflags::bitflags;
+
+use crate::memory::{physical::global_alloc::ALLOCATOR, virtual_to_physical};
+
+use super::registers::QUEUE_ALIGNMENT;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct DescriptorFlags: u16 {
+        const NEXT = 1 << 0;
+        const WRITE = 1 << 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct Descriptor {
+    pub address: u64,
+    pub length: u32,
+    pub flags: DescriptorFlags,
+    pub next: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct UsedElement {
+    pub id: u32,
+    pub length: u32,
+}
+
+// a buffer we hand the device, writable buffers are filled by the device instead of read by it
+#[derive(Debug, Clone, Copy)]
+pub struct VirtqueueBuffer {
+    pub address: usize,
+    pub length: usize,
+    pub device_writable: bool,
+}
+
+// split virtqueue in the legacy layout, https://docs.oasis-open.org/virtio/virtio/v1.1/csprd01/virtio-v1.1-csprd01.html#x1-240006
+// | descriptors | available ring | padding | used ring |
+pub struct Virtqueue {
+    index: u16,
+    size: u16,
+    memory: *mut u8,
+    descriptors: *mut Descriptor,
+    available: *mut u16,
+    used: *mut u16,
+    free_head: u16,
+    free_count: u16,
+    last_used_index: u16,
+}
+
+const fn align_up(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+impl Virtqueue {
+    pub fn memory_size(size: u16) -> usize {
+        let size = size as usize;
+        // flags, index, the ring and the event field
+        let available_ring = (3 + size) * size_of::<u16>();
+        let used_ring = 3 * size_of::<u16>() + size * size_of::<UsedElement>();
+
+        align_up(
+            size * size_of::<Descriptor>() + available_ring,
+            QUEUE_ALIGNMENT,
+        ) + align_up(used_ring, QUEUE_ALIGNMENT)
+    }
+
+    pub(super) unsafe fn new(index: u16, size: u16) -> Option<Self> {
+        let layout = Layout::from_size_align(Self::memory_size(size), QUEUE_ALIGNMENT).unwrap();
+        let memory = ALLOCATOR.alloc_zeroed(layout);
+        if memory.is_null() {
+            return None;
+        }
+
+        let descriptors = memory as *mut Descriptor;
+        let available = memory.add(size as usize * size_of::<Descriptor>()) as *mut u16;
+        let used = memory.add(align_up(
+            size as usize * size_of::<Descriptor>() + (3 + size as usize) * size_of::<u16>(),
+            QUEUE_ALIGNMENT,
+        )) as *mut u16;
+
+        // every descriptor starts out on the free list
+        for descriptor in 0..size {
+            (*descriptors.add(descriptor as usize)).next = descriptor + 1;
+        }
+
+        Some(Self {
+            index,
+            size,
+            memory,
+            descriptors,
+            available,
+            used,
+            free_head: 0,
+            free_count: size,
+            last_used_index: 0,
+        })
+    }
+
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    pub fn physical_address(&self) -> usize {
+        virtual_to_physical(self.memory.addr())
+    }
+
+    pub fn free_descriptors(&self) -> u16 {
+        self.free_count
+    }
+
+    // chains the buffers together and makes them available to the device, returns the head of the chain.
+    // the device only sees them after the queue is notified
+    pub unsafe fn add_buffers(&mut self, buffers: &[VirtqueueBuffer]) -> Option<u16> {
+        if buffers.is_empty() || buffers.len() > self.free_count as usize {
+            return None;
+        }
+
+        let head = self.free_head;
+        let mut current = head;
+
+        for (position, buffer) in buffers.iter().enumerate() {
+            let descriptor = &mut *self.descriptors.add(current as usize);
+
+            let mut flags = DescriptorFlags::empty();
+            flags.set(DescriptorFlags::WRITE, buffer.device_writable);
+            flags.set(DescriptorFlags::NEXT, position + 1 < buffers.len());
+
+            descriptor.address = virtual_to_physical(buffer.address) as u64;
+            descriptor.length = buffer.length as u32;
+            descriptor.flags = flags;
+
+            if position + 1 < buffers.len() {
+                current = descriptor.next;
+            } else {
+                self.free_head = descriptor.next;
+            }
+        }
+
+        self.free_count -= buffers.len() as u16;
+
+        let available_index = read_volatile(self.available.add(1));
+        write_volatile(
+            self.available
+                .add(2 + (available_index % self.size) as usize),
+            head,
+        );
+
+        // the ring entry has to be visible before the index that publishes it
+        fence(Ordering::SeqCst);
+        write_volatile(self.available.add(1), available_index.wrapping_add(1));
+        fence(Ordering::SeqCst);
+
+        Some(head)
+    }
+
+    // returns the head of the next chain the device is done with and how many bytes it wrote into it
+    pub unsafe fn pop_used(&mut self) -> Option<(u16, usize)> {
+        if self.last_used_index == read_volatile(self.used.add(1)) {
+            return None;
+        }
+
+        fence(Ordering::SeqCst);
+
+        let element = read_volatile(
+            (self.used.add(2) as *const UsedElement)
+                .add((self.last_used_index % self.size) as usize),
+        );
+        self.last_used_index = self.last_used_index.wrapping_add(1);
+
+        let head = element.id as u16;
+        self.free_chain(head);
+
+        Some((head, element.length as usize))
+    }
+
+    unsafe fn free_chain(&mut self, head: u16) {
+        let mut current = head;
+
+        loop {
+            let descriptor = &mut *self.descriptors.add(current as usize);
+            self.free_count += 1;
+
+            if !descriptor.flags.contains(DescriptorFlags::NEXT) {
+                descriptor.next = self.free_head;
+                break;
+            }
+
+            current = descriptor.next;
+        }
+
+        self.free_head = head;
+    }
+}
@@ -0,0 +1,74 @@
+// https://datatracker.ietf.org/doc/html/rfc4648#section-4
+// the standard alphabet with padding
+
+use alloc::{string::String, vec::Vec};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PADDING: u8 = b'=';
+
+pub fn encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        // 3 bytes are 4 characters, a short chunk leaves the characters it doesn't cover as padding
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3F;
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push(PADDING as char);
+            }
+        }
+    }
+
+    encoded
+}
+
+// None for anything that isn't canonical padded base64
+pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    if encoded.len() % 4 != 0 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+
+    for (i, chunk) in encoded.as_chunks::<4>().0.iter().enumerate() {
+        let is_last = i == encoded.len() / 4 - 1;
+        let padding = chunk
+            .iter()
+            .rev()
+            .take_while(|&&byte| byte == PADDING)
+            .count();
+
+        if padding > 2 || (padding > 0 && !is_last) {
+            return None;
+        }
+
+        let mut group = 0u32;
+        for &byte in &chunk[..4 - padding] {
+            let value = ALPHABET.iter().position(|&symbol| symbol == byte)?;
+            group = (group << 6) | value as u32;
+        }
+        group <<= 6 * padding as u32;
+
+        let bytes = group.to_be_bytes();
+        let length = 3 - padding;
+
+        // the bits the padding stands for have to be zero
+        if bytes[1 + length..].iter().any(|&byte| byte != 0) {
+            return None;
+        }
+
+        decoded.extend_from_slice(&bytes[1..1 + length]);
+    }
+
+    Some(decoded)
+}
@@ -0,0 +1,60 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::x86::{
+    enable_interrupt_and_hlt,
+    interrupts::{disable_interrupt, enable_interrupt},
+    pit,
+};
+
+// a flag that interrupt handlers raise to wake up whoever is halted waiting on it
+pub struct Event {
+    signaled: AtomicBool,
+}
+
+impl Event {
+    pub const fn new() -> Self {
+        Self {
+            signaled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn signal(&self) {
+        self.signaled.store(true, Ordering::SeqCst);
+    }
+
+    // consumes a pending signal without blocking
+    pub fn try_wait(&self) -> bool {
+        self.signaled.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn wait(&self) {
+        while !self.try_wait_or_hlt() {}
+    }
+
+    // same as wait but gives up after `ticks` timer ticks, returns whether the event was signaled
+    pub fn wait_timeout(&self, ticks: usize) -> bool {
+        let start = pit::ticks();
+
+        while !self.try_wait_or_hlt() {
+            if pit::elapsed(start, ticks) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // the check runs with interrupts off, otherwise a signal between it and the hlt would only be
+    // noticed after the next unrelated interrupt
+    fn try_wait_or_hlt(&self) -> bool {
+        unsafe { disable_interrupt() };
+
+        if self.try_wait() {
+            unsafe { enable_interrupt() };
+            return true;
+        }
+
+        unsafe { enable_interrupt_and_hlt() };
+        false
+    }
+}
@@ -0,0 +1,3 @@
+pub mod base64;
+pub mod event;
+pub mod sha1;
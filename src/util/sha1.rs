@@ -0,0 +1,75 @@
+// https://datatracker.ietf.org/doc/html/rfc3174
+// broken for anything that needs collision resistance, it is only here because protocols like
+// the websocket handshake ask for it
+
+const INITIAL_STATE: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+const BLOCK_SIZE: usize = 64;
+
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state = INITIAL_STATE;
+
+    let (blocks, remainder) = data.as_chunks::<BLOCK_SIZE>();
+    for block in blocks {
+        process_block(&mut state, block);
+    }
+
+    // the rest, a one bit, zeros and the length in bits fill one or two more blocks
+    let mut tail = [0; 2 * BLOCK_SIZE];
+    tail[..remainder.len()].copy_from_slice(remainder);
+    tail[remainder.len()] = 0x80;
+
+    let tail_length = if remainder.len() < BLOCK_SIZE - 8 {
+        BLOCK_SIZE
+    } else {
+        2 * BLOCK_SIZE
+    };
+    let bit_length = (data.len() as u64).wrapping_mul(8);
+    tail[tail_length - 8..tail_length].copy_from_slice(&bit_length.to_be_bytes());
+
+    for block in tail[..tail_length].as_chunks::<BLOCK_SIZE>().0 {
+        process_block(&mut state, block);
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.as_chunks_mut::<4>().0.iter_mut().zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn process_block(state: &mut [u32; 5], block: &[u8; BLOCK_SIZE]) {
+    let mut words = [0u32; 80];
+    for (word, &bytes) in words.iter_mut().zip(block.as_chunks::<4>().0) {
+        *word = u32::from_be_bytes(bytes);
+    }
+    for i in 16..80 {
+        words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+
+    for (i, word) in words.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5A827999),
+            20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+            _ => (b ^ c ^ d, 0xCA62C1D6),
+        };
+
+        let temp = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(*word);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+    }
+
+    for (value, new) in state.iter_mut().zip([a, b, c, d, e]) {
+        *value = value.wrapping_add(new);
+    }
+}
@@ -8,13 +8,15 @@ use core::{
 
 use lazy_static::lazy_static;
 
-use crate::mutex::Mutex;
+use crate::{memory::physical_to_virtual, mutex::Mutex};
+
+const BUFFER_ADDRESS: usize = 0xb8000;
 
 lazy_static! {
     pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
         column_position: 0,
         color: VgaColor::new(Color::Yellow, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
+        buffer: unsafe { &mut *(physical_to_virtual(BUFFER_ADDRESS) as *mut Buffer) },
     });
 }
 
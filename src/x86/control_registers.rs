@@ -0,0 +1,35 @@
+// intel sdm vol 3a 2.5 "control registers"
+
+use core::arch::asm;
+
+const CR0_PAGING: usize = 1 << 31;
+// read only pages hold for the kernel too, not only for user mode
+const CR0_WRITE_PROTECT: usize = 1 << 16;
+
+// the address of the last page fault
+pub fn read_cr2() -> usize {
+    let value: usize;
+    unsafe { asm!("mov {}, cr2", out(reg) value) };
+    value
+}
+
+// switching directories flushes every non global entry from the TLB
+pub unsafe fn write_cr3(page_directory: usize) {
+    asm!("mov cr3, {}", in(reg) page_directory);
+}
+
+// cr3 has to point at a directory that maps the code doing this
+pub unsafe fn enable_paging() {
+    asm!(
+        "mov {tmp}, cr0",
+        "or {tmp}, {paging}",
+        "mov cr0, {tmp}",
+        tmp = out(reg) _,
+        paging = in(reg) CR0_PAGING | CR0_WRITE_PROTECT,
+    );
+}
+
+// drops the TLB entry of one page
+pub unsafe fn invalidate_page(address: usize) {
+    asm!("invlpg [{}]", in(reg) address);
+}
@@ -1,12 +1,15 @@
 use crate::{
+    memory::paging::regions,
     print, println,
     x86::{
+        control_registers::read_cr2,
         interrupts::{pic_8259::PIC, PciInterruptIndex},
         io::io_in_u8,
+        pit,
     },
 };
 
-use super::InterruptStackFrame;
+use super::{InterruptStackFrame, PageFaultErrorCode};
 
 #[allow(dead_code)]
 pub extern "x86-interrupt" fn generic_exception_handler(
@@ -47,10 +50,26 @@ pub extern "x86-interrupt" fn general_protection_fault_fault_handler(
     );
 }
 
+// demand paged and guard regions are looked at first, anything they don't resolve is a bug
+pub extern "x86-interrupt" fn page_fault_handler(
+    interrupt_stack_frame: &mut InterruptStackFrame,
+    error_code: usize,
+) {
+    let address = read_cr2();
+    let decoded = PageFaultErrorCode::from_bits_truncate(error_code);
+
+    if let Err(err) = regions::resolve_fault(address, decoded) {
+        panic!(
+            "PAGE FAULT EXCEPTION! {} at {:#x}: {}\n{:#x?}\nerror_code: {:#x} ({:?})",
+            decoded, address, err, interrupt_stack_frame, error_code, decoded
+        );
+    }
+}
+
 pub extern "x86-interrupt" fn timer_interrupt_handler(
     _interrupt_stack_frame: &mut InterruptStackFrame,
 ) {
-    // print!(".");
+    pit::tick();
 
     unsafe {
         PIC.lock()
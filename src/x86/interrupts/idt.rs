@@ -17,7 +17,8 @@ use crate::{
         gdt::{self, SegmentSelector},
         interrupts::handlers::{
             double_fault_handler, general_protection_fault_fault_handler,
-            generic_interrupt_handler, keyboard_interrupt_handler, timer_interrupt_handler,
+            generic_interrupt_handler, keyboard_interrupt_handler, page_fault_handler,
+            timer_interrupt_handler,
         },
         PrivilegeLevel, TableDescriptor,
     },
@@ -204,6 +205,7 @@ lazy_static! {
         idt.division_error.set_handler_fn(generic_interrupt_handler);
         idt.general_protection_fault
             .set_handler_fn(general_protection_fault_fault_handler);
+        idt.page_fault.set_handler_fn(page_fault_handler);
 
         idt[PciInterruptIndex::Timer as u8].set_handler_fn(timer_interrupt_handler);
 
@@ -0,0 +1,53 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::mutex::Mutex;
+
+use super::{
+    idt::{load_idt, IDT},
+    pic_8259::{MASTER_INTERRUPT_OFFSET, PIC},
+    InterruptHandler, InterruptStackFrame,
+};
+
+// PCI devices share interrupt lines, so every line gets a stub that runs all the handlers registered on it
+pub type IrqHandler = Box<dyn Fn()>;
+
+const IRQ_LINES: usize = 16;
+
+const EMPTY_HANDLER_LIST: Vec<IrqHandler> = Vec::new();
+static IRQ_HANDLERS: Mutex<[Vec<IrqHandler>; IRQ_LINES]> =
+    Mutex::new([EMPTY_HANDLER_LIST; IRQ_LINES]);
+
+macro_rules! irq_stubs {
+    ($($line:literal),*) => {
+        [$({
+            extern "x86-interrupt" fn stub(_interrupt_stack_frame: &mut InterruptStackFrame) {
+                dispatch_irq($line);
+            }
+
+            stub as InterruptHandler
+        }),*]
+    };
+}
+
+const IRQ_STUBS: [InterruptHandler; IRQ_LINES] =
+    irq_stubs!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+
+fn dispatch_irq(line: u8) {
+    for handler in IRQ_HANDLERS.lock()[line as usize].iter() {
+        handler();
+    }
+
+    unsafe {
+        PIC.lock()
+            .notify_end_of_interrupt(MASTER_INTERRUPT_OFFSET + line);
+    }
+}
+
+pub fn register_irq_handler(line: u8, handler: IrqHandler) {
+    assert!((line as usize) < IRQ_LINES, "invalid irq line {}", line);
+
+    IRQ_HANDLERS.lock()[line as usize].push(handler);
+
+    IDT.lock()[MASTER_INTERRUPT_OFFSET + line].set_handler_fn(IRQ_STUBS[line as usize]);
+    load_idt();
+}
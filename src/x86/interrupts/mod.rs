@@ -1,4 +1,6 @@
-use core::arch::asm;
+use core::{arch::asm, fmt::Display};
+
+use bitflags::bitflags;
 
 use self::pic_8259::MASTER_INTERRUPT_OFFSET;
 
@@ -6,6 +8,7 @@ use super::cpu_flags::CpuFlags;
 
 pub mod handlers;
 pub mod idt;
+pub mod irq;
 pub mod pic_8259;
 
 #[derive(Debug, Clone, Copy)]
@@ -25,6 +28,48 @@ pub struct InterruptStackFrame {
     stack_segment: usize,
 }
 
+bitflags! {
+    // pushed with a page fault, intel sdm vol 3a 4.7
+    pub struct PageFaultErrorCode: usize {
+        // the page was there but the access wasn't allowed, otherwise it wasn't mapped
+        const PRESENT = 1 << 0;
+        const WRITE = 1 << 1;
+        const USER = 1 << 2;
+        // an entry on the way has a reserved bit set, the tables are broken
+        const RESERVED = 1 << 3;
+        const INSTRUCTION_FETCH = 1 << 4;
+    }
+}
+
+impl Display for PageFaultErrorCode {
+    // like "kernel write to a page that is not present"
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mode = match self.contains(Self::USER) {
+            true => "user",
+            false => "kernel",
+        };
+        let access = match (
+            self.contains(Self::INSTRUCTION_FETCH),
+            self.contains(Self::WRITE),
+        ) {
+            (true, _) => "instruction fetch from",
+            (false, true) => "write to",
+            (false, false) => "read from",
+        };
+        let page = match self.contains(Self::PRESENT) {
+            true => "a present page",
+            false => "a page that is not present",
+        };
+
+        write!(f, "{} {} {}", mode, access, page)?;
+        if self.contains(Self::RESERVED) {
+            write!(f, " with a reserved bit set")?;
+        }
+
+        Ok(())
+    }
+}
+
 pub type ExceptionHandler =
     extern "x86-interrupt" fn(interrupt_stack_frame: &mut InterruptStackFrame, error_code: usize);
 
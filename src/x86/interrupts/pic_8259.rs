@@ -106,7 +106,12 @@ impl Pics {
     }
 
     pub unsafe fn notify_end_of_interrupt(&self, interrupt_id: u8) {
-        self.master.end_of_interrupt(interrupt_id);
-        self.slave.end_of_interrupt(interrupt_id);
+        // the slave is cascaded through the master so the master must be acknowledged as well
+        if self.slave.irq_offset <= interrupt_id && interrupt_id < (self.slave.irq_offset + 8) {
+            self.slave.end_of_interrupt(interrupt_id);
+            io_out_u8(self.master.command_address, END_OF_INTERRUPT_COMMAND);
+        } else {
+            self.master.end_of_interrupt(interrupt_id);
+        }
     }
 }
@@ -2,10 +2,12 @@ use core::arch::asm;
 
 use modular_bitfield::BitfieldSpecifier;
 
+pub mod control_registers;
 pub mod cpu_flags;
 pub mod gdt;
 pub mod interrupts;
 pub mod io;
+pub mod pit;
 
 #[derive(BitfieldSpecifier, Clone, Copy, Debug)]
 #[bits = 2]
@@ -27,6 +29,24 @@ pub unsafe fn hlt() {
     asm!("hlt");
 }
 
+// sti only takes effect after the instruction that follows it, so no interrupt can come in
+// between the two and the hlt always wakes up on the next one
+pub unsafe fn enable_interrupt_and_hlt() {
+    asm!("sti", "hlt");
+}
+
+// cycles since reset, only good as a fast moving counter since the frequency is unknown
+pub fn read_timestamp_counter() -> u64 {
+    let low: u32;
+    let high: u32;
+
+    unsafe {
+        asm!("rdtsc", out("eax") low, out("edx") high);
+    }
+
+    ((high as u64) << 32) | low as u64
+}
+
 pub fn hlt_loop() -> ! {
     loop {
         unsafe {
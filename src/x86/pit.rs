@@ -0,0 +1,39 @@
+// https://wiki.osdev.org/Programmable_Interval_Timer
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::io::io_out_u8;
+
+const PIT_FREQUENCY: usize = 1_193_182;
+pub const TICKS_PER_SECOND: usize = 100;
+
+const CHANNEL_0_DATA_PORT: u16 = 0x40;
+const MODE_COMMAND_PORT: u16 = 0x43;
+// channel 0, low byte then high byte, square wave generator
+const SQUARE_WAVE_COMMAND: u8 = 0b0011_0110;
+
+// a u32 at 100 ticks a second wraps after ~497 days, users compare with wrapping_sub
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn init() {
+    let divisor = (PIT_FREQUENCY / TICKS_PER_SECOND) as u16;
+
+    unsafe {
+        io_out_u8(MODE_COMMAND_PORT, SQUARE_WAVE_COMMAND);
+        io_out_u8(CHANNEL_0_DATA_PORT, divisor as u8);
+        io_out_u8(CHANNEL_0_DATA_PORT, (divisor >> 8) as u8);
+    }
+}
+
+pub fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn ticks() -> usize {
+    TICKS.load(Ordering::Relaxed)
+}
+
+// true once `ticks` have passed since `since`
+pub fn elapsed(since: usize, ticks: usize) -> bool {
+    self::ticks().wrapping_sub(since) >= ticks
+}